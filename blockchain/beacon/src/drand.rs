@@ -191,26 +191,36 @@ pub struct DrandBeacon {
 }
 
 impl DrandBeacon {
-    /// Construct a new DrandBeacon.
+    /// Construct a new DrandBeacon. In debug builds, the chain info of the mainnet beacon is
+    /// checked against the remote server.
     pub async fn new(
         genesis_ts: u64,
         interval: u64,
         config: &DrandConfig<'_>,
     ) -> Result<Self, anyhow::Error> {
-        if genesis_ts == 0 {
-            panic!("Genesis timestamp cannot be 0")
-        }
-
-        let chain_info = &config.chain_info;
-
         if cfg!(debug_assertions) && config.network_type == DrandNetwork::Mainnet {
             let remote_chain_info: ChainInfo = surf::get(&format!("{}/info", &config.server))
                 .recv_json()
                 .await
                 .map_err(|e| anyhow::anyhow!("{}", e))?;
-            debug_assert!(&remote_chain_info == chain_info);
+            debug_assert!(remote_chain_info == config.chain_info);
         }
 
+        Self::new_unverified(genesis_ts, interval, config)
+    }
+
+    /// Construct a new DrandBeacon without checking its chain info against the remote server,
+    /// for nodes running without network access.
+    pub fn new_unverified(
+        genesis_ts: u64,
+        interval: u64,
+        config: &DrandConfig<'_>,
+    ) -> Result<Self, anyhow::Error> {
+        if genesis_ts == 0 {
+            panic!("Genesis timestamp cannot be 0")
+        }
+
+        let chain_info = &config.chain_info;
         Ok(Self {
             url: config.server,
             pub_key: DrandPublic {
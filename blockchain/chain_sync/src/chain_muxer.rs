@@ -5,11 +5,12 @@ use crate::bad_block_cache::BadBlockCache;
 use crate::consensus::Consensus;
 use crate::metrics;
 use crate::network_context::SyncNetworkContext;
+use crate::peer_manager::PeerManager;
 use crate::sync_state::SyncState;
 use crate::tipset_syncer::{
     TipsetProcessor, TipsetProcessorError, TipsetRangeSyncer, TipsetRangeSyncerError,
 };
-use crate::validation::{TipsetValidationError, TipsetValidator};
+use crate::validation::{GossipBlockValidator, TipsetValidationError, TipsetValidator};
 
 use chain::{ChainStore, Error as ChainStoreError};
 use cid::Cid;
@@ -17,13 +18,15 @@ use forest_blocks::{
     Block, Error as ForestBlockError, FullTipset, GossipBlock, Tipset, TipsetKeys,
 };
 use forest_libp2p::{
-    hello::HelloRequest, rpc::RequestResponseError, NetworkEvent, NetworkMessage, PubsubMessage,
+    hello::HelloRequest, rpc::RequestResponseError, MessageAcceptance, MessageId, NetworkEvent,
+    NetworkMessage, PeerStore, PubsubMessage,
 };
 use forest_message::SignedMessage;
+use fvm_shared::address::Protocol;
 use fvm_shared::message::Message;
 use ipld_blockstore::BlockStore;
 use libp2p::core::PeerId;
-use message_pool::{MessagePool, Provider};
+use message_pool::{Error as MessagePoolError, MessagePool, Provider};
 use state_manager::StateManager;
 
 use async_std::channel::{Receiver, Sender};
@@ -70,11 +73,16 @@ pub enum ChainMuxerError<C: Consensus> {
 
 /// Struct that defines syncing configuration options
 #[derive(Debug, Deserialize, Serialize, Clone, PartialEq, Eq)]
+#[serde(default)]
 pub struct SyncConfig {
     /// Request window length for tipsets during chain exchange
     pub req_window: i64,
     /// Sample size of tipsets to acquire before determining what the network head is
     pub tipset_sample_size: usize,
+    /// Maximum number of request windows of messages fetched concurrently from different peers.
+    /// Headers are not affected: a window of headers starts at the parents of the window before
+    /// it, so only the next window is requested while the last one received is processed.
+    pub max_in_flight_windows: usize,
 }
 
 impl SyncConfig {
@@ -82,6 +90,7 @@ impl SyncConfig {
         Self {
             req_window,
             tipset_sample_size,
+            ..Default::default()
         }
     }
 }
@@ -91,6 +100,7 @@ impl Default for SyncConfig {
         Self {
             req_window: 200,
             tipset_sample_size: 5,
+            max_in_flight_windows: 4,
         }
     }
 }
@@ -172,14 +182,16 @@ where
         mpool: Arc<MessagePool<M>>,
         network_send: Sender<NetworkMessage>,
         network_rx: Receiver<NetworkEvent>,
+        peer_store: Arc<PeerStore>,
         genesis: Arc<Tipset>,
         tipset_sender: Sender<Arc<Tipset>>,
         tipset_receiver: Receiver<Arc<Tipset>>,
         cfg: SyncConfig,
     ) -> Result<Self, ChainMuxerError<C>> {
+        let peer_manager = Arc::new(PeerManager::new(peer_store, network_send.clone()));
         let network = SyncNetworkContext::new(
             network_send,
-            Default::default(),
+            peer_manager,
             state_manager.blockstore_cloned(),
         );
 
@@ -283,12 +295,15 @@ where
                     network.peer_manager().log_success(peer_id, dur).await;
                 }
                 Some(Err(why)) => match why {
+                    RequestResponseError::UnsupportedProtocols => {
+                        network
+                            .peer_manager()
+                            .mark_peer_bad(peer_id, format!("Hello request failed: {:?}", why))
+                            .await;
+                    }
                     RequestResponseError::ConnectionClosed
                     | RequestResponseError::DialFailure
-                    | RequestResponseError::UnsupportedProtocols => {
-                        network.peer_manager().mark_peer_bad(peer_id).await;
-                    }
-                    RequestResponseError::Timeout => {
+                    | RequestResponseError::Timeout => {
                         network.peer_manager().log_failure(peer_id, dur).await;
                     }
                 },
@@ -343,13 +358,27 @@ where
         Ok(FullTipset::new(vec![block]).unwrap())
     }
 
-    async fn handle_pubsub_message(mem_pool: Arc<MessagePool<M>>, message: SignedMessage) {
-        if let Err(why) = mem_pool.add(message).await {
-            debug!(
-                "GossipSub message could not be added to the mem pool: {}",
-                why
-            );
-        }
+    async fn handle_pubsub_message(
+        mem_pool: Arc<MessagePool<M>>,
+        network: SyncNetworkContext<DB>,
+        message_id: MessageId,
+        source: PeerId,
+        message: SignedMessage,
+    ) {
+        let from_key_address = message.message().from.protocol() != Protocol::ID;
+        let acceptance = match mem_pool.add_untrusted(message).await {
+            Ok(()) => MessageAcceptance::Accept,
+            Err(why) => {
+                debug!(
+                    "GossipSub message could not be added to the mem pool: {}",
+                    why
+                );
+                gossip_message_acceptance(&why, from_key_address)
+            }
+        };
+        network
+            .report_validation_result(message_id, source, acceptance)
+            .await;
     }
 
     #[allow(clippy::too_many_arguments)]
@@ -363,7 +392,7 @@ where
         message_processing_strategy: PubsubMessageProcessingStrategy,
         block_delay: u64,
     ) -> Result<Option<(FullTipset, PeerId)>, ChainMuxerError<C>> {
-        let (tipset, source) = match event {
+        let (tipset, source, from_gossip) = match event {
             NetworkEvent::HelloRequest { request, source } => {
                 metrics::LIBP2P_MESSAGE_TOTAL
                     .with_label_values(&[metrics::values::HELLO_REQUEST])
@@ -383,7 +412,7 @@ where
                         return Err(why);
                     }
                 };
-                (tipset, source)
+                (tipset, source, false)
             }
             NetworkEvent::PeerConnected(peer_id) => {
                 metrics::LIBP2P_MESSAGE_TOTAL
@@ -409,15 +438,33 @@ where
                 ));
                 return Ok(None);
             }
-            NetworkEvent::PubsubMessage { source, message } => match message {
+            NetworkEvent::PubsubMessage {
+                source,
+                message_id,
+                message,
+            } => match message {
                 PubsubMessage::Block(b) => {
                     metrics::LIBP2P_MESSAGE_TOTAL
                         .with_label_values(&[metrics::values::PUBSUB_BLOCK])
                         .inc();
+                    // Cheap checks before the block is relayed and its messages fetched
+                    let validation = GossipBlockValidator(&b)
+                        .validate(&bad_block_cache, &genesis, block_delay)
+                        .await;
+                    let acceptance = match &validation {
+                        Ok(()) => MessageAcceptance::Accept,
+                        // The block may become valid, or our clock may be off
+                        Err(TipsetValidationError::EpochTooLarge) => MessageAcceptance::Ignore,
+                        Err(_) => MessageAcceptance::Reject,
+                    };
+                    network
+                        .report_validation_result(message_id, source, acceptance)
+                        .await;
+                    validation?;
                     // Assemble full tipset from block
                     let tipset =
                         Self::gossipsub_block_to_full_tipset(b, source, network.clone()).await?;
-                    (tipset, source)
+                    (tipset, source, true)
                 }
                 PubsubMessage::Message(m) => {
                     metrics::LIBP2P_MESSAGE_TOTAL
@@ -425,7 +472,18 @@ where
                         .inc();
                     if let PubsubMessageProcessingStrategy::Process = message_processing_strategy {
                         // Spawn and immediately move on to the next event
-                        async_std::task::spawn(Self::handle_pubsub_message(mem_pool.clone(), m));
+                        async_std::task::spawn(Self::handle_pubsub_message(
+                            mem_pool.clone(),
+                            network.clone(),
+                            message_id,
+                            source,
+                            m,
+                        ));
+                    } else {
+                        // Messages can't be checked against the state before the node is synced
+                        network
+                            .report_validation_result(message_id, source, MessageAcceptance::Ignore)
+                            .await;
                     }
                     return Ok(None);
                 }
@@ -461,6 +519,9 @@ where
                 "Validating tipset received through GossipSub failed: {}",
                 why
             );
+            if from_gossip {
+                network.peer_manager().log_invalid_block(source).await;
+            }
             return Err(why.into());
         }
 
@@ -572,6 +633,7 @@ where
         let trs_network = self.network.clone();
         let trs_tracker = self.worker_state.clone();
         let trs_genesis = self.genesis.clone();
+        let trs_sync_config = self.sync_config.clone();
         let tipset_range_syncer: ChainMuxerFuture<(), ChainMuxerError<C>> = Box::pin(async move {
             let network_head_epoch = network_head.epoch();
             let tipset_range_syncer = match TipsetRangeSyncer::new(
@@ -584,6 +646,7 @@ where
                 trs_chain_store,
                 trs_bad_block_cache,
                 trs_genesis,
+                trs_sync_config,
             ) {
                 Ok(tipset_range_syncer) => tipset_range_syncer,
                 Err(why) => {
@@ -670,6 +733,7 @@ where
         let tp_tipset_receiver = self.tipset_receiver.clone();
         let tp_tracker = self.worker_state.clone();
         let tp_genesis = self.genesis.clone();
+        let tp_sync_config = self.sync_config.clone();
         enum UnexpectedReturnKind {
             TipsetProcessor,
         }
@@ -684,6 +748,7 @@ where
                     tp_chain_store,
                     tp_bad_block_cache,
                     tp_genesis,
+                    tp_sync_config,
                 )
                 .await
                 .map_err(ChainMuxerError::TipsetProcessor)?;
@@ -878,6 +943,23 @@ where
     }
 }
 
+/// GossipSub validation result of a message the mem pool did not accept. Messages that are
+/// invalid on their own are rejected, penalizing the peer that relayed them, while messages that
+/// only conflict with the current state of the pool are ignored.
+fn gossip_message_acceptance(
+    error: &MessagePoolError,
+    from_key_address: bool,
+) -> MessageAcceptance {
+    match error {
+        MessagePoolError::MessageTooBig
+        | MessagePoolError::MessageValueTooHigh
+        | MessagePoolError::InvalidMessage(_) => MessageAcceptance::Reject,
+        // Signatures of messages from ID addresses can't be checked without the state
+        MessagePoolError::InvalidSignature(_) if from_key_address => MessageAcceptance::Reject,
+        _ => MessageAcceptance::Ignore,
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use std::convert::TryFrom;
@@ -934,4 +1016,74 @@ mod tests {
         let smoke_height = ChainConfig::default().epoch(Height::Smoke);
         assert!(chain::compute_base_fee(&blockstore, &ts, smoke_height).is_err());
     }
+
+    #[test]
+    fn gossip_message_acceptance_test() {
+        use super::gossip_message_acceptance;
+        use forest_libp2p::MessageAcceptance;
+        use message_pool::Error;
+
+        assert!(matches!(
+            gossip_message_acceptance(&Error::MessageTooBig, true),
+            MessageAcceptance::Reject
+        ));
+        assert!(matches!(
+            gossip_message_acceptance(&Error::InvalidSignature(String::new()), true),
+            MessageAcceptance::Reject
+        ));
+        assert!(matches!(
+            gossip_message_acceptance(&Error::InvalidSignature(String::new()), false),
+            MessageAcceptance::Ignore
+        ));
+        assert!(matches!(
+            gossip_message_acceptance(&Error::NonceGap, true),
+            MessageAcceptance::Ignore
+        ));
+    }
+
+    #[test]
+    fn gossip_block_validation_test() {
+        use crate::bad_block_cache::BadBlockCache;
+        use crate::validation::{GossipBlockValidator, TipsetValidationError};
+        use async_std::task;
+        use forest_blocks::GossipBlock;
+        use fvm_shared::crypto::signature::Signature;
+
+        let genesis = Tipset::new(vec![BlockHeader::builder()
+            .miner_address(Address::new_id(0))
+            .build()
+            .unwrap()])
+        .unwrap();
+        let gossip_block = |signature: Option<Signature>, epoch| GossipBlock {
+            header: BlockHeader::builder()
+                .miner_address(Address::new_id(1))
+                .signature(signature)
+                .epoch(epoch)
+                .build()
+                .unwrap(),
+            bls_messages: vec![],
+            secpk_messages: vec![],
+        };
+        let bad_block_cache = BadBlockCache::new(10);
+        let validate = |block: &GossipBlock| {
+            task::block_on(GossipBlockValidator(block).validate(&bad_block_cache, &genesis, 30))
+        };
+
+        let block = gossip_block(Some(Signature::new_bls(vec![])), 1);
+        assert!(validate(&block).is_ok());
+        assert!(matches!(
+            validate(&gossip_block(None, 1)),
+            Err(TipsetValidationError::InvalidBlock(..))
+        ));
+        assert!(matches!(
+            validate(&gossip_block(Some(Signature::new_bls(vec![])), i64::MAX)),
+            Err(TipsetValidationError::EpochTooLarge)
+        ));
+
+        task::block_on(bad_block_cache.put(*block.header.cid(), "bad".to_owned()));
+        assert!(matches!(
+            validate(&block),
+            Err(TipsetValidationError::InvalidBlock(..))
+        ));
+    }
 }
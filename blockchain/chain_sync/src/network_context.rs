@@ -1,20 +1,20 @@
 // Copyright 2019-2022 ChainSafe Systems
 // SPDX-License-Identifier: Apache-2.0, MIT
 
-use super::peer_manager::PeerManager;
+use super::peer_manager::{PeerManager, SHUFFLE_PEERS_PREFIX};
 use cid::Cid;
 use encoding::de::DeserializeOwned;
 use forest_blocks::{FullTipset, Tipset, TipsetKeys};
 use forest_libp2p::{
     chain_exchange::{
-        ChainExchangeRequest, ChainExchangeResponse, CompactedMessages, TipsetBundle, HEADERS,
-        MESSAGES,
+        ChainExchangeRequest, ChainExchangeResponse, TipsetBundle, HEADERS, MESSAGES,
     },
     hello::{HelloRequest, HelloResponse},
     rpc::RequestResponseError,
-    NetworkMessage,
+    MessageAcceptance, MessageId, NetworkMessage,
 };
 use futures::channel::oneshot::channel as oneshot_channel;
+use futures::stream::{self, StreamExt, TryStreamExt};
 use ipld_blockstore::{BlockStore, BlockStoreExt};
 use libp2p::core::PeerId;
 use log::{debug, trace, warn};
@@ -73,6 +73,24 @@ where
         self.peer_manager.as_ref()
     }
 
+    /// Reports the validation result of a message received over GossipSub, the network service
+    /// only forwards the accepted messages to other peers.
+    pub async fn report_validation_result(
+        &self,
+        message_id: MessageId,
+        source: PeerId,
+        acceptance: MessageAcceptance,
+    ) {
+        let message = NetworkMessage::ValidationResult {
+            message_id,
+            source,
+            acceptance,
+        };
+        if self.network_send.send(message).await.is_err() {
+            warn!("Failed to report GossipSub validation result, network receiver dropped");
+        }
+    }
+
     /// Send a chain_exchange request for only block headers (ignore messages).
     /// If `peer_id` is `None`, requests will be sent to a set of shuffled peers.
     pub async fn chain_exchange_headers(
@@ -84,17 +102,6 @@ where
         self.handle_chain_exchange_request(peer_id, tsk, count, HEADERS)
             .await
     }
-    /// Send a chain_exchange request for only messages (ignore block headers).
-    /// If `peer_id` is `None`, requests will be sent to a set of shuffled peers.
-    pub async fn chain_exchange_messages(
-        &self,
-        peer_id: Option<PeerId>,
-        tsk: &TipsetKeys,
-        count: u64,
-    ) -> Result<Vec<CompactedMessages>, String> {
-        self.handle_chain_exchange_request(peer_id, tsk, count, MESSAGES)
-            .await
-    }
 
     /// Send a chain_exchange request for a single full tipset (includes messages)
     /// If `peer_id` is `None`, requests will be sent to a set of shuffled peers.
@@ -116,6 +123,127 @@ where
         Ok(fts.remove(0))
     }
 
+    /// Send chain_exchange requests for several windows of tipsets, each given by the key of its
+    /// newest tipset and its length. Up to `max_in_flight` windows are requested concurrently,
+    /// spread over the top scored peers. The tipsets of each response are checked and converted
+    /// by `validate`, given the window index, the key the response starts at and the number of
+    /// tipsets of the window received before it. It returns the converted tipsets along with the
+    /// key to request the rest of the window from, if the window continues past them. The rest
+    /// of a partially served window is requested from the same peer, while a response that fails
+    /// is requested again from a different peer. Results are returned in the order of the
+    /// windows, newest tipset first.
+    pub async fn chain_exchange_windows<T, R, F>(
+        &self,
+        windows: &[(TipsetKeys, u64)],
+        options: u64,
+        max_in_flight: usize,
+        validate: F,
+    ) -> Result<Vec<Vec<R>>, String>
+    where
+        T: TryFrom<TipsetBundle, Error = String>,
+        F: Fn(usize, &TipsetKeys, usize, Vec<T>) -> Result<(Vec<R>, Option<TipsetKeys>), String>,
+    {
+        let peers: Vec<PeerId> = self
+            .peer_manager
+            .sorted_peers()
+            .await
+            .into_iter()
+            .take(SHUFFLE_PEERS_PREFIX)
+            .collect();
+        if peers.is_empty() {
+            return Err("No peers to send chain exchange requests to".to_string());
+        }
+
+        let global_pre_time = SystemTime::now();
+        let peers = &peers;
+        let validate = &validate;
+        let results = stream::iter(windows.iter().enumerate())
+            .map(|(i, (tsk, len))| async move {
+                let mut received = Vec::with_capacity(*len as usize);
+                let mut start = tsk.clone();
+                // Start each window on a different peer, moving on to the next one on failure
+                let mut peer_idx = i;
+                let mut failures = 0;
+                while failures < peers.len() {
+                    let p = peers[peer_idx % peers.len()];
+                    let remaining = *len - received.len() as u64;
+                    let request = ChainExchangeRequest {
+                        start: start.cids().to_vec(),
+                        request_len: remaining,
+                        options,
+                    };
+                    let tipsets = match self
+                        .chain_exchange_request(p, request)
+                        .await
+                        .and_then(|res| res.into_result())
+                    {
+                        Ok(tipsets) => tipsets,
+                        Err(e) => {
+                            debug!("Failed chain_exchange request to peer {:?}: {}", p, e);
+                            failures += 1;
+                            peer_idx += 1;
+                            continue;
+                        }
+                    };
+                    let validated = if tipsets.is_empty() {
+                        Err("Empty response".to_string())
+                    } else if tipsets.len() as u64 > remaining {
+                        Err(format!(
+                            "Got {} tipsets for a request of {}",
+                            tipsets.len(),
+                            remaining
+                        ))
+                    } else {
+                        validate(i, &start, received.len(), tipsets)
+                    };
+                    match validated {
+                        Ok((tipsets, next)) => {
+                            received.extend(tipsets);
+                            match next {
+                                Some(next) if (received.len() as u64) < *len => {
+                                    debug!(
+                                        "Partial chain_exchange response from peer {:?}, {} of {} tipsets",
+                                        p,
+                                        received.len(),
+                                        len
+                                    );
+                                    start = next;
+                                }
+                                _ => return Ok(received),
+                            }
+                        }
+                        Err(e) => {
+                            debug!("Invalid chain_exchange response from peer {:?}: {}", p, e);
+                            self.peer_manager
+                                .log_invalid_response(
+                                    p,
+                                    format!("Invalid chain exchange response: {}", e),
+                                )
+                                .await;
+                            failures += 1;
+                            peer_idx += 1;
+                        }
+                    }
+                }
+                Err(format!(
+                    "ChainExchange request for window {} failed for all top peers",
+                    i
+                ))
+            })
+            .buffered(max_in_flight.max(1))
+            .try_collect()
+            .await?;
+
+        match SystemTime::now().duration_since(global_pre_time) {
+            Ok(t) => self.peer_manager.log_global_success(t).await,
+            Err(e) => {
+                warn!("logged time less than before request: {}", e);
+            }
+        }
+
+        Ok(results)
+    }
+
     /// Requests that some content with a particular Cid get fetched over Bitswap if it doesn't
     /// exist in the BlockStore.
     pub async fn bitswap_get<TMessage: DeserializeOwned>(
@@ -256,14 +384,19 @@ where
             Ok(Ok(Err(e))) => {
                 // Internal libp2p error, score failure for peer and potentially disconnect
                 match e {
-                    RequestResponseError::ConnectionClosed
-                    | RequestResponseError::DialFailure
-                    | RequestResponseError::UnsupportedProtocols => {
-                        self.peer_manager.mark_peer_bad(peer_id).await;
+                    RequestResponseError::UnsupportedProtocols => {
+                        self.peer_manager
+                            .mark_peer_bad(
+                                peer_id,
+                                format!("Chain exchange request failed: {:?}", e),
+                            )
+                            .await;
                     }
                     // Ignore dropping peer on timeout for now. Can't be confident yet that the
-                    // specified timeout is adequate time.
-                    RequestResponseError::Timeout => {
+                    // specified timeout is adequate time. Connection failures are transient too.
+                    RequestResponseError::ConnectionClosed
+                    | RequestResponseError::DialFailure
+                    | RequestResponseError::Timeout => {
                         self.peer_manager.log_failure(peer_id, res_duration).await;
                     }
                 }
@@ -319,3 +452,136 @@ where
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use async_std::channel::bounded;
+    use async_std::task;
+    use db::MemoryDB;
+    use forest_blocks::BlockHeader;
+    use forest_libp2p::chain_exchange::ChainExchangeResponseStatus;
+    use fvm_shared::address::Address;
+    use std::sync::Mutex;
+
+    /// Returns a chain of single block tipsets, newest first.
+    fn mock_chain(len: i64) -> Vec<Arc<Tipset>> {
+        let mut parents = TipsetKeys::default();
+        let mut tipsets = Vec::new();
+        for epoch in 0..len {
+            let header = BlockHeader::builder()
+                .miner_address(Address::new_id(epoch as u64))
+                .parents(parents)
+                .epoch(epoch)
+                .build()
+                .unwrap();
+            let tipset = Arc::new(Tipset::new(vec![header]).unwrap());
+            parents = tipset.key().clone();
+            tipsets.push(tipset);
+        }
+        tipsets.reverse();
+        tipsets
+    }
+
+    #[test]
+    fn chain_exchange_windows_retry_and_resume() {
+        let chain = mock_chain(6);
+        let (good, bad) = (PeerId::random(), PeerId::random());
+        let requests = Arc::new(Mutex::new(Vec::new()));
+
+        // Stub network service: the bad peer fails all requests, the good one serves at most
+        // two tipsets per request
+        let (network_send, network_receiver) = bounded(16);
+        let served = chain.clone();
+        let logged = requests.clone();
+        task::spawn(async move {
+            while let Ok(message) = network_receiver.recv().await {
+                if let NetworkMessage::ChainExchangeRequest {
+                    peer_id,
+                    request,
+                    response_channel,
+                } = message
+                {
+                    logged.lock().unwrap().push((
+                        peer_id,
+                        request.start.clone(),
+                        request.request_len,
+                    ));
+                    let response = if peer_id == bad {
+                        ChainExchangeResponse {
+                            status: ChainExchangeResponseStatus::InternalError,
+                            message: "failed".to_string(),
+                            chain: vec![],
+                        }
+                    } else {
+                        let start = served
+                            .iter()
+                            .position(|ts| ts.cids() == request.start.as_slice())
+                            .unwrap();
+                        ChainExchangeResponse {
+                            status: ChainExchangeResponseStatus::PartialResponse,
+                            message: String::new(),
+                            chain: served[start..]
+                                .iter()
+                                .take(request.request_len.min(2) as usize)
+                                .map(|ts| TipsetBundle {
+                                    blocks: ts.blocks().to_vec(),
+                                    messages: None,
+                                })
+                                .collect(),
+                        }
+                    };
+                    let _ = response_channel.send(Ok(response));
+                }
+            }
+        });
+
+        let peer_manager = PeerManager::default();
+        task::block_on(peer_manager.update_peer_head(good, chain[0].clone()));
+        task::block_on(peer_manager.update_peer_head(bad, chain[0].clone()));
+        let network = SyncNetworkContext::new(
+            network_send,
+            Arc::new(peer_manager),
+            Arc::new(MemoryDB::default()),
+        );
+
+        // Each window starts on a different peer, so one of them is retried on the good peer
+        let windows = [(chain[0].key().clone(), 3), (chain[3].key().clone(), 3)];
+        let fetched = task::block_on(network.chain_exchange_windows(
+            &windows,
+            HEADERS,
+            2,
+            |_, start, _, tipsets: Vec<Arc<Tipset>>| {
+                if tipsets[0].key() != start {
+                    return Err("Response doesn't start at the requested tipset".to_string());
+                }
+                let next = tipsets.last().map(|ts| ts.parents().clone());
+                Ok((tipsets, next))
+            },
+        ))
+        .unwrap();
+        assert_eq!(fetched, vec![chain[..3].to_vec(), chain[3..].to_vec()]);
+
+        let requests = requests.lock().unwrap();
+        let bad_requests = requests.iter().filter(|(p, _, _)| *p == bad).count();
+        assert_eq!(bad_requests, 1);
+
+        // The rest of a partially served window is requested from the same peer
+        let mut good_requests: Vec<_> = requests
+            .iter()
+            .filter(|(p, _, _)| *p == good)
+            .map(|(_, start, len)| (start.clone(), *len))
+            .collect();
+        good_requests
+            .sort_by_key(|(start, _)| chain.iter().position(|ts| ts.cids() == start.as_slice()));
+        assert_eq!(
+            good_requests,
+            vec![
+                (chain[0].cids().to_vec(), 3),
+                (chain[2].cids().to_vec(), 1),
+                (chain[3].cids().to_vec(), 3),
+                (chain[5].cids().to_vec(), 1),
+            ]
+        );
+    }
+}
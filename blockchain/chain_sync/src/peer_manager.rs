@@ -1,13 +1,15 @@
 // Copyright 2019-2022 ChainSafe Systems
 // SPDX-License-Identifier: Apache-2.0, MIT
 
+use std::cmp::Ordering;
 use std::collections::HashMap;
 use std::sync::Arc;
 use std::time::Duration;
-use std::{cmp::Ordering, collections::HashSet};
 
+use async_std::channel::Sender;
 use async_std::sync::RwLock;
 use forest_blocks::Tipset;
+use forest_libp2p::{NetworkMessage, PeerRecord, PeerStore};
 use libp2p::core::PeerId;
 use log::{debug, trace};
 use rand::seq::SliceRandom;
@@ -25,6 +27,16 @@ const LOCAL_INV_ALPHA: u32 = 5;
 /// Global duration multiplier, affects duration delta change.
 const GLOBAL_INV_ALPHA: u32 = 20;
 
+/// Duration for which peers marked bad are banned.
+const BAN_DURATION: Duration = Duration::from_secs(30 * 60);
+
+/// GossipSub application score of a peer serving all requests successfully, the score of a
+/// peer failing all of them is the opposite.
+const APP_SCORE_SUCCESS_WEIGHT: f64 = 20.0;
+/// GossipSub application score penalty for each invalid block relayed by a peer. A few invalid
+/// blocks take the peer below the graylist threshold.
+const APP_SCORE_INVALID_BLOCK_PENALTY: f64 = 1000.0;
+
 #[derive(Debug, Default)]
 /// Contains info about the peer's head [Tipset], as well as the request stats.
 struct PeerInfo {
@@ -39,12 +51,14 @@ struct PeerInfo {
 }
 
 impl PeerInfo {
-    fn new(head: Arc<Tipset>) -> Self {
+    /// Creates the info of a peer, starting from the request stats recorded before if any.
+    fn new(head: Option<Arc<Tipset>>, record: Option<PeerRecord>) -> Self {
+        let record = record.unwrap_or_default();
         Self {
-            head: Some(head),
-            successes: 0,
-            failures: 0,
-            average_time: Default::default(),
+            head,
+            successes: record.successes,
+            failures: record.failures,
+            average_time: record.average_time,
         }
     }
 }
@@ -55,20 +69,36 @@ impl PeerInfo {
 struct PeerSets {
     /// Map of full peers available.
     full_peers: HashMap<PeerId, PeerInfo>,
-    /// Set of peers to ignore for being incompatible/ failing to accept connections.
-    bad_peers: HashSet<PeerId>,
+    /// Number of invalid blocks received over GossipSub from each peer while connected.
+    invalid_blocks: HashMap<PeerId, u32>,
 }
 
 /// Thread safe peer manager which handles peer management for the `ChainExchange` protocol.
+/// Request stats and bans are kept in the [PeerStore] so that they outlive a restart.
 #[derive(Default)]
 pub(crate) struct PeerManager {
-    /// Full and bad peer sets.
+    /// Full peer set.
     peers: RwLock<PeerSets>,
     /// Average response time from peers.
     avg_global_time: RwLock<Duration>,
+    /// Persisted peer records, including the bans of peers to ignore for being incompatible or
+    /// serving invalid data.
+    peer_store: Arc<PeerStore>,
+    /// Channel to the network service, used to update the GossipSub application scores.
+    network_send: Option<Sender<NetworkMessage>>,
 }
 
 impl PeerManager {
+    pub fn new(peer_store: Arc<PeerStore>, network_send: Sender<NetworkMessage>) -> Self {
+        let peer_manager = Self {
+            peer_store,
+            network_send: Some(network_send),
+            ..Default::default()
+        };
+        peer_manager.update_bad_peers_metric();
+        peer_manager
+    }
+
     /// Updates peer's heaviest tipset. If the peer does not exist in the set, a new `PeerInfo`
     /// will be generated.
     pub async fn update_peer_head(&self, peer_id: PeerId, ts: Arc<Tipset>) {
@@ -77,15 +107,16 @@ impl PeerManager {
         if let Some(pi) = peers.full_peers.get_mut(&peer_id) {
             pi.head = Some(ts);
         } else {
-            peers.full_peers.insert(peer_id, PeerInfo::new(ts));
+            let info = PeerInfo::new(Some(ts), self.peer_store.get(&peer_id));
+            peers.full_peers.insert(peer_id, info);
             metrics::FULL_PEERS.inc();
         }
     }
 
-    /// Returns true if peer is not marked as bad or not already in set.
+    /// Returns true if peer is not banned or not already in set.
     pub async fn is_peer_new(&self, peer_id: &PeerId) -> bool {
         let peers = self.peers.read().await;
-        !peers.bad_peers.contains(peer_id) && !peers.full_peers.contains_key(peer_id)
+        !self.peer_store.is_banned(peer_id) && !peers.full_peers.contains_key(peer_id)
     }
 
     /// Sort peers based on a score function with the success rate and latency of requests.
@@ -148,49 +179,58 @@ impl PeerManager {
     pub async fn log_success(&self, peer: PeerId, dur: Duration) {
         debug!("logging success for {:?}", peer);
         let mut peers = self.peers.write().await;
-        // Attempt to remove the peer and decrement bad peer count
-        if peers.bad_peers.remove(&peer) {
-            metrics::BAD_PEERS.dec();
+        // Attempt to lift the ban of the peer and update the bad peer count
+        if self.peer_store.unban(&peer) {
+            self.update_bad_peers_metric();
         };
-        // If the peer is not already accounted for, increment full peer count
-        if !peers.full_peers.contains_key(&peer) {
-            metrics::FULL_PEERS.inc();
-        }
-        let peer_stats = peers.full_peers.entry(peer).or_default();
+        let peer_stats = self.peer_info_mut(&mut peers, peer);
         peer_stats.successes += 1;
         log_time(peer_stats, dur);
+        self.record_stats(peer, &peers);
     }
 
     /// Logs a failure for the given peer, and updates the average request duration.
     pub async fn log_failure(&self, peer: PeerId, dur: Duration) {
         debug!("logging failure for {:?}", peer);
         let mut peers = self.peers.write().await;
-        if !peers.bad_peers.contains(&peer) {
+        if !self.peer_store.is_banned(&peer) {
             metrics::PEER_FAILURE_TOTAL.inc();
-            if !peers.full_peers.contains_key(&peer) {
-                metrics::FULL_PEERS.inc();
-            }
-            let peer_stats = peers.full_peers.entry(peer).or_default();
+            let peer_stats = self.peer_info_mut(&mut peers, peer);
             peer_stats.failures += 1;
             log_time(peer_stats, dur);
+            self.record_stats(peer, &peers);
         }
     }
 
-    /// Removes a peer from the set and returns true if the value was present previously
-    pub async fn mark_peer_bad(&self, peer_id: PeerId) -> bool {
+    /// Logs a failure for the given peer that responded with data failing validation, and bans
+    /// it for a while. The response time was already accounted for when the response was
+    /// received.
+    pub async fn log_invalid_response(&self, peer: PeerId, reason: impl Into<String>) {
+        debug!("logging invalid response for {:?}", peer);
         let mut peers = self.peers.write().await;
-        let removed = remove_peer(&mut peers, &peer_id);
-        if removed {
-            metrics::FULL_PEERS.dec();
+        if let Some(peer_stats) = peers.full_peers.get_mut(&peer) {
+            metrics::PEER_FAILURE_TOTAL.inc();
+            peer_stats.failures += 1;
+            self.record_stats(peer, &peers);
         }
+        self.ban_peer(&mut peers, peer, reason.into());
+    }
 
-        // Add peer to bad peer set
-        debug!("marked peer {} bad", peer_id);
-        if peers.bad_peers.insert(peer_id) {
-            metrics::BAD_PEERS.inc();
-        }
+    /// Logs an invalid block received over GossipSub from the given peer, lowering its GossipSub
+    /// score so that the messages it relays end up being ignored.
+    pub async fn log_invalid_block(&self, peer: PeerId) {
+        debug!("logging invalid block from {:?}", peer);
+        let mut peers = self.peers.write().await;
+        *peers.invalid_blocks.entry(peer).or_default() += 1;
+        self.update_application_score(peer, &peers);
+    }
 
-        removed
+    /// Removes a peer from the set and bans it for a while, for misbehaving on the protocol level.
+    /// Transient failures, such as closed connections, are logged as failures instead. Returns
+    /// true if the value was present previously
+    pub async fn mark_peer_bad(&self, peer_id: PeerId, reason: impl Into<String>) -> bool {
+        let mut peers = self.peers.write().await;
+        self.ban_peer(&mut peers, peer_id, reason.into())
     }
 
     /// Remove peer from managed set, does not mark as bad
@@ -204,6 +244,60 @@ impl PeerManager {
         removed
     }
 
+    fn ban_peer(&self, peers: &mut PeerSets, peer_id: PeerId, reason: String) -> bool {
+        let removed = remove_peer(peers, &peer_id);
+        if removed {
+            metrics::FULL_PEERS.dec();
+        }
+
+        // Ban the peer, bans are lifted after some time
+        debug!("marked peer {} bad: {}", peer_id, reason);
+        self.peer_store.ban(peer_id, reason, BAN_DURATION);
+        self.update_bad_peers_metric();
+
+        removed
+    }
+
+    /// Returns the info of a peer, adding it to the full peer set if not accounted for yet.
+    fn peer_info_mut<'a>(&self, peers: &'a mut PeerSets, peer: PeerId) -> &'a mut PeerInfo {
+        peers.full_peers.entry(peer).or_insert_with(|| {
+            metrics::FULL_PEERS.inc();
+            PeerInfo::new(None, self.peer_store.get(&peer))
+        })
+    }
+
+    fn record_stats(&self, peer: PeerId, peers: &PeerSets) {
+        if let Some(info) = peers.full_peers.get(&peer) {
+            self.peer_store
+                .update_stats(peer, info.successes, info.failures, info.average_time);
+        }
+        self.update_application_score(peer, peers);
+    }
+
+    /// Sends the application specific part of the GossipSub score of a peer to the network
+    /// service.
+    fn update_application_score(&self, peer_id: PeerId, peers: &PeerSets) {
+        if let Some(network_send) = &self.network_send {
+            let score = application_score(
+                peers.full_peers.get(&peer_id),
+                peers
+                    .invalid_blocks
+                    .get(&peer_id)
+                    .copied()
+                    .unwrap_or_default(),
+            );
+            if let Err(e) =
+                network_send.try_send(NetworkMessage::ApplicationScore { peer_id, score })
+            {
+                debug!("Failed to update application score of {}: {}", peer_id, e);
+            }
+        }
+    }
+
+    fn update_bad_peers_metric(&self) {
+        metrics::BAD_PEERS.set(self.peer_store.banned_peers().len() as u64);
+    }
+
     /// Gets count of full peers managed. This is just used for testing.
     #[allow(dead_code)]
     pub async fn len(&self) -> usize {
@@ -218,9 +312,20 @@ fn remove_peer(peers: &mut PeerSets, peer_id: &PeerId) -> bool {
         peers.full_peers.len()
     );
 
+    peers.invalid_blocks.remove(peer_id);
     peers.full_peers.remove(peer_id).is_some()
 }
 
+/// GossipSub application score of a peer from the success rate of the requests it served and
+/// the invalid blocks it relayed.
+fn application_score(info: Option<&PeerInfo>, invalid_blocks: u32) -> f64 {
+    let success_rate = info.map_or(0.5, |info| {
+        f64::from(info.successes + 1) / f64::from(info.successes + info.failures + 2)
+    });
+    APP_SCORE_SUCCESS_WEIGHT * (2.0 * success_rate - 1.0)
+        - APP_SCORE_INVALID_BLOCK_PENALTY * f64::from(invalid_blocks)
+}
+
 fn log_time(info: &mut PeerInfo, dur: Duration) {
     if info.average_time == Duration::default() {
         info.average_time = dur;
@@ -232,3 +337,52 @@ fn log_time(info: &mut PeerInfo, dur: Duration) {
         info.average_time += delta
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use async_std::task;
+
+    #[test]
+    fn bans_only_misbehaving_peers() {
+        let peer_manager = PeerManager::default();
+        let peer = PeerId::random();
+        let dur = Duration::from_millis(10);
+
+        task::block_on(peer_manager.log_failure(peer, dur));
+        assert!(!peer_manager.peer_store.is_banned(&peer));
+        assert_eq!(task::block_on(peer_manager.len()), 1);
+
+        task::block_on(peer_manager.log_invalid_response(peer, "Invalid tipset"));
+        assert!(peer_manager.peer_store.is_banned(&peer));
+        assert_eq!(task::block_on(peer_manager.len()), 0);
+
+        // A successful request lifts the ban
+        task::block_on(peer_manager.log_success(peer, dur));
+        assert!(!peer_manager.peer_store.is_banned(&peer));
+    }
+
+    #[test]
+    fn application_score_from_requests_and_invalid_blocks() {
+        let info = |successes, failures| PeerInfo {
+            successes,
+            failures,
+            ..Default::default()
+        };
+        // Unknown peers and peers without requests are neutral
+        assert_eq!(application_score(None, 0), 0.0);
+        assert_eq!(application_score(Some(&info(0, 0)), 0), 0.0);
+
+        // The score tends to the success weight as requests succeed, and to its opposite as
+        // they fail
+        let reliable = application_score(Some(&info(98, 0)), 0);
+        assert!(reliable > 0.0 && reliable <= APP_SCORE_SUCCESS_WEIGHT);
+        assert!((reliable - APP_SCORE_SUCCESS_WEIGHT * 0.98).abs() < 1e-9);
+        assert!((application_score(Some(&info(0, 98)), 0) + reliable).abs() < 1e-9);
+
+        // A few invalid blocks outweigh any request history
+        let spammer = application_score(Some(&info(98, 0)), 3);
+        assert!(spammer < -2500.0);
+        assert_eq!(spammer, reliable - 3.0 * APP_SCORE_INVALID_BLOCK_PENALTY);
+    }
+}
@@ -1,7 +1,7 @@
 // Copyright 2019-2022 ChainSafe Systems
 // SPDX-License-Identifier: Apache-2.0, MIT
 
-use std::cmp::{min, Ordering};
+use std::cmp::{max, min, Ordering};
 use std::collections::{HashMap, HashSet};
 use std::convert::TryFrom;
 use std::sync::Arc;
@@ -20,6 +20,7 @@ use nonempty::NonEmpty;
 use thiserror::Error;
 
 use crate::bad_block_cache::BadBlockCache;
+use crate::chain_muxer::SyncConfig;
 use crate::consensus::{collect_errs, Consensus};
 use crate::metrics;
 use crate::network_context::SyncNetworkContext;
@@ -34,7 +35,7 @@ use fil_types::{ALLOWABLE_CLOCK_DRIFT, BLOCK_GAS_LIMIT};
 use forest_blocks::{
     Block, BlockHeader, Error as ForestBlockError, FullTipset, Tipset, TipsetKeys,
 };
-use forest_libp2p::chain_exchange::TipsetBundle;
+use forest_libp2p::chain_exchange::{CompactedMessages, TipsetBundle, HEADERS, MESSAGES};
 use forest_message::message::valid_for_block_inclusion;
 use forest_message::Message as MessageTrait;
 use fvm::gas::price_list_by_network_version;
@@ -47,8 +48,6 @@ use networks::Height;
 use state_manager::Error as StateManagerError;
 use state_manager::StateManager;
 
-const MAX_TIPSETS_TO_REQUEST: u64 = 100;
-
 #[derive(Debug, Error)]
 pub enum TipsetProcessorError<C: Consensus> {
     #[error("TipsetRangeSyncer error: {0}")]
@@ -243,6 +242,7 @@ pub(crate) struct TipsetProcessor<DB, C: Consensus> {
     chain_store: Arc<ChainStore<DB>>,
     bad_block_cache: Arc<BadBlockCache>,
     genesis: Arc<Tipset>,
+    sync_config: SyncConfig,
 }
 
 impl<DB, C> TipsetProcessor<DB, C>
@@ -260,6 +260,7 @@ where
         chain_store: Arc<ChainStore<DB>>,
         bad_block_cache: Arc<BadBlockCache>,
         genesis: Arc<Tipset>,
+        sync_config: SyncConfig,
     ) -> Self {
         Self {
             state: TipsetProcessorState::Idle,
@@ -271,6 +272,7 @@ where
             chain_store,
             bad_block_cache,
             genesis,
+            sync_config,
         }
     }
 
@@ -285,6 +287,7 @@ where
         let bad_block_cache = self.bad_block_cache.clone();
         let tracker = self.tracker.clone();
         let genesis = self.genesis.clone();
+        let sync_config = self.sync_config.clone();
         Box::pin(async move {
             // Define the low end of the range
             // Unwrapping is safe here because the store always has at least one tipset
@@ -307,6 +310,7 @@ where
                 chain_store,
                 bad_block_cache,
                 genesis,
+                sync_config,
             )?;
             for tipset in tipset_group.tipsets() {
                 tipset_range_syncer.add_tipset(tipset)?;
@@ -613,6 +617,7 @@ pub(crate) struct TipsetRangeSyncer<DB, C: Consensus> {
     bad_block_cache: Arc<BadBlockCache>,
     genesis: Arc<Tipset>,
     consensus: Arc<C>,
+    sync_config: SyncConfig,
 }
 
 impl<DB, C> TipsetRangeSyncer<DB, C>
@@ -631,6 +636,7 @@ where
         chain_store: Arc<ChainStore<DB>>,
         bad_block_cache: Arc<BadBlockCache>,
         genesis: Arc<Tipset>,
+        sync_config: SyncConfig,
     ) -> Result<Self, TipsetRangeSyncerError<C>> {
         let tipset_tasks = Box::pin(FuturesUnordered::new());
         let tipset_range_length = proposed_head.epoch() - current_head.epoch();
@@ -653,6 +659,7 @@ where
             network.clone(),
             bad_block_cache.clone(),
             genesis.clone(),
+            sync_config.clone(),
         ));
 
         let mut tipsets_included = HashSet::new();
@@ -668,6 +675,7 @@ where
             chain_store,
             bad_block_cache,
             genesis,
+            sync_config,
         })
     }
 
@@ -705,6 +713,7 @@ where
             self.network.clone(),
             self.bad_block_cache.clone(),
             self.genesis.clone(),
+            self.sync_config.clone(),
         ));
         Ok(true)
     }
@@ -752,6 +761,7 @@ fn sync_tipset_range<DB: BlockStore + Sync + Send + 'static, C: Consensus>(
     network: SyncNetworkContext<DB>,
     bad_block_cache: Arc<BadBlockCache>,
     genesis: Arc<Tipset>,
+    sync_config: SyncConfig,
 ) -> TipsetRangeSyncerFuture<C> {
     Box::pin(async move {
         tracker
@@ -767,6 +777,7 @@ fn sync_tipset_range<DB: BlockStore + Sync + Send + 'static, C: Consensus>(
             bad_block_cache.clone(),
             chain_store.clone(),
             network.clone(),
+            &sync_config,
         )
         .await
         {
@@ -797,6 +808,7 @@ fn sync_tipset_range<DB: BlockStore + Sync + Send + 'static, C: Consensus>(
             parent_tipsets,
             genesis,
             InvalidBlockStrategy::Strict,
+            &sync_config,
         )
         .await
         {
@@ -837,6 +849,7 @@ async fn sync_headers_in_reverse<DB: BlockStore + Sync + Send + 'static, C: Cons
     bad_block_cache: Arc<BadBlockCache>,
     chain_store: Arc<ChainStore<DB>>,
     network: SyncNetworkContext<DB>,
+    sync_config: &SyncConfig,
 ) -> Result<Vec<Arc<Tipset>>, TipsetRangeSyncerError<C>> {
     let mut parent_blocks: Vec<Cid> = vec![];
     let mut parent_tipsets = Vec::with_capacity(tipset_range_length as usize + 1);
@@ -848,6 +861,14 @@ async fn sync_headers_in_reverse<DB: BlockStore + Sync + Send + 'static, C: Cons
     pb.message("Downloading headers ");
     pb.set_max_refresh_rate(Some(std::time::Duration::from_millis(500)));
 
+    let window_len = max(sync_config.req_window, 1);
+    // A window can only be requested once the key of its newest tipset is known from the window
+    // before it, so the next window is requested while the previous one is being processed
+    let mut next_window: Option<(
+        TipsetKeys,
+        task::JoinHandle<Result<Vec<Arc<Tipset>>, String>>,
+    )> = None;
+
     'sync: loop {
         // Unwrapping is safe here because the tipset vector always
         // has at least one element
@@ -874,13 +895,32 @@ async fn sync_headers_in_reverse<DB: BlockStore + Sync + Send + 'static, C: Cons
             continue;
         }
 
-        // TODO: Tweak request window when socket frame is tested
-        let epoch_diff = oldest_parent.epoch() - current_head.epoch();
-        let window = min(epoch_diff, MAX_TIPSETS_TO_REQUEST as i64);
-        let network_tipsets = network
-            .chain_exchange_headers(None, oldest_parent.parents(), window as u64)
-            .await
-            .map_err(TipsetRangeSyncerError::NetworkTipsetQueryFailed)?;
+        let start = oldest_parent.parents().clone();
+        let network_tipsets = match next_window.take() {
+            Some((next_start, handle)) if next_start == start => handle.await,
+            _ => {
+                let epoch_diff = oldest_parent.epoch() - current_head.epoch();
+                let len = min(epoch_diff, window_len) as u64;
+                request_header_window(network.clone(), start, len).await
+            }
+        }
+        .map_err(TipsetRangeSyncerError::NetworkTipsetQueryFailed)?;
+
+        // Windows are reassembled in order, the following one is already requested from the
+        // parents of the oldest tipset received
+        if let Some(oldest) = network_tipsets.last() {
+            let epoch_diff = oldest.epoch() - current_head.epoch();
+            if epoch_diff > 0 {
+                let len = min(epoch_diff, window_len) as u64;
+                let next_start = oldest.parents().clone();
+                let handle = task::spawn(request_header_window(
+                    network.clone(),
+                    next_start.clone(),
+                    len,
+                ));
+                next_window = Some((next_start, handle));
+            }
+        }
 
         for tipset in network_tipsets {
             // Break if have already traversed the entire tipset range
@@ -895,6 +935,9 @@ async fn sync_headers_in_reverse<DB: BlockStore + Sync + Send + 'static, C: Cons
         }
     }
     pb.finish();
+    if let Some((_, handle)) = next_window {
+        handle.cancel().await;
+    }
 
     // Unwrapping is safe here because we assume that the tipset
     // vector was initialized with a tipset that will not be removed
@@ -960,6 +1003,7 @@ fn sync_tipset<DB: BlockStore + Sync + Send + 'static, C: Consensus>(
     network: SyncNetworkContext<DB>,
     bad_block_cache: Arc<BadBlockCache>,
     genesis: Arc<Tipset>,
+    sync_config: SyncConfig,
 ) -> TipsetRangeSyncerFuture<C> {
     Box::pin(async move {
         // Persist the blocks from the proposed tipsets into the store
@@ -978,6 +1022,7 @@ fn sync_tipset<DB: BlockStore + Sync + Send + 'static, C: Consensus>(
             vec![proposed_head.clone()],
             genesis,
             InvalidBlockStrategy::Forgiving,
+            &sync_config,
         )
         .await
         {
@@ -1002,6 +1047,7 @@ fn sync_tipset<DB: BlockStore + Sync + Send + 'static, C: Consensus>(
 
 /// Going forward along the tipsets, try to load the messages in them from the blockstore,
 /// or download them from the network, then validate the full tipset on each epoch.
+/// Missing messages are downloaded in batches of windows requested concurrently.
 #[allow(clippy::too_many_arguments)]
 async fn sync_messages_check_state<DB: BlockStore + Send + Sync + 'static, C: Consensus>(
     tracker: crate::chain_muxer::WorkerState,
@@ -1013,91 +1059,218 @@ async fn sync_messages_check_state<DB: BlockStore + Send + Sync + 'static, C: Co
     tipsets: Vec<Arc<Tipset>>,
     genesis: Arc<Tipset>,
     invalid_block_strategy: InvalidBlockStrategy,
+    sync_config: &SyncConfig,
 ) -> Result<(), TipsetRangeSyncerError<C>> {
     // Iterate through tipsets in chronological order
-    let mut tipset_iter = tipsets.into_iter().rev();
-
-    // Sync the messages for one tipset @ a time
-    const REQUEST_WINDOW: usize = 1;
-
-    while let Some(tipset) = tipset_iter.next() {
-        match chainstore.fill_tipset(&tipset) {
-            Some(full_tipset) => {
-                let current_epoch = full_tipset.epoch();
-                validate_tipset::<_, C>(
-                    consensus.clone(),
-                    state_manager.clone(),
-                    chainstore.clone(),
-                    bad_block_cache.clone(),
-                    full_tipset,
-                    genesis.clone(),
-                    invalid_block_strategy,
-                )
-                .await?;
-                tracker.write().await.set_epoch(current_epoch);
-                metrics::LAST_VALIDATED_TIPSET_EPOCH.set(current_epoch as u64);
+    let tipsets: Vec<_> = tipsets.into_iter().rev().collect();
+    let window = max(sync_config.req_window, 1) as usize;
+    let max_in_flight = max(sync_config.max_in_flight_windows, 1);
+
+    for batch in tipsets.chunks(window * max_in_flight) {
+        let mut full_tipsets: Vec<_> = batch.iter().map(|t| chainstore.fill_tipset(t)).collect();
+        let from_network: Vec<_> = full_tipsets.iter().map(Option::is_none).collect();
+        fetch_missing_messages(
+            &network,
+            chainstore.blockstore(),
+            batch,
+            &mut full_tipsets,
+            window,
+            max_in_flight,
+        )
+        .await
+        .map_err(TipsetRangeSyncerError::NetworkMessageQueryFailed)?;
+
+        for (full_tipset, from_network) in full_tipsets.into_iter().zip(from_network) {
+            let full_tipset = full_tipset.ok_or_else(|| {
+                TipsetRangeSyncerError::NetworkMessageQueryFailed(String::from(
+                    "Messages missing for tipset in chain",
+                ))
+            })?;
+            // Keep the downloaded messages around to persist them once validated
+            let blocks = if from_network {
+                full_tipset.blocks().to_vec()
+            } else {
+                Vec::new()
+            };
+
+            // Validate the tipset and the messages
+            let timer = metrics::TIPSET_PROCESSING_TIME.start_timer();
+            let current_epoch = full_tipset.epoch();
+            validate_tipset::<_, C>(
+                consensus.clone(),
+                state_manager.clone(),
+                chainstore.clone(),
+                bad_block_cache.clone(),
+                full_tipset,
+                genesis.clone(),
+                invalid_block_strategy,
+            )
+            .await?;
+            tracker.write().await.set_epoch(current_epoch);
+            timer.observe_duration();
+            metrics::LAST_VALIDATED_TIPSET_EPOCH.set(current_epoch as u64);
+
+            // Persist the messages in the store
+            for block in blocks {
+                chain::persist_objects(chainstore.blockstore(), block.bls_msgs())?;
+                chain::persist_objects(chainstore.blockstore(), block.secp_msgs())?;
             }
-            None => {
-                // Full tipset is not in storage; request messages via chain_exchange
-                let batch_size = REQUEST_WINDOW;
-                debug!(
-                    "ChainExchange message sync tipsets: epoch: {}, len: {}",
-                    tipset.epoch(),
-                    batch_size,
-                );
+        }
+    }
+    Ok(())
+}
 
-                // Receive tipset bundle from block sync
-                let compacted_messages = network
-                    .chain_exchange_messages(None, tipset.key(), batch_size as u64)
-                    .await
-                    .map_err(TipsetRangeSyncerError::NetworkMessageQueryFailed)?;
-                // Chain current tipset with iterator
-                let mut inner_iter = std::iter::once(tipset).chain(&mut tipset_iter);
-
-                // Since the bundle only has messages, we have to put the headers in them
-                for messages in compacted_messages {
-                    // Construct full tipset from fetched messages
-                    let tipset = inner_iter.next().ok_or_else(|| {
-                        TipsetRangeSyncerError::NetworkMessageQueryFailed(String::from(
-                            "Messages returned exceeded tipsets in chain",
-                        ))
-                    })?;
-
-                    let bundle = TipsetBundle {
-                        blocks: tipset.blocks().to_vec(),
-                        messages: Some(messages),
-                    };
+/// Requests the messages of the tipsets in the batch, in chronological order, that are missing
+/// from `full_tipsets`. Runs of consecutive missing tipsets are split into windows of at most
+/// `window` tipsets, which are requested concurrently.
+async fn fetch_missing_messages<DB: BlockStore + Send + Sync + 'static>(
+    network: &SyncNetworkContext<DB>,
+    db: &DB,
+    batch: &[Arc<Tipset>],
+    full_tipsets: &mut [Option<FullTipset>],
+    window: usize,
+    max_in_flight: usize,
+) -> Result<(), String> {
+    let mut ranges: Vec<std::ops::Range<usize>> = Vec::new();
+    for (i, full_tipset) in full_tipsets.iter().enumerate() {
+        if full_tipset.is_some() {
+            continue;
+        }
+        match ranges.last_mut() {
+            Some(range) if range.end == i && range.len() < window => range.end += 1,
+            _ => ranges.push(i..i + 1),
+        }
+    }
+    if ranges.is_empty() {
+        return Ok(());
+    }
 
-                    let full_tipset = FullTipset::try_from(&bundle)
-                        .map_err(TipsetRangeSyncerError::GeneratingTipsetFromTipsetBundle)?;
-
-                    // Validate the tipset and the messages
-                    let timer = metrics::TIPSET_PROCESSING_TIME.start_timer();
-                    let current_epoch = full_tipset.epoch();
-                    validate_tipset::<_, C>(
-                        consensus.clone(),
-                        state_manager.clone(),
-                        chainstore.clone(),
-                        bad_block_cache.clone(),
-                        full_tipset,
-                        genesis.clone(),
-                        invalid_block_strategy,
-                    )
-                    .await?;
-                    tracker.write().await.set_epoch(current_epoch);
-                    timer.observe_duration();
-                    metrics::LAST_VALIDATED_TIPSET_EPOCH.set(current_epoch as u64);
-
-                    // Persist the messages in the store
-                    if let Some(m) = bundle.messages {
-                        chain::persist_objects(chainstore.blockstore(), &m.bls_msgs)?;
-                        chain::persist_objects(chainstore.blockstore(), &m.secp_msgs)?;
-                    } else {
-                        warn!("ChainExchange request for messages returned null messages");
-                    }
-                }
+    // Chain exchange walks back from the newest tipset of each window
+    let windows: Vec<_> = ranges
+        .iter()
+        .map(|range| (batch[range.end - 1].key().clone(), range.len() as u64))
+        .collect();
+    debug!(
+        "ChainExchange message sync tipsets: epoch: {}, windows: {}",
+        batch[ranges[0].start].epoch(),
+        windows.len(),
+    );
+    let fetched = network
+        .chain_exchange_windows(
+            &windows,
+            MESSAGES,
+            max_in_flight,
+            |i, _, received, messages| {
+                // Tipsets of the window that are still missing their messages
+                let window = &batch[ranges[i].clone()];
+                let remaining = &window[..window.len() - received];
+                let fetched = full_tipsets_from_messages(db, remaining, messages)?;
+                let next = remaining
+                    .len()
+                    .checked_sub(fetched.len() + 1)
+                    .map(|oldest| remaining[oldest].key().clone());
+                Ok((fetched, next))
+            },
+        )
+        .await?;
+
+    for (range, fetched) in ranges.into_iter().zip(fetched) {
+        for (full_tipset, fetched) in full_tipsets[range]
+            .iter_mut()
+            .zip(fetched.into_iter().rev())
+        {
+            *full_tipset = Some(fetched);
+        }
+    }
+    Ok(())
+}
+
+/// Puts together the newest tipsets of a window, in chronological order, with the messages
+/// downloaded for them, newest first, checking that the messages match the block headers. A
+/// partial response covers only the newest tipsets. The full tipsets are returned newest first.
+fn full_tipsets_from_messages<DB: BlockStore>(
+    db: &DB,
+    tipsets: &[Arc<Tipset>],
+    messages: Vec<CompactedMessages>,
+) -> Result<Vec<FullTipset>, String> {
+    if messages.len() > tipsets.len() {
+        return Err(format!(
+            "Expected messages for at most {} tipsets, got {}",
+            tipsets.len(),
+            messages.len()
+        ));
+    }
+
+    tipsets
+        .iter()
+        .rev()
+        .zip(messages)
+        .map(|(tipset, messages)| {
+            // Since the bundle only has messages, we have to put the headers in them
+            let bundle = TipsetBundle {
+                blocks: tipset.blocks().to_vec(),
+                messages: Some(messages),
+            };
+            let full_tipset = FullTipset::try_from(&bundle)?;
+            for block in full_tipset.blocks() {
+                TipsetValidator(&full_tipset)
+                    .validate_msg_root(db, block)
+                    .map_err(|e| e.to_string())?;
             }
+            Ok(full_tipset)
+        })
+        .collect()
+}
+
+/// Requests a window of `len` tipset headers, newest first, starting at the tipset with the
+/// given key. The window ends early at genesis.
+async fn request_header_window<DB: BlockStore + Sync + Send + 'static>(
+    network: SyncNetworkContext<DB>,
+    start: TipsetKeys,
+    len: u64,
+) -> Result<Vec<Arc<Tipset>>, String> {
+    let mut windows = network
+        .chain_exchange_windows(
+            &[(start, len)],
+            HEADERS,
+            1,
+            |_, start, _, tipsets: Vec<Arc<Tipset>>| {
+                validate_header_window(start, &tipsets)?;
+                let next = tipsets
+                    .last()
+                    .filter(|tipset| tipset.epoch() > 0)
+                    .map(|tipset| tipset.parents().clone());
+                Ok((tipsets, next))
+            },
+        )
+        .await?;
+    Ok(windows.pop().unwrap_or_default())
+}
+
+/// Checks that the tipsets of a window of headers, newest first, form a chain starting at the
+/// requested tipset key.
+fn validate_header_window(start: &TipsetKeys, tipsets: &[Arc<Tipset>]) -> Result<(), String> {
+    if tipsets.is_empty() {
+        return Err("Empty response for tipset headers".to_string());
+    }
+    let mut expected_key = start;
+    let mut last_epoch = ChainEpoch::MAX;
+    for tipset in tipsets {
+        if tipset.key() != expected_key {
+            return Err(format!(
+                "Tipset at epoch {} does not match the expected key",
+                tipset.epoch()
+            ));
         }
+        if tipset.epoch() >= last_epoch {
+            return Err(format!(
+                "Tipset epoch {} is not lower than its child epoch {}",
+                tipset.epoch(),
+                last_epoch
+            ));
+        }
+        expected_key = tipset.parents();
+        last_epoch = tipset.epoch();
     }
     Ok(())
 }
@@ -1647,4 +1820,36 @@ mod test {
         assert_eq!(index, 2);
         assert_eq!(weight, &BigInt::from(10));
     }
+
+    /// Returns a chain of single block tipsets, newest first.
+    fn mock_chain(len: i64) -> Vec<Arc<Tipset>> {
+        let mut parents = TipsetKeys::default();
+        let mut tipsets = Vec::new();
+        for epoch in 0..len {
+            let header = BlockHeader::builder()
+                .miner_address(Address::new_id(epoch as u64))
+                .parents(parents)
+                .epoch(epoch)
+                .build()
+                .unwrap();
+            let tipset = Arc::new(Tipset::new(vec![header]).unwrap());
+            parents = tipset.key().clone();
+            tipsets.push(tipset);
+        }
+        tipsets.reverse();
+        tipsets
+    }
+
+    #[test]
+    pub fn test_validate_header_window() {
+        let tipsets = mock_chain(4);
+        assert!(validate_header_window(tipsets[0].key(), &tipsets).is_ok());
+        assert!(validate_header_window(tipsets[1].key(), &tipsets[1..]).is_ok());
+
+        // Windows must start at the requested tipset and be linked by parents
+        assert!(validate_header_window(tipsets[1].key(), &tipsets).is_err());
+        let gapped = vec![tipsets[0].clone(), tipsets[2].clone()];
+        assert!(validate_header_window(tipsets[0].key(), &gapped).is_err());
+        assert!(validate_header_window(tipsets[0].key(), &[]).is_err());
+    }
 }
@@ -6,7 +6,7 @@ use crate::bad_block_cache::BadBlockCache;
 use chain::ChainStore;
 use cid::{multihash::Code::Blake2b256, Cid};
 use encoding::{Cbor, Error as EncodingError};
-use forest_blocks::{Block, FullTipset, Tipset, TxMeta};
+use forest_blocks::{Block, FullTipset, GossipBlock, Tipset, TxMeta, BLOCK_MESSAGE_LIMIT};
 use forest_message::SignedMessage;
 use fvm_shared::message::Message;
 use ipld_blockstore::{BlockStore, BlockStoreExt};
@@ -87,12 +87,7 @@ impl<'a> TipsetValidator<'a> {
         genesis_tipset: Arc<Tipset>,
         block_delay: u64,
     ) -> Result<(), TipsetValidationError> {
-        let now = SystemTime::now()
-            .duration_since(UNIX_EPOCH)
-            .unwrap()
-            .as_secs();
-        let max_epoch = ((now - genesis_tipset.min_timestamp()) / block_delay) + MAX_HEIGHT_DRIFT;
-        let too_far_ahead_in_time = self.0.epoch() as u64 > max_epoch;
+        let too_far_ahead_in_time = self.0.epoch() as u64 > max_epoch(&genesis_tipset, block_delay);
         if too_far_ahead_in_time {
             Err(TipsetValidationError::EpochTooLarge)
         } else {
@@ -142,3 +137,47 @@ impl<'a> TipsetValidator<'a> {
             .map_err(|e| TipsetValidationError::Blockstore(e.to_string()))
     }
 }
+
+/// Cheap checks on a block received over GossipSub, done before relaying it to other peers and
+/// before fetching its messages.
+pub struct GossipBlockValidator<'a>(pub &'a GossipBlock);
+
+impl<'a> GossipBlockValidator<'a> {
+    pub async fn validate(
+        &self,
+        bad_block_cache: &BadBlockCache,
+        genesis_tipset: &Tipset,
+        block_delay: u64,
+    ) -> Result<(), TipsetValidationError> {
+        let header = &self.0.header;
+        if header.signature().is_none() {
+            return Err(TipsetValidationError::InvalidBlock(
+                *header.cid(),
+                "Block has no signature".to_owned(),
+            ));
+        }
+        let message_count = self.0.bls_messages.len() + self.0.secpk_messages.len();
+        if message_count > BLOCK_MESSAGE_LIMIT {
+            return Err(TipsetValidationError::InvalidBlock(
+                *header.cid(),
+                format!("Block has too many messages: {}", message_count),
+            ));
+        }
+        if let Some(bad) = bad_block_cache.peek(header.cid()).await {
+            return Err(TipsetValidationError::InvalidBlock(*header.cid(), bad));
+        }
+        if header.epoch() as u64 > max_epoch(genesis_tipset, block_delay) {
+            return Err(TipsetValidationError::EpochTooLarge);
+        }
+        Ok(())
+    }
+}
+
+/// Highest epoch a block can have at this time, allowing for some clock drift.
+fn max_epoch(genesis_tipset: &Tipset, block_delay: u64) -> u64 {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_secs();
+    ((now - genesis_tipset.min_timestamp()) / block_delay) + MAX_HEIGHT_DRIFT
+}
@@ -0,0 +1,83 @@
+// Copyright 2019-2022 ChainSafe Systems
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+//! Benchmarks greedy and optimal message selection. Runs against the snapshot at the path in
+//! `MPOOL_SNAPSHOT` when set, otherwise against a generated pending set.
+
+use async_std::task;
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+use forest_message::SignedMessage;
+use fvm_shared::address::Address;
+use fvm_shared::bigint::BigInt;
+use fvm_shared::crypto::signature::SignatureType;
+use fvm_shared::message::Message;
+use key_management::{KeyStore, KeyStoreConfig, Wallet};
+use message_pool::simulation::{MpoolSnapshot, SelectionStrategy, Simulation, SnapshotActor};
+use message_pool::test_provider::mock_block;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+
+const ACTORS: usize = 50;
+const MESSAGES_PER_ACTOR: u64 = 100;
+const TICKET_QUALITIES: [f64; 3] = [0.1, 0.5, 0.9];
+
+/// Pending set of senders with random gas limits and premiums, generated from a fixed seed.
+fn generated_snapshot() -> MpoolSnapshot {
+    let mut wallet = Wallet::new(KeyStore::new(KeyStoreConfig::Memory).unwrap());
+    let mut rng = StdRng::seed_from_u64(0);
+    let to = Address::new_id(1000);
+
+    let mut actors = Vec::with_capacity(ACTORS);
+    let mut pending = Vec::with_capacity(ACTORS * MESSAGES_PER_ACTOR as usize);
+    for _ in 0..ACTORS {
+        let from = wallet.generate_addr(SignatureType::Secp256k1).unwrap();
+        actors.push(SnapshotActor {
+            address: from,
+            sequence: 0,
+            balance: BigInt::from(10_000_000_000_000_000_u64),
+        });
+        for sequence in 0..MESSAGES_PER_ACTOR {
+            let premium: u64 = rng.gen_range(1..500);
+            let msg = Message {
+                to,
+                from,
+                sequence,
+                gas_limit: rng.gen_range(1_000_000..10_000_000),
+                gas_fee_cap: BigInt::from(premium + 200),
+                gas_premium: BigInt::from(premium),
+                ..Message::default()
+            };
+            let sig = wallet.sign(&from, &msg.to_signing_bytes()).unwrap();
+            pending.push(SignedMessage::new_from_parts(msg, sig).unwrap());
+        }
+    }
+
+    MpoolSnapshot {
+        tipset: vec![mock_block(1, 1)],
+        base_fee: BigInt::from(100),
+        actors,
+        pending,
+    }
+}
+
+fn selection(c: &mut Criterion) {
+    let snapshot = match std::env::var("MPOOL_SNAPSHOT") {
+        Ok(path) => MpoolSnapshot::load(path).expect("Failed to load snapshot"),
+        Err(_) => generated_snapshot(),
+    };
+    let simulation = task::block_on(Simulation::new(&snapshot)).unwrap();
+
+    let mut group = c.benchmark_group("select_messages");
+    group.sample_size(10);
+    for strategy in [SelectionStrategy::Greedy, SelectionStrategy::Optimal] {
+        for tq in TICKET_QUALITIES {
+            group.bench_with_input(BenchmarkId::new(strategy.to_string(), tq), &tq, |b, &tq| {
+                b.iter(|| task::block_on(simulation.select(tq, strategy)).unwrap())
+            });
+        }
+    }
+    group.finish();
+}
+
+criterion_group!(benches, selection);
+criterion_main!(benches);
@@ -0,0 +1,62 @@
+// Copyright 2019-2022 ChainSafe Systems
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+//! Replays a recorded message pool snapshot through greedy and optimal message selection for a
+//! range of ticket qualities.
+//!
+//! Usage: `cargo run --release -p message_pool --example selection_sim -- <snapshot> [tq...]`
+
+use message_pool::simulation::{MpoolSnapshot, SelectionStrategy, Simulation};
+
+const DEFAULT_TICKET_QUALITIES: [f64; 6] = [0.1, 0.3, 0.5, 0.7, 0.9, 1.0];
+
+#[async_std::main]
+async fn main() {
+    let mut args = std::env::args().skip(1);
+    let path = match args.next() {
+        Some(path) => path,
+        None => {
+            eprintln!("Usage: selection_sim <snapshot> [ticket quality...]");
+            std::process::exit(1);
+        }
+    };
+    let mut ticket_qualities: Vec<f64> = args
+        .map(|tq| tq.parse().expect("Ticket quality must be a number"))
+        .collect();
+    if ticket_qualities.is_empty() {
+        ticket_qualities = DEFAULT_TICKET_QUALITIES.to_vec();
+    }
+
+    let snapshot = MpoolSnapshot::load(&path).expect("Failed to load snapshot");
+    println!(
+        "{} pending messages from {} senders, base fee {}",
+        snapshot.pending.len(),
+        snapshot.actors.len(),
+        snapshot.base_fee
+    );
+    let simulation = Simulation::new(&snapshot)
+        .await
+        .expect("Failed to load snapshot into the message pool");
+
+    println!(
+        "{:<8} {:>6} {:>8} {:>14} {:>24} {:>6}",
+        "strategy", "tq", "messages", "gas used", "gas reward", "fill"
+    );
+    for tq in ticket_qualities {
+        for strategy in [SelectionStrategy::Greedy, SelectionStrategy::Optimal] {
+            let report = simulation
+                .run(tq, strategy)
+                .await
+                .expect("Message selection failed");
+            println!(
+                "{:<8} {:>6.2} {:>8} {:>14} {:>24} {:>5.1}%",
+                report.strategy.to_string(),
+                report.ticket_quality,
+                report.messages,
+                report.gas_used,
+                report.gas_reward,
+                report.fill_ratio * 100.0
+            );
+        }
+    }
+}
@@ -13,11 +13,20 @@ const SIZE_LIMIT_HIGH: i64 = 30000;
 const PRUNE_COOLDOWN: Duration = Duration::from_secs(60); // 1 minute
 const REPLACE_BY_FEE_RATIO: f64 = 1.25;
 const GAS_LIMIT_OVERESTIMATION: f64 = 1.25;
+const MAX_ACTOR_PENDING_MESSAGES: u64 = 1000;
+const MAX_UNTRUSTED_ACTOR_PENDING_MESSAGES: u64 = 10;
+const MAX_NONCE_GAP: u64 = 4;
+const MAX_UNTRUSTED_NONCE_GAP: u64 = 0;
+const MIN_UNTRUSTED_GAS_PREMIUM: u64 = 100;
 
 /// Config available for the [MessagePool].
 ///
+/// Trusted senders are local addresses and `priority_addrs`, messages of other senders received
+/// over gossip are subject to the untrusted limits.
+///
 /// [MessagePool]: crate::MessagePool
 #[derive(Clone, Serialize, Deserialize)]
+#[serde(default)]
 pub struct MpoolConfig {
     pub priority_addrs: Vec<Address>,
     pub size_limit_high: i64,
@@ -25,6 +34,16 @@ pub struct MpoolConfig {
     pub replace_by_fee_ratio: f64,
     pub prune_cooldown: Duration,
     pub gas_limit_overestimation: f64,
+    /// Maximum number of pending messages of a trusted sender
+    pub max_actor_pending_messages: u64,
+    /// Maximum number of pending messages of an untrusted sender
+    pub max_untrusted_actor_pending_messages: u64,
+    /// How far ahead of a trusted sender's next nonce a message's nonce may be
+    pub max_nonce_gap: u64,
+    /// How far ahead of an untrusted sender's next nonce a message's nonce may be
+    pub max_untrusted_nonce_gap: u64,
+    /// Minimum gas premium in attoFIL of messages from untrusted senders
+    pub min_untrusted_gas_premium: u64,
 }
 
 impl Default for MpoolConfig {
@@ -36,6 +55,11 @@ impl Default for MpoolConfig {
             replace_by_fee_ratio: REPLACE_BY_FEE_RATIO,
             prune_cooldown: PRUNE_COOLDOWN,
             gas_limit_overestimation: GAS_LIMIT_OVERESTIMATION,
+            max_actor_pending_messages: MAX_ACTOR_PENDING_MESSAGES,
+            max_untrusted_actor_pending_messages: MAX_UNTRUSTED_ACTOR_PENDING_MESSAGES,
+            max_nonce_gap: MAX_NONCE_GAP,
+            max_untrusted_nonce_gap: MAX_UNTRUSTED_NONCE_GAP,
+            min_untrusted_gas_premium: MIN_UNTRUSTED_GAS_PREMIUM,
         }
     }
 }
@@ -69,6 +93,7 @@ impl MpoolConfig {
             replace_by_fee_ratio,
             prune_cooldown,
             gas_limit_overestimation,
+            ..Default::default()
         })
     }
 
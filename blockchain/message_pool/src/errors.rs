@@ -25,10 +25,20 @@ pub enum Error {
     InvalidToAddr,
     #[error("Invalid from address")]
     InvalidFromAddr,
+    #[error("Invalid message signature: {0}")]
+    InvalidSignature(String),
+    #[error("Invalid message: {0}")]
+    InvalidMessage(String),
     #[error("Message with sequence already in mempool")]
     DuplicateSequence,
     #[error("State inconsistency with message. Try again")]
     TryAgain,
+    #[error("Sender has too many pending messages")]
+    TooManyPendingMessages,
+    #[error("Message nonce is too far ahead of the sender's next nonce")]
+    NonceGap,
+    #[error("Gas premium is below the minimum for untrusted messages")]
+    GasPremiumTooLow,
     #[error("Validation Error: {0}")]
     SoftValidationFailure(String),
     #[error("{0}")]
@@ -4,12 +4,14 @@
 mod block_prob;
 mod config;
 mod errors;
+mod metrics;
 mod msg_chain;
 mod msgpool;
 
 pub use self::block_prob::*;
 pub use self::config::*;
 pub use self::errors::*;
+pub use self::msgpool::check::{CheckStatusCode, MessageCheckStatus};
 pub use self::msgpool::msg_pool::MessagePool;
 pub use self::msgpool::provider::{MpoolRpcProvider, Provider};
 pub use self::msgpool::*;
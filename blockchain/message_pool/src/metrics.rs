@@ -0,0 +1,85 @@
+// Copyright 2019-2022 ChainSafe Systems
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+use crate::errors::Error;
+use lazy_static::lazy_static;
+use prometheus::core::{AtomicU64, GenericCounterVec, Opts};
+
+lazy_static! {
+    pub static ref MPOOL_MESSAGE_REJECTED_TOTAL: Box<GenericCounterVec<AtomicU64>> = {
+        let mpool_message_rejected_total = Box::new(
+            GenericCounterVec::<AtomicU64>::new(
+                Opts::new(
+                    "mpool_message_rejected_total",
+                    "Total number of messages rejected by the message pool by reason",
+                ),
+                &[labels::REJECTION_REASON],
+            )
+            .expect("Defining the mpool_message_rejected_total metric must succeed"),
+        );
+        prometheus::default_registry()
+            .register(mpool_message_rejected_total.clone())
+            .expect(
+                "Registering the mpool_message_rejected_total metric with the metrics registry must succeed",
+            );
+        mpool_message_rejected_total
+    };
+}
+
+pub mod labels {
+    pub const REJECTION_REASON: &str = "reason";
+}
+
+pub mod values {
+    // mpool_message_rejected_total
+    pub const MESSAGE_TOO_BIG: &str = "message_too_big";
+    pub const REPLACE_BY_FEE_TOO_LOW: &str = "replace_by_fee_too_low";
+    pub const GAS_FEE_CAP_TOO_LOW: &str = "gas_fee_cap_too_low";
+    pub const GAS_PREMIUM_TOO_LOW: &str = "gas_premium_too_low";
+    pub const VALUE_TOO_HIGH: &str = "value_too_high";
+    pub const SEQUENCE_TOO_LOW: &str = "sequence_too_low";
+    pub const DUPLICATE_SEQUENCE: &str = "duplicate_sequence";
+    pub const NONCE_GAP: &str = "nonce_gap";
+    pub const TOO_MANY_PENDING_MESSAGES: &str = "too_many_pending_messages";
+    pub const NOT_ENOUGH_FUNDS: &str = "not_enough_funds";
+    pub const INVALID_ADDRESS: &str = "invalid_address";
+    pub const INVALID_SIGNATURE: &str = "invalid_signature";
+    pub const INVALID_MESSAGE: &str = "invalid_message";
+    pub const VALIDATION_FAILURE: &str = "validation_failure";
+    pub const OTHER: &str = "other";
+}
+
+/// Counts a rejected message under the reason of the error it was rejected with.
+pub(crate) fn record_rejection(e: Error) -> Error {
+    let reason = match &e {
+        Error::MessageTooBig => values::MESSAGE_TOO_BIG,
+        Error::GasPriceTooLow => values::REPLACE_BY_FEE_TOO_LOW,
+        Error::GasFeeCapTooLow => values::GAS_FEE_CAP_TOO_LOW,
+        Error::GasPremiumTooLow => values::GAS_PREMIUM_TOO_LOW,
+        Error::MessageValueTooHigh => values::VALUE_TOO_HIGH,
+        Error::SequenceTooLow => values::SEQUENCE_TOO_LOW,
+        Error::DuplicateSequence => values::DUPLICATE_SEQUENCE,
+        Error::NonceGap => values::NONCE_GAP,
+        Error::TooManyPendingMessages => values::TOO_MANY_PENDING_MESSAGES,
+        Error::NotEnoughFunds => values::NOT_ENOUGH_FUNDS,
+        Error::InvalidToAddr | Error::InvalidFromAddr => values::INVALID_ADDRESS,
+        Error::InvalidSignature(_) => values::INVALID_SIGNATURE,
+        Error::InvalidMessage(_) => values::INVALID_MESSAGE,
+        Error::SoftValidationFailure(_) => values::VALIDATION_FAILURE,
+        Error::TryAgain | Error::Other(_) => values::OTHER,
+    };
+    MPOOL_MESSAGE_REJECTED_TOTAL
+        .with_label_values(&[reason])
+        .inc();
+    e
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn metrics_defined_and_registered() {
+        let _ = MPOOL_MESSAGE_REJECTED_TOTAL.with_label_values(&[values::NONCE_GAP]);
+    }
+}
@@ -0,0 +1,300 @@
+// Copyright 2019-2022 ChainSafe Systems
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+// Preflight checks of messages against the current head, reporting the outcome of every check
+// instead of stopping at the first error like `MessagePool::push` does.
+
+use super::BASE_FEE_LOWER_BOUND_FACTOR;
+use crate::errors::Error;
+use crate::msg_pool::MessagePool;
+use crate::provider::Provider;
+use crate::utils::get_base_fee_lower_bound;
+use chain::MINIMUM_BASE_FEE;
+use cid::Cid;
+use encoding::Cbor;
+use forest_blocks::Tipset;
+use forest_message::message::valid_for_block_inclusion;
+use forest_message::Message as MessageTrait;
+use fvm::gas::{price_list_by_network_version, Gas};
+use fvm_shared::address::Address;
+use fvm_shared::bigint::BigInt;
+use fvm_shared::message::Message;
+use networks::NEWEST_NETWORK_VERSION;
+use std::collections::hash_map::Entry;
+use std::collections::HashMap;
+
+const MAX_MESSAGE_SIZE: usize = 32 * 1024;
+
+/// Check performed on a message. The values match the Lotus check status codes.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CheckStatusCode {
+    MessageSerialize = 1,
+    MessageSize = 2,
+    MessageValidity = 3,
+    MessageMinGas = 4,
+    MessageMinBaseFee = 5,
+    MessageBaseFee = 6,
+    MessageBaseFeeLowerBound = 7,
+    MessageGetStateNonce = 9,
+    MessageNonce = 10,
+    MessageGetStateBalance = 11,
+    MessageBalance = 12,
+}
+
+/// Outcome of a single check on a message, with hints on the values the message was checked
+/// against.
+#[derive(Clone, Debug, PartialEq)]
+pub struct MessageCheckStatus {
+    pub cid: Cid,
+    pub code: CheckStatusCode,
+    pub ok: bool,
+    pub err: String,
+    pub hint: HashMap<String, String>,
+}
+
+impl MessageCheckStatus {
+    fn new(
+        cid: Cid,
+        code: CheckStatusCode,
+        result: Result<(), String>,
+        hint: HashMap<String, String>,
+    ) -> Self {
+        Self {
+            cid,
+            code,
+            ok: result.is_ok(),
+            err: result.err().unwrap_or_default(),
+            hint,
+        }
+    }
+}
+
+/// Nonce and funds of a sender, advanced as its messages are checked.
+struct SenderState {
+    state_nonce: u64,
+    next_nonce: u64,
+    balance: BigInt,
+    required_funds: BigInt,
+}
+
+impl<T> MessagePool<T>
+where
+    T: Provider + std::marker::Send + std::marker::Sync + 'static,
+{
+    /// Checks messages against the current head. Each message is paired with whether its nonce
+    /// is set, otherwise it is assumed to take the next nonce of its sender. Messages of the same
+    /// sender are checked in order, after the sender's pending messages in the pool.
+    pub async fn check_messages(
+        &self,
+        msgs: Vec<(Message, bool)>,
+    ) -> Result<Vec<Vec<MessageCheckStatus>>, Error> {
+        self.check_messages_with_pending(msgs, true).await
+    }
+
+    /// Checks the pending messages of the given sender against the current head.
+    pub async fn check_pending_messages(
+        &self,
+        from: &Address,
+    ) -> Result<Vec<Vec<MessageCheckStatus>>, Error> {
+        let msgs = self
+            .pending_for(from)
+            .await
+            .unwrap_or_default()
+            .into_iter()
+            .map(|m| (m.message().clone(), true))
+            .collect();
+        self.check_messages_with_pending(msgs, false).await
+    }
+
+    async fn check_messages_with_pending(
+        &self,
+        msgs: Vec<(Message, bool)>,
+        include_pending: bool,
+    ) -> Result<Vec<Vec<MessageCheckStatus>>, Error> {
+        let cur_ts = self.cur_tipset.read().await.clone();
+        let base_fee = self.api.read().await.chain_compute_base_fee(&cur_ts)?;
+        let base_fee_lower_bound = get_base_fee_lower_bound(&base_fee, BASE_FEE_LOWER_BOUND_FACTOR);
+        let price_list =
+            price_list_by_network_version(self.chain_config.network_version(cur_ts.epoch()));
+
+        let mut senders: HashMap<Address, Result<SenderState, String>> = HashMap::new();
+        let mut result = Vec::with_capacity(msgs.len());
+        for (msg, check_nonce) in msgs {
+            let mut statuses = Vec::new();
+
+            let size = match msg.marshal_cbor() {
+                Ok(bytes) => bytes.len(),
+                Err(e) => {
+                    statuses.push(MessageCheckStatus::new(
+                        Cid::default(),
+                        CheckStatusCode::MessageSerialize,
+                        Err(e.to_string()),
+                        HashMap::new(),
+                    ));
+                    result.push(statuses);
+                    continue;
+                }
+            };
+            let cid = msg.cid()?;
+            let mut check = |code, result, hint| {
+                statuses.push(MessageCheckStatus::new(cid, code, result, hint));
+            };
+
+            check(CheckStatusCode::MessageSerialize, Ok(()), HashMap::new());
+            check(
+                CheckStatusCode::MessageSize,
+                if size > MAX_MESSAGE_SIZE {
+                    Err(format!("message too big ({} bytes)", size))
+                } else {
+                    Ok(())
+                },
+                HashMap::from([("MaxSize".to_owned(), MAX_MESSAGE_SIZE.to_string())]),
+            );
+            check(
+                CheckStatusCode::MessageValidity,
+                valid_for_block_inclusion(&msg, Gas::new(0), NEWEST_NETWORK_VERSION)
+                    .map_err(|e| e.to_string()),
+                HashMap::new(),
+            );
+
+            let min_gas = price_list.on_chain_message(size).total();
+            check(
+                CheckStatusCode::MessageMinGas,
+                if Gas::new(msg.gas_limit) < min_gas {
+                    Err("GasLimit less than epoch minimum gas".to_owned())
+                } else {
+                    Ok(())
+                },
+                HashMap::from([("MinGas".to_owned(), min_gas.to_string())]),
+            );
+
+            let fee_cap_check = |bound: &BigInt, err: &str, hint: &str| {
+                (
+                    if &msg.gas_fee_cap < bound {
+                        Err(err.to_owned())
+                    } else {
+                        Ok(())
+                    },
+                    HashMap::from([
+                        ("GasFeeCap".to_owned(), msg.gas_fee_cap.to_string()),
+                        (hint.to_owned(), bound.to_string()),
+                    ]),
+                )
+            };
+            let (res, hint) = fee_cap_check(
+                &*MINIMUM_BASE_FEE,
+                "GasFeeCap less than minimum base fee",
+                "MinBaseFee",
+            );
+            check(CheckStatusCode::MessageMinBaseFee, res, hint);
+            let (res, hint) =
+                fee_cap_check(&base_fee, "GasFeeCap less than current base fee", "BaseFee");
+            check(CheckStatusCode::MessageBaseFee, res, hint);
+            let (res, hint) = fee_cap_check(
+                &base_fee_lower_bound,
+                "GasFeeCap less than base fee lower bound for inclusion in next 20 epochs",
+                "BaseFeeLowerBound",
+            );
+            check(CheckStatusCode::MessageBaseFeeLowerBound, res, hint);
+
+            let state = match senders.entry(msg.from) {
+                Entry::Occupied(entry) => entry.into_mut(),
+                Entry::Vacant(entry) => entry.insert(
+                    self.sender_state(&msg.from, &cur_ts, include_pending)
+                        .await
+                        .map_err(|e| e.to_string()),
+                ),
+            };
+            let state = match state {
+                Ok(state) => state,
+                Err(e) => {
+                    check(
+                        CheckStatusCode::MessageGetStateNonce,
+                        Err(e.clone()),
+                        HashMap::new(),
+                    );
+                    check(
+                        CheckStatusCode::MessageGetStateBalance,
+                        Err(e.clone()),
+                        HashMap::new(),
+                    );
+                    result.push(statuses);
+                    continue;
+                }
+            };
+            check(
+                CheckStatusCode::MessageGetStateNonce,
+                Ok(()),
+                HashMap::new(),
+            );
+            check(
+                CheckStatusCode::MessageGetStateBalance,
+                Ok(()),
+                HashMap::new(),
+            );
+
+            let nonce_hint = HashMap::from([("Nonce".to_owned(), state.next_nonce.to_string())]);
+            let nonce_result = if !check_nonce {
+                Ok(())
+            } else if msg.sequence < state.state_nonce {
+                Err("message nonce too low".to_owned())
+            } else if msg.sequence != state.next_nonce {
+                Err(format!(
+                    "message nonce doesn't match next nonce ({})",
+                    state.next_nonce
+                ))
+            } else {
+                Ok(())
+            };
+            if nonce_result.is_ok() {
+                state.next_nonce += 1;
+            }
+            check(CheckStatusCode::MessageNonce, nonce_result, nonce_hint);
+
+            // Funds needed by this message and every pending or earlier message of the sender
+            state.required_funds += &msg.gas_fee_cap * msg.gas_limit + &msg.value;
+            check(
+                CheckStatusCode::MessageBalance,
+                if state.balance < state.required_funds {
+                    Err("insufficient balance".to_owned())
+                } else {
+                    Ok(())
+                },
+                HashMap::from([
+                    ("Balance".to_owned(), state.balance.to_string()),
+                    ("RequiredFunds".to_owned(), state.required_funds.to_string()),
+                ]),
+            );
+
+            result.push(statuses);
+        }
+
+        Ok(result)
+    }
+
+    async fn sender_state(
+        &self,
+        from: &Address,
+        cur_ts: &Tipset,
+        include_pending: bool,
+    ) -> Result<SenderState, Error> {
+        let actor = self.api.read().await.get_actor_after(from, cur_ts)?;
+        let mut state = SenderState {
+            state_nonce: actor.sequence,
+            next_nonce: actor.sequence,
+            balance: actor.balance,
+            required_funds: BigInt::default(),
+        };
+        if include_pending {
+            if let Some(mset) = self.pending.read().await.get(from) {
+                state.next_nonce = state.next_nonce.max(mset.next_sequence);
+                for m in mset.msgs.values() {
+                    if m.sequence() >= actor.sequence {
+                        state.required_funds += m.required_funds();
+                    }
+                }
+            }
+        }
+        Ok(state)
+    }
+}
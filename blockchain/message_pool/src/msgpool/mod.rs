@@ -1,9 +1,11 @@
 // Copyright 2019-2022 ChainSafe Systems
 // SPDX-License-Identifier: Apache-2.0, MIT
 
+pub(crate) mod check;
 pub(crate) mod msg_pool;
 pub(crate) mod provider;
 mod selection;
+pub mod simulation;
 pub mod test_provider;
 pub(crate) mod utils;
 
@@ -20,6 +22,7 @@ use forest_blocks::Tipset;
 use forest_libp2p::{NetworkMessage, Topic, PUBSUB_MSG_STR};
 use forest_message::{Message as MessageTrait, SignedMessage};
 use fvm_shared::address::Address;
+use fvm_shared::bigint::{BigInt, Integer};
 use fvm_shared::crypto::signature::Signature;
 use log::error;
 use lru::LruCache;
@@ -39,6 +42,42 @@ const PROPAGATION_DELAY_SECS: u64 = 6;
 // TODO: Implement guess gas module
 const MIN_GAS: i64 = 1298450;
 
+/// Kind of change to the pending messages of the pool. The values match the Lotus update types.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MpoolUpdateType {
+    Add = 0,
+    Remove = 1,
+}
+
+/// Message added to or removed from the pending messages of the pool.
+#[derive(Clone, Debug, PartialEq)]
+pub struct MpoolUpdate {
+    pub update_type: MpoolUpdateType,
+    pub message: SignedMessage,
+}
+
+impl MpoolUpdate {
+    pub(crate) fn add(message: SignedMessage) -> Self {
+        Self {
+            update_type: MpoolUpdateType::Add,
+            message,
+        }
+    }
+
+    pub(crate) fn remove(message: SignedMessage) -> Self {
+        Self {
+            update_type: MpoolUpdateType::Remove,
+            message,
+        }
+    }
+}
+
+/// Returns the premium a replacement message must exceed to replace a pending message with the
+/// given premium.
+pub(crate) fn rbf_min_price(premium: &BigInt) -> BigInt {
+    premium + (premium * RBF_NUM).div_floor(&BigInt::from(RBF_DENOM)) + 1u8
+}
+
 /// Get the state of the base_sequence for a given address in the current Tipset
 async fn get_state_sequence<T>(
     api: &RwLock<T>,
@@ -185,7 +224,8 @@ where
 }
 
 /// This function will revert and/or apply tipsets to the message pool. This function should be
-/// called every time that there is a head change in the message pool.
+/// called every time that there is a head change in the message pool. Returns the messages that
+/// were removed from or re-added to the pending messages.
 #[allow(clippy::too_many_arguments)]
 pub async fn head_change<T>(
     api: &RwLock<T>,
@@ -196,10 +236,11 @@ pub async fn head_change<T>(
     cur_tipset: &RwLock<Arc<Tipset>>,
     revert: Vec<Tipset>,
     apply: Vec<Tipset>,
-) -> Result<(), Error>
+) -> Result<Vec<MpoolUpdate>, Error>
 where
     T: Provider + 'static,
 {
+    let mut updates = Vec::new();
     let mut repub = false;
     let mut rmsgs: HashMap<Address, HashMap<u64, SignedMessage>> = HashMap::new();
     for ts in revert {
@@ -227,15 +268,27 @@ where
             let (msgs, smsgs) = api.read().await.messages_for_block(b)?;
 
             for msg in smsgs {
-                remove_from_selected_msgs(msg.from(), pending, msg.sequence(), rmsgs.borrow_mut())
-                    .await?;
+                if let Some(m) = remove_from_selected_msgs(
+                    msg.from(),
+                    pending,
+                    msg.sequence(),
+                    rmsgs.borrow_mut(),
+                )
+                .await?
+                {
+                    updates.push(MpoolUpdate::remove(m));
+                }
                 if !repub && republished.write().await.insert(msg.cid()?) {
                     repub = true;
                 }
             }
             for msg in msgs {
-                remove_from_selected_msgs(&msg.from, pending, msg.sequence, rmsgs.borrow_mut())
-                    .await?;
+                if let Some(m) =
+                    remove_from_selected_msgs(&msg.from, pending, msg.sequence, rmsgs.borrow_mut())
+                        .await?
+                {
+                    updates.push(MpoolUpdate::remove(m));
+                }
                 if !repub && republished.write().await.insert(msg.cid()?) {
                     repub = true;
                 }
@@ -253,32 +306,31 @@ where
         for (_, msg) in hm {
             let sequence =
                 get_state_sequence(api, msg.from(), &cur_tipset.read().await.clone()).await?;
-            if let Err(e) = add_helper(api, bls_sig_cache, pending, msg, sequence).await {
-                error!("Failed to readd message from reorg to mpool: {}", e);
+            match add_helper(api, bls_sig_cache, pending, msg.clone(), sequence, None).await {
+                Ok(()) => updates.push(MpoolUpdate::add(msg)),
+                Err(e) => error!("Failed to readd message from reorg to mpool: {}", e),
             }
         }
     }
-    Ok(())
+    Ok(updates)
 }
 
 /// This is a helper function for head_change. This method will remove a sequence for a from address
-/// from the messages selected by priority hashmap. It also removes the 'from' address and sequence from the MessagePool.
+/// from the messages selected by priority hashmap. It also removes the 'from' address and sequence from the MessagePool,
+/// returning the message if it was pending.
 pub(crate) async fn remove_from_selected_msgs(
     from: &Address,
     pending: &RwLock<HashMap<Address, MsgSet>>,
     sequence: u64,
     rmsgs: &mut HashMap<Address, HashMap<u64, SignedMessage>>,
-) -> Result<(), Error> {
+) -> Result<Option<SignedMessage>, Error> {
     if let Some(temp) = rmsgs.get_mut(from) {
         if temp.get_mut(&sequence).is_some() {
             temp.remove(&sequence);
-        } else {
-            remove(from, pending, sequence, true).await?;
+            return Ok(None);
         }
-    } else {
-        remove(from, pending, sequence, true).await?;
     }
-    Ok(())
+    remove(from, pending, sequence, true).await
 }
 
 /// This is a helper function for head_change. This method will add a signed message to
@@ -300,7 +352,8 @@ pub(crate) fn add_to_selected_msgs(
 pub mod tests {
     use super::*;
     use crate::msg_chain::{create_message_chains, Chains};
-    use crate::msg_pool::MessagePool;
+    use crate::msg_pool::{MessagePool, UPDATE_SUBSCRIPTION_CAPACITY};
+    use crate::CheckStatusCode;
     use async_std::channel::bounded;
     use async_std::task;
     use forest_blocks::Tipset;
@@ -311,6 +364,7 @@ pub mod tests {
     use fvm_shared::message::Message;
     use key_management::{KeyStore, KeyStoreConfig, Wallet};
     use networks::ChainConfig;
+    use simulation::{MpoolSnapshot, SelectionStrategy, Simulation};
     use std::borrow::BorrowMut;
     use std::thread::sleep;
     use std::time::Duration;
@@ -507,6 +561,302 @@ pub mod tests {
         })
     }
 
+    #[test]
+    fn test_replace_by_fee() {
+        let keystore = KeyStore::new(KeyStoreConfig::Memory).unwrap();
+        let mut wallet = Wallet::new(keystore);
+        let sender = wallet.generate_addr(SignatureType::Secp256k1).unwrap();
+        let target = wallet.generate_addr(SignatureType::Secp256k1).unwrap();
+
+        let mut tma = TestApi::default();
+        tma.set_state_sequence(&sender, 0);
+        let (tx, _rx) = bounded(50);
+
+        task::block_on(async move {
+            let mut mpool = MessagePool::new(
+                tma,
+                "mptest".to_string(),
+                tx,
+                Default::default(),
+                Arc::default(),
+            )
+            .await
+            .unwrap();
+
+            let msg = create_smsg(&target, &sender, wallet.borrow_mut(), 0, 1000000, 100);
+            mpool.push(msg).await.unwrap();
+
+            assert_eq!(
+                mpool.min_replacement_premium(&BigInt::from(100)),
+                BigInt::from(127)
+            );
+
+            let too_low = create_smsg(&target, &sender, wallet.borrow_mut(), 0, 1000000, 126);
+            assert_eq!(mpool.push(too_low).await, Err(Error::GasPriceTooLow));
+
+            let replacement = create_smsg(&target, &sender, wallet.borrow_mut(), 0, 1000000, 127);
+            mpool.push(replacement.clone()).await.unwrap();
+            assert_eq!(mpool.pending_for(&sender).await.unwrap(), vec![replacement]);
+
+            mpool.config.replace_by_fee_ratio = 2.0;
+            assert_eq!(
+                mpool.min_replacement_premium(&BigInt::from(100)),
+                BigInt::from(200)
+            );
+        })
+    }
+
+    #[test]
+    fn test_check_messages() {
+        let keystore = KeyStore::new(KeyStoreConfig::Memory).unwrap();
+        let mut wallet = Wallet::new(keystore);
+        let sender = wallet.generate_addr(SignatureType::Secp256k1).unwrap();
+        let target = wallet.generate_addr(SignatureType::Secp256k1).unwrap();
+
+        let mut tma = TestApi::default();
+        tma.set_state_sequence(&sender, 0);
+        let (tx, _rx) = bounded(50);
+
+        task::block_on(async move {
+            let mpool = MessagePool::new(
+                tma,
+                "mptest".to_string(),
+                tx,
+                Default::default(),
+                Arc::default(),
+            )
+            .await
+            .unwrap();
+
+            let pending = create_smsg(&target, &sender, wallet.borrow_mut(), 0, 1000000, 1);
+            mpool.push(pending).await.unwrap();
+
+            let next = create_smsg(&target, &sender, wallet.borrow_mut(), 1, 1000000, 1);
+            let gap = create_smsg(&target, &sender, wallet.borrow_mut(), 3, 1000000, 1);
+            let statuses = mpool
+                .check_messages(vec![
+                    (next.message().clone(), true),
+                    (gap.message().clone(), true),
+                ])
+                .await
+                .unwrap();
+            assert!(statuses[0].iter().all(|s| s.ok));
+            let failed: Vec<_> = statuses[1]
+                .iter()
+                .filter(|s| !s.ok)
+                .map(|s| s.code)
+                .collect();
+            assert_eq!(failed, vec![CheckStatusCode::MessageNonce]);
+
+            // The pending message is counted against the balance
+            mpool
+                .api
+                .write()
+                .await
+                .set_state_balance_raw(&sender, BigInt::from(150_000_000));
+            let statuses = mpool
+                .check_messages(vec![(next.message().clone(), false)])
+                .await
+                .unwrap();
+            let failed: Vec<_> = statuses[0]
+                .iter()
+                .filter(|s| !s.ok)
+                .map(|s| s.code)
+                .collect();
+            assert_eq!(failed, vec![CheckStatusCode::MessageBalance]);
+
+            let statuses = mpool.check_pending_messages(&sender).await.unwrap();
+            assert_eq!(statuses.len(), 1);
+            assert!(statuses[0].iter().all(|s| s.ok));
+        })
+    }
+
+    #[test]
+    fn test_untrusted_sender_limits() {
+        let keystore = KeyStore::new(KeyStoreConfig::Memory).unwrap();
+        let mut wallet = Wallet::new(keystore);
+        let sender = wallet.generate_addr(SignatureType::Secp256k1).unwrap();
+        let target = wallet.generate_addr(SignatureType::Secp256k1).unwrap();
+
+        let mut tma = TestApi::default();
+        tma.set_state_sequence(&sender, 0);
+        let (tx, _rx) = bounded(50);
+
+        task::block_on(async move {
+            let mut mpool = MessagePool::new(
+                tma,
+                "mptest".to_string(),
+                tx,
+                Default::default(),
+                Arc::default(),
+            )
+            .await
+            .unwrap();
+            mpool.config.max_untrusted_actor_pending_messages = 2;
+
+            let cheap = create_smsg(&target, &sender, wallet.borrow_mut(), 0, 1000000, 99);
+            assert_eq!(
+                mpool.add_untrusted(cheap).await,
+                Err(Error::GasPremiumTooLow)
+            );
+
+            let gap = create_smsg(&target, &sender, wallet.borrow_mut(), 1, 1000000, 100);
+            assert_eq!(mpool.add_untrusted(gap).await, Err(Error::NonceGap));
+
+            for i in 0..2 {
+                let msg = create_smsg(&target, &sender, wallet.borrow_mut(), i, 1000000, 100);
+                mpool.add_untrusted(msg).await.unwrap();
+            }
+            let over = create_smsg(&target, &sender, wallet.borrow_mut(), 2, 1000000, 100);
+            assert_eq!(
+                mpool.add_untrusted(over.clone()).await,
+                Err(Error::TooManyPendingMessages)
+            );
+
+            // Replacing a pending message does not count against the cap
+            let replacement = create_smsg(&target, &sender, wallet.borrow_mut(), 1, 1000000, 200);
+            mpool.add_untrusted(replacement).await.unwrap();
+
+            // Priority senders are held to the trusted limits
+            mpool.config.priority_addrs.push(sender);
+            mpool.add_untrusted(over).await.unwrap();
+            assert_eq!(mpool.get_sequence(&sender).await.unwrap(), 3);
+
+            // Local pushes are not held to the nonce gap limit
+            let sequence = 4 + mpool.config.max_nonce_gap;
+            let local = create_smsg(
+                &target,
+                &sender,
+                wallet.borrow_mut(),
+                sequence,
+                1000000,
+                100,
+            );
+            assert_eq!(
+                mpool.add_untrusted(local.clone()).await,
+                Err(Error::NonceGap)
+            );
+            mpool.push(local).await.unwrap();
+            assert_eq!(mpool.get_sequence(&sender).await.unwrap(), sequence + 1);
+        })
+    }
+
+    #[async_std::test]
+    async fn test_selection_simulation() {
+        let keystore = KeyStore::new(KeyStoreConfig::Memory).unwrap();
+        let mut wallet = Wallet::new(keystore);
+        let sender = wallet.generate_addr(SignatureType::Secp256k1).unwrap();
+        let target = wallet.generate_addr(SignatureType::Secp256k1).unwrap();
+
+        let (tx, _rx) = bounded(50);
+        let mpool = MessagePool::new(
+            TestApi::default(),
+            "mptest".to_string(),
+            tx,
+            Default::default(),
+            Arc::default(),
+        )
+        .await
+        .unwrap();
+        for i in 0..3 {
+            let msg = create_smsg(&target, &sender, wallet.borrow_mut(), i, 1000000, 1);
+            mpool.add(msg).await.unwrap();
+        }
+
+        let snapshot = mpool.snapshot().await.unwrap();
+        assert_eq!(snapshot.pending.len(), 3);
+        let decoded = MpoolSnapshot::unmarshal_cbor(&snapshot.marshal_cbor().unwrap()).unwrap();
+        assert_eq!(decoded, snapshot);
+
+        let simulation = Simulation::new(&decoded).await.unwrap();
+        for strategy in [SelectionStrategy::Greedy, SelectionStrategy::Optimal] {
+            let report = simulation.run(0.5, strategy).await.unwrap();
+            assert_eq!(report.messages, 3);
+            assert_eq!(report.gas_used, 3000000);
+            assert_eq!(
+                report.fill_ratio,
+                3000000.0 / fil_types::BLOCK_GAS_LIMIT as f64
+            );
+        }
+    }
+
+    #[async_std::test]
+    async fn test_mpool_updates() {
+        let keystore = KeyStore::new(KeyStoreConfig::Memory).unwrap();
+        let mut wallet = Wallet::new(keystore);
+        let sender = wallet.generate_addr(SignatureType::Secp256k1).unwrap();
+        let other = wallet.generate_addr(SignatureType::Secp256k1).unwrap();
+        let target = wallet.generate_addr(SignatureType::Secp256k1).unwrap();
+
+        let (tx, _rx) = bounded(50);
+        let mut mpool = MessagePool::new(
+            TestApi::default(),
+            "mptest".to_string(),
+            tx,
+            Default::default(),
+            Arc::default(),
+        )
+        .await
+        .unwrap();
+        let mut updates = mpool.subscribe_updates();
+        let sub_id = mpool.sub_updates(vec![sender]).await;
+
+        let ignored = create_smsg(&target, &other, wallet.borrow_mut(), 0, 1000000, 1);
+        mpool.add(ignored.clone()).await.unwrap();
+        let msg = create_smsg(&target, &sender, wallet.borrow_mut(), 0, 1000000, 1);
+        mpool.add(msg.clone()).await.unwrap();
+        mpool.remove(&sender, 0, true).await.unwrap();
+
+        assert_eq!(updates.recv().await.unwrap(), MpoolUpdate::add(ignored));
+        assert_eq!(updates.recv().await.unwrap(), MpoolUpdate::add(msg.clone()));
+        assert_eq!(
+            updates.recv().await.unwrap(),
+            MpoolUpdate::remove(msg.clone())
+        );
+
+        // Only updates of the subscribed sender are received
+        assert_eq!(
+            mpool.next_update(&sub_id).await,
+            Some(MpoolUpdate::add(msg.clone()))
+        );
+        assert_eq!(
+            mpool.next_update(&sub_id).await,
+            Some(MpoolUpdate::remove(msg))
+        );
+    }
+
+    #[async_std::test]
+    async fn test_mpool_updates_drop_idle_subscriptions() {
+        let keystore = KeyStore::new(KeyStoreConfig::Memory).unwrap();
+        let mut wallet = Wallet::new(keystore);
+        let sender = wallet.generate_addr(SignatureType::Secp256k1).unwrap();
+        let target = wallet.generate_addr(SignatureType::Secp256k1).unwrap();
+
+        let (tx, _rx) = bounded(50);
+        let mpool = MessagePool::new(
+            TestApi::default(),
+            "mptest".to_string(),
+            tx,
+            Default::default(),
+            Arc::default(),
+        )
+        .await
+        .unwrap();
+        let sub_id = mpool.sub_updates(vec![]).await;
+
+        // Nobody polls the subscription, as when its websocket is closed
+        for sequence in 0..=UPDATE_SUBSCRIPTION_CAPACITY as u64 {
+            let msg = create_smsg(&target, &sender, wallet.borrow_mut(), sequence, 1000000, 1);
+            mpool.add(msg).await.unwrap();
+        }
+
+        let dropped = async_std::future::timeout(Duration::from_secs(5), async {
+            while mpool.next_update(&sub_id).await.is_some() {}
+        })
+        .await;
+        assert!(dropped.is_ok(), "Idle subscription was not dropped");
+    }
+
     #[test]
     fn test_async_message_pool() {
         let keystore = KeyStore::new(KeyStoreConfig::Memory).unwrap();
@@ -9,14 +9,16 @@
 use crate::config::MpoolConfig;
 use crate::errors::Error;
 use crate::head_change;
+use crate::metrics;
 use crate::msgpool::recover_sig;
 use crate::msgpool::republish_pending_messages;
 use crate::msgpool::BASE_FEE_LOWER_BOUND_FACTOR_CONSERVATIVE;
 use crate::msgpool::PROPAGATION_DELAY_SECS;
-use crate::msgpool::{RBF_DENOM, RBF_NUM};
+use crate::msgpool::{rbf_min_price, RBF_DENOM};
+use crate::msgpool::{MpoolUpdate, Publisher, Subscriber};
 use crate::provider::Provider;
 use crate::utils::get_base_fee_lower_bound;
-use async_std::channel::{bounded, Sender};
+use async_std::channel::{bounded, Receiver, Sender};
 use async_std::stream::interval;
 use async_std::sync::{Arc, RwLock};
 use async_std::task;
@@ -33,23 +35,26 @@ use fvm::gas::{price_list_by_network_version, Gas};
 use fvm_shared::address::{Address, Protocol};
 use fvm_shared::bigint::{BigInt, Integer};
 use fvm_shared::crypto::signature::{Signature, SignatureType};
-use log::warn;
+use log::{debug, warn};
 use lru::LruCache;
 use networks::{ChainConfig, NEWEST_NETWORK_VERSION};
 use std::collections::{HashMap, HashSet};
+use std::sync::atomic::{AtomicI64, Ordering};
 use std::time::Duration;
-use tokio::sync::broadcast::error::RecvError;
+use tokio::sync::broadcast::{self, error::RecvError};
 
 // LruCache sizes have been taken from the lotus implementation
 const BLS_SIG_CACHE_SIZE: usize = 40000;
 const SIG_VAL_CACHE_SIZE: usize = 32000;
+const UPDATES_CHANNEL_CAPACITY: usize = 1024;
+pub(crate) const UPDATE_SUBSCRIPTION_CAPACITY: usize = 64;
 
 /// Simple struct that contains a hashmap of messages where k: a message from address, v: a message
 /// which corresponds to that address.
 #[derive(Clone, Default, Debug)]
 pub struct MsgSet {
     pub(crate) msgs: HashMap<u64, SignedMessage>,
-    next_sequence: u64,
+    pub(crate) next_sequence: u64,
     required_funds: BigInt,
 }
 
@@ -71,9 +76,7 @@ impl MsgSet {
         }
         if let Some(exms) = self.msgs.get(&m.sequence()) {
             if m.cid()? != exms.cid()? {
-                let premium = exms.message().gas_premium.clone();
-                let rbf_denom = BigInt::from(RBF_DENOM);
-                let min_price = premium.clone() + ((premium * RBF_NUM).div_floor(&rbf_denom)) + 1u8;
+                let min_price = rbf_min_price(&exms.message().gas_premium);
                 if m.message().gas_premium <= min_price {
                     return Err(Error::GasPriceTooLow);
                 }
@@ -85,8 +88,9 @@ impl MsgSet {
         Ok(())
     }
 
-    /// Removes message with the given sequence. If applied, update the set's next sequence.
-    pub fn rm(&mut self, sequence: u64, applied: bool) {
+    /// Removes message with the given sequence, returning it if it was in the set. If applied,
+    /// update the set's next sequence.
+    pub fn rm(&mut self, sequence: u64, applied: bool) -> Option<SignedMessage> {
         let m = if let Some(m) = self.msgs.remove(&sequence) {
             m
         } else {
@@ -96,7 +100,7 @@ impl MsgSet {
                     self.next_sequence += 1;
                 }
             }
-            return;
+            return None;
         };
         self.required_funds -= m.required_funds();
 
@@ -107,13 +111,14 @@ impl MsgSet {
             if sequence >= self.next_sequence {
                 self.next_sequence = sequence + 1;
             }
-            return;
+            return Some(m);
         }
         // we removed a message because it was pruned
         // we have to adjust the sequence if it creates a gap or rewinds state
         if sequence < self.next_sequence {
             self.next_sequence = sequence;
         }
+        Some(m)
     }
 
     fn get_required_funds(&self, sequence: u64) -> BigInt {
@@ -158,6 +163,12 @@ pub struct MessagePool<T> {
     pub config: MpoolConfig,
     /// Chain сonfig
     pub chain_config: Arc<ChainConfig>,
+    /// Publishes messages added to and removed from pending
+    updates: Publisher<MpoolUpdate>,
+    /// Receivers of the update subscriptions, keyed by subscription ID
+    update_subscriptions: Arc<RwLock<HashMap<i64, Receiver<MpoolUpdate>>>>,
+    /// Keeps track of the number of update subscriptions to auto-increment the subscription ID
+    update_subscriptions_count: AtomicI64,
 }
 
 impl<T> MessagePool<T>
@@ -188,6 +199,7 @@ where
         let block_delay = chain_config.block_delay_secs;
 
         let (repub_trigger, mut repub_trigger_rx) = bounded::<()>(4);
+        let (updates, _) = broadcast::channel(UPDATES_CHANNEL_CAPACITY);
         let mut mp = MessagePool {
             local_addrs,
             pending,
@@ -204,6 +216,9 @@ where
             network_sender,
             repub_trigger,
             chain_config: Arc::clone(&chain_config),
+            updates,
+            update_subscriptions: Default::default(),
+            update_subscriptions_count: Default::default(),
         };
 
         mp.load_local().await?;
@@ -217,6 +232,7 @@ where
 
         let cur_tipset = mp.cur_tipset.clone();
         let repub_trigger = Arc::new(mp.repub_trigger.clone());
+        let updates = mp.updates.clone();
 
         // Reacts to new HeadChanges
         task::spawn(async move {
@@ -236,7 +252,7 @@ where
                                 vec![tipset.as_ref().clone()],
                             ),
                         };
-                        head_change(
+                        match head_change(
                             api.as_ref(),
                             bls_sig_cache.as_ref(),
                             repub_trigger.clone(),
@@ -247,7 +263,14 @@ where
                             app,
                         )
                         .await
-                        .unwrap_or_else(|err| warn!("Error changing head: {:?}", err));
+                        {
+                            Ok(changes) => {
+                                for update in changes {
+                                    let _ = updates.send(update);
+                                }
+                            }
+                            Err(err) => warn!("Error changing head: {:?}", err),
+                        }
                     }
                     Err(RecvError::Lagged(e)) => {
                         warn!("Head change subscriber lagged: skipping {} events", e);
@@ -300,10 +323,18 @@ where
 
     /// Push a signed message to the MessagePool. Additionally performs
     pub async fn push(&self, msg: SignedMessage) -> Result<Cid, Error> {
-        self.check_message(&msg).await?;
+        self.check_message(&msg)
+            .await
+            .map_err(metrics::record_rejection)?;
         let cid = msg.cid().map_err(|err| Error::Other(err.to_string()))?;
         let cur_ts = self.cur_tipset.read().await.clone();
-        let publish = self.add_tipset(msg.clone(), &cur_ts, true).await?;
+        let limits = self
+            .sender_limits(&msg, true, true)
+            .map_err(metrics::record_rejection)?;
+        let publish = self
+            .add_tipset(msg.clone(), &cur_ts, true, Some(limits))
+            .await
+            .map_err(metrics::record_rejection)?;
         let msg_ser = msg.marshal_cbor()?;
         self.add_local(msg).await?;
         if publish {
@@ -323,7 +354,8 @@ where
         if msg.marshal_cbor()?.len() > 32 * 1024 {
             return Err(Error::MessageTooBig);
         }
-        valid_for_block_inclusion(msg.message(), Gas::new(0), NEWEST_NETWORK_VERSION)?;
+        valid_for_block_inclusion(msg.message(), Gas::new(0), NEWEST_NETWORK_VERSION)
+            .map_err(|e| Error::InvalidMessage(e.to_string()))?;
         if msg.value() > &fil_types::TOTAL_FILECOIN {
             return Err(Error::MessageValueTooHigh);
         }
@@ -336,17 +368,69 @@ where
     /// This is a helper to push that will help to make sure that the message fits the parameters
     /// to be pushed to the MessagePool.
     pub async fn add(&self, msg: SignedMessage) -> Result<(), Error> {
-        self.check_message(&msg).await?;
+        self.check_message(&msg)
+            .await
+            .map_err(metrics::record_rejection)?;
 
         let tip = self.cur_tipset.read().await.clone();
 
-        self.add_tipset(msg, &tip, false).await?;
+        self.add_tipset(msg, &tip, false, None)
+            .await
+            .map_err(metrics::record_rejection)?;
+        Ok(())
+    }
+
+    /// Adds a message received from the network. Senders that are neither local nor priority
+    /// addresses are held to the untrusted limits of the pool configuration.
+    pub async fn add_untrusted(&self, msg: SignedMessage) -> Result<(), Error> {
+        self.check_message(&msg)
+            .await
+            .map_err(metrics::record_rejection)?;
+
+        let trusted = self.config.priority_addrs().contains(msg.from())
+            || self.local_addrs.read().await.contains(msg.from());
+        let limits = self
+            .sender_limits(&msg, trusted, false)
+            .map_err(metrics::record_rejection)?;
+        let tip = self.cur_tipset.read().await.clone();
+        self.add_tipset(msg, &tip, false, Some(limits))
+            .await
+            .map_err(metrics::record_rejection)?;
         Ok(())
     }
 
+    /// Checks the gas premium floor for untrusted senders and returns the limits the sender's
+    /// pending messages are held to. As in Lotus, local messages are not subject to the nonce
+    /// gap limit.
+    fn sender_limits(
+        &self,
+        msg: &SignedMessage,
+        trusted: bool,
+        local: bool,
+    ) -> Result<SenderLimits, Error> {
+        let (max_pending, max_nonce_gap) = if trusted {
+            (
+                self.config.max_actor_pending_messages,
+                self.config.max_nonce_gap,
+            )
+        } else {
+            if msg.gas_premium() < &BigInt::from(self.config.min_untrusted_gas_premium) {
+                return Err(Error::GasPremiumTooLow);
+            }
+            (
+                self.config.max_untrusted_actor_pending_messages,
+                self.config.max_untrusted_nonce_gap,
+            )
+        };
+        Ok(SenderLimits {
+            max_pending,
+            max_nonce_gap: (!local).then(|| max_nonce_gap),
+        })
+    }
+
     /// Add a SignedMessage without doing any of the checks.
     pub async fn add_skip_checks(&mut self, m: SignedMessage) -> Result<(), Error> {
-        self.add_helper(m).await
+        self.add_helper(m, None).await
     }
 
     /// Verify the message signature. first check if it has already been verified and put into
@@ -358,7 +442,7 @@ where
             return Ok(());
         }
 
-        msg.verify().map_err(Error::Other)?;
+        msg.verify().map_err(Error::InvalidSignature)?;
 
         self.sig_val_cache.write().await.put(cid, ());
 
@@ -372,6 +456,7 @@ where
         msg: SignedMessage,
         cur_ts: &Tipset,
         local: bool,
+        limits: Option<SenderLimits>,
     ) -> Result<bool, Error> {
         let sequence = self.get_state_sequence(msg.from(), cur_ts).await?;
 
@@ -387,24 +472,31 @@ where
         if balance < msg_balance {
             return Err(Error::NotEnoughFunds);
         }
-        self.add_helper(msg).await?;
+        self.add_helper(msg, limits).await?;
         Ok(publish)
     }
 
     /// Finish verifying signed message before adding it to the pending mset hashmap. If an entry
     /// in the hashmap does not yet exist, create a new mset that will correspond to the from
     /// message and push it to the pending hashmap.
-    async fn add_helper(&self, msg: SignedMessage) -> Result<(), Error> {
+    async fn add_helper(
+        &self,
+        msg: SignedMessage,
+        limits: Option<SenderLimits>,
+    ) -> Result<(), Error> {
         let from = *msg.from();
         let cur_ts = self.cur_tipset.read().await.clone();
         add_helper(
             self.api.as_ref(),
             self.bls_sig_cache.as_ref(),
             self.pending.as_ref(),
-            msg,
+            msg.clone(),
             self.get_state_sequence(&from, &cur_ts).await?,
+            limits,
         )
-        .await
+        .await?;
+        let _ = self.updates.send(MpoolUpdate::add(msg));
+        Ok(())
     }
 
     /// Get the sequence for a given address, return Error if there is a failure to retrieve
@@ -470,7 +562,7 @@ where
 
         let publish = verify_msg_before_add(&msg, &cur_ts, true, &self.chain_config)?;
         self.check_balance(&msg, &cur_ts).await?;
-        self.add_helper(msg.clone()).await?;
+        self.add_helper(msg.clone(), None).await?;
         self.add_local(msg.clone()).await?;
 
         if publish {
@@ -511,7 +603,71 @@ where
         sequence: u64,
         applied: bool,
     ) -> Result<(), Error> {
-        remove(from, self.pending.as_ref(), sequence, applied).await
+        if let Some(m) = remove(from, self.pending.as_ref(), sequence, applied).await? {
+            let _ = self.updates.send(MpoolUpdate::remove(m));
+        }
+        Ok(())
+    }
+
+    /// Subscribes to messages added to and removed from pending.
+    pub fn subscribe_updates(&self) -> Subscriber<MpoolUpdate> {
+        self.updates.subscribe()
+    }
+
+    /// Subscribes to messages added to and removed from pending, limited to the given senders
+    /// unless `from` is empty. Returns an ID corresponding to the receiver of the updates, to
+    /// be polled with [`MessagePool::next_update`]. Updates are skipped when the subscriber lags.
+    /// The subscription is dropped once its buffer of updates is full, as happens when its
+    /// receiver stops polling.
+    pub async fn sub_updates(&self, from: Vec<Address>) -> i64 {
+        let (tx, rx) = bounded(UPDATE_SUBSCRIPTION_CAPACITY);
+        let mut subscriber = self.updates.subscribe();
+        let sub_id = self
+            .update_subscriptions_count
+            .fetch_add(1, Ordering::Relaxed)
+            + 1;
+        self.update_subscriptions.write().await.insert(sub_id, rx);
+
+        let subscriptions = self.update_subscriptions.clone();
+        task::spawn(async move {
+            loop {
+                match subscriber.recv().await {
+                    Ok(update) => {
+                        if !from.is_empty() && !from.contains(update.message.from()) {
+                            continue;
+                        }
+                        // Waiting for room would keep the task alive forever once the receiver
+                        // stops polling, such as when its websocket closed
+                        if let Err(e) = tx.try_send(update) {
+                            if e.is_full() {
+                                debug!("Mpool update subscription ID {} fell behind", sub_id);
+                            }
+                            subscriptions.write().await.remove(&sub_id);
+                            break;
+                        }
+                    }
+                    Err(RecvError::Lagged(e)) => {
+                        warn!("Mpool update subscriber lagged: skipping {} updates", e);
+                    }
+                    Err(RecvError::Closed) => break,
+                }
+            }
+        });
+
+        debug!("Mpool update subscription ID {} created", sub_id);
+        sub_id
+    }
+
+    /// Waits for the next update of the subscription with the given ID.
+    pub async fn next_update(&self, sub_id: &i64) -> Option<MpoolUpdate> {
+        let rx = self.update_subscriptions.read().await.get(sub_id).cloned();
+        match rx {
+            Some(rx) => rx.recv().await.ok(),
+            None => {
+                warn!("No mpool update subscription with ID {}", sub_id);
+                None
+            }
+        }
     }
 
     /// Return a tuple that contains a vector of all signed messages and the current tipset for
@@ -589,6 +745,15 @@ where
         }
     }
 
+    /// Returns the lowest gas premium a message replacing one with the given premium will be
+    /// accepted with. This is the larger of the minimum enforced when adding messages and the
+    /// premium scaled by the configured replace by fee ratio.
+    pub fn min_replacement_premium(&self, premium: &BigInt) -> BigInt {
+        let rbf_ratio = (self.config.replace_by_fee_ratio * RBF_DENOM as f64) as u64;
+        let by_ratio = (premium * rbf_ratio).div_ceil(&BigInt::from(RBF_DENOM));
+        std::cmp::max(rbf_min_price(premium) + 1u8, by_ratio)
+    }
+
     /// Loads local messages to the message pool to be applied.
     pub async fn load_local(&mut self) -> Result<(), Error> {
         let mut local_msgs = self.local_msgs.write().await;
@@ -638,6 +803,44 @@ where
 
 // Helpers for MessagePool
 
+/// Limits on the pending messages of a single sender.
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct SenderLimits {
+    max_pending: u64,
+    /// Maximum distance of a message sequence from the next expected sequence, if limited.
+    max_nonce_gap: Option<u64>,
+}
+
+impl SenderLimits {
+    /// Checks a message against the pending set of its sender. Replacing a pending message does
+    /// not count against the pending limit.
+    fn check(
+        &self,
+        msg: &SignedMessage,
+        mset: Option<&MsgSet>,
+        state_sequence: u64,
+    ) -> Result<(), Error> {
+        let (next_sequence, pending_count, replaces) = match mset {
+            Some(mset) => (
+                mset.next_sequence.max(state_sequence),
+                mset.msgs.len() as u64,
+                mset.msgs.contains_key(&msg.sequence()),
+            ),
+            None => (state_sequence, 0, false),
+        };
+
+        if let Some(max_gap) = self.max_nonce_gap {
+            if msg.sequence() > next_sequence + max_gap {
+                return Err(Error::NonceGap);
+            }
+        }
+        if !replaces && pending_count >= self.max_pending {
+            return Err(Error::TooManyPendingMessages);
+        }
+        Ok(())
+    }
+}
+
 /// Finish verifying signed message before adding it to the pending mset hashmap. If an entry
 /// in the hashmap does not yet exist, create a new mset that will correspond to the from message
 /// and push it to the pending hashmap.
@@ -647,6 +850,7 @@ pub(crate) async fn add_helper<T>(
     pending: &RwLock<HashMap<Address, MsgSet>>,
     msg: SignedMessage,
     sequence: u64,
+    limits: Option<SenderLimits>,
 ) -> Result<(), Error>
 where
     T: Provider,
@@ -672,6 +876,11 @@ where
         .put_message(&ChainMessage::Unsigned(msg.message().clone()))?;
 
     let mut pending = pending.write().await;
+    // The limits are checked under the same lock as the insertion, so that concurrent adds
+    // from one sender cannot all pass the check against the same pending set.
+    if let Some(limits) = limits {
+        limits.check(&msg, pending.get(&msg.message().from), sequence)?;
+    }
     let msett = pending.get_mut(&msg.message().from);
     match msett {
         Some(mset) => mset.add(msg)?,
@@ -713,25 +922,26 @@ fn verify_msg_before_add(
     Ok(local)
 }
 
-/// Remove a message from pending given the from address and sequence.
+/// Remove a message from pending given the from address and sequence, returning the removed
+/// message.
 pub async fn remove(
     from: &Address,
     pending: &RwLock<HashMap<Address, MsgSet>>,
     sequence: u64,
     applied: bool,
-) -> Result<(), Error> {
+) -> Result<Option<SignedMessage>, Error> {
     let mut pending = pending.write().await;
     let mset = if let Some(mset) = pending.get_mut(from) {
         mset
     } else {
-        return Ok(());
+        return Ok(None);
     };
 
-    mset.rm(sequence, applied);
+    let removed = mset.rm(sequence, applied);
 
     if mset.msgs.is_empty() {
         pending.remove(from);
     }
 
-    Ok(())
+    Ok(removed)
 }
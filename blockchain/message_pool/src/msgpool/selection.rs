@@ -57,7 +57,7 @@ where
         Ok(msgs)
     }
 
-    async fn select_messages_greedy(
+    pub(crate) async fn select_messages_greedy(
         &self,
         cur_ts: &Tipset,
         ts: &Tipset,
@@ -100,7 +100,7 @@ where
         Ok(msgs)
     }
 
-    async fn select_messages_optimal(
+    pub(crate) async fn select_messages_optimal(
         &self,
         cur_ts: &Tipset,
         target_tipset: &Tipset,
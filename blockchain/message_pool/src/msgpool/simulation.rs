@@ -0,0 +1,304 @@
+// Copyright 2019-2022 ChainSafe Systems
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+//! Replays recorded message pool snapshots through message selection, so that changes to the
+//! selection algorithm can be evaluated against real-world pending sets.
+//!
+//! A snapshot holds the pending messages of a pool together with the tipset, base fee and sender
+//! states they were selected against. Snapshots are recorded from a running pool with
+//! [`MessagePool::snapshot`] and stored as CBOR files, such as those written by `forest mpool snapshot`.
+
+use crate::errors::Error;
+use crate::msg_pool::MessagePool;
+use crate::msgpool::utils::get_gas_reward;
+use crate::msgpool::{Publisher, Subscriber};
+use crate::provider::Provider;
+use async_std::channel::bounded;
+use async_std::sync::Arc;
+use async_trait::async_trait;
+use chain::HeadChange;
+use cid::Cid;
+use encoding::tuple::*;
+use encoding::Cbor;
+use forest_blocks::{BlockHeader, Tipset, TipsetKeys};
+use forest_message::{ChainMessage, Message as MessageTrait, SignedMessage};
+use forest_vm::ActorState;
+use fvm_shared::address::{Address, Protocol};
+use fvm_shared::bigint::BigInt;
+use fvm_shared::message::Message;
+use std::collections::HashMap;
+use std::fmt;
+use std::path::Path;
+use tokio::sync::broadcast;
+
+/// State of a sender of pending messages at the snapshot tipset.
+#[derive(Clone, Debug, PartialEq, Serialize_tuple, Deserialize_tuple)]
+pub struct SnapshotActor {
+    pub address: Address,
+    pub sequence: u64,
+    #[serde(with = "fvm_shared::bigint::bigint_ser")]
+    pub balance: BigInt,
+}
+
+/// Pending messages of a message pool along with the chain state they are selected against.
+#[derive(Clone, Debug, PartialEq, Serialize_tuple, Deserialize_tuple)]
+pub struct MpoolSnapshot {
+    pub tipset: Vec<BlockHeader>,
+    #[serde(with = "fvm_shared::bigint::bigint_ser")]
+    pub base_fee: BigInt,
+    pub actors: Vec<SnapshotActor>,
+    pub pending: Vec<SignedMessage>,
+}
+
+impl Cbor for MpoolSnapshot {}
+
+impl MpoolSnapshot {
+    /// Reads a CBOR encoded snapshot from a file.
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, Error> {
+        let bytes = std::fs::read(path).map_err(|e| Error::Other(e.to_string()))?;
+        Ok(Self::unmarshal_cbor(&bytes)?)
+    }
+
+    /// Writes the snapshot CBOR encoded to a file.
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), Error> {
+        std::fs::write(path, self.marshal_cbor()?).map_err(|e| Error::Other(e.to_string()))
+    }
+}
+
+impl<T> MessagePool<T>
+where
+    T: Provider + std::marker::Send + std::marker::Sync + 'static,
+{
+    /// Records the pending messages of the pool against its current tipset.
+    pub async fn snapshot(&self) -> Result<MpoolSnapshot, Error> {
+        let cur_ts = self.cur_tipset.read().await.clone();
+        let api = self.api.read().await;
+        let base_fee = api.chain_compute_base_fee(&cur_ts)?;
+
+        let mut actors = Vec::new();
+        let mut pending = Vec::new();
+        for (address, mset) in self.pending.read().await.iter() {
+            let actor = api.get_actor_after(address, &cur_ts)?;
+            actors.push(SnapshotActor {
+                address: *address,
+                sequence: actor.sequence,
+                balance: actor.balance,
+            });
+            pending.extend(mset.msgs.values().cloned());
+        }
+        pending.sort_by_key(|m| (m.message().from.to_bytes(), m.sequence()));
+
+        Ok(MpoolSnapshot {
+            tipset: cur_ts.blocks().to_vec(),
+            base_fee,
+            actors,
+            pending,
+        })
+    }
+}
+
+/// Provider serving the chain state recorded in a [`MpoolSnapshot`].
+pub struct SnapshotProvider {
+    tipset: Arc<Tipset>,
+    base_fee: BigInt,
+    actors: HashMap<Address, ActorState>,
+    publisher: Publisher<HeadChange>,
+}
+
+impl SnapshotProvider {
+    pub fn new(snapshot: &MpoolSnapshot) -> Result<Self, Error> {
+        let tipset =
+            Tipset::new(snapshot.tipset.clone()).map_err(|e| Error::Other(e.to_string()))?;
+        let actors = snapshot
+            .actors
+            .iter()
+            .map(|a| {
+                (
+                    a.address,
+                    ActorState::new(
+                        Cid::default(),
+                        Cid::default(),
+                        a.balance.clone(),
+                        a.sequence,
+                    ),
+                )
+            })
+            .collect();
+        let (publisher, _) = broadcast::channel(1);
+        Ok(Self {
+            tipset: Arc::new(tipset),
+            base_fee: snapshot.base_fee.clone(),
+            actors,
+            publisher,
+        })
+    }
+}
+
+#[async_trait]
+impl Provider for SnapshotProvider {
+    async fn subscribe_head_changes(&mut self) -> Subscriber<HeadChange> {
+        self.publisher.subscribe()
+    }
+
+    async fn get_heaviest_tipset(&mut self) -> Option<Arc<Tipset>> {
+        Some(self.tipset.clone())
+    }
+
+    fn put_message(&self, msg: &ChainMessage) -> Result<Cid, Error> {
+        Ok(msg.cid()?)
+    }
+
+    fn get_actor_after(&self, addr: &Address, _ts: &Tipset) -> Result<ActorState, Error> {
+        self.actors
+            .get(addr)
+            .cloned()
+            .ok_or_else(|| Error::Other(format!("No actor state for {}", addr)))
+    }
+
+    fn messages_for_block(
+        &self,
+        _h: &BlockHeader,
+    ) -> Result<(Vec<Message>, Vec<SignedMessage>), Error> {
+        Ok((Vec::new(), Vec::new()))
+    }
+
+    async fn state_account_key(&self, addr: &Address, _ts: &Arc<Tipset>) -> Result<Address, Error> {
+        match addr.protocol() {
+            Protocol::BLS | Protocol::Secp256k1 => Ok(*addr),
+            _ => Err(Error::Other("given address was not a key addr".to_string())),
+        }
+    }
+
+    fn messages_for_tipset(&self, _h: &Tipset) -> Result<Vec<ChainMessage>, Error> {
+        Ok(Vec::new())
+    }
+
+    async fn load_tipset(&self, tsk: &TipsetKeys) -> Result<Arc<Tipset>, Error> {
+        if tsk == self.tipset.key() {
+            Ok(self.tipset.clone())
+        } else {
+            Err(Error::Other(
+                "Tipset is not part of the snapshot".to_owned(),
+            ))
+        }
+    }
+
+    fn chain_compute_base_fee(&self, _ts: &Tipset) -> Result<BigInt, Error> {
+        Ok(self.base_fee.clone())
+    }
+}
+
+/// Message selection strategy to simulate.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SelectionStrategy {
+    /// Picks the strategy from the ticket quality, as [`MessagePool::select_messages`] does.
+    Auto,
+    Greedy,
+    Optimal,
+}
+
+impl fmt::Display for SelectionStrategy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SelectionStrategy::Auto => write!(f, "auto"),
+            SelectionStrategy::Greedy => write!(f, "greedy"),
+            SelectionStrategy::Optimal => write!(f, "optimal"),
+        }
+    }
+}
+
+/// Outcome of a message selection run. Messages are not executed, so gas used is the sum of the
+/// gas limits of the selected messages.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SelectionReport {
+    pub strategy: SelectionStrategy,
+    pub ticket_quality: f64,
+    pub messages: usize,
+    pub gas_used: i64,
+    pub gas_reward: BigInt,
+    /// Share of the block gas limit used by the selected messages.
+    pub fill_ratio: f64,
+}
+
+/// Message pool loaded with the pending messages of a snapshot.
+pub struct Simulation {
+    mpool: MessagePool<SnapshotProvider>,
+    tipset: Arc<Tipset>,
+    base_fee: BigInt,
+}
+
+impl Simulation {
+    /// Loads the snapshot into a new message pool. Pending messages are added as recorded,
+    /// without being validated again.
+    pub async fn new(snapshot: &MpoolSnapshot) -> Result<Self, Error> {
+        let provider = SnapshotProvider::new(snapshot)?;
+        let tipset = provider.tipset.clone();
+        let (tx, _rx) = bounded(1);
+        let mut mpool = MessagePool::new(
+            provider,
+            "simulation".to_owned(),
+            tx,
+            Default::default(),
+            Arc::default(),
+        )
+        .await?;
+        for msg in snapshot.pending.iter().cloned() {
+            mpool.add_skip_checks(msg).await?;
+        }
+        Ok(Self {
+            mpool,
+            tipset,
+            base_fee: snapshot.base_fee.clone(),
+        })
+    }
+
+    /// Selects messages for a block with the given ticket quality and reports the result.
+    pub async fn run(
+        &self,
+        ticket_quality: f64,
+        strategy: SelectionStrategy,
+    ) -> Result<SelectionReport, Error> {
+        let msgs = self.select(ticket_quality, strategy).await?;
+
+        let mut gas_used = 0;
+        let mut gas_reward = BigInt::default();
+        for m in &msgs {
+            gas_used += m.gas_limit();
+            gas_reward += get_gas_reward(m, &self.base_fee);
+        }
+
+        Ok(SelectionReport {
+            strategy,
+            ticket_quality,
+            messages: msgs.len(),
+            gas_used,
+            gas_reward,
+            fill_ratio: gas_used as f64 / fil_types::BLOCK_GAS_LIMIT as f64,
+        })
+    }
+
+    /// Selects messages for a block with the given ticket quality.
+    pub async fn select(
+        &self,
+        ticket_quality: f64,
+        strategy: SelectionStrategy,
+    ) -> Result<Vec<SignedMessage>, Error> {
+        match strategy {
+            SelectionStrategy::Auto => {
+                self.mpool
+                    .select_messages(&self.tipset, ticket_quality)
+                    .await
+            }
+            SelectionStrategy::Greedy => {
+                self.mpool
+                    .select_messages_greedy(&self.tipset, &self.tipset)
+                    .await
+            }
+            SelectionStrategy::Optimal => {
+                self.mpool
+                    .select_messages_optimal(&self.tipset, &self.tipset, ticket_quality)
+                    .await
+            }
+        }
+    }
+}
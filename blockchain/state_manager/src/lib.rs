@@ -468,6 +468,17 @@ where
         })
     }
 
+    /// Sets the pair of (parent state root, message receipt root) of a tipset, which is then
+    /// returned by [`StateManager::tipset_state`] instead of being computed. This lets tests run
+    /// chains of tipsets whose messages can't be executed.
+    #[cfg(feature = "test-utils")]
+    pub async fn set_tipset_state(&self, tipset_keys: TipsetKeys, state: (Cid, Cid)) {
+        self.cache
+            .write()
+            .await
+            .insert(tipset_keys, Arc::new(RwLock::new(Some(state))));
+    }
+
     fn call_raw(
         self: &Arc<Self>,
         msg: &mut Message,
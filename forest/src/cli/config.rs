@@ -4,6 +4,7 @@
 use chain_sync::SyncConfig;
 use directories::ProjectDirs;
 use forest_libp2p::Libp2pConfig;
+use key_management::RemoteSignerConfig;
 use networks::ChainConfig;
 use rpc_client::DEFAULT_PORT;
 use serde::{Deserialize, Serialize};
@@ -29,12 +30,17 @@ pub struct Config {
     /// Will use the cids in the header of the file to index the chain.
     pub skip_load: bool,
     pub encrypt_keystore: bool,
+    /// File to read the keystore passphrase from when `encrypt_keystore` is set.
+    /// The `FOREST_KEYSTORE_PHRASE` environment variable takes precedence.
+    pub keystore_passphrase_file: Option<PathBuf>,
     /// Metrics bind, e.g. 127.0.0.1:6116
     pub metrics_address: SocketAddr,
     pub rocks_db: db::rocks_config::RocksDbConfig,
     pub network: Libp2pConfig,
     pub sync: SyncConfig,
     pub chain: Arc<ChainConfig>,
+    /// External signer used for addresses whose keys are not in the local keystore.
+    pub remote_signer: Option<RemoteSignerConfig>,
 }
 
 impl Default for Config {
@@ -53,9 +59,11 @@ impl Default for Config {
             skip_load: false,
             sync: SyncConfig::default(),
             encrypt_keystore: true,
+            keystore_passphrase_file: None,
             metrics_address: FromStr::from_str("127.0.0.1:6116").unwrap(),
             rocks_db: db::rocks_config::RocksDbConfig::default(),
             chain: Arc::default(),
+            remote_signer: None,
         }
     }
 }
@@ -0,0 +1,161 @@
+// Copyright 2019-2022 ChainSafe Systems
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+use super::{cli_error_and_die, Config};
+use anyhow::{anyhow, bail, Context};
+use key_management::{KeyStore, KeyStoreConfig, ENCRYPTED_KEYSTORE_NAME};
+use log::error;
+use rpassword::read_password;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use structopt::StructOpt;
+use utils::read_file_to_string;
+
+/// Environment variable holding the keystore passphrase, used for non-interactive starts.
+pub const FOREST_KEYSTORE_PHRASE_ENV: &str = "FOREST_KEYSTORE_PHRASE";
+
+#[derive(Debug, StructOpt)]
+pub enum KeystoreCommands {
+    #[structopt(
+        about = "Re-encrypt the keystore with a new passphrase. The daemon must not be running"
+    )]
+    ChangePassphrase {
+        #[structopt(
+            long,
+            help = "Read the new passphrase from a file (or a file descriptor such as /dev/fd/3) instead of prompting"
+        )]
+        new_passphrase_file: Option<PathBuf>,
+    },
+}
+
+impl KeystoreCommands {
+    pub fn run(&self, config: &Config) {
+        match self {
+            Self::ChangePassphrase {
+                new_passphrase_file,
+            } => {
+                if !config.data_dir.join(ENCRYPTED_KEYSTORE_NAME).exists() {
+                    return cli_error_and_die(
+                        &format!(
+                            "No encrypted keystore found in {}",
+                            config.data_dir.display()
+                        ),
+                        1,
+                    );
+                }
+
+                let mut ks = match read_encrypted_keystore(config) {
+                    Ok(ks) => ks,
+                    Err(e) => return cli_error_and_die(&e.to_string(), 1),
+                };
+
+                let new_passphrase = match new_passphrase_file {
+                    Some(path) => read_passphrase_file(path),
+                    None => prompt_new_passphrase("Enter the new keystore passphrase: "),
+                };
+                let new_passphrase = match new_passphrase {
+                    Ok(passphrase) => passphrase,
+                    Err(e) => return cli_error_and_die(&e.to_string(), 1),
+                };
+
+                if let Err(e) = ks.change_passphrase(&new_passphrase) {
+                    return cli_error_and_die(
+                        &format!("Failed to re-encrypt the keystore: {}", e),
+                        1,
+                    );
+                }
+
+                println!("Keystore passphrase changed");
+            }
+        }
+    }
+}
+
+/// Opens the encrypted keystore in the configured data directory.
+///
+/// The passphrase is taken from the `FOREST_KEYSTORE_PHRASE` environment variable or from
+/// `keystore_passphrase_file` when either is set, in which case a wrong passphrase is an error
+/// rather than a retry. Otherwise the passphrase is prompted for, which is only possible when
+/// standard input is a terminal.
+pub(crate) fn read_encrypted_keystore(config: &Config) -> anyhow::Result<KeyStore> {
+    let open = |passphrase: String| {
+        KeyStore::new(KeyStoreConfig::Encrypted(
+            PathBuf::from(&config.data_dir),
+            passphrase,
+        ))
+    };
+
+    if let Some(passphrase) = read_passphrase_non_interactive(config)? {
+        return open(passphrase).map_err(|_| anyhow!("Incorrect keystore passphrase"));
+    }
+
+    if !atty::is(atty::Stream::Stdin) {
+        bail!(
+            "The keystore is encrypted but no passphrase was provided. Set the {} environment \
+            variable or `keystore_passphrase_file`, or disable keystore encryption",
+            FOREST_KEYSTORE_PHRASE_ENV
+        );
+    }
+
+    let keystore_exists = config.data_dir.join(ENCRYPTED_KEYSTORE_NAME).exists();
+    loop {
+        let passphrase = if keystore_exists {
+            prompt("Enter the keystore passphrase: ")?
+        } else {
+            match prompt_new_passphrase("Enter the keystore passphrase: ") {
+                Ok(passphrase) => passphrase,
+                Err(e) => {
+                    error!("{}", e);
+                    continue;
+                }
+            }
+        };
+
+        match open(passphrase) {
+            Ok(ks) => break Ok(ks),
+            Err(_) => {
+                error!("Incorrect passphrase entered. Please try again.")
+            }
+        };
+    }
+}
+
+/// Reads the keystore passphrase from the environment or the configured passphrase file.
+/// Returns `None` if neither is set.
+fn read_passphrase_non_interactive(config: &Config) -> anyhow::Result<Option<String>> {
+    if let Ok(passphrase) = std::env::var(FOREST_KEYSTORE_PHRASE_ENV) {
+        return Ok(Some(passphrase));
+    }
+    config
+        .keystore_passphrase_file
+        .as_deref()
+        .map(read_passphrase_file)
+        .transpose()
+}
+
+/// Reads a passphrase from a file, ignoring the trailing line break.
+fn read_passphrase_file(path: &Path) -> anyhow::Result<String> {
+    let passphrase = read_file_to_string(path)
+        .with_context(|| format!("Failed to read passphrase file {}", path.display()))?;
+    Ok(passphrase
+        .trim_end_matches(|c| c == '\n' || c == '\r')
+        .to_owned())
+}
+
+fn prompt(msg: &str) -> anyhow::Result<String> {
+    print!("{}", msg);
+    std::io::stdout().flush()?;
+    read_password().context("Error reading passphrase")
+}
+
+/// Prompts for a new passphrase twice and checks both entries match.
+fn prompt_new_passphrase(msg: &str) -> anyhow::Result<String> {
+    if !atty::is(atty::Stream::Stdin) {
+        bail!("Cannot prompt for a passphrase, standard input is not a terminal");
+    }
+    let passphrase = prompt(msg)?;
+    if passphrase != prompt("Confirm passphrase: ")? {
+        bail!("Passphrases do not match. Please retry.");
+    }
+    Ok(passphrase)
+}
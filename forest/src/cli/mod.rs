@@ -7,8 +7,11 @@ mod config;
 mod config_cmd;
 mod fetch_params_cmd;
 mod genesis_cmd;
+mod keystore_cmd;
 mod mpool_cmd;
+mod msig_cmd;
 mod net_cmd;
+mod send_cmd;
 mod state_cmd;
 mod sync_cmd;
 mod wallet_cmd;
@@ -18,8 +21,11 @@ pub(super) use self::chain_cmd::ChainCommands;
 pub use self::config::Config;
 pub(super) use self::fetch_params_cmd::FetchCommands;
 pub(super) use self::genesis_cmd::GenesisCommands;
+pub(super) use self::keystore_cmd::{read_encrypted_keystore, KeystoreCommands};
 pub(super) use self::mpool_cmd::MpoolCommands;
+pub(super) use self::msig_cmd::MsigCommands;
 pub(super) use self::net_cmd::NetCommands;
+pub(super) use self::send_cmd::SendCommand;
 pub(super) use self::state_cmd::StateCommands;
 pub(super) use self::sync_cmd::SyncCommands;
 pub(super) use self::wallet_cmd::WalletCommands;
@@ -27,6 +33,7 @@ pub(super) use self::wallet_cmd::WalletCommands;
 use byte_unit::Byte;
 use directories::ProjectDirs;
 use fil_types::FILECOIN_PRECISION;
+use fvm_shared::address::Address;
 use fvm_shared::bigint::BigInt;
 use jsonrpc_v2::Error as JsonRpcError;
 use log::{error, info, warn};
@@ -39,6 +46,7 @@ use std::io::{self, Write};
 use std::net::SocketAddr;
 use std::path::PathBuf;
 use std::process;
+use std::str::FromStr;
 use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::Arc;
 use structopt::StructOpt;
@@ -46,6 +54,7 @@ use structopt::StructOpt;
 use crate::cli::config_cmd::ConfigCommands;
 use cid::Cid;
 use forest_blocks::tipset_json::TipsetJson;
+use rpc_client::wallet_ops::wallet_default_address;
 use utils::{read_file_to_string, read_toml};
 
 /// CLI structure generated when interacting with Forest binary
@@ -88,12 +97,21 @@ pub enum Subcommand {
     #[structopt(name = "wallet", about = "Manage wallet")]
     Wallet(WalletCommands),
 
+    #[structopt(name = "keystore", about = "Manage the local keystore")]
+    Keystore(KeystoreCommands),
+
     #[structopt(name = "sync", about = "Inspect or interact with the chain syncer")]
     Sync(SyncCommands),
 
     #[structopt(name = "mpool", about = "Interact with the Message Pool")]
     Mpool(MpoolCommands),
 
+    #[structopt(name = "msig", about = "Interact with multisig wallets")]
+    Msig(MsigCommands),
+
+    #[structopt(name = "send", about = "Send funds between accounts")]
+    Send(SendCommand),
+
     #[structopt(name = "state", about = "Interact with and query filecoin chain state")]
     State(StateCommands),
 
@@ -127,6 +145,11 @@ pub struct CliOpts {
     pub kademlia: Option<bool>,
     #[structopt(long, help = "Allow MDNS (default = false)")]
     pub mdns: Option<bool>,
+    #[structopt(
+        long,
+        help = "Map the listening ports on the UPnP gateway (default = false)"
+    )]
+    pub upnp: Option<bool>,
     #[structopt(long, help = "Validate snapshot at given EPOCH")]
     pub height: Option<i64>,
     #[structopt(long, help = "Import a snapshot from a local CAR file or url")]
@@ -156,6 +179,11 @@ pub struct CliOpts {
     pub target_peer_count: Option<u32>,
     #[structopt(long, help = "Encrypt the keystore (default = true)")]
     pub encrypt_keystore: Option<bool>,
+    #[structopt(
+        long,
+        help = "Read the keystore passphrase from a file (or a file descriptor such as /dev/fd/3)"
+    )]
+    pub keystore_passphrase_file: Option<PathBuf>,
     #[structopt(
         long,
         help = "Choose network chain to sync to",
@@ -216,6 +244,7 @@ impl CliOpts {
 
         cfg.network.kademlia = self.kademlia.unwrap_or(cfg.network.kademlia);
         cfg.network.mdns = self.mdns.unwrap_or(cfg.network.mdns);
+        cfg.network.upnp = self.upnp.unwrap_or(cfg.network.upnp);
         if let Some(target_peer_count) = self.target_peer_count {
             cfg.network.target_peer_count = target_peer_count;
         }
@@ -232,6 +261,9 @@ impl CliOpts {
         if let Some(encrypt_keystore) = self.encrypt_keystore {
             cfg.encrypt_keystore = encrypt_keystore;
         }
+        if let Some(keystore_passphrase_file) = &self.keystore_passphrase_file {
+            cfg.keystore_passphrase_file = Some(keystore_passphrase_file.to_owned());
+        }
 
         Ok(cfg)
     }
@@ -421,6 +453,51 @@ pub(super) fn balance_to_fil(balance: BigInt) -> Result<Float, ParseFloatError>
     Ok(Float::with_val(128, b / p))
 }
 
+/// Parse an address, exiting with an error if it is invalid
+pub(super) fn parse_address(addr: &str) -> Address {
+    Address::from_str(addr)
+        .map_err(|_| cli_error_and_die(&format!("{} is not a valid address", addr), 1))
+        .unwrap()
+}
+
+/// Returns the given sender address, or the wallet default address if none was given
+pub(super) async fn sender(from: &Option<String>) -> Address {
+    match from {
+        Some(addr) => parse_address(addr),
+        None => {
+            let default = wallet_default_address()
+                .await
+                .map_err(handle_rpc_err)
+                .unwrap();
+            parse_address(&default)
+        }
+    }
+}
+
+/// Parse a decimal FIL amount, such as `1.5`, into attoFIL
+pub(super) fn parse_fil(amount: &str) -> Result<BigInt, String> {
+    let amount = amount.trim();
+    let invalid = || format!("{} is not a valid FIL amount", amount);
+    let (whole, frac) = amount.split_once('.').unwrap_or((amount, ""));
+    if (whole.is_empty() && frac.is_empty())
+        || frac.len() > 18
+        || !whole
+            .chars()
+            .chain(frac.chars())
+            .all(|c| c.is_ascii_digit())
+    {
+        return Err(invalid());
+    }
+
+    let whole = if whole.is_empty() {
+        BigInt::from(0)
+    } else {
+        BigInt::from_str(whole).map_err(|_| invalid())?
+    };
+    let frac = BigInt::from_str(&format!("{:0<18}", frac)).map_err(|_| invalid())?;
+    Ok(whole * FILECOIN_PRECISION + frac)
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
@@ -459,4 +536,25 @@ mod test {
     fn to_size_string_too_large_input_should_fail() {
         to_size_string(&(BigInt::from(u128::MAX) + 1));
     }
+
+    #[test]
+    fn parse_fil_amounts() {
+        let cases = [
+            ("1", BigInt::from(FILECOIN_PRECISION)),
+            ("0.5", BigInt::from(FILECOIN_PRECISION / 2)),
+            (".000000000000000001", BigInt::from(1)),
+            ("12.25", BigInt::from(12_250_000_000_000_000_000u128)),
+        ];
+
+        for (input, expected) in cases {
+            assert_eq!(parse_fil(input).unwrap(), expected);
+        }
+    }
+
+    #[test]
+    fn parse_fil_invalid_amounts() {
+        for input in ["", ".", "-1", "1.0000000000000000001", "1e18", "one"] {
+            assert!(parse_fil(input).is_err());
+        }
+    }
 }
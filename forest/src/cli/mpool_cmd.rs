@@ -3,8 +3,10 @@
 
 use std::collections::HashMap;
 
+use encoding::Cbor;
 use forest_blocks::tipset_keys_json::TipsetKeysJson;
 use forest_json::address::json::AddressJson;
+use forest_message::signed_message::json::SignedMessageJson;
 use forest_message::Message;
 use forest_message::SignedMessage;
 use fvm_shared::address::Address;
@@ -13,12 +15,13 @@ use jsonrpc_v2::Error;
 use structopt::StructOpt;
 
 use forest_json::cid::vec::CidJsonVec;
+use message_pool::simulation::MpoolSnapshot;
 use rpc_client::chain_ops::*;
 use rpc_client::mpool_ops::*;
 use rpc_client::state_ops::state_get_actor;
 use rpc_client::wallet_ops::wallet_list;
 
-use crate::cli::handle_rpc_err;
+use crate::cli::{cli_error_and_die, handle_rpc_err, parse_address};
 
 #[derive(Debug, StructOpt)]
 pub enum MpoolCommands {
@@ -35,6 +38,33 @@ pub enum MpoolCommands {
         #[structopt(short, help = "Print stats for local addresses only")]
         local: bool,
     },
+    #[structopt(help = "Replace a pending message with one paying higher gas fees")]
+    Replace {
+        #[structopt(long, help = "Sender of the pending message")]
+        from: String,
+        #[structopt(long, help = "Nonce of the pending message")]
+        nonce: u64,
+        #[structopt(
+            long,
+            conflicts_with_all = &["gas-premium", "gas-feecap"],
+            help = "Re-estimate the gas fees, raising the premium to the minimum accepted replacement"
+        )]
+        auto: bool,
+        #[structopt(long, required_unless = "auto", help = "New gas premium in attoFIL")]
+        gas_premium: Option<String>,
+        #[structopt(
+            long,
+            help = "New gas fee cap in attoFIL. Defaults to the old fee cap, raised to the premium"
+        )]
+        gas_feecap: Option<String>,
+    },
+    #[structopt(
+        help = "Write a snapshot of the pending messages for the selection simulation to a file"
+    )]
+    Snapshot {
+        #[structopt(help = "Path of the snapshot file")]
+        path: String,
+    },
 }
 
 impl MpoolCommands {
@@ -45,6 +75,55 @@ impl MpoolCommands {
                 let messages = res.map_err(handle_rpc_err).unwrap();
                 println!("{:#?}", messages);
             }
+            Self::Replace {
+                from,
+                nonce,
+                auto,
+                gas_premium,
+                gas_feecap,
+            } => {
+                let SignedMessageJson(smsg) = mpool_replace((
+                    AddressJson(parse_address(from)),
+                    *nonce,
+                    *auto,
+                    gas_premium.clone(),
+                    gas_feecap.clone(),
+                ))
+                .await
+                .map_err(handle_rpc_err)
+                .unwrap();
+                println!(
+                    "Replaced message with nonce {}, premium: {} attoFIL, fee cap: {} attoFIL",
+                    nonce,
+                    smsg.message().gas_premium,
+                    smsg.message().gas_fee_cap
+                );
+                println!("{}", smsg.cid().unwrap());
+            }
+            Self::Snapshot { path } => {
+                let encoded = mpool_snapshot(()).await.map_err(handle_rpc_err).unwrap();
+                let bytes = base64::decode(encoded)
+                    .map_err(|e| {
+                        cli_error_and_die(&format!("Failed to decode the snapshot: {}", e), 1)
+                    })
+                    .unwrap();
+                let snapshot = MpoolSnapshot::unmarshal_cbor(&bytes)
+                    .map_err(|e| {
+                        cli_error_and_die(&format!("Failed to decode the snapshot: {}", e), 1)
+                    })
+                    .unwrap();
+                snapshot
+                    .save(path)
+                    .map_err(|e| {
+                        cli_error_and_die(&format!("Failed to write the snapshot: {}", e), 1)
+                    })
+                    .unwrap();
+                println!(
+                    "Wrote {} pending messages to {}",
+                    snapshot.pending.len(),
+                    path
+                );
+            }
             Self::Stat {
                 base_fee_lookback,
                 local,
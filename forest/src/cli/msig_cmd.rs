@@ -0,0 +1,254 @@
+// Copyright 2019-2022 ChainSafe Systems
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+use forest_blocks::tipset_keys_json::TipsetKeysJson;
+use forest_json::address::json::AddressJson;
+use fvm_shared::bigint::BigInt;
+use fvm_shared::clock::ChainEpoch;
+use rpc_client::chain_ops::*;
+use rpc_client::msig_ops::*;
+use structopt::StructOpt;
+
+use super::{balance_to_fil, cli_error_and_die, handle_rpc_err, parse_address, parse_fil, sender};
+
+#[derive(Debug, StructOpt)]
+pub enum MsigCommands {
+    #[structopt(about = "Create a new multisig wallet")]
+    Create {
+        #[structopt(help = "Addresses of the signers")]
+        signers: Vec<String>,
+        #[structopt(
+            long,
+            help = "Number of approvals required to execute a transaction. Defaults to all signers"
+        )]
+        required: Option<u64>,
+        #[structopt(
+            long,
+            default_value = "0",
+            help = "Number of epochs over which the initial balance vests"
+        )]
+        duration: ChainEpoch,
+        #[structopt(long, default_value = "0", help = "Initial balance in FIL")]
+        value: String,
+        #[structopt(
+            long,
+            help = "Account sending the create message. Defaults to the wallet default"
+        )]
+        from: Option<String>,
+    },
+    #[structopt(about = "Propose a multisig transaction")]
+    Propose {
+        #[structopt(help = "Address of the multisig")]
+        multisig: String,
+        #[structopt(help = "Recipient of the transaction")]
+        to: String,
+        #[structopt(help = "Amount of FIL to send")]
+        value: String,
+        #[structopt(long, default_value = "0", help = "Method to call on the recipient")]
+        method: u64,
+        #[structopt(long, default_value = "", help = "Base64 encoded method parameters")]
+        params: String,
+        #[structopt(
+            long,
+            help = "Signer proposing the transaction. Defaults to the wallet default"
+        )]
+        from: Option<String>,
+    },
+    #[structopt(about = "Approve a pending multisig transaction")]
+    Approve {
+        #[structopt(help = "Address of the multisig")]
+        multisig: String,
+        #[structopt(help = "ID of the pending transaction")]
+        txn_id: i64,
+        #[structopt(
+            long,
+            help = "Signer approving the transaction. Defaults to the wallet default"
+        )]
+        from: Option<String>,
+    },
+    #[structopt(about = "Cancel a pending multisig transaction")]
+    Cancel {
+        #[structopt(help = "Address of the multisig")]
+        multisig: String,
+        #[structopt(help = "ID of the pending transaction")]
+        txn_id: i64,
+        #[structopt(
+            long,
+            help = "Signer cancelling the transaction, must be the proposer. Defaults to the wallet default"
+        )]
+        from: Option<String>,
+    },
+    #[structopt(about = "List the pending transactions of a multisig")]
+    Pending {
+        #[structopt(help = "Address of the multisig")]
+        multisig: String,
+    },
+    #[structopt(about = "Print the amount vested between two epochs")]
+    Vested {
+        #[structopt(help = "Address of the multisig")]
+        multisig: String,
+        #[structopt(long, default_value = "0", help = "Start epoch")]
+        start_epoch: ChainEpoch,
+        #[structopt(long, help = "End epoch. Defaults to the current head")]
+        end_epoch: Option<ChainEpoch>,
+    },
+}
+
+impl MsigCommands {
+    pub async fn run(&self) {
+        match self {
+            Self::Create {
+                signers,
+                required,
+                duration,
+                value,
+                from,
+            } => {
+                if signers.is_empty() {
+                    return cli_error_and_die("At least one signer is required", 1);
+                }
+                let required = required.unwrap_or(signers.len() as u64);
+                let signers = signers
+                    .iter()
+                    .map(|s| AddressJson(parse_address(s)))
+                    .collect();
+                let value = parse_fil(value)
+                    .map_err(|e| cli_error_and_die(&e, 1))
+                    .unwrap();
+                let from = sender(from).await;
+
+                let cid = msig_create((
+                    required,
+                    signers,
+                    *duration,
+                    value.to_string(),
+                    AddressJson(from),
+                ))
+                .await
+                .map_err(handle_rpc_err)
+                .unwrap();
+                println!("Sent create in message: {}", cid.0);
+            }
+            Self::Propose {
+                multisig,
+                to,
+                value,
+                method,
+                params,
+                from,
+            } => {
+                let value = parse_fil(value)
+                    .map_err(|e| cli_error_and_die(&e, 1))
+                    .unwrap();
+                if base64::decode(params).is_err() {
+                    return cli_error_and_die("Params must be base64 encoded", 1);
+                }
+                let from = sender(from).await;
+
+                let cid = msig_propose((
+                    AddressJson(parse_address(multisig)),
+                    AddressJson(parse_address(to)),
+                    value.to_string(),
+                    AddressJson(from),
+                    *method,
+                    params.clone(),
+                ))
+                .await
+                .map_err(handle_rpc_err)
+                .unwrap();
+                println!("Sent proposal in message: {}", cid.0);
+            }
+            Self::Approve {
+                multisig,
+                txn_id,
+                from,
+            } => {
+                let from = sender(from).await;
+                let cid = msig_approve((
+                    AddressJson(parse_address(multisig)),
+                    *txn_id,
+                    AddressJson(from),
+                ))
+                .await
+                .map_err(handle_rpc_err)
+                .unwrap();
+                println!("Sent approval in message: {}", cid.0);
+            }
+            Self::Cancel {
+                multisig,
+                txn_id,
+                from,
+            } => {
+                let from = sender(from).await;
+                let cid = msig_cancel((
+                    AddressJson(parse_address(multisig)),
+                    *txn_id,
+                    AddressJson(from),
+                ))
+                .await
+                .map_err(handle_rpc_err)
+                .unwrap();
+                println!("Sent cancellation in message: {}", cid.0);
+            }
+            Self::Pending { multisig } => {
+                let head = chain_head().await.map_err(handle_rpc_err).unwrap();
+                let pending = msig_get_pending((
+                    AddressJson(parse_address(multisig)),
+                    TipsetKeysJson(head.0.key().to_owned()),
+                ))
+                .await
+                .map_err(handle_rpc_err)
+                .unwrap();
+
+                if pending.is_empty() {
+                    println!("No pending transactions");
+                    return;
+                }
+                println!("ID\tApprovals\tTo\tValue\tMethod\tParams");
+                for txn in pending {
+                    println!(
+                        "{}\t{}\t{}\t{} FIL\t{}\t{}",
+                        txn.id,
+                        txn.approved.len(),
+                        txn.to.0,
+                        balance_to_fil(txn.value).unwrap(),
+                        txn.method,
+                        txn.params
+                    );
+                }
+            }
+            Self::Vested {
+                multisig,
+                start_epoch,
+                end_epoch,
+            } => {
+                let head = chain_head().await.map_err(handle_rpc_err).unwrap().0;
+                let tipset_at =
+                    |epoch: ChainEpoch| chain_get_tipset_by_height((epoch, head.key().to_owned()));
+                let start = tipset_at(*start_epoch)
+                    .await
+                    .map_err(handle_rpc_err)
+                    .unwrap()
+                    .0;
+                let end = match end_epoch {
+                    Some(epoch) => tipset_at(*epoch).await.map_err(handle_rpc_err).unwrap().0,
+                    None => head.clone(),
+                };
+
+                let vested = msig_get_vested((
+                    AddressJson(parse_address(multisig)),
+                    TipsetKeysJson(start.key().to_owned()),
+                    TipsetKeysJson(end.key().to_owned()),
+                ))
+                .await
+                .map_err(handle_rpc_err)
+                .unwrap();
+                let vested: BigInt = vested
+                    .parse()
+                    .map_err(|_| cli_error_and_die("Invalid vested amount returned", 1))
+                    .unwrap();
+                println!("{} FIL", balance_to_fil(vested).unwrap());
+            }
+        }
+    }
+}
@@ -1,8 +1,10 @@
 // Copyright 2019-2022 ChainSafe Systems
 // SPDX-License-Identifier: Apache-2.0, MIT
 
+use cid::Cid;
+use forest_json::cid::CidJson;
 use forest_libp2p::{Multiaddr, Protocol};
-use rpc_api::data_types::AddrInfo;
+use rpc_api::data_types::{AddrInfo, BandwidthStats, ExtendedPeerInfo, NetBlockList};
 use std::collections::HashSet;
 use structopt::StructOpt;
 
@@ -18,7 +20,27 @@ pub enum NetCommands {
     Listen,
     /// Lists libp2p swarm peers
     #[structopt(about = "Print peers")]
-    Peers,
+    Peers {
+        #[structopt(
+            long,
+            short,
+            help = "Print agent versions, latency, connection details and scores of the peers"
+        )]
+        extended: bool,
+    },
+    /// Prints the round trip time of the pings to a peer
+    #[structopt(about = "Ping a connected peer")]
+    Ping {
+        #[structopt(help = "Peer ID to ping")]
+        id: String,
+        #[structopt(
+            long,
+            short,
+            default_value = "1",
+            help = "Number of pings, the peer is pinged every 15 seconds"
+        )]
+        count: usize,
+    },
     /// Connects to a peer
     #[structopt(about = "Connect to a peer by its peer ID and multiaddresses")]
     Connect {
@@ -31,6 +53,61 @@ pub enum NetCommands {
         #[structopt(about = "Peer ID to disconnect from")]
         id: String,
     },
+    /// Looks up the addresses of a peer in the DHT
+    #[structopt(about = "Find the addresses of a peer by its peer ID")]
+    FindPeer {
+        #[structopt(help = "Peer ID to look up")]
+        id: String,
+    },
+    /// Blocks peers, IP addresses or subnets from connecting
+    #[structopt(about = "Block peers, IP addresses or subnets")]
+    BlockAdd(BlockListEntries),
+    /// Lifts the block of peers, IP addresses or subnets
+    #[structopt(about = "Unblock peers, IP addresses or subnets")]
+    BlockRemove(BlockListEntries),
+    /// Lists the blocked peers, IP addresses and subnets
+    #[structopt(about = "List blocked peers, IP addresses and subnets")]
+    BlockList,
+    /// Prints whether the node can be dialed by other peers
+    #[structopt(about = "Print information about reachability from the internet")]
+    Reachability,
+    /// Fetches a block over bitswap into the local store
+    #[structopt(about = "<CID> Fetch a block from peers over bitswap")]
+    BitswapFetch {
+        #[structopt(help = "CID of the block to fetch")]
+        cid: String,
+    },
+    /// Prints the bandwidth used by the node
+    #[structopt(about = "Print bandwidth usage information")]
+    Bandwidth {
+        #[structopt(long, help = "Print bandwidth usage information by peer")]
+        by_peer: bool,
+        #[structopt(
+            long,
+            help = "Print the payload bytes exchanged by protocol, leaving out Kademlia, Identify, ping and framing overhead"
+        )]
+        by_protocol: bool,
+    },
+}
+
+#[derive(Debug, StructOpt)]
+pub struct BlockListEntries {
+    #[structopt(long, about = "Peer ID")]
+    peer: Vec<String>,
+    #[structopt(long, about = "IP address")]
+    ip: Vec<String>,
+    #[structopt(long, about = "IP subnet in CIDR notation")]
+    subnet: Vec<String>,
+}
+
+impl BlockListEntries {
+    fn to_block_list(&self) -> NetBlockList {
+        NetBlockList {
+            peers: self.peer.clone(),
+            ip_addrs: self.ip.clone(),
+            ip_subnets: self.subnet.clone(),
+        }
+    }
 }
 
 impl NetCommands {
@@ -48,7 +125,20 @@ impl NetCommands {
                 }
                 Err(e) => handle_rpc_err(e),
             },
-            Self::Peers => match net_peers(()).await {
+            Self::Peers { extended: true } => match net_peers(()).await {
+                Ok(addrs) => {
+                    let mut peers = Vec::with_capacity(addrs.len());
+                    for info in addrs {
+                        // Peers that disconnected in the meantime are left out
+                        if let Ok(peer) = net_peer_info((info.id,)).await {
+                            peers.push(peer);
+                        }
+                    }
+                    print_stdout(format_peers(peers));
+                }
+                Err(e) => handle_rpc_err(e),
+            },
+            Self::Peers { extended: false } => match net_peers(()).await {
                 Ok(addrs) => {
                     let output: Vec<String> = addrs
                         .into_iter()
@@ -119,6 +209,248 @@ impl NetCommands {
                 }
                 Err(e) => handle_rpc_err(e),
             },
+            Self::Ping { id, count } => {
+                for _ in 0..*count {
+                    match net_ping((id.to_owned(),)).await {
+                        Ok(rtt) => println!("Pong received: time={}", format_duration(rtt)),
+                        Err(e) => handle_rpc_err(e),
+                    }
+                }
+            }
+            Self::FindPeer { id } => match net_find_peer((id.to_owned(),)).await {
+                Ok(info) => {
+                    let addresses: Vec<String> =
+                        info.addrs.iter().map(|addr| addr.to_string()).collect();
+                    print_stdout(addresses.join("\n"));
+                }
+                Err(e) => handle_rpc_err(e),
+            },
+            Self::BlockAdd(entries) => match net_block_add((entries.to_block_list(),)).await {
+                Ok(_) => println!("block add: success"),
+                Err(e) => handle_rpc_err(e),
+            },
+            Self::BlockRemove(entries) => {
+                match net_block_remove((entries.to_block_list(),)).await {
+                    Ok(_) => println!("block remove: success"),
+                    Err(e) => handle_rpc_err(e),
+                }
+            }
+            Self::BlockList => match net_block_list(()).await {
+                Ok(block_list) => {
+                    let output: Vec<String> = block_list
+                        .peers
+                        .into_iter()
+                        .map(|peer| format!("peer {}", peer))
+                        .chain(
+                            block_list
+                                .ip_addrs
+                                .into_iter()
+                                .map(|ip| format!("ip {}", ip)),
+                        )
+                        .chain(
+                            block_list
+                                .ip_subnets
+                                .into_iter()
+                                .map(|subnet| format!("subnet {}", subnet)),
+                        )
+                        .collect();
+                    print_stdout(output.join("\n"));
+                }
+                Err(e) => handle_rpc_err(e),
+            },
+            Self::Reachability => match net_auto_nat_status(()).await {
+                Ok(info) => {
+                    let status = match info.reachability {
+                        1 => "Public",
+                        2 => "Private",
+                        _ => "Unknown",
+                    };
+                    println!("AutoNAT status: {}", status);
+                    if !info.public_addr.is_empty() {
+                        println!("Public address: {}", info.public_addr);
+                    }
+                }
+                Err(e) => handle_rpc_err(e),
+            },
+            Self::BitswapFetch { cid } => {
+                let cid: Cid = cid
+                    .parse()
+                    .map_err(|e| {
+                        cli_error_and_die(&format!("Error parsing CID. Error was: {}", e), 1);
+                    })
+                    .expect("Parse provided CID from string");
+                match net_bitswap_fetch((CidJson(cid),)).await {
+                    Ok(_) => println!("fetch {}: success", cid),
+                    Err(e) => handle_rpc_err(e),
+                }
+            }
+            Self::Bandwidth {
+                by_peer,
+                by_protocol,
+            } => {
+                let segments = if *by_peer {
+                    net_bandwidth_stats_by_peer(()).await
+                } else if *by_protocol {
+                    net_bandwidth_stats_by_protocol(()).await
+                } else {
+                    net_bandwidth_stats(())
+                        .await
+                        .map(|stats| [("Total".to_owned(), stats)].into_iter().collect())
+                };
+                match segments {
+                    Ok(segments) => {
+                        let mut table = format_bandwidth(segments.into_iter().collect());
+                        if *by_protocol {
+                            table.push_str(PROTOCOL_BANDWIDTH_NOTE);
+                        }
+                        print_stdout(table)
+                    }
+                    Err(e) => handle_rpc_err(e),
+                }
+            }
         }
     }
 }
+
+/// Printed under the bandwidth by protocol, which doesn't add up to the totals.
+const PROTOCOL_BANDWIDTH_NOTE: &str =
+    "\nPayload bytes only: Kademlia, Identify, ping and framing overhead are not counted.";
+
+/// Formats bandwidth stats as a table, with the segments sorted by total traffic.
+fn format_bandwidth(mut segments: Vec<(String, BandwidthStats)>) -> String {
+    segments.sort_by_key(|(_, stats)| std::cmp::Reverse(stats.total_in + stats.total_out));
+    let width = segments
+        .iter()
+        .map(|(segment, _)| segment.len())
+        .max()
+        .unwrap_or_default()
+        .max("Segment".len());
+    let mut lines = vec![format!(
+        "{:<width$}  {:>10}  {:>10}  {:>12}  {:>12}",
+        "Segment",
+        "TotalIn",
+        "TotalOut",
+        "RateIn",
+        "RateOut",
+        width = width
+    )];
+    for (segment, stats) in segments {
+        lines.push(format!(
+            "{:<width$}  {:>10}  {:>10}  {:>12}  {:>12}",
+            segment,
+            format_bytes(stats.total_in as f64),
+            format_bytes(stats.total_out as f64),
+            format!("{}/s", format_bytes(stats.rate_in)),
+            format!("{}/s", format_bytes(stats.rate_out)),
+            width = width
+        ));
+    }
+    lines.join("\n")
+}
+
+/// Formats the details of peers as a table, with the peers sorted by GossipSub score.
+fn format_peers(mut peers: Vec<ExtendedPeerInfo>) -> String {
+    peers.sort_by(|a, b| {
+        b.gossip_score
+            .unwrap_or_default()
+            .partial_cmp(&a.gossip_score.unwrap_or_default())
+            .unwrap_or(std::cmp::Ordering::Equal)
+    });
+    let id_width = peers
+        .iter()
+        .map(|peer| peer.id.len())
+        .max()
+        .unwrap_or_default()
+        .max("ID".len());
+    let agent_width = peers
+        .iter()
+        .map(|peer| peer.agent.len())
+        .max()
+        .unwrap_or_default()
+        .max("Agent".len());
+    let mut lines = vec![format!(
+        "{:<id_width$}  {:<agent_width$}  {:<9}  {:>10}  {:>10}  {:>8}  {:>13}  {:>10}",
+        "ID",
+        "Agent",
+        "Direction",
+        "Connected",
+        "Latency",
+        "Score",
+        "Requests",
+        "AvgTime",
+        id_width = id_width,
+        agent_width = agent_width
+    )];
+    for peer in peers {
+        lines.push(format!(
+            "{:<id_width$}  {:<agent_width$}  {:<9}  {:>10}  {:>10}  {:>8}  {:>13}  {:>10}",
+            peer.id,
+            peer.agent,
+            if peer.inbound { "inbound" } else { "outbound" },
+            format_duration(peer.connected_for),
+            format_duration(peer.latency),
+            peer.gossip_score
+                .map_or_else(|| "-".to_owned(), |score| format!("{:.2}", score)),
+            format!(
+                "{}/{}",
+                peer.chain_exchange.successes,
+                peer.chain_exchange.successes + peer.chain_exchange.failures
+            ),
+            format_duration(peer.chain_exchange.average_time),
+            id_width = id_width,
+            agent_width = agent_width
+        ));
+    }
+    lines.join("\n")
+}
+
+/// Formats a duration in nanoseconds with the largest unit it has at least one of.
+fn format_duration(nanos: u64) -> String {
+    let duration = std::time::Duration::from_nanos(nanos);
+    let secs = duration.as_secs();
+    if secs >= 3600 {
+        format!("{}h{}m", secs / 3600, secs % 3600 / 60)
+    } else if secs >= 60 {
+        format!("{}m{}s", secs / 60, secs % 60)
+    } else if secs > 0 {
+        format!("{:.1}s", duration.as_secs_f64())
+    } else {
+        format!("{:.1}ms", duration.as_secs_f64() * 1000.0)
+    }
+}
+
+/// Formats a number of bytes with a binary unit prefix.
+fn format_bytes(bytes: f64) -> String {
+    const UNITS: [&str; 5] = ["B", "KiB", "MiB", "GiB", "TiB"];
+    let mut value = bytes;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{:.0} {}", value, UNITS[unit])
+    } else {
+        format!("{:.1} {}", value, UNITS[unit])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn formats_bytes() {
+        assert_eq!(format_bytes(512.0), "512 B");
+        assert_eq!(format_bytes(1536.0), "1.5 KiB");
+        assert_eq!(format_bytes(3.0 * 1024.0 * 1024.0 * 1024.0), "3.0 GiB");
+    }
+
+    #[test]
+    fn formats_durations() {
+        assert_eq!(format_duration(12_345_678), "12.3ms");
+        assert_eq!(format_duration(2_500_000_000), "2.5s");
+        assert_eq!(format_duration(125 * 1_000_000_000), "2m5s");
+        assert_eq!(format_duration(7320 * 1_000_000_000), "2h2m");
+    }
+}
@@ -0,0 +1,312 @@
+// Copyright 2019-2022 ChainSafe Systems
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+use encoding::Cbor;
+use forest_blocks::tipset_keys_json::TipsetKeysJson;
+use forest_ipld::json::IpldJson;
+use forest_json::address::json::AddressJson;
+use forest_json::cid::CidJson;
+use forest_message::message::json::MessageJson;
+use forest_message::signed_message::json::SignedMessageJson;
+use forest_vm::Serialized;
+use fvm_shared::address::{Address, Protocol};
+use fvm_shared::bigint::{BigInt, Zero};
+use fvm_shared::message::Message;
+use rpc_client::{
+    chain_head, gas_estimate_message_gas, mpool_push, mpool_push_message, state_account_key,
+    state_wait_msg, wallet_sign_message,
+};
+use std::io::{self, Write};
+use std::str::FromStr;
+use structopt::StructOpt;
+
+use super::{balance_to_fil, cli_error_and_die, handle_rpc_err, parse_fil, sender};
+
+#[derive(Debug, StructOpt)]
+pub struct SendCommand {
+    #[structopt(help = "Address of the recipient")]
+    target: String,
+    #[structopt(help = "Amount of FIL to send")]
+    amount: String,
+    #[structopt(long, help = "Account to send from. Defaults to the wallet default")]
+    from: Option<String>,
+    #[structopt(long, default_value = "0", help = "Method to call on the recipient")]
+    method: u64,
+    #[structopt(
+        long,
+        conflicts_with = "params-hex",
+        help = "Method parameters as IPLD JSON, sent CBOR encoded"
+    )]
+    params_json: Option<String>,
+    #[structopt(long, help = "Hex encoded CBOR method parameters")]
+    params_hex: Option<String>,
+    #[structopt(long, help = "Gas premium in attoFIL. Estimated if not set")]
+    gas_premium: Option<String>,
+    #[structopt(long, help = "Gas fee cap in attoFIL. Estimated if not set")]
+    gas_feecap: Option<String>,
+    #[structopt(
+        long,
+        help = "Nonce of the message. Defaults to the next nonce of the sender in the message pool"
+    )]
+    nonce: Option<u64>,
+    #[structopt(short, long, help = "Send without asking for confirmation")]
+    yes: bool,
+    #[structopt(long, help = "Wait for the message to be included on chain")]
+    wait: bool,
+    #[structopt(
+        long,
+        default_value = "5",
+        help = "Number of epochs to wait for after inclusion when using --wait"
+    )]
+    confidence: i64,
+}
+
+impl SendCommand {
+    pub async fn run(&self) {
+        let head = chain_head().await.map_err(handle_rpc_err).unwrap().0;
+        let tsk = TipsetKeysJson(head.key().to_owned());
+
+        // Messages must be signed by the key behind the sender, not its ID address
+        let mut from = sender(&self.from).await;
+        if from.protocol() == Protocol::ID {
+            from = match state_account_key((AddressJson(from), tsk.clone()))
+                .await
+                .map_err(handle_rpc_err)
+                .unwrap()
+            {
+                Some(AddressJson(key_addr)) => key_addr,
+                None => return cli_error_and_die(&format!("No account key found for {}", from), 1),
+            };
+        }
+
+        let msg = self
+            .message(from)
+            .map_err(|e| cli_error_and_die(&e, 1))
+            .unwrap();
+        let (to, value) = (msg.to, msg.value.clone());
+
+        let MessageJson(msg) = gas_estimate_message_gas((MessageJson(msg), None, tsk))
+            .await
+            .map_err(handle_rpc_err)
+            .unwrap();
+        if msg.gas_premium > msg.gas_fee_cap {
+            return cli_error_and_die("Gas premium is greater than the gas fee cap", 1);
+        }
+
+        let max_fee = msg.gas_fee_cap.clone() * msg.gas_limit;
+        let nonce = match self.nonce {
+            Some(nonce) => format!("nonce {}", nonce),
+            None => "the next nonce of the sender".to_owned(),
+        };
+        println!(
+            "Sending {} FIL from {} to {} with {}",
+            balance_to_fil(value).unwrap(),
+            from,
+            to,
+            nonce
+        );
+        println!(
+            "Gas limit: {}, fee cap: {} attoFIL, premium: {} attoFIL",
+            msg.gas_limit, msg.gas_fee_cap, msg.gas_premium
+        );
+        println!("Maximum fee: {} FIL", balance_to_fil(max_fee).unwrap());
+
+        if !self.yes && !confirm("Send this message?") {
+            return cli_error_and_die("Message not sent", 1);
+        }
+
+        let cid = match self.nonce {
+            // The node assigns the nonce while holding its push lock, so that concurrent sends
+            // from the same account don't sign the same nonce
+            None => {
+                let SignedMessageJson(smsg) = mpool_push_message((MessageJson(msg), None))
+                    .await
+                    .map_err(handle_rpc_err)
+                    .unwrap();
+                smsg.cid().unwrap()
+            }
+            Some(_) => {
+                let SignedMessageJson(smsg) =
+                    wallet_sign_message((from.to_string(), MessageJson(msg)))
+                        .await
+                        .map_err(handle_rpc_err)
+                        .unwrap();
+                let CidJson(cid) = mpool_push((SignedMessageJson(smsg),))
+                    .await
+                    .map_err(handle_rpc_err)
+                    .unwrap();
+                cid
+            }
+        };
+        println!("{}", cid);
+
+        if self.wait {
+            let lookup = state_wait_msg((CidJson(cid), self.confidence))
+                .await
+                .map_err(handle_rpc_err)
+                .unwrap();
+            let exit_code = lookup.receipt.0.exit_code.value();
+            println!(
+                "Message included at height {} with exit code {}",
+                lookup.height, exit_code
+            );
+            if exit_code != 0 {
+                cli_error_and_die("Message execution failed", 1);
+            }
+        }
+    }
+
+    /// Build the message from the arguments. The gas fields that are not set are left to zero to
+    /// be estimated, and the nonce is zero unless set, for the node to assign it
+    fn message(&self, from: Address) -> Result<Message, String> {
+        let to = Address::from_str(&self.target)
+            .map_err(|_| format!("{} is not a valid address", self.target))?;
+        let gas_premium = parse_atto(&self.gas_premium, "gas premium")?;
+        let gas_fee_cap = parse_atto(&self.gas_feecap, "gas fee cap")?;
+        if !gas_fee_cap.is_zero() && gas_premium > gas_fee_cap {
+            return Err("Gas premium is greater than the gas fee cap".to_owned());
+        }
+
+        Ok(Message {
+            version: Default::default(),
+            from,
+            to,
+            sequence: self.nonce.unwrap_or_default(),
+            value: parse_fil(&self.amount)?,
+            method_num: self.method,
+            params: self.params()?,
+            gas_limit: 0,
+            gas_fee_cap,
+            gas_premium,
+        })
+    }
+
+    /// Encode the method parameters given as IPLD JSON or hex encoded CBOR
+    fn params(&self) -> Result<Serialized, String> {
+        match (&self.params_json, &self.params_hex) {
+            (Some(json), _) => {
+                let IpldJson(ipld) = serde_json::from_str(json)
+                    .map_err(|e| format!("Invalid JSON params: {}", e))?;
+                Serialized::serialize(ipld).map_err(|e| format!("Invalid JSON params: {}", e))
+            }
+            (None, Some(hex_params)) => hex::decode(hex_params)
+                .map(Serialized::new)
+                .map_err(|e| format!("Invalid hex params: {}", e)),
+            (None, None) => Ok(Serialized::default()),
+        }
+    }
+}
+
+/// Parse an optional attoFIL amount, zero when not set so that it gets estimated
+fn parse_atto(amount: &Option<String>, name: &str) -> Result<BigInt, String> {
+    match amount {
+        Some(amount) => {
+            BigInt::from_str(amount).map_err(|_| format!("Invalid {} {}", name, amount))
+        }
+        None => Ok(BigInt::default()),
+    }
+}
+
+/// Ask the user for a yes/no confirmation on the terminal
+fn confirm(question: &str) -> bool {
+    if !atty::is(atty::Stream::Stdin) {
+        cli_error_and_die(
+            "Cannot ask for confirmation, standard input is not a terminal. Use --yes",
+            1,
+        );
+    }
+    print!("{} [y/N] ", question);
+    io::stdout().flush().unwrap();
+
+    let mut answer = String::new();
+    io::stdin().read_line(&mut answer).unwrap();
+    matches!(answer.trim().to_lowercase().as_str(), "y" | "yes")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn command(args: &[&str]) -> SendCommand {
+        SendCommand::from_iter_safe(["send"].iter().chain(args).copied()).unwrap()
+    }
+
+    fn message(args: &[&str]) -> Result<Message, String> {
+        command(args).message(Address::new_id(1000))
+    }
+
+    #[test]
+    fn builds_message_to_estimate() {
+        let msg = message(&["f01234", "1.5"]).unwrap();
+        assert_eq!(msg.from, Address::new_id(1000));
+        assert_eq!(msg.to, Address::new_id(1234));
+        assert_eq!(msg.value, BigInt::from(1_500_000_000_000_000_000u64));
+        assert_eq!(msg.method_num, 0);
+        assert_eq!(msg.params, Serialized::default());
+        // Left for the node to estimate and assign
+        assert_eq!(msg.sequence, 0);
+        assert_eq!(msg.gas_limit, 0);
+        assert!(msg.gas_premium.is_zero());
+        assert!(msg.gas_fee_cap.is_zero());
+    }
+
+    #[test]
+    fn sets_nonce_and_gas() {
+        let msg = message(&[
+            "f01234",
+            "1",
+            "--nonce",
+            "7",
+            "--gas-premium",
+            "100",
+            "--gas-feecap",
+            "200",
+        ])
+        .unwrap();
+        assert_eq!(msg.sequence, 7);
+        assert_eq!(msg.gas_premium, BigInt::from(100));
+        assert_eq!(msg.gas_fee_cap, BigInt::from(200));
+
+        // The fee cap is estimated above the premium when only the premium is set
+        let msg = message(&["f01234", "1", "--gas-premium", "100"]).unwrap();
+        assert_eq!(msg.gas_premium, BigInt::from(100));
+        assert!(msg.gas_fee_cap.is_zero());
+    }
+
+    #[test]
+    fn encodes_params() {
+        let hex = message(&["f01234", "0", "--method", "2", "--params-hex", "820102"]).unwrap();
+        assert_eq!(hex.method_num, 2);
+        assert_eq!(hex.params, Serialized::new(vec![0x82, 0x01, 0x02]));
+
+        let json = message(&["f01234", "0", "--params-json", "[1, 2]"]).unwrap();
+        assert_eq!(json.params, hex.params);
+
+        assert!(SendCommand::from_iter_safe([
+            "send",
+            "f01234",
+            "0",
+            "--params-json",
+            "[]",
+            "--params-hex",
+            "80"
+        ])
+        .is_err());
+    }
+
+    #[test]
+    fn rejects_invalid_arguments() {
+        for args in [
+            &["not-an-address", "1"][..],
+            &["f01234", "1.5.0"],
+            &["f01234", "1,5"],
+            &["f01234", "1", "--gas-premium", "abc"],
+            &["f01234", "1", "--gas-feecap", "1.5"],
+            &["f01234", "1", "--gas-premium", "300", "--gas-feecap", "200"],
+            &["f01234", "1", "--params-hex", "zz"],
+            &["f01234", "1", "--params-json", "{"],
+        ] {
+            assert!(message(args).is_err(), "{:?}", args);
+        }
+    }
+}
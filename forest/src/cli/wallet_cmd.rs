@@ -5,12 +5,19 @@ use forest_crypto::signature::json::{signature_type::SignatureTypeJson, Signatur
 use forest_json::address::json::AddressJson;
 use fvm_shared::address::Address;
 use fvm_shared::crypto::signature::{Signature, SignatureType};
-use key_management::json::KeyInfoJson;
+use jsonrpc_v2::Error as JsonRpcError;
+use key_management::{
+    decode_key_info, encode_key_info, json::KeyInfoJson, read_lotus_keystore,
+    write_lotus_keystore_key, Key, KeyFormat,
+};
 use rpassword::read_password;
 use rpc_client::wallet_ops::*;
 use std::{
-    path::PathBuf,
-    str::{self, FromStr},
+    convert::TryFrom,
+    fs::File,
+    io::Write,
+    path::{Path, PathBuf},
+    str::FromStr,
 };
 use structopt::StructOpt;
 use utils::read_file_to_string;
@@ -38,6 +45,18 @@ pub enum WalletCommands {
     Export {
         #[structopt(about = "The address that contains the keys to export")]
         address: String,
+        #[structopt(
+            long,
+            default_value = "hex-lotus",
+            help = "Output format. One of hex-lotus, json-lotus or lotus-keystore"
+        )]
+        format: KeyFormatOpt,
+        #[structopt(
+            short,
+            long,
+            help = "Write the key to this file instead of standard output. Required for lotus-keystore, where it is the keystore directory"
+        )]
+        output: Option<PathBuf>,
     },
     #[structopt(about = "Check if the wallet has a key")]
     Has {
@@ -46,8 +65,16 @@ pub enum WalletCommands {
     },
     #[structopt(about = "Import keys from existing wallet")]
     Import {
-        #[structopt(help = "The path to the private key")]
+        #[structopt(
+            help = "The path to the private key, or to the keystore directory for lotus-keystore"
+        )]
         path: Option<String>,
+        #[structopt(
+            long,
+            default_value = "hex-lotus",
+            help = "Input format. One of hex-lotus, json-lotus or lotus-keystore"
+        )]
+        format: KeyFormatOpt,
     },
     #[structopt(about = "List addresses of the wallet")]
     List,
@@ -76,6 +103,28 @@ pub enum WalletCommands {
     },
 }
 
+/// Key format parsed from the command line
+#[derive(Debug)]
+pub struct KeyFormatOpt(KeyFormat);
+
+impl FromStr for KeyFormatOpt {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        KeyFormat::from_str(s)
+            .map(KeyFormatOpt)
+            .map_err(|e| e.to_string())
+    }
+}
+
+/// Write an exported key to a file readable only by the current user
+fn write_key_file(path: &Path, key: &str) -> std::io::Result<()> {
+    let mut file = File::create(path)?;
+    #[cfg(unix)]
+    utils::set_user_perm(&file)?;
+    file.write_all(key.as_bytes())
+}
+
 impl WalletCommands {
     pub async fn run(&self) {
         match self {
@@ -107,14 +156,45 @@ impl WalletCommands {
                     .unwrap();
                 println!("{}", response);
             }
-            Self::Export { address } => {
-                let response = wallet_export((address.to_string(),))
+            Self::Export {
+                address,
+                format,
+                output,
+            } => {
+                let KeyInfoJson(key_info) = wallet_export((address.to_string(),))
                     .await
                     .map_err(handle_rpc_err)
                     .unwrap();
 
-                let encoded_key = serde_json::to_string(&response).unwrap();
-                println!("{}", hex::encode(encoded_key))
+                match (format.0, output) {
+                    (KeyFormat::LotusKeystore, Some(dir)) => {
+                        let key = match Key::try_from(key_info) {
+                            Ok(key) => key,
+                            Err(e) => return cli_error_and_die(&e.to_string(), 1),
+                        };
+                        match write_lotus_keystore_key(dir, &key) {
+                            Ok(path) => println!("{}", path.display()),
+                            Err(e) => cli_error_and_die(&format!("Failed to export key: {}", e), 1),
+                        }
+                    }
+                    (KeyFormat::LotusKeystore, None) => {
+                        cli_error_and_die("lotus-keystore export requires --output <dir>", 1)
+                    }
+                    (format, output) => {
+                        let encoded_key = encode_key_info(&key_info, format).unwrap();
+                        match output {
+                            Some(path) => {
+                                if let Err(e) = write_key_file(path, &encoded_key) {
+                                    cli_error_and_die(
+                                        &format!("Failed to write {}: {}", path.display(), e),
+                                        1,
+                                    );
+                                }
+                            }
+                            None => println!("{}", encoded_key),
+                        }
+                    }
+                }
             }
             Self::Has { key } => {
                 let response = wallet_has((key.to_string(),))
@@ -123,7 +203,41 @@ impl WalletCommands {
                     .unwrap();
                 println!("{}", response);
             }
-            Self::Import { path } => {
+            Self::Import { path, format } => {
+                if format.0 == KeyFormat::LotusKeystore {
+                    let dir = match path {
+                        Some(dir) => PathBuf::from(dir),
+                        None => {
+                            return cli_error_and_die(
+                                "lotus-keystore import requires the keystore directory",
+                                1,
+                            )
+                        }
+                    };
+                    let keys = match read_lotus_keystore(&dir) {
+                        Ok(keys) => keys,
+                        Err(e) => {
+                            return cli_error_and_die(
+                                &format!("Failed to read keystore {}: {}", dir.display(), e),
+                                1,
+                            )
+                        }
+                    };
+
+                    for key in keys {
+                        match wallet_import(vec![KeyInfoJson(key.key_info)]).await {
+                            Ok(addr) => println!("{}", addr),
+                            Err(JsonRpcError::Full { message, .. }) => {
+                                eprintln!("Skipping {}: {}", key.address, message)
+                            }
+                            Err(JsonRpcError::Provided { message, .. }) => {
+                                eprintln!("Skipping {}: {}", key.address, message)
+                            }
+                        }
+                    }
+                    return;
+                }
+
                 let key = match path {
                     Some(path) => match read_file_to_string(&PathBuf::from(path)) {
                         Ok(key) => key,
@@ -139,26 +253,17 @@ impl WalletCommands {
 
                 let key = key.trim();
 
-                let decoded_key_result = hex::decode(&key);
-
-                if decoded_key_result.is_err() {
-                    cli_error_and_die("Key must be hex encoded", 1);
-                }
-
-                let decoded_key = decoded_key_result.unwrap();
-
-                let key_str = str::from_utf8(&decoded_key).unwrap();
-
-                let key_result: Result<KeyInfoJson, serde_json::error::Error> =
-                    serde_json::from_str(key_str);
-
-                if key_result.is_err() {
-                    cli_error_and_die(&format!("{} is not a valid key to import", key), 1);
-                }
-
-                let key = key_result.unwrap();
+                let key_info = match decode_key_info(key, format.0) {
+                    Ok(key_info) => key_info,
+                    Err(_) => {
+                        return cli_error_and_die(
+                            &format!("{} is not a valid key to import", key),
+                            1,
+                        );
+                    }
+                };
 
-                let key = wallet_import(vec![KeyInfoJson(key.0)])
+                let key = wallet_import(vec![KeyInfoJson(key_info)])
                     .await
                     .map_err(handle_rpc_err)
                     .unwrap();
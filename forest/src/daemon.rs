@@ -1,7 +1,7 @@
 // Copyright 2019-2022 ChainSafe Systems
 // SPDX-License-Identifier: Apache-2.0, MIT
 
-use super::cli::{block_until_sigint, Config};
+use super::cli::{block_until_sigint, read_encrypted_keystore, Config};
 use async_std::net::TcpListener;
 use auth::{create_token, generate_priv_key, ADMIN, JWT_IDENTIFIER};
 use beacon::DrandBeacon;
@@ -9,10 +9,9 @@ use chain::ChainStore;
 use chain_sync::ChainMuxer;
 use fil_cns::FilecoinConsensus;
 use fil_types::verifier::FullVerifier;
-use forest_libp2p::{get_keypair, Libp2pConfig, Libp2pService};
+use forest_libp2p::{get_keypair, Libp2pConfig, Libp2pService, PeerStore};
 use genesis::{get_network_name_from_genesis, import_chain, read_genesis_header};
-use key_management::ENCRYPTED_KEYSTORE_NAME;
-use key_management::{KeyStore, KeyStoreConfig};
+use key_management::{KeyStore, KeyStoreConfig, MultiSigner, RemoteSigner};
 use message_pool::{MessagePool, MpoolConfig, MpoolRpcProvider};
 use paramfetch::{get_params_default, set_proofs_parameter_cache_dir_env, SectorSizeOpt};
 use rpc::start_rpc;
@@ -23,10 +22,8 @@ use utils::write_to_file;
 use async_std::{channel::bounded, sync::RwLock, task};
 use libp2p::identity::{ed25519, Keypair};
 use log::{debug, error, info, trace, warn};
-use rpassword::read_password;
 
 use db::rocks::RocksDb;
-use std::io::prelude::*;
 use std::path::PathBuf;
 use std::sync::Arc;
 use std::time;
@@ -59,34 +56,12 @@ pub(super) async fn start(config: Config) {
     });
 
     let mut ks = if config.encrypt_keystore {
-        loop {
-            print!("Enter the keystore passphrase: ");
-            std::io::stdout().flush().unwrap();
-
-            let passphrase = read_password().expect("Error reading passphrase");
-
-            let data_dir = PathBuf::from(&config.data_dir).join(ENCRYPTED_KEYSTORE_NAME);
-            if !data_dir.exists() {
-                print!("Confirm passphrase: ");
-                std::io::stdout().flush().unwrap();
-
-                if passphrase != read_password().unwrap() {
-                    error!("Passphrases do not match. Please retry.");
-                    continue;
-                }
+        match read_encrypted_keystore(&config) {
+            Ok(ks) => ks,
+            Err(e) => {
+                error!("Failed to open the encrypted keystore: {}", e);
+                std::process::exit(1);
             }
-
-            let key_store_init_result = KeyStore::new(KeyStoreConfig::Encrypted(
-                PathBuf::from(&config.data_dir),
-                passphrase,
-            ));
-
-            match key_store_init_result {
-                Ok(ks) => break ks,
-                Err(_) => {
-                    error!("Incorrect passphrase entered. Please try again.")
-                }
-            };
         }
     } else {
         warn!("Warning: Keystore encryption disabled!");
@@ -118,6 +93,12 @@ pub(super) async fn start(config: Config) {
     info!("Admin token: {}", token);
 
     let keystore = Arc::new(RwLock::new(ks));
+    let mut signer = MultiSigner::new(Arc::clone(&keystore));
+    if let Some(remote_signer) = &config.remote_signer {
+        info!("Using remote signer at {}", remote_signer.url);
+        signer = signer.with_remote(RemoteSigner::new(remote_signer.clone()));
+    }
+    let signer = Arc::new(signer);
 
     #[cfg(feature = "rocksdb")]
     let db = db::rocks::RocksDb::open(db_path(&config), &config.rocks_db)
@@ -181,10 +162,17 @@ pub(super) async fn start(config: Config) {
         config
     };
 
+    // Load the peers known from previous runs
+    let peer_store = Arc::new(PeerStore::load(db.as_ref()).unwrap_or_else(|e| {
+        warn!("Failed to load peer records, starting without: {}", e);
+        PeerStore::default()
+    }));
+
     // Libp2p service setup
     let p2p_service = Libp2pService::new(
         config.network,
         Arc::clone(&chain_store),
+        Arc::clone(&peer_store),
         net_keypair,
         &network_name,
     );
@@ -218,6 +206,7 @@ pub(super) async fn start(config: Config) {
         Arc::clone(&mpool),
         network_send.clone(),
         network_rx,
+        peer_store,
         Arc::new(genesis),
         chain_muxer_tipset_sink,
         tipset_stream,
@@ -245,7 +234,9 @@ pub(super) async fn start(config: Config) {
                 Arc::new(RPCState {
                     state_manager: Arc::clone(&state_manager),
                     keystore: keystore_rpc,
+                    signer,
                     mpool,
+                    mpool_push_lock: Default::default(),
                     bad_blocks,
                     sync_state,
                     network_send,
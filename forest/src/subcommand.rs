@@ -25,12 +25,21 @@ pub(super) async fn process(command: Subcommand, config: Config) {
         Subcommand::Wallet(cmd) => {
             cmd.run().await;
         }
+        Subcommand::Keystore(cmd) => {
+            cmd.run(&config);
+        }
         Subcommand::Sync(cmd) => {
             cmd.run().await;
         }
         Subcommand::Mpool(cmd) => {
             cmd.run().await;
         }
+        Subcommand::Msig(cmd) => {
+            cmd.run().await;
+        }
+        Subcommand::Send(cmd) => {
+            cmd.run().await;
+        }
         Subcommand::State(cmd) => {
             cmd.run().await;
         }
@@ -0,0 +1,135 @@
+// Copyright 2019-2022 ChainSafe Systems
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+use super::errors::Error;
+use super::json::{KeyInfoJson, KeyInfoJsonRef};
+use super::{Key, KeyInfo};
+use data_encoding::BASE32_NOPAD;
+use log::warn;
+use std::convert::TryFrom;
+use std::fs::{self, File};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+/// Prefix of wallet key names in Forest and Lotus keystores
+pub const WALLET_KEY_PREFIX: &str = "wallet-";
+
+/// Formats keys can be imported from and exported to, matching the formats understood by
+/// `lotus wallet import` and `lotus wallet export`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum KeyFormat {
+    /// Hex encoded JSON `KeyInfo`, the default format of `lotus wallet export`
+    HexLotus,
+    /// Plain JSON `KeyInfo`
+    JsonLotus,
+    /// Lotus keystore directory, one JSON `KeyInfo` per file named by the base32 encoded key name
+    LotusKeystore,
+}
+
+impl FromStr for KeyFormat {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "hex-lotus" => Ok(KeyFormat::HexLotus),
+            "json-lotus" => Ok(KeyFormat::JsonLotus),
+            "lotus-keystore" => Ok(KeyFormat::LotusKeystore),
+            _ => Err(Error::Other(format!(
+                "unknown key format {}, expected one of hex-lotus, json-lotus, lotus-keystore",
+                s
+            ))),
+        }
+    }
+}
+
+/// Encode a `KeyInfo` as a string in the `hex-lotus` or `json-lotus` format
+pub fn encode_key_info(key_info: &KeyInfo, format: KeyFormat) -> Result<String, Error> {
+    let json = serde_json::to_string(&KeyInfoJsonRef(key_info))
+        .map_err(|e| Error::Other(e.to_string()))?;
+    match format {
+        KeyFormat::HexLotus => Ok(hex::encode(json)),
+        KeyFormat::JsonLotus => Ok(json),
+        KeyFormat::LotusKeystore => Err(Error::Other(
+            "lotus-keystore keys are written to a directory".to_string(),
+        )),
+    }
+}
+
+/// Decode a `KeyInfo` from a string in the `hex-lotus` or `json-lotus` format
+pub fn decode_key_info(input: &str, format: KeyFormat) -> Result<KeyInfo, Error> {
+    let json = match format {
+        KeyFormat::HexLotus => {
+            let bytes = hex::decode(input.trim()).map_err(|_| Error::KeyInfoConversion)?;
+            String::from_utf8(bytes).map_err(|_| Error::KeyInfoConversion)?
+        }
+        KeyFormat::JsonLotus => input.trim().to_owned(),
+        KeyFormat::LotusKeystore => {
+            return Err(Error::Other(
+                "lotus-keystore keys are read from a directory".to_string(),
+            ))
+        }
+    };
+    let KeyInfoJson(key_info) =
+        serde_json::from_str(&json).map_err(|_| Error::KeyInfoConversion)?;
+    Ok(key_info)
+}
+
+/// Return the file name Lotus uses to store the key with the given name
+pub fn lotus_keystore_file_name(name: &str) -> String {
+    BASE32_NOPAD.encode(name.as_bytes())
+}
+
+/// Read all wallet keys from a Lotus keystore directory (e.g. `~/.lotus/keystore`).
+/// Entries that are not wallet keys, such as the libp2p host key, the JWT secret or the
+/// `default` alias, are skipped.
+pub fn read_lotus_keystore(dir: &Path) -> Result<Vec<Key>, Error> {
+    let mut keys = Vec::new();
+    let mut entries = fs::read_dir(dir)?.collect::<Result<Vec<_>, _>>()?;
+    entries.sort_by_key(|e| e.file_name());
+
+    for entry in entries {
+        if !entry.file_type()?.is_file() {
+            continue;
+        }
+        let file_name = entry.file_name();
+        let name = match file_name
+            .to_str()
+            .and_then(|f| BASE32_NOPAD.decode(f.as_bytes()).ok())
+            .and_then(|n| String::from_utf8(n).ok())
+        {
+            Some(name) => name,
+            None => {
+                warn!("Skipping {:?}, not a Lotus keystore entry", file_name);
+                continue;
+            }
+        };
+        if !name.starts_with(WALLET_KEY_PREFIX) {
+            continue;
+        }
+
+        let KeyInfoJson(key_info) = serde_json::from_str(&fs::read_to_string(entry.path())?)
+            .map_err(|e| Error::Other(format!("invalid key {}: {}", name, e)))?;
+        keys.push(Key::try_from(key_info)?);
+    }
+
+    Ok(keys)
+}
+
+/// Write a wallet key into a Lotus keystore directory, return the path of the written file
+pub fn write_lotus_keystore_key(dir: &Path, key: &Key) -> Result<PathBuf, Error> {
+    fs::create_dir_all(dir)?;
+    let name = format!("{}{}", WALLET_KEY_PREFIX, key.address);
+    let path = dir.join(lotus_keystore_file_name(&name));
+    if path.exists() {
+        return Err(Error::KeyExists);
+    }
+
+    let mut file = File::create(&path)?;
+    // Restrict permissions on files containing private keys
+    #[cfg(unix)]
+    utils::set_user_perm(&file)?;
+    file.write_all(encode_key_info(&key.key_info, KeyFormat::JsonLotus)?.as_bytes())?;
+
+    Ok(path)
+}
@@ -320,7 +320,11 @@ impl KeyStore {
                     .parent()
                     .ok_or_else(|| Error::Other("Invalid Path".to_string()))?;
                 fs::create_dir_all(dir)?;
-                let file = File::create(&persistent_keystore.file_path)?;
+
+                // The keys are written to a temporary file which then replaces the keystore, so
+                // that an interrupted write can't leave the keystore truncated
+                let tmp_path = persistent_keystore.file_path.with_extension("tmp");
+                let file = File::create(&tmp_path)?;
 
                 // Restrict permissions on files containing private keys
                 #[cfg(unix)]
@@ -343,8 +347,6 @@ impl KeyStore {
                         let mut salt_vec = encrypted_keystore.salt.as_ref().to_vec();
                         salt_vec.extend(encrypted_data);
                         writer.write_all(&salt_vec)?;
-
-                        Ok(())
                     }
                     None => {
                         let mut key_info: HashMap<String, PersistentKeyInfo> = HashMap::new();
@@ -359,13 +361,16 @@ impl KeyStore {
                         }
 
                         // Flush for PersistentKeyStore
-                        serde_json::to_writer_pretty(writer, &key_info).map_err(|e| {
+                        serde_json::to_writer_pretty(&mut writer, &key_info).map_err(|e| {
                             Error::Other(format!("failed to serialize and write key info: {}", e))
                         })?;
-
-                        Ok(())
                     }
                 }
+
+                let file = writer.into_inner().map_err(|e| Error::IO(e.to_string()))?;
+                file.sync_all()?;
+                fs::rename(&tmp_path, &persistent_keystore.file_path)?;
+                Ok(())
             }
             None => {
                 // NoOp for MemKeyStore
@@ -408,6 +413,34 @@ impl KeyStore {
 
         Ok(key_out)
     }
+
+    /// Return true if the `KeyStore` is encrypted
+    pub fn is_encrypted(&self) -> bool {
+        self.encryption.is_some()
+    }
+
+    /// Re-encrypt the `KeyStore` with a key derived from a new passphrase and a fresh salt.
+    /// The keystore file is replaced atomically, so it is never left half-written.
+    pub fn change_passphrase(&mut self, new_passphrase: &str) -> Result<(), Error> {
+        if self.encryption.is_none() {
+            return Err(Error::Other(
+                EncryptedKeyStoreError::ConfigurationError.to_string(),
+            ));
+        }
+
+        let (salt, encryption_key) =
+            EncryptedKeyStore::derive_key(new_passphrase, None).map_err(|error| {
+                error!("Failed to create key from passphrase");
+                Error::Other(error.to_string())
+            })?;
+
+        self.encryption = Some(EncryptedKeyStore {
+            salt,
+            encryption_key,
+        });
+
+        self.flush()
+    }
 }
 
 impl EncryptedKeyStore {
@@ -504,6 +537,55 @@ mod test {
         assert_eq!(plaintext, message.as_bytes());
     }
 
+    #[test]
+    fn test_change_passphrase() {
+        let dir = tempfile::tempdir().unwrap();
+        let location = dir.path().to_path_buf();
+        let mut ks = KeyStore::new(KeyStoreConfig::Encrypted(
+            location.clone(),
+            PASSPHRASE.to_string(),
+        ))
+        .unwrap();
+
+        let key = wallet::generate_key(SignatureType::BLS).unwrap();
+        let addr = format!("wallet-{}", key.address);
+        ks.put(addr.clone(), key.key_info.clone()).unwrap();
+
+        ks.change_passphrase("new passphrase").unwrap();
+
+        assert!(
+            KeyStore::new(KeyStoreConfig::Encrypted(
+                location.clone(),
+                PASSPHRASE.to_string()
+            ))
+            .is_err(),
+            "Old passphrase must no longer decrypt the keystore"
+        );
+
+        let ks = KeyStore::new(KeyStoreConfig::Encrypted(
+            location.clone(),
+            "new passphrase".to_string(),
+        ))
+        .unwrap();
+        assert_eq!(ks.get(&addr).unwrap(), key.key_info);
+
+        let files: Vec<_> = fs::read_dir(&location)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name())
+            .collect();
+        assert_eq!(
+            files,
+            vec![ENCRYPTED_KEYSTORE_NAME],
+            "The temporary file must be renamed over the keystore"
+        );
+    }
+
+    #[test]
+    fn test_change_passphrase_unencrypted() {
+        let mut ks = KeyStore::new(KeyStoreConfig::Memory).unwrap();
+        assert!(ks.change_passphrase(PASSPHRASE).is_err());
+    }
+
     #[test]
     #[ignore = "fragile test, requires encrypted keystore to exist"]
     fn test_read_encrypted_keystore() {
@@ -2,11 +2,21 @@
 // SPDX-License-Identifier: Apache-2.0, MIT
 
 mod errors;
+#[cfg(feature = "json")]
+mod key_format;
 mod keystore;
+#[cfg(feature = "remote-signer")]
+mod remote_signer;
+mod signer;
 mod wallet;
 mod wallet_helpers;
 
 pub use errors::*;
+#[cfg(feature = "json")]
+pub use key_format::*;
 pub use keystore::*;
+#[cfg(feature = "remote-signer")]
+pub use remote_signer::*;
+pub use signer::*;
 pub use wallet::*;
 pub use wallet_helpers::*;
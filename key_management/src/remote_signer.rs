@@ -0,0 +1,279 @@
+// Copyright 2019-2022 ChainSafe Systems
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+use super::errors::Error;
+use super::signer::{MsgMeta, Signer};
+use async_std::future::timeout;
+use async_trait::async_trait;
+use forest_crypto::signature::json::SignatureJson;
+use fvm_shared::address::Address;
+use fvm_shared::crypto::signature::Signature;
+use log::debug;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::str::FromStr;
+use std::time::Duration;
+
+/// Configuration of a remote signer.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RemoteSignerConfig {
+    /// JSON-RPC endpoint of the signer, e.g. `http://127.0.0.1:1777/rpc/v0`
+    pub url: String,
+    /// Optional token sent in the `Authorization` header
+    pub token: Option<String>,
+    /// Time in seconds to wait for the signer to answer a request
+    pub timeout_secs: u64,
+}
+
+impl Default for RemoteSignerConfig {
+    fn default() -> Self {
+        Self {
+            url: "http://127.0.0.1:1777/rpc/v0".to_owned(),
+            token: None,
+            timeout_secs: 30,
+        }
+    }
+}
+
+/// Signer delegating to an external process over HTTP JSON-RPC, using the
+/// `Filecoin.WalletHas`, `Filecoin.WalletList` and `Filecoin.WalletSign` methods of the Lotus
+/// remote wallet API. This makes it possible to keep keys in an HSM or custody service.
+pub struct RemoteSigner {
+    config: RemoteSignerConfig,
+}
+
+#[derive(Deserialize)]
+struct RemoteError {
+    code: i64,
+    message: String,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum RemoteResponse<R> {
+    Result { result: R },
+    Error { error: RemoteError },
+}
+
+impl RemoteSigner {
+    pub fn new(config: RemoteSignerConfig) -> Self {
+        Self { config }
+    }
+
+    async fn call<R: DeserializeOwned>(
+        &self,
+        method: &str,
+        params: serde_json::Value,
+    ) -> Result<R, Error> {
+        let req = json!({
+            "jsonrpc": "2.0",
+            "method": method,
+            "params": params,
+            "id": 0,
+        });
+
+        debug!("Calling remote signer {} at {}", method, self.config.url);
+
+        let mut request = surf::post(&self.config.url)
+            .content_type("application/json")
+            .body(surf::Body::from_json(&req).map_err(|e| Error::Other(e.to_string()))?);
+        if let Some(token) = &self.config.token {
+            request = request.header("Authorization", format!("Bearer {}", token));
+        }
+        // A signer that stops answering would otherwise hold up message pushes indefinitely
+        let body: RemoteResponse<R> =
+            timeout(Duration::from_secs(self.config.timeout_secs), async {
+                let mut res = request
+                    .await
+                    .map_err(|e| Error::Other(format!("remote signer unreachable: {}", e)))?;
+
+                if !res.status().is_success() {
+                    return Err(Error::Other(format!(
+                        "remote signer returned HTTP status {}",
+                        res.status()
+                    )));
+                }
+
+                res.body_json()
+                    .await
+                    .map_err(|e| Error::Other(format!("invalid remote signer response: {}", e)))
+            })
+            .await
+            .map_err(|_| Error::Other("remote signer timed out".to_owned()))??;
+
+        match body {
+            RemoteResponse::Result { result } => Ok(result),
+            RemoteResponse::Error { error } => Err(Error::Other(format!(
+                "remote signer error {}: {}",
+                error.code, error.message
+            ))),
+        }
+    }
+}
+
+#[async_trait]
+impl Signer for RemoteSigner {
+    async fn has_key(&self, addr: &Address) -> Result<bool, Error> {
+        self.call("Filecoin.WalletHas", json!([addr.to_string()]))
+            .await
+    }
+
+    async fn list_addrs(&self) -> Result<Vec<Address>, Error> {
+        let addrs: Vec<String> = self.call("Filecoin.WalletList", json!([])).await?;
+        addrs
+            .iter()
+            .map(|a| Address::from_str(a).map_err(|e| Error::Other(e.to_string())))
+            .collect()
+    }
+
+    async fn sign(&self, addr: &Address, msg: &[u8], meta: MsgMeta) -> Result<Signature, Error> {
+        let SignatureJson(sig) = self
+            .call(
+                "Filecoin.WalletSign",
+                json!([addr.to_string(), base64::encode(msg), meta]),
+            )
+            .await?;
+        Ok(sig)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{generate_key, sign, KeyStore, KeyStoreConfig, MultiSigner};
+    use async_std::io::{ReadExt, WriteExt};
+    use async_std::net::TcpListener;
+    use async_std::sync::{Arc, RwLock};
+    use async_std::task;
+    use fvm_shared::crypto::signature::SignatureType;
+
+    /// Serves a single JSON-RPC request with the given HTTP status and response body, in place
+    /// of a remote wallet. Returns the config of a signer using it.
+    async fn mock_remote(status: u16, body: String) -> RemoteSignerConfig {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/rpc/v0", listener.local_addr().unwrap());
+        task::spawn(async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+
+            // Read the whole request before answering
+            let mut request = Vec::new();
+            let mut buf = [0; 1024];
+            loop {
+                let n = stream.read(&mut buf).await.unwrap();
+                if n == 0 {
+                    break;
+                }
+                request.extend_from_slice(&buf[..n]);
+                let text = String::from_utf8_lossy(&request).to_lowercase();
+                if let Some(end) = text.find("\r\n\r\n") {
+                    let len = text
+                        .lines()
+                        .find_map(|l| l.strip_prefix("content-length:"))
+                        .map_or(0, |l| l.trim().parse().unwrap());
+                    if request.len() >= end + 4 + len {
+                        break;
+                    }
+                }
+            }
+
+            let response = format!(
+                "HTTP/1.1 {} Mock\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                status,
+                body.len(),
+                body
+            );
+            stream.write_all(response.as_bytes()).await.unwrap();
+        });
+        RemoteSignerConfig {
+            url,
+            ..Default::default()
+        }
+    }
+
+    #[async_std::test]
+    async fn remote_signer_has_key() {
+        let key = generate_key(SignatureType::BLS).unwrap();
+        let config = mock_remote(200, r#"{"jsonrpc":"2.0","result":true,"id":0}"#.into()).await;
+
+        assert!(RemoteSigner::new(config)
+            .has_key(&key.address)
+            .await
+            .unwrap());
+    }
+
+    #[async_std::test]
+    async fn remote_signer_failures_are_errors() {
+        let key = generate_key(SignatureType::BLS).unwrap();
+
+        let body = r#"{"jsonrpc":"2.0","error":{"code":1,"message":"wallet locked"},"id":0}"#;
+        let config = mock_remote(200, body.into()).await;
+        assert_eq!(
+            RemoteSigner::new(config)
+                .has_key(&key.address)
+                .await
+                .unwrap_err(),
+            Error::Other("remote signer error 1: wallet locked".to_owned())
+        );
+
+        let config = mock_remote(500, "{}".into()).await;
+        assert!(RemoteSigner::new(config)
+            .has_key(&key.address)
+            .await
+            .is_err());
+
+        // Nothing listens on the port once the listener is dropped
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/rpc/v0", listener.local_addr().unwrap());
+        drop(listener);
+        let ks = KeyStore::new(KeyStoreConfig::Memory).unwrap();
+        let signer = MultiSigner::new(Arc::new(RwLock::new(ks))).with_remote(RemoteSigner::new(
+            RemoteSignerConfig {
+                url,
+                ..Default::default()
+            },
+        ));
+        assert!(signer.has_key(&key.address).await.is_err());
+    }
+
+    #[async_std::test]
+    async fn remote_signer_times_out() {
+        let key = generate_key(SignatureType::BLS).unwrap();
+
+        // The connection is accepted but never answered
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/rpc/v0", listener.local_addr().unwrap());
+        let _stream = task::spawn(async move { listener.accept().await });
+        let config = RemoteSignerConfig {
+            url,
+            timeout_secs: 1,
+            ..Default::default()
+        };
+        assert_eq!(
+            RemoteSigner::new(config)
+                .has_key(&key.address)
+                .await
+                .unwrap_err(),
+            Error::Other("remote signer timed out".to_owned())
+        );
+    }
+
+    #[async_std::test]
+    async fn multi_signer_falls_back_to_remote() {
+        let key = generate_key(SignatureType::Secp256k1).unwrap();
+        let msg = b"forest";
+        let sig = sign(*key.key_info.key_type(), key.key_info.private_key(), msg).unwrap();
+        let body = json!({ "jsonrpc": "2.0", "result": SignatureJson(sig), "id": 0 });
+        let config = mock_remote(200, body.to_string()).await;
+
+        let ks = KeyStore::new(KeyStoreConfig::Memory).unwrap();
+        let signer =
+            MultiSigner::new(Arc::new(RwLock::new(ks))).with_remote(RemoteSigner::new(config));
+        let sig = signer
+            .sign(&key.address, msg, MsgMeta::default())
+            .await
+            .unwrap();
+        assert!(sig.verify(msg, &key.address).is_ok());
+    }
+}
@@ -0,0 +1,215 @@
+// Copyright 2019-2022 ChainSafe Systems
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+use super::errors::Error;
+use super::{list_addrs, sign, try_find, Key, KeyStore};
+use async_std::sync::{Arc, RwLock};
+use async_trait::async_trait;
+use fvm_shared::address::Address;
+use fvm_shared::crypto::signature::Signature;
+use serde::{Deserialize, Serialize};
+use std::convert::TryFrom;
+
+/// The kind of data being signed, passed to signers so that external signers can apply
+/// policies before signing.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum MsgType {
+    #[serde(rename = "unknown")]
+    Unknown,
+    /// Signing bytes of a chain message. `MsgMeta::extra` holds the CBOR encoded message.
+    #[serde(rename = "message")]
+    ChainMsg,
+    /// Signing bytes of a block header. `MsgMeta::extra` holds the CBOR encoded header.
+    #[serde(rename = "block")]
+    BlockHeader,
+}
+
+/// Metadata describing the data being signed, in the format used by Lotus remote wallets.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct MsgMeta {
+    #[serde(rename = "Type")]
+    pub msg_type: MsgType,
+    #[serde(with = "base64_bytes")]
+    pub extra: Vec<u8>,
+}
+
+impl MsgMeta {
+    pub fn new(msg_type: MsgType, extra: Vec<u8>) -> Self {
+        Self { msg_type, extra }
+    }
+}
+
+impl Default for MsgMeta {
+    fn default() -> Self {
+        Self::new(MsgType::Unknown, Vec::new())
+    }
+}
+
+/// Produces signatures on behalf of addresses. Implementors may hold keys locally or delegate
+/// to an external process, in which case private keys never touch the node.
+#[async_trait]
+pub trait Signer: Send + Sync {
+    /// Return whether or not this signer can sign for the given address. Fails if that can't
+    /// be determined, e.g. when an external signer is unreachable.
+    async fn has_key(&self, addr: &Address) -> Result<bool, Error>;
+
+    /// Return the addresses this signer can sign for
+    async fn list_addrs(&self) -> Result<Vec<Address>, Error>;
+
+    /// Sign the given bytes with the key for `addr`
+    async fn sign(&self, addr: &Address, msg: &[u8], meta: MsgMeta) -> Result<Signature, Error>;
+}
+
+/// Signer backed by the local `KeyStore`.
+pub struct KeyStoreSigner {
+    keystore: Arc<RwLock<KeyStore>>,
+}
+
+impl KeyStoreSigner {
+    pub fn new(keystore: Arc<RwLock<KeyStore>>) -> Self {
+        Self { keystore }
+    }
+}
+
+#[async_trait]
+impl Signer for KeyStoreSigner {
+    async fn has_key(&self, addr: &Address) -> Result<bool, Error> {
+        Ok(try_find(addr, &mut *self.keystore.write().await).is_ok())
+    }
+
+    async fn list_addrs(&self) -> Result<Vec<Address>, Error> {
+        list_addrs(&*self.keystore.read().await)
+    }
+
+    async fn sign(&self, addr: &Address, msg: &[u8], _meta: MsgMeta) -> Result<Signature, Error> {
+        let key_info = try_find(addr, &mut *self.keystore.write().await)?;
+        let key = Key::try_from(key_info)?;
+        sign(*key.key_info.key_type(), key.key_info.private_key(), msg)
+    }
+}
+
+/// Signer that signs with the local `KeyStore` when it holds the key and falls back to an
+/// optional remote signer otherwise.
+pub struct MultiSigner {
+    local: KeyStoreSigner,
+    remote: Option<Box<dyn Signer>>,
+}
+
+impl MultiSigner {
+    pub fn new(keystore: Arc<RwLock<KeyStore>>) -> Self {
+        Self {
+            local: KeyStoreSigner::new(keystore),
+            remote: None,
+        }
+    }
+
+    /// Signs for the addresses whose keys are not in the local `KeyStore` with the given signer.
+    pub fn with_remote(mut self, remote: impl Signer + 'static) -> Self {
+        self.remote = Some(Box::new(remote));
+        self
+    }
+}
+
+#[async_trait]
+impl Signer for MultiSigner {
+    async fn has_key(&self, addr: &Address) -> Result<bool, Error> {
+        if self.local.has_key(addr).await? {
+            return Ok(true);
+        }
+        match &self.remote {
+            Some(remote) => remote.has_key(addr).await,
+            None => Ok(false),
+        }
+    }
+
+    async fn list_addrs(&self) -> Result<Vec<Address>, Error> {
+        let mut addrs = self.local.list_addrs().await?;
+        if let Some(remote) = &self.remote {
+            for addr in remote.list_addrs().await? {
+                if !addrs.contains(&addr) {
+                    addrs.push(addr);
+                }
+            }
+        }
+        Ok(addrs)
+    }
+
+    async fn sign(&self, addr: &Address, msg: &[u8], meta: MsgMeta) -> Result<Signature, Error> {
+        if self.local.has_key(addr).await? {
+            return self.local.sign(addr, msg, meta).await;
+        }
+        match &self.remote {
+            Some(remote) => remote.sign(addr, msg, meta).await,
+            None => Err(Error::KeyNotExists),
+        }
+    }
+}
+
+mod base64_bytes {
+    use serde::{de, Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S>(bytes: &[u8], serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.serialize_str(&base64::encode(bytes))
+    }
+
+    pub fn deserialize<'de, D>(deserializer: D) -> Result<Vec<u8>, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let s: String = Deserialize::deserialize(deserializer)?;
+        base64::decode(s).map_err(de::Error::custom)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{generate_key, KeyStoreConfig};
+    use fvm_shared::crypto::signature::SignatureType;
+
+    #[async_std::test]
+    async fn keystore_signer_signs_with_local_key() {
+        let mut ks = KeyStore::new(KeyStoreConfig::Memory).unwrap();
+        let key = generate_key(SignatureType::Secp256k1).unwrap();
+        ks.put(format!("wallet-{}", key.address), key.key_info.clone())
+            .unwrap();
+        let signer = MultiSigner::new(Arc::new(RwLock::new(ks)));
+
+        let msg = b"forest";
+        assert!(signer.has_key(&key.address).await.unwrap());
+        let sig = signer
+            .sign(&key.address, msg, MsgMeta::default())
+            .await
+            .unwrap();
+        assert!(sig.verify(msg, &key.address).is_ok());
+        assert_eq!(signer.list_addrs().await.unwrap(), vec![key.address]);
+    }
+
+    #[async_std::test]
+    async fn unknown_address_without_remote() {
+        let ks = KeyStore::new(KeyStoreConfig::Memory).unwrap();
+        let signer = MultiSigner::new(Arc::new(RwLock::new(ks)));
+        let key = generate_key(SignatureType::BLS).unwrap();
+
+        assert!(!signer.has_key(&key.address).await.unwrap());
+        assert_eq!(
+            signer
+                .sign(&key.address, b"forest", MsgMeta::default())
+                .await
+                .unwrap_err(),
+            Error::KeyNotExists
+        );
+    }
+
+    #[test]
+    fn msg_meta_json() {
+        let meta = MsgMeta::new(MsgType::ChainMsg, vec![1, 2, 3]);
+        let json = serde_json::to_string(&meta).unwrap();
+        assert_eq!(json, r#"{"Type":"message","Extra":"AQID"}"#);
+        assert_eq!(serde_json::from_str::<MsgMeta>(&json).unwrap(), meta);
+    }
+}
@@ -0,0 +1,103 @@
+// Copyright 2019-2022 ChainSafe Systems
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+use fvm_shared::address::Address;
+use fvm_shared::crypto::signature::SignatureType;
+use key_management::{
+    decode_key_info, encode_key_info, generate_key, lotus_keystore_file_name, read_lotus_keystore,
+    write_lotus_keystore_key, KeyFormat,
+};
+use std::fs;
+use std::path::Path;
+use std::str::FromStr;
+
+const LOTUS_KEYSTORE_DIR: &str = "./tests/fixtures/lotus-keystore";
+const LOTUS_EXPORT_FILE: &str = "./tests/fixtures/lotus-wallet-export.hex";
+
+const ADDR_1: &str = "f16gdhn4fw6ovl33bros6et4b7nu34kd5pvebhyoq";
+const ADDR_2: &str = "f164dgkwqwjkdqpwxjr57vy47pfeqbxzn6koysx2i";
+
+#[test]
+fn lotus_keystore_file_names() {
+    assert_eq!(lotus_keystore_file_name("default"), "MRSWMYLVNR2A");
+    assert_eq!(
+        lotus_keystore_file_name(&format!("wallet-{}", ADDR_1)),
+        "O5QWY3DFOQWWMMJWM5SGQ3RUMZ3TM33WNQZTGYTSN5ZTMZLUGRRDO3TVGM2GWZBVOB3GKYTIPFXXC"
+    );
+}
+
+#[test]
+fn read_lotus_keystore_fixture() {
+    let keys = read_lotus_keystore(Path::new(LOTUS_KEYSTORE_DIR)).unwrap();
+
+    // `default` and `libp2p-host` entries are skipped
+    let mut addrs: Vec<Address> = keys.iter().map(|k| k.address).collect();
+    addrs.sort_by_key(|a| a.to_string());
+    assert_eq!(
+        addrs,
+        vec![
+            Address::from_str(ADDR_2).unwrap(),
+            Address::from_str(ADDR_1).unwrap()
+        ]
+    );
+    assert!(keys
+        .iter()
+        .all(|k| *k.key_info.key_type() == SignatureType::Secp256k1));
+}
+
+#[test]
+fn lotus_keystore_round_trip() {
+    let keys = read_lotus_keystore(Path::new(LOTUS_KEYSTORE_DIR)).unwrap();
+    let dir = tempfile::tempdir().unwrap();
+
+    for key in keys.iter() {
+        let path = write_lotus_keystore_key(dir.path(), key).unwrap();
+        let fixture = Path::new(LOTUS_KEYSTORE_DIR).join(path.file_name().unwrap());
+        assert_eq!(
+            fs::read_to_string(path).unwrap(),
+            fs::read_to_string(fixture).unwrap()
+        );
+    }
+
+    let bls = generate_key(SignatureType::BLS).unwrap();
+    write_lotus_keystore_key(dir.path(), &bls).unwrap();
+    assert!(write_lotus_keystore_key(dir.path(), &bls).is_err());
+
+    let read_back = read_lotus_keystore(dir.path()).unwrap();
+    assert_eq!(read_back.len(), keys.len() + 1);
+    assert!(read_back.contains(&bls));
+    for key in keys {
+        assert!(read_back.contains(&key));
+    }
+}
+
+#[test]
+fn lotus_export_round_trip() {
+    let exported = fs::read_to_string(LOTUS_EXPORT_FILE).unwrap();
+    let key_info = decode_key_info(&exported, KeyFormat::HexLotus).unwrap();
+
+    let keys = read_lotus_keystore(Path::new(LOTUS_KEYSTORE_DIR)).unwrap();
+    let key = keys
+        .iter()
+        .find(|k| k.address == Address::from_str(ADDR_1).unwrap())
+        .unwrap();
+    assert_eq!(key_info, key.key_info);
+
+    assert_eq!(
+        encode_key_info(&key_info, KeyFormat::HexLotus).unwrap(),
+        exported.trim()
+    );
+
+    let json = encode_key_info(&key_info, KeyFormat::JsonLotus).unwrap();
+    assert_eq!(
+        decode_key_info(&json, KeyFormat::JsonLotus).unwrap(),
+        key_info
+    );
+}
+
+#[test]
+fn invalid_key_formats() {
+    assert!(KeyFormat::from_str("gfc-json").is_err());
+    assert!(decode_key_info("not hex", KeyFormat::HexLotus).is_err());
+    assert!(decode_key_info("{}", KeyFormat::JsonLotus).is_err());
+}
@@ -0,0 +1,357 @@
+// Copyright 2019-2022 ChainSafe Systems
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+use crate::metrics::{self, values};
+use futures::io::{AsyncRead, AsyncWrite};
+use libp2p::core::{Multiaddr, PeerId};
+use prometheus::core::GenericCounterVec;
+use std::collections::HashMap;
+use std::io;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, RwLock, Weak};
+use std::task::{Context, Poll};
+use std::time::Instant;
+
+/// Protocol ID under which the GossipSub messages are accounted.
+pub const GOSSIPSUB_PROTOCOL_ID: &[u8] = b"/meshsub/1.1.0";
+/// Protocol ID under which the bitswap blocks are accounted.
+pub const BITSWAP_PROTOCOL_ID: &[u8] = b"/ipfs/bitswap/1.2.0";
+
+/// Bandwidth used by the node, a peer or a protocol.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct BandwidthStats {
+    pub total_in: u64,
+    pub total_out: u64,
+    /// Bytes received per second over the last sampling interval.
+    pub rate_in: f64,
+    /// Bytes sent per second over the last sampling interval.
+    pub rate_out: f64,
+}
+
+/// Counts bytes transferred in each direction.
+pub trait RecordTraffic {
+    fn record_in(&self, bytes: usize);
+    fn record_out(&self, bytes: usize);
+}
+
+/// Bytes transferred in each direction.
+#[derive(Debug, Default)]
+pub struct Traffic {
+    bytes_in: AtomicU64,
+    bytes_out: AtomicU64,
+}
+
+impl Traffic {
+    fn totals(&self) -> (u64, u64) {
+        (
+            self.bytes_in.load(Ordering::Relaxed),
+            self.bytes_out.load(Ordering::Relaxed),
+        )
+    }
+}
+
+impl RecordTraffic for Traffic {
+    fn record_in(&self, bytes: usize) {
+        self.bytes_in.fetch_add(bytes as u64, Ordering::Relaxed);
+    }
+
+    fn record_out(&self, bytes: usize) {
+        self.bytes_out.fetch_add(bytes as u64, Ordering::Relaxed);
+    }
+}
+
+/// Traffic of a connection, counted towards the node totals and, once the connection is
+/// authenticated, towards its peer.
+#[derive(Debug)]
+pub struct ConnectionTraffic {
+    total: Arc<Traffic>,
+    peer: RwLock<Option<Arc<Traffic>>>,
+}
+
+impl RecordTraffic for ConnectionTraffic {
+    fn record_in(&self, bytes: usize) {
+        self.total.record_in(bytes);
+        if let Some(peer) = self.peer.read().unwrap().as_ref() {
+            peer.record_in(bytes);
+        }
+    }
+
+    fn record_out(&self, bytes: usize) {
+        self.total.record_out(bytes);
+        if let Some(peer) = self.peer.read().unwrap().as_ref() {
+            peer.record_out(bytes);
+        }
+    }
+}
+
+/// Stream wrapper counting the bytes read from and written to the inner stream.
+pub struct CountingStream<S, C> {
+    inner: S,
+    counter: Arc<C>,
+}
+
+impl<S, C> CountingStream<S, C> {
+    pub fn new(inner: S, counter: Arc<C>) -> Self {
+        Self { inner, counter }
+    }
+}
+
+impl<S: AsyncRead + Unpin, C: RecordTraffic> AsyncRead for CountingStream<S, C> {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<io::Result<usize>> {
+        let this = &mut *self;
+        let poll = Pin::new(&mut this.inner).poll_read(cx, buf);
+        if let Poll::Ready(Ok(bytes)) = poll {
+            this.counter.record_in(bytes);
+        }
+        poll
+    }
+}
+
+impl<S: AsyncWrite + Unpin, C: RecordTraffic> AsyncWrite for CountingStream<S, C> {
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        let this = &mut *self;
+        let poll = Pin::new(&mut this.inner).poll_write(cx, buf);
+        if let Poll::Ready(Ok(bytes)) = poll {
+            this.counter.record_out(bytes);
+        }
+        poll
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.inner).poll_flush(cx)
+    }
+
+    fn poll_close(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.inner).poll_close(cx)
+    }
+}
+
+/// Totals at the last sample and the rates over the sampling interval.
+#[derive(Debug, Default)]
+struct Rate {
+    last_in: u64,
+    last_out: u64,
+    rate_in: f64,
+    rate_out: f64,
+}
+
+impl Rate {
+    /// Updates the rates from the traffic over the elapsed seconds, returning the bytes received
+    /// and sent since the last sample.
+    fn sample(&mut self, traffic: &Traffic, elapsed_secs: f64) -> (u64, u64) {
+        let (total_in, total_out) = traffic.totals();
+        let delta = (total_in - self.last_in, total_out - self.last_out);
+        if elapsed_secs > 0.0 {
+            self.rate_in = delta.0 as f64 / elapsed_secs;
+            self.rate_out = delta.1 as f64 / elapsed_secs;
+        }
+        self.last_in = total_in;
+        self.last_out = total_out;
+        delta
+    }
+
+    fn stats(&self, traffic: &Traffic) -> BandwidthStats {
+        let (total_in, total_out) = traffic.totals();
+        BandwidthStats {
+            total_in,
+            total_out,
+            rate_in: self.rate_in,
+            rate_out: self.rate_out,
+        }
+    }
+}
+
+struct MeterState {
+    last_sample: Instant,
+    total: Rate,
+    peers: HashMap<PeerId, (Arc<Traffic>, Rate)>,
+    protocols: HashMap<String, (Arc<Traffic>, Rate)>,
+    /// Connections whose peer is not known yet, by remote address.
+    pending: HashMap<Multiaddr, Vec<Weak<ConnectionTraffic>>>,
+}
+
+/// Accounts the bandwidth used by the node, by peer and by protocol. The totals and the per peer
+/// figures count every byte of the connections, the per protocol figures count the requests,
+/// responses, messages and blocks exchanged over each protocol. Peers are accounted for as long
+/// as they are connected.
+pub struct BandwidthMeter {
+    total: Arc<Traffic>,
+    state: Mutex<MeterState>,
+}
+
+impl Default for BandwidthMeter {
+    fn default() -> Self {
+        Self {
+            total: Default::default(),
+            state: Mutex::new(MeterState {
+                last_sample: Instant::now(),
+                total: Default::default(),
+                peers: Default::default(),
+                protocols: Default::default(),
+                pending: Default::default(),
+            }),
+        }
+    }
+}
+
+impl BandwidthMeter {
+    /// Returns the counter of a new connection with the given remote address.
+    pub(crate) fn connection(&self, remote_addr: &Multiaddr) -> Arc<ConnectionTraffic> {
+        let connection = Arc::new(ConnectionTraffic {
+            total: self.total.clone(),
+            peer: RwLock::new(None),
+        });
+        let mut state = self.state.lock().unwrap();
+        let pending = state.pending.entry(remote_addr.clone()).or_default();
+        pending.retain(|connection| connection.strong_count() > 0);
+        pending.push(Arc::downgrade(&connection));
+        connection
+    }
+
+    /// Accounts the traffic of the pending connections with the given remote address to the
+    /// authenticated peer.
+    pub(crate) fn connection_authenticated(&self, remote_addr: &Multiaddr, peer_id: PeerId) {
+        let mut state = self.state.lock().unwrap();
+        let connections = match state.pending.remove(remote_addr) {
+            Some(connections) => connections,
+            None => return,
+        };
+        let (peer, _) = state.peers.entry(peer_id).or_default();
+        for connection in connections.iter().filter_map(Weak::upgrade) {
+            *connection.peer.write().unwrap() = Some(peer.clone());
+        }
+    }
+
+    /// Returns the counter of a protocol.
+    pub(crate) fn protocol(&self, protocol_id: &[u8]) -> Arc<Traffic> {
+        let protocol_id = String::from_utf8_lossy(protocol_id).into_owned();
+        let mut state = self.state.lock().unwrap();
+        let (traffic, _) = state.protocols.entry(protocol_id).or_default();
+        traffic.clone()
+    }
+
+    /// Updates the rates and the metrics from the traffic since the last sample, and forgets
+    /// the disconnected peers.
+    pub(crate) fn sample(&self) {
+        let mut state = self.state.lock().unwrap();
+        let state = &mut *state;
+        let now = Instant::now();
+        let elapsed_secs = now.duration_since(state.last_sample).as_secs_f64();
+        state.last_sample = now;
+
+        let (bytes_in, bytes_out) = state.total.sample(&self.total, elapsed_secs);
+        record_bytes(&metrics::NETWORK_BYTES_TOTAL, &[], bytes_in, bytes_out);
+        for (protocol_id, (traffic, rate)) in state.protocols.iter_mut() {
+            let (bytes_in, bytes_out) = rate.sample(traffic, elapsed_secs);
+            record_bytes(
+                &metrics::NETWORK_PROTOCOL_BYTES_TOTAL,
+                &[protocol_id],
+                bytes_in,
+                bytes_out,
+            );
+        }
+        // Connections hold the traffic of their peer
+        state
+            .peers
+            .retain(|_, (traffic, _)| Arc::strong_count(traffic) > 1);
+        for (traffic, rate) in state.peers.values_mut() {
+            rate.sample(traffic, elapsed_secs);
+        }
+        state.pending.retain(|_, connections| {
+            connections.retain(|connection| connection.strong_count() > 0);
+            !connections.is_empty()
+        });
+    }
+
+    /// Returns the bandwidth used by the node.
+    pub fn stats(&self) -> BandwidthStats {
+        self.state.lock().unwrap().total.stats(&self.total)
+    }
+
+    /// Returns the bandwidth used with each connected peer.
+    pub fn stats_by_peer(&self) -> HashMap<PeerId, BandwidthStats> {
+        let state = self.state.lock().unwrap();
+        state
+            .peers
+            .iter()
+            .map(|(peer_id, (traffic, rate))| (*peer_id, rate.stats(traffic)))
+            .collect()
+    }
+
+    /// Returns the payload bytes exchanged over each protocol. Only the hello and chain exchange
+    /// requests and responses, the GossipSub messages and the bitswap blocks are counted, so the
+    /// figures leave out Kademlia, Identify, ping and framing overhead and don't add up to the
+    /// totals.
+    pub fn stats_by_protocol(&self) -> HashMap<String, BandwidthStats> {
+        let state = self.state.lock().unwrap();
+        state
+            .protocols
+            .iter()
+            .map(|(protocol_id, (traffic, rate))| (protocol_id.clone(), rate.stats(traffic)))
+            .collect()
+    }
+}
+
+/// Adds the bytes received and sent to a counter with a direction label.
+fn record_bytes(
+    counter: &GenericCounterVec<prometheus::core::AtomicU64>,
+    labels: &[&str],
+    bytes_in: u64,
+    bytes_out: u64,
+) {
+    for (direction, bytes) in [(values::INBOUND, bytes_in), (values::OUTBOUND, bytes_out)] {
+        let mut labels = labels.to_vec();
+        labels.push(direction);
+        counter.with_label_values(&labels).inc_by(bytes);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::io::{AsyncReadExt, AsyncWriteExt, Cursor};
+
+    fn addr(port: u16) -> Multiaddr {
+        format!("/ip4/10.0.0.1/tcp/{}", port).parse().unwrap()
+    }
+
+    #[async_std::test]
+    async fn accounts_by_peer_and_protocol() {
+        let meter = BandwidthMeter::default();
+        let peer = PeerId::random();
+
+        let mut connection =
+            CountingStream::new(Cursor::new(vec![0; 10]), meter.connection(&addr(1)));
+        // The handshake is only counted in the totals
+        let mut buf = [0; 4];
+        connection.read_exact(&mut buf).await.unwrap();
+        meter.connection_authenticated(&addr(1), peer);
+        connection.read_exact(&mut buf).await.unwrap();
+        connection.write_all(&[1; 6]).await.unwrap();
+
+        let mut stream = CountingStream::new(Cursor::new(vec![0; 3]), meter.protocol(b"/test"));
+        stream.read_to_end(&mut vec![]).await.unwrap();
+
+        meter.sample();
+        let stats = meter.stats();
+        assert_eq!((stats.total_in, stats.total_out), (8, 6));
+        let by_peer = meter.stats_by_peer();
+        assert_eq!((by_peer[&peer].total_in, by_peer[&peer].total_out), (4, 6));
+        assert_eq!(meter.stats_by_protocol()["/test"].total_in, 3);
+
+        // Disconnected peers are forgotten
+        drop(connection);
+        meter.sample();
+        assert!(meter.stats_by_peer().is_empty());
+        assert_eq!(meter.stats().total_in, 8);
+    }
+}
@@ -2,8 +2,12 @@
 // SPDX-License-Identifier: Apache-2.0, MIT
 
 use crate::{
+    bandwidth::{
+        BandwidthMeter, RecordTraffic, Traffic, BITSWAP_PROTOCOL_ID, GOSSIPSUB_PROTOCOL_ID,
+    },
     chain_exchange::{
         ChainExchangeCodec, ChainExchangeProtocolName, ChainExchangeRequest, ChainExchangeResponse,
+        CHAIN_XCHG_PROTOCOL_ID,
     },
     discovery::DiscoveryOut,
     gossip_params::{build_peer_score_params, build_peer_score_threshold},
@@ -12,7 +16,7 @@ use crate::{
 use crate::{config::Libp2pConfig, discovery::DiscoveryBehaviour};
 use crate::{
     discovery::DiscoveryConfig,
-    hello::{HelloCodec, HelloProtocolName, HelloRequest, HelloResponse},
+    hello::{HelloCodec, HelloProtocolName, HelloRequest, HelloResponse, HELLO_PROTOCOL_ID},
 };
 use cid::Cid;
 use forest_encoding::blake2b_256;
@@ -33,13 +37,13 @@ use libp2p::{core::PeerId, gossipsub::GossipsubMessage};
 use libp2p::{
     gossipsub::{
         error::PublishError, error::SubscriptionError, Gossipsub, GossipsubConfigBuilder,
-        GossipsubEvent, IdentTopic as Topic, MessageAuthenticity, MessageId, TopicHash,
-        ValidationMode,
+        GossipsubEvent, IdentTopic as Topic, MessageAcceptance, MessageAuthenticity, MessageId,
+        TopicHash, ValidationMode,
     },
     Multiaddr,
 };
 use libp2p::{
-    identify::{Identify, IdentifyConfig, IdentifyEvent},
+    identify::{Identify, IdentifyConfig, IdentifyEvent, IdentifyInfo},
     ping::{PingFailure, PingSuccess},
 };
 use libp2p_bitswap::{Bitswap, BitswapEvent, Priority};
@@ -47,6 +51,7 @@ use log::{debug, trace, warn};
 use std::collections::HashSet;
 use std::convert::TryFrom;
 use std::pin::Pin;
+use std::sync::Arc;
 use std::time::Duration;
 use std::time::{SystemTime, UNIX_EPOCH};
 use std::{collections::HashMap, convert::TryInto};
@@ -77,6 +82,13 @@ pub(crate) struct ForestBehaviour {
     bitswap: Bitswap,
     #[behaviour(ignore)]
     events: Vec<ForestBehaviourEvent>,
+    /// Bytes of the GossipSub messages delivered to the node and published by it. Forwarded
+    /// messages, control messages and the framing overhead are not counted.
+    #[behaviour(ignore)]
+    gossipsub_traffic: Arc<Traffic>,
+    /// Bytes of the bitswap blocks received and sent.
+    #[behaviour(ignore)]
+    bitswap_traffic: Arc<Traffic>,
     /// Keeps track of Chain exchange requests to responses
     #[behaviour(ignore)]
     cx_request_table:
@@ -107,6 +119,7 @@ pub(crate) enum ForestBehaviourEvent {
     PeerDisconnected(PeerId),
     GossipMessage {
         source: PeerId,
+        message_id: MessageId,
         topic: TopicHash,
         message: Vec<u8>,
     },
@@ -121,6 +134,16 @@ pub(crate) enum ForestBehaviourEvent {
         request: ChainExchangeRequest,
         channel: OneShotSender<ChainExchangeResponse>,
     },
+    /// Identify information received from a peer.
+    PeerIdentified {
+        peer: PeerId,
+        info: IdentifyInfo,
+    },
+    /// Round trip time of a ping to a peer.
+    PeerPinged {
+        peer: PeerId,
+        rtt: Duration,
+    },
 }
 
 impl NetworkBehaviourEventProcess<DiscoveryOut> for ForestBehaviour {
@@ -142,6 +165,7 @@ impl NetworkBehaviourEventProcess<BitswapEvent> for ForestBehaviour {
     fn inject_event(&mut self, event: BitswapEvent) {
         match event {
             BitswapEvent::ReceivedBlock(peer_id, cid, data) => {
+                self.bitswap_traffic.record_in(data.len());
                 // The `cid` from this event has a different type
                 let cid = cid.to_bytes();
                 match Cid::try_from(cid) {
@@ -178,11 +202,13 @@ impl NetworkBehaviourEventProcess<GossipsubEvent> for ForestBehaviour {
         if let GossipsubEvent::Message {
             propagation_source,
             message,
-            message_id: _,
+            message_id,
         } = message
         {
+            self.gossipsub_traffic.record_in(message.data.len());
             self.events.push(ForestBehaviourEvent::GossipMessage {
                 source: propagation_source,
+                message_id,
                 topic: message.topic,
                 message: message.data,
             })
@@ -199,6 +225,10 @@ impl NetworkBehaviourEventProcess<PingEvent> for ForestBehaviour {
                     event.peer.to_base58(),
                     rtt.as_millis()
                 );
+                self.events.push(ForestBehaviourEvent::PeerPinged {
+                    peer: event.peer,
+                    rtt,
+                });
             }
             Ok(PingSuccess::Pong) => {
                 trace!("PingSuccess::Pong from {}", event.peer.to_base58());
@@ -226,6 +256,10 @@ impl NetworkBehaviourEventProcess<IdentifyEvent> for ForestBehaviour {
                 trace!("listening_ addresses {:?}", info.listen_addrs);
                 trace!("observed_address {}", info.observed_addr);
                 trace!("protocols {:?}", info.protocols);
+                self.events.push(ForestBehaviourEvent::PeerIdentified {
+                    peer: peer_id,
+                    info,
+                });
             }
             IdentifyEvent::Sent { .. } => (),
             IdentifyEvent::Pushed { .. } => (),
@@ -435,10 +469,17 @@ impl ForestBehaviour {
         Poll::Pending
     }
 
-    pub fn new(local_key: &Keypair, config: &Libp2pConfig, network_name: &str) -> Self {
+    pub fn new(
+        local_key: &Keypair,
+        config: &Libp2pConfig,
+        network_name: &str,
+        bandwidth: &BandwidthMeter,
+    ) -> Self {
         let mut gs_config_builder = GossipsubConfigBuilder::default();
         gs_config_builder.max_transmit_size(1 << 20);
         gs_config_builder.validation_mode(ValidationMode::Strict);
+        // Messages are only forwarded once the node has reported them valid
+        gs_config_builder.validate_messages();
         gs_config_builder.message_id_fn(|msg: &GossipsubMessage| {
             let s = blake2b_256(&msg.data);
             MessageId::from(s)
@@ -451,12 +492,14 @@ impl ForestBehaviour {
         )
         .unwrap();
 
-        gossipsub
-            .with_peer_score(
-                build_peer_score_params(network_name),
-                build_peer_score_threshold(),
-            )
-            .unwrap();
+        if config.gossipsub_scoring {
+            gossipsub
+                .with_peer_score(
+                    build_peer_score_params(network_name),
+                    build_peer_score_threshold(),
+                )
+                .unwrap();
+        }
 
         let bitswap = Bitswap::new();
 
@@ -464,6 +507,7 @@ impl ForestBehaviour {
         discovery_config
             .with_mdns(config.mdns)
             .with_kademlia(config.kademlia)
+            .with_dht(config.dht.clone())
             .with_user_defined(config.bootstrap_peers.clone())
             // TODO allow configuring this through config.
             .discovery_limit(config.target_peer_count as u64);
@@ -479,14 +523,27 @@ impl ForestBehaviour {
             gossipsub,
             discovery: discovery_config.finish(),
             ping: Ping::default(),
-            identify: Identify::new(IdentifyConfig::new("ipfs/0.1.0".into(), local_key.public())),
+            identify: Identify::new(
+                IdentifyConfig::new("ipfs/0.1.0".into(), local_key.public())
+                    .with_agent_version(format!("forest-{}+git.{}", *VERSION, *CURRENT_COMMIT)),
+            ),
             bitswap,
-            hello: RequestResponse::new(HelloCodec::default(), hp, req_res_config.clone()),
-            chain_exchange: RequestResponse::new(ChainExchangeCodec::default(), cp, req_res_config),
+            hello: RequestResponse::new(
+                HelloCodec::new(bandwidth.protocol(HELLO_PROTOCOL_ID)),
+                hp,
+                req_res_config.clone(),
+            ),
+            chain_exchange: RequestResponse::new(
+                ChainExchangeCodec::new(bandwidth.protocol(CHAIN_XCHG_PROTOCOL_ID)),
+                cp,
+                req_res_config,
+            ),
             cx_pending_responses: Default::default(),
             cx_request_table: Default::default(),
             hello_request_table: Default::default(),
             events: vec![],
+            gossipsub_traffic: bandwidth.protocol(GOSSIPSUB_PROTOCOL_ID),
+            bitswap_traffic: bandwidth.protocol(BITSWAP_PROTOCOL_ID),
         }
     }
 
@@ -495,12 +552,23 @@ impl ForestBehaviour {
         self.discovery.bootstrap()
     }
 
+    /// Looks up the addresses of a peer and sends them to the channel.
+    pub fn find_peer(
+        &mut self,
+        peer_id: PeerId,
+        response_channel: OneShotSender<Result<Vec<Multiaddr>, String>>,
+    ) {
+        self.discovery.find_peer(peer_id, response_channel)
+    }
+
     /// Publish data over the gossip network.
     pub fn publish(
         &mut self,
         topic: Topic,
         data: impl Into<Vec<u8>>,
     ) -> Result<MessageId, PublishError> {
+        let data: Vec<u8> = data.into();
+        self.gossipsub_traffic.record_out(data.len());
         self.gossipsub.publish(topic, data)
     }
 
@@ -509,6 +577,35 @@ impl ForestBehaviour {
         self.gossipsub.subscribe(topic)
     }
 
+    /// Adds a peer that GossipSub always sends messages to, outside of the mesh.
+    pub fn add_explicit_peer(&mut self, peer_id: &PeerId) {
+        self.gossipsub.add_explicit_peer(peer_id);
+    }
+
+    /// Reports the validation result of a received message to GossipSub, which forwards accepted
+    /// messages and penalizes the peers that propagated rejected ones. Returns false if the
+    /// message is no longer in the cache.
+    pub fn report_message_validation_result(
+        &mut self,
+        message_id: &MessageId,
+        source: &PeerId,
+        acceptance: MessageAcceptance,
+    ) -> Result<bool, PublishError> {
+        self.gossipsub
+            .report_message_validation_result(message_id, source, acceptance)
+    }
+
+    /// Sets the application specific GossipSub score of a peer. Returns false if peer scoring is
+    /// disabled or the peer is not known to GossipSub.
+    pub fn set_application_score(&mut self, peer_id: &PeerId, score: f64) -> bool {
+        self.gossipsub.set_application_score(peer_id, score)
+    }
+
+    /// Returns the GossipSub score of a peer, if peer scoring is enabled.
+    pub fn peer_score(&self, peer_id: &PeerId) -> Option<f64> {
+        self.gossipsub.peer_score(peer_id)
+    }
+
     /// Send a hello request or response to some peer.
     pub fn send_hello_request(
         &mut self,
@@ -551,6 +648,7 @@ impl ForestBehaviour {
         debug!("send {}", cid.to_string());
         let cid = cid.to_bytes();
         let cid = Cid2::try_from(cid)?;
+        self.bitswap_traffic.record_out(data.len());
         self.bitswap.send_block(peer_id, cid, data);
         Ok(())
     }
@@ -0,0 +1,153 @@
+// Copyright 2019-2022 ChainSafe Systems
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+use crate::metrics::{self, values};
+use cid::multihash::{Code, MultihashDigest};
+use cid::Cid;
+use ipld_blockstore::{BlockStore, BlockStoreExt};
+use libp2p::PeerId;
+use log::{debug, trace, warn};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+/// Limits on the bitswap blocks served to other peers.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(default)]
+pub struct BitswapServerConfig {
+    /// Blocks of the local store served to peers that want them.
+    pub enabled: bool,
+    /// Maximum number of bytes served to a single peer within the budget window.
+    pub max_bytes_per_peer: u64,
+    /// Length of the per peer budget window in seconds.
+    pub budget_window_secs: u64,
+}
+
+impl Default for BitswapServerConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            max_bytes_per_peer: 64 * 1024 * 1024,
+            budget_window_secs: 60,
+        }
+    }
+}
+
+/// Serves the blocks wanted by peers from the local store. Once a peer has been served its byte
+/// budget, its wants are ignored until the budget window ends.
+pub(crate) struct BitswapServer {
+    config: BitswapServerConfig,
+    /// Start of the current budget window and the bytes served in it, by peer.
+    peer_windows: HashMap<PeerId, (Instant, u64)>,
+}
+
+impl BitswapServer {
+    pub fn new(config: BitswapServerConfig) -> Self {
+        Self {
+            config,
+            peer_windows: Default::default(),
+        }
+    }
+
+    /// Returns the block wanted by a peer if it is in the store and within the peer's budget.
+    pub fn serve<BS: BlockStore>(&mut self, bs: &BS, peer: PeerId, cid: &Cid) -> Option<Vec<u8>> {
+        if !self.config.enabled {
+            return None;
+        }
+        let data = match bs.get_bytes(cid) {
+            Ok(Some(data)) => data,
+            Ok(None) => {
+                trace!("Don't have data for: {}", cid);
+                record_want(values::NOT_FOUND);
+                return None;
+            }
+            Err(e) => {
+                warn!("Failed to get data for {}: {}", cid, e);
+                record_want(values::ERROR);
+                return None;
+            }
+        };
+        if !self.spend_budget(peer, data.len() as u64) {
+            debug!("Bitswap byte budget exceeded by peer {}", peer);
+            record_want(values::RATE_LIMITED);
+            return None;
+        }
+        record_want(values::SUCCESS);
+        metrics::BITSWAP_BYTES_SERVED_TOTAL.inc_by(data.len() as u64);
+        Some(data)
+    }
+
+    /// Counts bytes against the peer's budget window, returning whether they can be served.
+    fn spend_budget(&mut self, peer: PeerId, bytes: u64) -> bool {
+        let window = Duration::from_secs(self.config.budget_window_secs);
+        let now = Instant::now();
+        // Forget peers whose window has ended, so that the map stays bounded by active peers
+        self.peer_windows
+            .retain(|_, (start, _)| now.duration_since(*start) < window);
+
+        let (_, spent) = self.peer_windows.entry(peer).or_insert((now, 0));
+        if *spent + bytes > self.config.max_bytes_per_peer {
+            return false;
+        }
+        *spent += bytes;
+        true
+    }
+}
+
+/// Checks that the data of a block received over bitswap hashes to its CID.
+pub(crate) fn verify_block(cid: &Cid, data: &[u8]) -> bool {
+    Code::try_from(cid.hash().code()).map_or(false, |code| code.digest(data) == *cid.hash())
+}
+
+fn record_want(result: &str) {
+    metrics::BITSWAP_WANT_TOTAL
+        .with_label_values(&[result])
+        .inc();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use db::MemoryDB;
+    use fvm_ipld_encoding::DAG_CBOR;
+
+    #[test]
+    fn serves_within_budget() {
+        let db = MemoryDB::default();
+        let cid = db.put_raw(vec![0; 100], Code::Blake2b256).unwrap();
+        let mut server = BitswapServer::new(BitswapServerConfig {
+            max_bytes_per_peer: 250,
+            ..Default::default()
+        });
+        let peer = PeerId::random();
+
+        for _ in 0..2 {
+            assert_eq!(server.serve(&db, peer, &cid), Some(vec![0; 100]));
+        }
+        assert_eq!(server.serve(&db, peer, &cid), None);
+
+        // Other peers have their own budget
+        assert!(server.serve(&db, PeerId::random(), &cid).is_some());
+
+        let missing = Cid::new_v1(DAG_CBOR, Code::Blake2b256.digest(&[1]));
+        assert_eq!(server.serve(&db, PeerId::random(), &missing), None);
+    }
+
+    #[test]
+    fn serves_nothing_when_disabled() {
+        let db = MemoryDB::default();
+        let cid = db.put_raw(vec![0; 100], Code::Blake2b256).unwrap();
+        let mut server = BitswapServer::new(BitswapServerConfig {
+            enabled: false,
+            ..Default::default()
+        });
+        assert_eq!(server.serve(&db, PeerId::random(), &cid), None);
+    }
+
+    #[test]
+    fn verifies_blocks() {
+        let cid = Cid::new_v1(DAG_CBOR, Code::Sha2_256.digest(b"block"));
+        assert!(verify_block(&cid, b"block"));
+        assert!(!verify_block(&cid, b"other block"));
+    }
+}
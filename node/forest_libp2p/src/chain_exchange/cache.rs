@@ -0,0 +1,137 @@
+// Copyright 2019-2022 ChainSafe Systems
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+use super::TipsetBundle;
+use crate::metrics::{self, values};
+use forest_blocks::TipsetKeys;
+use forest_encoding::tuple::*;
+use fvm_shared::clock::ChainEpoch;
+use lru::LruCache;
+use std::sync::{Arc, Mutex};
+
+/// Tipset bundle served for a tipset along with what is needed to continue walking the chain,
+/// as the bundle has no headers when only messages are requested.
+#[derive(Clone, Serialize_tuple)]
+pub(crate) struct CachedBundle {
+    pub bundle: TipsetBundle,
+    pub parents: TipsetKeys,
+    pub epoch: ChainEpoch,
+}
+
+struct Inner {
+    /// Cached bundles along with their estimated size.
+    entries: LruCache<(TipsetKeys, u64), (Arc<CachedBundle>, usize)>,
+    size: usize,
+}
+
+/// LRU cache of the tipset bundles served over chain exchange, keyed by tipset key and request
+/// options. Bundles are held decoded, and the size of each is estimated from its CBOR encoding
+/// when it is inserted. Chain data is immutable, so entries are only ever evicted to stay within
+/// the size limit.
+pub struct ChainExchangeCache {
+    inner: Mutex<Inner>,
+    max_size: usize,
+}
+
+impl ChainExchangeCache {
+    /// Creates a cache holding bundles up to an estimated `max_size` bytes. A size of zero
+    /// disables caching.
+    pub fn new(max_size: usize) -> Self {
+        Self {
+            inner: Mutex::new(Inner {
+                entries: LruCache::unbounded(),
+                size: 0,
+            }),
+            max_size,
+        }
+    }
+
+    pub(crate) fn get(&self, key: &TipsetKeys, options: u64) -> Option<Arc<CachedBundle>> {
+        if self.max_size == 0 {
+            return None;
+        }
+        let mut inner = self.inner.lock().unwrap();
+        let entry = inner
+            .entries
+            .get(&(key.clone(), options))
+            .map(|(bundle, _)| bundle.clone());
+        let result = if entry.is_some() {
+            values::HIT
+        } else {
+            values::MISS
+        };
+        metrics::CHAIN_EXCHANGE_CACHE_TOTAL
+            .with_label_values(&[result])
+            .inc();
+        entry
+    }
+
+    pub(crate) fn put(&self, key: TipsetKeys, options: u64, bundle: Arc<CachedBundle>) {
+        let size = match forest_encoding::to_vec(bundle.as_ref()) {
+            Ok(bytes) => bytes.len(),
+            Err(_) => return,
+        };
+        if size > self.max_size {
+            return;
+        }
+
+        let mut inner = self.inner.lock().unwrap();
+        inner.size += size;
+        if let Some((_, old)) = inner.entries.put((key, options), (bundle, size)) {
+            inner.size -= old;
+        }
+        while inner.size > self.max_size {
+            match inner.entries.pop_lru() {
+                Some((_, (_, evicted))) => inner.size -= evicted,
+                None => break,
+            }
+        }
+    }
+
+    /// Total estimated size of the cached bundles in bytes.
+    pub fn size(&self) -> usize {
+        self.inner.lock().unwrap().size
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(epoch: ChainEpoch) -> Arc<CachedBundle> {
+        Arc::new(CachedBundle {
+            bundle: TipsetBundle::default(),
+            parents: TipsetKeys::default(),
+            epoch,
+        })
+    }
+
+    #[test]
+    fn evicts_least_recently_used() {
+        let bundle_size = forest_encoding::to_vec(entry(1).as_ref()).unwrap().len();
+        let cache = ChainExchangeCache::new(2 * bundle_size);
+
+        let first = entry(1);
+        cache.put(TipsetKeys::default(), 1, first.clone());
+        cache.put(TipsetKeys::default(), 2, entry(2));
+        // Hits share the cached bundle instead of decoding a copy
+        assert!(Arc::ptr_eq(
+            &cache.get(&TipsetKeys::default(), 1).unwrap(),
+            &first
+        ));
+
+        cache.put(TipsetKeys::default(), 3, entry(3));
+        assert_eq!(cache.size(), 2 * bundle_size);
+        assert!(cache.get(&TipsetKeys::default(), 2).is_none());
+        assert_eq!(cache.get(&TipsetKeys::default(), 1).unwrap().epoch, 1);
+        assert_eq!(cache.get(&TipsetKeys::default(), 3).unwrap().epoch, 3);
+    }
+
+    #[test]
+    fn disabled_when_empty() {
+        let cache = ChainExchangeCache::new(0);
+        cache.put(TipsetKeys::default(), 1, entry(1));
+        assert!(cache.get(&TipsetKeys::default(), 1).is_none());
+        assert_eq!(cache.size(), 0);
+    }
+}
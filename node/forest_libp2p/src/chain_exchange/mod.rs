@@ -1,11 +1,15 @@
 // Copyright 2019-2022 ChainSafe Systems
 // SPDX-License-Identifier: Apache-2.0, MIT
 
+mod cache;
 mod message;
 mod provider;
+mod server;
 
+pub use self::cache::ChainExchangeCache;
 pub use self::message::*;
 pub use self::provider::*;
+pub use self::server::*;
 use super::rpc::CborRequestResponse;
 use libp2p::core::ProtocolName;
 
@@ -7,10 +7,12 @@ use forest_blocks::{Tipset, TipsetKeys};
 use ipld_blockstore::BlockStore;
 use log::debug;
 use std::collections::HashMap;
+use std::sync::Arc;
 
+use super::cache::CachedBundle;
 use super::{
-    ChainExchangeRequest, ChainExchangeResponse, ChainExchangeResponseStatus, CompactedMessages,
-    TipsetBundle,
+    ChainExchangeCache, ChainExchangeRequest, ChainExchangeResponse, ChainExchangeResponseStatus,
+    CompactedMessages, TipsetBundle, HEADERS, MESSAGES,
 };
 
 /// Builds chain exchange response out of chain data.
@@ -18,58 +20,57 @@ pub async fn make_chain_exchange_response<DB>(
     cs: &ChainStore<DB>,
     request: &ChainExchangeRequest,
 ) -> ChainExchangeResponse
+where
+    DB: BlockStore + Send + Sync + 'static,
+{
+    build_chain_exchange_response(cs, request, None).await
+}
+
+/// Builds chain exchange response out of chain data, serving the tipset bundles found in the
+/// cache and caching the ones read from the database.
+pub async fn make_cached_chain_exchange_response<DB>(
+    cs: &ChainStore<DB>,
+    request: &ChainExchangeRequest,
+    cache: &ChainExchangeCache,
+) -> ChainExchangeResponse
+where
+    DB: BlockStore + Send + Sync + 'static,
+{
+    build_chain_exchange_response(cs, request, Some(cache)).await
+}
+
+async fn build_chain_exchange_response<DB>(
+    cs: &ChainStore<DB>,
+    request: &ChainExchangeRequest,
+    cache: Option<&ChainExchangeCache>,
+) -> ChainExchangeResponse
 where
     DB: BlockStore + Send + Sync + 'static,
 {
     let mut response_chain: Vec<TipsetBundle> = Vec::with_capacity(request.request_len as usize);
+    let options = request.options & (HEADERS | MESSAGES);
 
-    let mut curr_tipset_cids = request.start.clone();
+    let mut curr_tipset_keys = TipsetKeys::new(request.start.clone());
 
     loop {
-        let mut tipset_bundle: TipsetBundle = TipsetBundle::default();
-        let tipset = match cs
-            .tipset_from_keys(&TipsetKeys::new(curr_tipset_cids))
-            .await
-        {
-            Ok(tipset) => tipset,
-            Err(err) => {
-                debug!("Cannot get tipset from keys: {}", err);
-
-                return ChainExchangeResponse {
-                    chain: vec![],
-                    status: ChainExchangeResponseStatus::InternalError,
-                    message: "Tipset was not found in the database".to_owned(),
+        let cached = match cache.and_then(|cache| cache.get(&curr_tipset_keys, options)) {
+            Some(cached) => cached,
+            None => {
+                let cached = match load_tipset_bundle(cs, &curr_tipset_keys, request).await {
+                    Ok(cached) => Arc::new(cached),
+                    Err(response) => return response,
                 };
-            }
-        };
-
-        if request.include_messages() {
-            match compact_messages(cs.blockstore(), &tipset) {
-                Ok(compacted_messages) => tipset_bundle.messages = Some(compacted_messages),
-                Err(err) => {
-                    debug!("Cannot compact messages for tipset: {}", err);
-
-                    return ChainExchangeResponse {
-                        chain: vec![],
-                        status: ChainExchangeResponseStatus::InternalError,
-                        message: "Can not fullfil the request".to_owned(),
-                    };
+                if let Some(cache) = cache {
+                    cache.put(curr_tipset_keys, options, cached.clone());
                 }
+                cached
             }
-        }
-
-        curr_tipset_cids = tipset.parents().cids().to_vec();
-        let tipset_epoch = tipset.epoch();
-
-        if request.include_blocks() {
-            // TODO Cloning blocks isn't ideal, this can maybe be switched to serialize this
-            // data in the function. This may not be possible without overriding rpc in libp2p
-            tipset_bundle.blocks = tipset.blocks().to_vec();
-        }
+        };
 
-        response_chain.push(tipset_bundle);
+        curr_tipset_keys = cached.parents.clone();
+        response_chain.push(cached.bundle.clone());
 
-        if response_chain.len() as u64 >= request.request_len || tipset_epoch == 0 {
+        if response_chain.len() as u64 >= request.request_len || cached.epoch == 0 {
             break;
         }
     }
@@ -87,6 +88,57 @@ where
     }
 }
 
+// Reads the requested parts of the tipset with the given keys from the database.
+async fn load_tipset_bundle<DB>(
+    cs: &ChainStore<DB>,
+    keys: &TipsetKeys,
+    request: &ChainExchangeRequest,
+) -> Result<CachedBundle, ChainExchangeResponse>
+where
+    DB: BlockStore + Send + Sync + 'static,
+{
+    let mut tipset_bundle: TipsetBundle = TipsetBundle::default();
+    let tipset = match cs.tipset_from_keys(keys).await {
+        Ok(tipset) => tipset,
+        Err(err) => {
+            debug!("Cannot get tipset from keys: {}", err);
+
+            return Err(ChainExchangeResponse {
+                chain: vec![],
+                status: ChainExchangeResponseStatus::InternalError,
+                message: "Tipset was not found in the database".to_owned(),
+            });
+        }
+    };
+
+    if request.include_messages() {
+        match compact_messages(cs.blockstore(), &tipset) {
+            Ok(compacted_messages) => tipset_bundle.messages = Some(compacted_messages),
+            Err(err) => {
+                debug!("Cannot compact messages for tipset: {}", err);
+
+                return Err(ChainExchangeResponse {
+                    chain: vec![],
+                    status: ChainExchangeResponseStatus::InternalError,
+                    message: "Can not fullfil the request".to_owned(),
+                });
+            }
+        }
+    }
+
+    if request.include_blocks() {
+        // TODO Cloning blocks isn't ideal, this can maybe be switched to serialize this
+        // data in the function. This may not be possible without overriding rpc in libp2p
+        tipset_bundle.blocks = tipset.blocks().to_vec();
+    }
+
+    Ok(CachedBundle {
+        bundle: tipset_bundle,
+        parents: tipset.parents().clone(),
+        epoch: tipset.epoch(),
+    })
+}
+
 // Builds CompactedMessages for given Tipset.
 fn compact_messages<DB>(db: &DB, tipset: &Tipset) -> Result<CompactedMessages, ChainError>
 where
@@ -149,7 +201,6 @@ where
 
 #[cfg(test)]
 mod tests {
-    use super::super::{HEADERS, MESSAGES};
     use super::*;
     use async_std::io::BufReader;
     use db::MemoryDB;
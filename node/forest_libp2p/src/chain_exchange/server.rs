@@ -0,0 +1,300 @@
+// Copyright 2019-2022 ChainSafe Systems
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+use super::{
+    make_cached_chain_exchange_response, ChainExchangeCache, ChainExchangeRequest,
+    ChainExchangeResponse, ChainExchangeResponseStatus,
+};
+use crate::metrics::{self, values};
+use chain::ChainStore;
+use ipld_blockstore::BlockStore;
+use libp2p::PeerId;
+use log::debug;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// Limits on the chain exchange requests served to other peers.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(default)]
+pub struct ChainExchangeServerConfig {
+    /// Maximum number of tipsets served for a request of headers only.
+    pub max_request_len: u64,
+    /// Maximum number of tipsets served for a request including messages.
+    pub max_request_len_with_messages: u64,
+    /// Maximum number of requests served to a single peer within the rate limit window.
+    pub max_requests_per_peer: u32,
+    /// Length of the per peer rate limit window in seconds.
+    pub rate_limit_window_secs: u64,
+    /// Maximum number of responses built at the same time.
+    pub max_concurrent_responses: usize,
+    /// Estimated size in bytes of the cache of recently served tipsets, zero disables the cache.
+    pub cache_size: usize,
+}
+
+impl Default for ChainExchangeServerConfig {
+    fn default() -> Self {
+        Self {
+            max_request_len: 900,
+            max_request_len_with_messages: 200,
+            max_requests_per_peer: 60,
+            rate_limit_window_secs: 10,
+            max_concurrent_responses: 32,
+            cache_size: 64 * 1024 * 1024,
+        }
+    }
+}
+
+/// Serves chain exchange requests within the configured limits. Requests over the per peer rate
+/// or the concurrent response limit are answered with [`ChainExchangeResponseStatus::GoAway`],
+/// requests for more tipsets than allowed are served partially.
+pub struct ChainExchangeServer {
+    config: ChainExchangeServerConfig,
+    /// Start of the current rate limit window and the requests served in it, by peer.
+    peer_windows: Mutex<HashMap<PeerId, (Instant, u32)>>,
+    active_responses: AtomicUsize,
+    cache: ChainExchangeCache,
+}
+
+/// Releases a concurrent response slot when dropped.
+struct ResponseSlot<'a>(&'a AtomicUsize);
+
+impl Drop for ResponseSlot<'_> {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+impl ChainExchangeServer {
+    pub fn new(config: ChainExchangeServerConfig) -> Self {
+        Self {
+            cache: ChainExchangeCache::new(config.cache_size),
+            config,
+            peer_windows: Default::default(),
+            active_responses: Default::default(),
+        }
+    }
+
+    /// Builds the response to a chain exchange request of the given peer.
+    pub async fn respond<DB>(
+        &self,
+        cs: &ChainStore<DB>,
+        peer: PeerId,
+        request: &ChainExchangeRequest,
+    ) -> ChainExchangeResponse
+    where
+        DB: BlockStore + Send + Sync + 'static,
+    {
+        if request.request_len == 0 || !(request.include_blocks() || request.include_messages()) {
+            record_request(values::BAD_REQUEST);
+            return error_response(
+                ChainExchangeResponseStatus::BadRequest,
+                "Invalid request length or options",
+            );
+        }
+        if !self.admit_peer(peer) {
+            debug!("Chain exchange rate limit exceeded by peer {}", peer);
+            record_request(values::RATE_LIMITED);
+            return error_response(
+                ChainExchangeResponseStatus::GoAway,
+                "Too many requests, slow down",
+            );
+        }
+        let _slot = match self.response_slot() {
+            Some(slot) => slot,
+            None => {
+                debug!(
+                    "Too many concurrent chain exchange responses, refusing {}",
+                    peer
+                );
+                record_request(values::BUSY);
+                return error_response(
+                    ChainExchangeResponseStatus::GoAway,
+                    "Too many concurrent requests, try again later",
+                );
+            }
+        };
+
+        let max_len = if request.include_messages() {
+            self.config.max_request_len_with_messages
+        } else {
+            self.config.max_request_len
+        };
+        let capped = request.request_len > max_len;
+        let mut response = if capped {
+            let request = ChainExchangeRequest {
+                request_len: max_len,
+                ..request.clone()
+            };
+            make_cached_chain_exchange_response(cs, &request, &self.cache).await
+        } else {
+            make_cached_chain_exchange_response(cs, request, &self.cache).await
+        };
+        if capped && response.status == ChainExchangeResponseStatus::Success {
+            response.status = ChainExchangeResponseStatus::PartialResponse;
+        }
+
+        record_request(match response.status {
+            ChainExchangeResponseStatus::Success => values::SUCCESS,
+            ChainExchangeResponseStatus::PartialResponse => values::PARTIAL,
+            _ => values::ERROR,
+        });
+        metrics::CHAIN_EXCHANGE_TIPSETS_SERVED_TOTAL.inc_by(response.chain.len() as u64);
+        response
+    }
+
+    /// Counts a request against the peer's rate limit window, returning whether it is allowed.
+    fn admit_peer(&self, peer: PeerId) -> bool {
+        let window = Duration::from_secs(self.config.rate_limit_window_secs);
+        let now = Instant::now();
+        let mut peer_windows = self.peer_windows.lock().unwrap();
+        // Forget peers whose window has ended, so that the map stays bounded by active peers
+        peer_windows.retain(|_, (start, _)| now.duration_since(*start) < window);
+
+        let (_, count) = peer_windows.entry(peer).or_insert((now, 0));
+        if *count >= self.config.max_requests_per_peer {
+            return false;
+        }
+        *count += 1;
+        true
+    }
+
+    fn response_slot(&self) -> Option<ResponseSlot<'_>> {
+        let active = self.active_responses.fetch_add(1, Ordering::Relaxed);
+        let slot = ResponseSlot(&self.active_responses);
+        if active >= self.config.max_concurrent_responses {
+            return None;
+        }
+        Some(slot)
+    }
+}
+
+fn error_response(status: ChainExchangeResponseStatus, message: &str) -> ChainExchangeResponse {
+    ChainExchangeResponse {
+        chain: vec![],
+        status,
+        message: message.to_owned(),
+    }
+}
+
+fn record_request(result: &str) {
+    metrics::CHAIN_EXCHANGE_REQUEST_TOTAL
+        .with_label_values(&[result])
+        .inc();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::{HEADERS, MESSAGES};
+    use super::*;
+    use async_std::io::BufReader;
+    use cid::Cid;
+    use db::MemoryDB;
+    use fvm_ipld_car::load_car;
+    use genesis::EXPORT_SR_40;
+    use std::sync::Arc;
+
+    fn request(request_len: u64, options: u64) -> ChainExchangeRequest {
+        ChainExchangeRequest {
+            start: vec![],
+            request_len,
+            options,
+        }
+    }
+
+    #[async_std::test]
+    async fn rate_limits_peers() {
+        let cs = ChainStore::new(Arc::new(MemoryDB::default()));
+        let server = ChainExchangeServer::new(ChainExchangeServerConfig {
+            max_requests_per_peer: 2,
+            ..Default::default()
+        });
+        let peer = PeerId::random();
+
+        for _ in 0..2 {
+            let response = server.respond(&cs, peer, &request(1, HEADERS)).await;
+            assert_ne!(response.status, ChainExchangeResponseStatus::GoAway);
+        }
+        let response = server.respond(&cs, peer, &request(1, HEADERS)).await;
+        assert_eq!(response.status, ChainExchangeResponseStatus::GoAway);
+
+        // Other peers have their own budget
+        let response = server
+            .respond(&cs, PeerId::random(), &request(1, HEADERS))
+            .await;
+        assert_ne!(response.status, ChainExchangeResponseStatus::GoAway);
+    }
+
+    #[async_std::test]
+    async fn rejects_bad_requests() {
+        let cs = ChainStore::new(Arc::new(MemoryDB::default()));
+        let server = ChainExchangeServer::new(Default::default());
+
+        let response = server
+            .respond(&cs, PeerId::random(), &request(0, HEADERS))
+            .await;
+        assert_eq!(response.status, ChainExchangeResponseStatus::BadRequest);
+        let response = server.respond(&cs, PeerId::random(), &request(1, 0)).await;
+        assert_eq!(response.status, ChainExchangeResponseStatus::BadRequest);
+    }
+
+    #[async_std::test]
+    async fn caps_request_len() {
+        let db = MemoryDB::default();
+        let reader = BufReader::<&[u8]>::new(EXPORT_SR_40);
+        let cids: Vec<Cid> = load_car(&db, reader).await.unwrap();
+        let cs = ChainStore::new(Arc::new(db));
+        let server = ChainExchangeServer::new(ChainExchangeServerConfig {
+            max_request_len_with_messages: 2,
+            ..Default::default()
+        });
+
+        let response = server
+            .respond(
+                &cs,
+                PeerId::random(),
+                &ChainExchangeRequest {
+                    start: cids.clone(),
+                    request_len: 5,
+                    options: HEADERS | MESSAGES,
+                },
+            )
+            .await;
+        assert_eq!(
+            response.status,
+            ChainExchangeResponseStatus::PartialResponse
+        );
+        assert_eq!(response.chain.len(), 2);
+
+        // Headers only requests have their own limit
+        let response = server
+            .respond(
+                &cs,
+                PeerId::random(),
+                &ChainExchangeRequest {
+                    start: cids,
+                    request_len: 5,
+                    options: HEADERS,
+                },
+            )
+            .await;
+        assert_eq!(response.status, ChainExchangeResponseStatus::Success);
+        assert_eq!(response.chain.len(), 5);
+        assert!(server.cache.size() > 0);
+    }
+
+    #[test]
+    fn limits_concurrent_responses() {
+        let server = ChainExchangeServer::new(ChainExchangeServerConfig {
+            max_concurrent_responses: 1,
+            ..Default::default()
+        });
+        let slot = server.response_slot();
+        assert!(slot.is_some());
+        assert!(server.response_slot().is_none());
+        drop(slot);
+        assert!(server.response_slot().is_some());
+    }
+}
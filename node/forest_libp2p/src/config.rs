@@ -1,33 +1,131 @@
 // Copyright 2019-2022 ChainSafe Systems
 // SPDX-License-Identifier: Apache-2.0, MIT
 
+use crate::chain_exchange::ChainExchangeServerConfig;
+use crate::{BitswapServerConfig, DhtConfig};
+use ipnet::IpNet;
 use libp2p::Multiaddr;
-use serde::{Deserialize, Serialize};
+use serde::{Deserialize, Deserializer, Serialize};
 
 /// Libp2p config for the Forest node.
-#[derive(Serialize, Deserialize, PartialEq)]
+#[derive(Clone, Serialize, Deserialize, PartialEq)]
 #[serde(default)]
 pub struct Libp2pConfig {
-    /// Local address.
-    pub listening_multiaddr: Multiaddr,
+    /// Local addresses to listen on. A single address is accepted for the former
+    /// `listening_multiaddr` option.
+    #[serde(
+        alias = "listening_multiaddr",
+        deserialize_with = "deserialize_listening_multiaddrs"
+    )]
+    pub listening_multiaddrs: Vec<Multiaddr>,
+    /// Addresses advertised to peers in addition to the listening addresses, such as the public
+    /// address of a NAT with forwarded ports.
+    pub announce_addresses: Vec<Multiaddr>,
+    /// Addresses never advertised to peers as external addresses of the node, even when peers
+    /// observed the node at them.
+    pub no_announce_addresses: Vec<Multiaddr>,
     /// Bootstrap peer list.
     pub bootstrap_peers: Vec<Multiaddr>,
+    /// Peers always kept connected, reconnected when dropped and never banned. The addresses
+    /// must end with the `/p2p/` peer ID.
+    pub static_peers: Vec<Multiaddr>,
+    /// Peer IDs refused connections.
+    pub denied_peers: Vec<String>,
+    /// IP ranges refused connections.
+    pub denied_ip_ranges: Vec<IpNet>,
+    /// Maximum number of connections from a single IP address, zero for no limit.
+    pub max_connections_per_ip: usize,
     /// Mdns discovery enabled.
     pub mdns: bool,
+    /// Map the TCP listening ports on the UPnP gateway of the local network and advertise the
+    /// resulting external addresses.
+    pub upnp: bool,
     /// Kademlia discovery enabled.
     pub kademlia: bool,
     /// Target peer count.
     pub target_peer_count: u32,
+    /// GossipSub peer scoring enabled.
+    pub gossipsub_scoring: bool,
+    /// Kademlia DHT parameters.
+    pub dht: DhtConfig,
+    /// Limits on the chain exchange requests served to peers.
+    pub chain_exchange: ChainExchangeServerConfig,
+    /// Limits on the bitswap blocks served to peers.
+    pub bitswap: BitswapServerConfig,
+    /// Transports and stream multiplexers used to connect to peers.
+    pub transport: TransportConfig,
 }
 
 impl Default for Libp2pConfig {
     fn default() -> Self {
         Self {
-            listening_multiaddr: "/ip4/0.0.0.0/tcp/0".parse().unwrap(),
+            listening_multiaddrs: vec!["/ip4/0.0.0.0/tcp/0".parse().unwrap()],
+            announce_addresses: vec![],
+            no_announce_addresses: vec![],
             bootstrap_peers: vec![],
+            static_peers: vec![],
+            denied_peers: vec![],
+            denied_ip_ranges: vec![],
+            max_connections_per_ip: 8,
             mdns: false,
+            upnp: false,
             kademlia: true,
             target_peer_count: 75,
+            gossipsub_scoring: true,
+            dht: Default::default(),
+            chain_exchange: Default::default(),
+            bitswap: Default::default(),
+            transport: Default::default(),
         }
     }
 }
+
+/// Transports and stream multiplexers used to connect to peers. Connections are made over TCP,
+/// optionally wrapped in websockets, and multiplexed with yamux, falling back to mplex. Nodes
+/// running in the same process can also connect in memory, over `/memory/<port>` addresses.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(default)]
+pub struct TransportConfig {
+    /// Websocket transport enabled.
+    pub websocket: bool,
+    /// Mplex stream multiplexer enabled.
+    pub mplex: bool,
+    /// Yamux receive window size in bytes.
+    pub yamux_receive_window_size: u32,
+    /// Yamux maximum buffer size in bytes.
+    pub yamux_max_buffer_size: usize,
+    /// Timeout in seconds for setting up a connection, including the protocol upgrades.
+    pub connection_timeout_secs: u64,
+    /// In-memory transport enabled, for nodes running in the same process.
+    pub memory: bool,
+}
+
+impl Default for TransportConfig {
+    fn default() -> Self {
+        Self {
+            websocket: true,
+            mplex: true,
+            yamux_receive_window_size: 16 * 1024 * 1024,
+            yamux_max_buffer_size: 16 * 1024 * 1024,
+            connection_timeout_secs: 20,
+            memory: false,
+        }
+    }
+}
+
+fn deserialize_listening_multiaddrs<'de, D>(deserializer: D) -> Result<Vec<Multiaddr>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum OneOrMany {
+        One(Multiaddr),
+        Many(Vec<Multiaddr>),
+    }
+
+    Ok(match OneOrMany::deserialize(deserializer)? {
+        OneOrMany::One(addr) => vec![addr],
+        OneOrMany::Many(addrs) => addrs,
+    })
+}
@@ -1,8 +1,10 @@
 // Copyright 2019-2022 ChainSafe Systems
 // SPDX-License-Identifier: Apache-2.0, MIT
 
+use crate::metrics::{self, values};
 use async_std::stream::{self, Interval};
 use async_std::task;
+use futures::channel::oneshot::Sender as OneShotSender;
 use futures::prelude::*;
 use libp2p::swarm::DialError;
 use libp2p::{
@@ -10,7 +12,10 @@ use libp2p::{
         connection::{ConnectionId, ListenerId},
         ConnectedPoint, Multiaddr, PeerId, PublicKey,
     },
-    kad::{handler::KademliaHandlerProto, Kademlia, KademliaConfig, KademliaEvent, QueryId},
+    kad::{
+        handler::KademliaHandlerProto, Kademlia, KademliaConfig, KademliaEvent, QueryId,
+        QueryResult,
+    },
     mdns::MdnsEvent,
     multiaddr::Protocol,
     swarm::{
@@ -21,11 +26,13 @@ use libp2p::{
 };
 use libp2p::{kad::record::store::MemoryStore, mdns::Mdns};
 use log::{debug, error, trace, warn};
+use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::{
     cmp,
     collections::{HashSet, VecDeque},
     io,
+    num::NonZeroUsize,
     task::{Context, Poll},
     time::Duration,
 };
@@ -40,6 +47,38 @@ pub enum DiscoveryOut {
     Disconnected(PeerId),
 }
 
+/// Kademlia DHT parameters used for peer discovery.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(default)]
+pub struct DhtConfig {
+    /// Protocol name of the DHT, `/fil/kad/<network name>/kad/1.0.0` if empty.
+    pub protocol_name: String,
+    /// Number of closest peers looked for by queries and records are replicated to.
+    pub replication_factor: usize,
+    /// Number of peers a query contacts concurrently.
+    pub parallelism: usize,
+    /// Timeout of a query in seconds.
+    pub query_timeout_secs: u64,
+    /// Delay in seconds between the first random walks looking for peers. The delay doubles
+    /// after each walk until `target_peer_count` is reached.
+    pub random_walk_min_interval_secs: u64,
+    /// Maximum delay in seconds between random walks.
+    pub random_walk_max_interval_secs: u64,
+}
+
+impl Default for DhtConfig {
+    fn default() -> Self {
+        Self {
+            protocol_name: String::new(),
+            replication_factor: 20,
+            parallelism: 3,
+            query_timeout_secs: 60,
+            random_walk_min_interval_secs: 1,
+            random_walk_max_interval_secs: 60,
+        }
+    }
+}
+
 /// `DiscoveryBehaviour` configuration.
 ///
 /// Note: In order to discover nodes or load and store values via Kademlia one has to add at least
@@ -50,6 +89,7 @@ pub struct DiscoveryConfig<'a> {
     discovery_max: u64,
     enable_mdns: bool,
     enable_kademlia: bool,
+    dht: DhtConfig,
     network_name: &'a str,
 }
 
@@ -62,6 +102,7 @@ impl<'a> DiscoveryConfig<'a> {
             discovery_max: std::u64::MAX,
             enable_mdns: false,
             enable_kademlia: true,
+            dht: Default::default(),
             network_name,
         }
     }
@@ -93,6 +134,12 @@ impl<'a> DiscoveryConfig<'a> {
         self
     }
 
+    /// Sets the Kademlia DHT parameters.
+    pub fn with_dht(&mut self, dht: DhtConfig) -> &mut Self {
+        self.dht = dht;
+        self
+    }
+
     /// Create a `DiscoveryBehaviour` from this config.
     pub fn finish(self) -> DiscoveryBehaviour {
         let DiscoveryConfig {
@@ -101,6 +148,7 @@ impl<'a> DiscoveryConfig<'a> {
             discovery_max,
             enable_mdns,
             enable_kademlia,
+            dht,
             network_name,
         } = self;
 
@@ -110,8 +158,25 @@ impl<'a> DiscoveryConfig<'a> {
         // Kademlia config
         let store = MemoryStore::new(local_peer_id.to_owned());
         let mut kad_config = KademliaConfig::default();
-        let network = format!("/fil/kad/{}/kad/1.0.0", network_name);
-        kad_config.set_protocol_name(network.as_bytes().to_vec());
+        let protocol_name = if dht.protocol_name.is_empty() {
+            format!("/fil/kad/{}/kad/1.0.0", network_name)
+        } else {
+            dht.protocol_name
+        };
+        kad_config.set_protocol_name(protocol_name.into_bytes());
+        if let Some(replication_factor) = NonZeroUsize::new(dht.replication_factor) {
+            kad_config.set_replication_factor(replication_factor);
+        }
+        if let Some(parallelism) = NonZeroUsize::new(dht.parallelism) {
+            kad_config.set_parallelism(parallelism);
+        }
+        kad_config.set_query_timeout(Duration::from_secs(dht.query_timeout_secs));
+        // A zero delay would keep the walks firing while discovery is paused
+        let random_walk_min = Duration::from_secs(cmp::max(dht.random_walk_min_interval_secs, 1));
+        let random_walk_max = cmp::max(
+            Duration::from_secs(dht.random_walk_max_interval_secs),
+            random_walk_min,
+        );
 
         // TODO this parsing should probably be done when parsing config, not initializing node
         let user_defined: Vec<(PeerId, Multiaddr)> = user_defined
@@ -156,7 +221,10 @@ impl<'a> DiscoveryConfig<'a> {
             user_defined,
             kademlia: kademlia_opt.into(),
             next_kad_random_query: stream::interval(Duration::new(0, 0)),
-            duration_to_next_kad: Duration::from_secs(1),
+            duration_to_next_kad: random_walk_min,
+            random_walk_min,
+            random_walk_max,
+            find_peer_queries: HashMap::new(),
             pending_events: VecDeque::new(),
             num_connections: 0,
             mdns: mdns_opt.into(),
@@ -180,6 +248,13 @@ pub struct DiscoveryBehaviour {
     next_kad_random_query: Interval,
     /// After `next_kad_random_query` triggers, the next one triggers after this duration.
     duration_to_next_kad: Duration,
+    /// Delay between random Kademlia queries when discovery starts or resumes.
+    random_walk_min: Duration,
+    /// Maximum delay between random Kademlia queries.
+    random_walk_max: Duration,
+    /// Pending `FindPeer` queries with the peer looked for and the channel to send its addresses
+    /// to.
+    find_peer_queries: HashMap<QueryId, (PeerId, OneShotSender<Result<Vec<Multiaddr>, String>>)>,
     /// Events to return in priority when polled.
     pending_events: VecDeque<DiscoveryOut>,
     /// Number of nodes we're currently connected to.
@@ -211,6 +286,76 @@ impl DiscoveryBehaviour {
             Err("Kademlia is not activated".to_string())
         }
     }
+
+    /// Looks up the addresses of a peer, from the connected peers or with a Kademlia query, and
+    /// sends them to the channel.
+    pub fn find_peer(
+        &mut self,
+        peer_id: PeerId,
+        response_channel: OneShotSender<Result<Vec<Multiaddr>, String>>,
+    ) {
+        if self.peers.contains(&peer_id) {
+            if let Some(addrs) = self.peer_addresses.get(&peer_id) {
+                if !addrs.is_empty() {
+                    let _ = response_channel.send(Ok(addrs.clone()));
+                    return;
+                }
+            }
+        }
+        match self.kademlia.as_mut() {
+            Some(kademlia) => {
+                let query_id = kademlia.get_closest_peers(peer_id);
+                self.find_peer_queries
+                    .insert(query_id, (peer_id, response_channel));
+            }
+            None => {
+                let _ = response_channel.send(Err("Kademlia is not activated".to_string()));
+            }
+        }
+    }
+
+    fn query_completed(&mut self, query_id: QueryId, result: QueryResult) {
+        match result {
+            QueryResult::GetClosestPeers(result) => {
+                let (peer_id, response_channel) = match self.find_peer_queries.remove(&query_id) {
+                    Some(query) => query,
+                    None => {
+                        record_query(values::RANDOM_WALK, result.is_ok());
+                        return;
+                    }
+                };
+                record_query(values::FIND_PEER, result.is_ok());
+                let addrs = self.addresses_of_peer(&peer_id);
+                let response = if addrs.is_empty() {
+                    Err(format!("Peer {} not found", peer_id))
+                } else {
+                    Ok(addrs)
+                };
+                let _ = response_channel.send(response);
+            }
+            QueryResult::Bootstrap(result) => record_query(values::BOOTSTRAP, result.is_ok()),
+            other => debug!("Libp2p => Unhandled Kademlia query result: {:?}", other),
+        }
+        self.update_routing_table_size();
+    }
+
+    fn update_routing_table_size(&mut self) {
+        if let Some(kademlia) = self.kademlia.as_mut() {
+            let size: usize = kademlia.kbuckets().map(|bucket| bucket.num_entries()).sum();
+            metrics::KADEMLIA_ROUTING_TABLE_SIZE.set(size as u64);
+        }
+    }
+}
+
+fn record_query(kind: &str, success: bool) {
+    let result = if success {
+        values::SUCCESS
+    } else {
+        values::TIMEOUT
+    };
+    metrics::KADEMLIA_QUERY_TOTAL
+        .with_label_values(&[kind, result])
+        .inc();
 }
 
 impl NetworkBehaviour for DiscoveryBehaviour {
@@ -352,13 +497,18 @@ impl NetworkBehaviour for DiscoveryBehaviour {
                 if let Some(k) = self.kademlia.as_mut() {
                     k.get_closest_peers(random_peer_id);
                 }
-            }
 
-            // Schedule the next random query with exponentially increasing delay,
-            // capped at 60 seconds.
-            self.next_kad_random_query = stream::interval(self.duration_to_next_kad);
-            self.duration_to_next_kad =
-                cmp::min(self.duration_to_next_kad * 2, Duration::from_secs(60));
+                // Schedule the next random query with exponentially increasing delay,
+                // capped at the maximum interval.
+                self.next_kad_random_query = stream::interval(self.duration_to_next_kad);
+                self.duration_to_next_kad =
+                    cmp::min(self.duration_to_next_kad * 2, self.random_walk_max);
+            } else {
+                // Random queries resume at the shortest delay once connections drop below
+                // the discovery max.
+                self.duration_to_next_kad = self.random_walk_min;
+                self.next_kad_random_query = stream::interval(self.duration_to_next_kad);
+            }
         }
 
         // Poll Kademlia.
@@ -367,7 +517,10 @@ impl NetworkBehaviour for DiscoveryBehaviour {
                 NetworkBehaviourAction::GenerateEvent(ev) => match ev {
                     // Adding to Kademlia buckets is automatic with our config,
                     // no need to do manually.
-                    KademliaEvent::RoutingUpdated { .. } => {}
+                    KademliaEvent::RoutingUpdated { .. } => self.update_routing_table_size(),
+                    KademliaEvent::OutboundQueryCompleted { id, result, .. } => {
+                        self.query_completed(id, result)
+                    }
                     KademliaEvent::RoutablePeer { .. } => {}
                     KademliaEvent::PendingRoutablePeer { .. } => {
                         // Intentionally ignore
@@ -0,0 +1,293 @@
+// Copyright 2019-2022 ChainSafe Systems
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+use db::Store;
+use forest_encoding::tuple::*;
+use forest_encoding::{from_slice, to_vec};
+use ipnet::IpNet;
+use libp2p::core::{multiaddr::Protocol, Multiaddr, PeerId};
+use std::collections::{HashMap, HashSet};
+use std::net::IpAddr;
+use std::str::FromStr;
+use std::sync::{Mutex, RwLock};
+
+/// Key under which the block list is persisted in the database.
+const BLOCK_LIST_KEY: &str = "net_block_list";
+
+/// Peers, IP addresses and IP subnets refused connections.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct BlockList {
+    pub peers: HashSet<PeerId>,
+    pub ip_addrs: HashSet<IpAddr>,
+    pub ip_subnets: HashSet<IpNet>,
+}
+
+/// Encoded form of a block list.
+#[derive(Default, Serialize_tuple, Deserialize_tuple)]
+struct StoredBlockList {
+    peers: Vec<String>,
+    ip_addrs: Vec<String>,
+    ip_subnets: Vec<String>,
+}
+
+impl BlockList {
+    /// Parses a block list from the string forms of its peer IDs, IP addresses and subnets.
+    pub fn parse(
+        peers: &[String],
+        ip_addrs: &[String],
+        ip_subnets: &[String],
+    ) -> Result<Self, anyhow::Error> {
+        Ok(Self {
+            peers: parse_all(peers)?,
+            ip_addrs: parse_all(ip_addrs)?,
+            ip_subnets: parse_all(ip_subnets)?,
+        })
+    }
+
+    /// Loads the block list persisted in the database.
+    pub fn load<DB: Store>(db: &DB) -> Result<Self, anyhow::Error> {
+        let stored: StoredBlockList = match db.read(BLOCK_LIST_KEY)? {
+            Some(bytes) => from_slice(&bytes)?,
+            None => return Ok(Self::default()),
+        };
+        Self::parse(&stored.peers, &stored.ip_addrs, &stored.ip_subnets)
+    }
+
+    /// Persists the block list in the database.
+    pub fn save<DB: Store>(&self, db: &DB) -> Result<(), anyhow::Error> {
+        let stored = StoredBlockList {
+            peers: self.peers.iter().map(PeerId::to_string).collect(),
+            ip_addrs: self.ip_addrs.iter().map(IpAddr::to_string).collect(),
+            ip_subnets: self.ip_subnets.iter().map(IpNet::to_string).collect(),
+        };
+        Ok(db.write(BLOCK_LIST_KEY, to_vec(&stored)?)?)
+    }
+
+    /// Adds the entries of another block list to this one.
+    pub fn extend(&mut self, other: BlockList) {
+        self.peers.extend(other.peers);
+        self.ip_addrs.extend(other.ip_addrs);
+        self.ip_subnets.extend(other.ip_subnets);
+    }
+
+    /// Removes the entries of another block list from this one.
+    pub fn remove(&mut self, other: &BlockList) {
+        self.peers.retain(|peer| !other.peers.contains(peer));
+        self.ip_addrs.retain(|ip| !other.ip_addrs.contains(ip));
+        self.ip_subnets
+            .retain(|subnet| !other.ip_subnets.contains(subnet));
+    }
+
+    pub fn blocks_peer(&self, peer_id: &PeerId) -> bool {
+        self.peers.contains(peer_id)
+    }
+
+    pub fn blocks_ip(&self, ip: &IpAddr) -> bool {
+        self.ip_addrs.contains(ip) || self.ip_subnets.iter().any(|subnet| subnet.contains(ip))
+    }
+}
+
+fn parse_all<T>(values: &[String]) -> Result<HashSet<T>, anyhow::Error>
+where
+    T: FromStr + Eq + std::hash::Hash,
+    T::Err: std::fmt::Display,
+{
+    values
+        .iter()
+        .map(|value| {
+            value
+                .parse()
+                .map_err(|e| anyhow::anyhow!("Invalid block list entry {}: {}", value, e))
+        })
+        .collect()
+}
+
+/// Returns the IP address of a multiaddress, if it has one.
+pub(crate) fn multiaddr_ip(addr: &Multiaddr) -> Option<IpAddr> {
+    addr.iter().find_map(|protocol| match protocol {
+        Protocol::Ip4(ip) => Some(IpAddr::V4(ip)),
+        Protocol::Ip6(ip) => Some(IpAddr::V6(ip)),
+        _ => None,
+    })
+}
+
+/// Decides which connections are allowed, from the denylist of the configuration, the block
+/// list managed at runtime and the number of connections open from each IP address. Static peers
+/// are exempt from the per IP limit.
+pub(crate) struct ConnectionGater {
+    /// Denylist of the configuration, which can't be changed at runtime.
+    denied: BlockList,
+    /// Block list managed over RPC and persisted in the database.
+    blocked: RwLock<BlockList>,
+    static_peers: HashSet<PeerId>,
+    /// Maximum number of connections from a single IP address, zero for no limit.
+    max_connections_per_ip: usize,
+    /// IP addresses of the open connections, by peer.
+    connections: Mutex<HashMap<PeerId, Vec<IpAddr>>>,
+}
+
+impl ConnectionGater {
+    pub fn new(
+        denied: BlockList,
+        blocked: BlockList,
+        static_peers: HashSet<PeerId>,
+        max_connections_per_ip: usize,
+    ) -> Self {
+        Self {
+            denied,
+            blocked: RwLock::new(blocked),
+            static_peers,
+            max_connections_per_ip,
+            connections: Default::default(),
+        }
+    }
+
+    /// Checks whether a new connection to a peer at the given address is allowed.
+    pub fn check_connection(&self, peer_id: &PeerId, addr: &Multiaddr) -> Result<(), String> {
+        let blocked = self.blocked.read().unwrap();
+        if self.denied.blocks_peer(peer_id) || blocked.blocks_peer(peer_id) {
+            return Err(format!("Peer {} is blocked", peer_id));
+        }
+        let ip = match multiaddr_ip(addr) {
+            Some(ip) => ip,
+            None => return Ok(()),
+        };
+        if self.denied.blocks_ip(&ip) || blocked.blocks_ip(&ip) {
+            return Err(format!("IP address {} is blocked", ip));
+        }
+        if self.max_connections_per_ip > 0 && !self.static_peers.contains(peer_id) {
+            let open = self
+                .connections
+                .lock()
+                .unwrap()
+                .values()
+                .flatten()
+                .filter(|conn_ip| **conn_ip == ip)
+                .count();
+            if open >= self.max_connections_per_ip {
+                return Err(format!("Too many connections from {}", ip));
+            }
+        }
+        Ok(())
+    }
+
+    pub fn connection_established(&self, peer_id: PeerId, addr: &Multiaddr) {
+        if let Some(ip) = multiaddr_ip(addr) {
+            let mut connections = self.connections.lock().unwrap();
+            connections.entry(peer_id).or_default().push(ip);
+        }
+    }
+
+    pub fn connection_closed(&self, peer_id: &PeerId, addr: &Multiaddr) {
+        if let Some(ip) = multiaddr_ip(addr) {
+            let mut connections = self.connections.lock().unwrap();
+            if let Some(ips) = connections.get_mut(peer_id) {
+                if let Some(i) = ips.iter().position(|conn_ip| *conn_ip == ip) {
+                    ips.swap_remove(i);
+                }
+                if ips.is_empty() {
+                    connections.remove(peer_id);
+                }
+            }
+        }
+    }
+
+    pub fn is_static_peer(&self, peer_id: &PeerId) -> bool {
+        self.static_peers.contains(peer_id)
+    }
+
+    /// Returns the block list managed at runtime.
+    pub fn block_list(&self) -> BlockList {
+        self.blocked.read().unwrap().clone()
+    }
+
+    /// Adds entries to the runtime block list, returning the connected peers that are now
+    /// blocked.
+    pub fn block(&self, entries: BlockList) -> Vec<PeerId> {
+        let mut blocked = self.blocked.write().unwrap();
+        blocked.extend(entries);
+        self.connections
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|(peer_id, ips)| {
+                blocked.blocks_peer(peer_id) || ips.iter().any(|ip| blocked.blocks_ip(ip))
+            })
+            .map(|(peer_id, _)| *peer_id)
+            .collect()
+    }
+
+    /// Removes entries from the runtime block list.
+    pub fn unblock(&self, entries: &BlockList) {
+        self.blocked.write().unwrap().remove(entries);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use db::MemoryDB;
+
+    fn addr(ip: &str) -> Multiaddr {
+        format!("/ip4/{}/tcp/1234", ip).parse().unwrap()
+    }
+
+    #[test]
+    fn persists_block_list() {
+        let db = MemoryDB::default();
+        let block_list = BlockList::parse(
+            &[PeerId::random().to_string()],
+            &["10.0.0.1".to_owned()],
+            &["192.168.0.0/16".to_owned()],
+        )
+        .unwrap();
+        block_list.save(&db).unwrap();
+        assert_eq!(BlockList::load(&db).unwrap(), block_list);
+
+        assert!(BlockList::parse(&["not a peer".to_owned()], &[], &[]).is_err());
+    }
+
+    #[test]
+    fn gates_connections() {
+        let (denied_peer, static_peer) = (PeerId::random(), PeerId::random());
+        let denied = BlockList::parse(&[denied_peer.to_string()], &[], &[]).unwrap();
+        let gater = ConnectionGater::new(
+            denied,
+            BlockList::default(),
+            [static_peer].into_iter().collect(),
+            1,
+        );
+
+        assert!(gater
+            .check_connection(&denied_peer, &addr("10.0.0.1"))
+            .is_err());
+        let peer = PeerId::random();
+        assert!(gater.check_connection(&peer, &addr("10.0.0.1")).is_ok());
+
+        // Only one connection per IP address, except for static peers
+        gater.connection_established(peer, &addr("10.0.0.1"));
+        assert!(gater
+            .check_connection(&PeerId::random(), &addr("10.0.0.1"))
+            .is_err());
+        assert!(gater
+            .check_connection(&static_peer, &addr("10.0.0.1"))
+            .is_ok());
+        gater.connection_closed(&peer, &addr("10.0.0.1"));
+        assert!(gater
+            .check_connection(&PeerId::random(), &addr("10.0.0.1"))
+            .is_ok());
+
+        // Blocking a subnet disconnects the peers connected from it
+        gater.connection_established(peer, &addr("10.0.0.1"));
+        let entries = BlockList::parse(&[], &[], &["10.0.0.0/8".to_owned()]).unwrap();
+        assert_eq!(gater.block(entries.clone()), vec![peer]);
+        assert!(gater
+            .check_connection(&static_peer, &addr("10.1.2.3"))
+            .is_err());
+        gater.unblock(&entries);
+        assert!(gater
+            .check_connection(&static_peer, &addr("10.1.2.3"))
+            .is_ok());
+        assert_eq!(gater.block_list(), BlockList::default());
+    }
+}
@@ -8,11 +8,9 @@ use libp2p::gossipsub::{
 use std::{collections::HashMap, time::Duration};
 
 // All these parameters are copied from what Lotus has set for their Topic scores.
-// They are currently unused because enabling them causes GossipSub blocks to come
-// delayed usually by 1 second compared to when we have these parameters disabled.
-// Leaving these here so that we can enable and fix these parameters when they are needed.
+// Peer scoring is enabled with `Libp2pConfig::gossipsub_scoring`, the application specific
+// part of the score is set by the node from how peers serve requests and the blocks they relay.
 
-#[allow(dead_code)]
 fn build_msg_topic_config() -> TopicScoreParams {
     TopicScoreParams {
         // expected 10 blocks/min
@@ -45,7 +43,6 @@ fn build_msg_topic_config() -> TopicScoreParams {
     }
 }
 
-#[allow(dead_code)]
 fn build_block_topic_config() -> TopicScoreParams {
     TopicScoreParams {
         topic_weight: 0.1,
@@ -77,7 +74,6 @@ fn build_block_topic_config() -> TopicScoreParams {
     }
 }
 
-#[allow(dead_code)]
 pub(crate) fn build_peer_score_params(network_name: &str) -> PeerScoreParams {
     let mut psp_topics = HashMap::new();
 
@@ -109,7 +105,6 @@ pub(crate) fn build_peer_score_params(network_name: &str) -> PeerScoreParams {
     }
 }
 
-#[allow(dead_code)]
 pub(crate) fn build_peer_score_threshold() -> PeerScoreThresholds {
     PeerScoreThresholds {
         gossip_threshold: -500.0,
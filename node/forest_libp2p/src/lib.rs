@@ -6,17 +6,32 @@
 #[macro_use]
 extern crate lazy_static;
 
+mod bandwidth;
 mod behaviour;
+mod bitswap;
 pub mod chain_exchange;
 mod config;
 mod discovery;
+mod gater;
 mod gossip_params;
 pub mod hello;
+mod metrics;
+mod nat;
+mod peer_info;
+mod peer_store;
 pub mod rpc;
 mod service;
+mod upnp;
 
+pub use self::bandwidth::{BandwidthMeter, BandwidthStats};
 pub(crate) use self::behaviour::*;
+pub use self::bitswap::BitswapServerConfig;
 pub use self::config::*;
+pub use self::discovery::DhtConfig;
+pub use self::gater::BlockList;
+pub use self::nat::{NatStatus, Reachability};
+pub use self::peer_info::PeerInfo;
+pub use self::peer_store::*;
 pub use self::service::*;
 
 // Re-export some libp2p types
@@ -0,0 +1,237 @@
+// Copyright 2019-2022 ChainSafe Systems
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+use prometheus::core::{AtomicU64, GenericCounter, GenericCounterVec, GenericGauge, Opts};
+
+lazy_static! {
+    pub static ref CHAIN_EXCHANGE_REQUEST_TOTAL: Box<GenericCounterVec<AtomicU64>> = {
+        let chain_exchange_request_total = Box::new(
+            GenericCounterVec::<AtomicU64>::new(
+                Opts::new(
+                    "chain_exchange_request_total",
+                    "Total number of chain exchange requests served to peers by result",
+                ),
+                &[labels::RESULT],
+            )
+            .expect("Defining the chain_exchange_request_total metric must succeed"),
+        );
+        prometheus::default_registry()
+            .register(chain_exchange_request_total.clone())
+            .expect(
+                "Registering the chain_exchange_request_total metric with the metrics registry must succeed",
+            );
+        chain_exchange_request_total
+    };
+    pub static ref CHAIN_EXCHANGE_TIPSETS_SERVED_TOTAL: Box<GenericCounter<AtomicU64>> = {
+        let chain_exchange_tipsets_served_total = Box::new(
+            GenericCounter::<AtomicU64>::new(
+                "chain_exchange_tipsets_served_total",
+                "Total number of tipsets served to peers over chain exchange",
+            )
+            .expect("Defining the chain_exchange_tipsets_served_total metric must succeed"),
+        );
+        prometheus::default_registry()
+            .register(chain_exchange_tipsets_served_total.clone())
+            .expect(
+                "Registering the chain_exchange_tipsets_served_total metric with the metrics registry must succeed",
+            );
+        chain_exchange_tipsets_served_total
+    };
+    pub static ref CHAIN_EXCHANGE_CACHE_TOTAL: Box<GenericCounterVec<AtomicU64>> = {
+        let chain_exchange_cache_total = Box::new(
+            GenericCounterVec::<AtomicU64>::new(
+                Opts::new(
+                    "chain_exchange_cache_total",
+                    "Total number of chain exchange cache lookups by result",
+                ),
+                &[labels::RESULT],
+            )
+            .expect("Defining the chain_exchange_cache_total metric must succeed"),
+        );
+        prometheus::default_registry()
+            .register(chain_exchange_cache_total.clone())
+            .expect(
+                "Registering the chain_exchange_cache_total metric with the metrics registry must succeed",
+            );
+        chain_exchange_cache_total
+    };
+    pub static ref GOSSIP_VALIDATION_TOTAL: Box<GenericCounterVec<AtomicU64>> = {
+        let gossip_validation_total = Box::new(
+            GenericCounterVec::<AtomicU64>::new(
+                Opts::new(
+                    "gossip_validation_total",
+                    "Total number of GossipSub messages validated by result",
+                ),
+                &[labels::RESULT],
+            )
+            .expect("Defining the gossip_validation_total metric must succeed"),
+        );
+        prometheus::default_registry()
+            .register(gossip_validation_total.clone())
+            .expect(
+                "Registering the gossip_validation_total metric with the metrics registry must succeed",
+            );
+        gossip_validation_total
+    };
+    pub static ref BITSWAP_WANT_TOTAL: Box<GenericCounterVec<AtomicU64>> = {
+        let bitswap_want_total = Box::new(
+            GenericCounterVec::<AtomicU64>::new(
+                Opts::new(
+                    "bitswap_want_total",
+                    "Total number of bitswap wants received from peers by result",
+                ),
+                &[labels::RESULT],
+            )
+            .expect("Defining the bitswap_want_total metric must succeed"),
+        );
+        prometheus::default_registry()
+            .register(bitswap_want_total.clone())
+            .expect(
+                "Registering the bitswap_want_total metric with the metrics registry must succeed",
+            );
+        bitswap_want_total
+    };
+    pub static ref BITSWAP_BYTES_SERVED_TOTAL: Box<GenericCounter<AtomicU64>> = {
+        let bitswap_bytes_served_total = Box::new(
+            GenericCounter::<AtomicU64>::new(
+                "bitswap_bytes_served_total",
+                "Total number of bytes of blocks served to peers over bitswap",
+            )
+            .expect("Defining the bitswap_bytes_served_total metric must succeed"),
+        );
+        prometheus::default_registry()
+            .register(bitswap_bytes_served_total.clone())
+            .expect(
+                "Registering the bitswap_bytes_served_total metric with the metrics registry must succeed",
+            );
+        bitswap_bytes_served_total
+    };
+    pub static ref NETWORK_BYTES_TOTAL: Box<GenericCounterVec<AtomicU64>> = {
+        let network_bytes_total = Box::new(
+            GenericCounterVec::<AtomicU64>::new(
+                Opts::new(
+                    "network_bytes_total",
+                    "Total number of bytes transferred over the connections to peers by direction",
+                ),
+                &[labels::DIRECTION],
+            )
+            .expect("Defining the network_bytes_total metric must succeed"),
+        );
+        prometheus::default_registry()
+            .register(network_bytes_total.clone())
+            .expect(
+                "Registering the network_bytes_total metric with the metrics registry must succeed",
+            );
+        network_bytes_total
+    };
+    pub static ref NETWORK_PROTOCOL_BYTES_TOTAL: Box<GenericCounterVec<AtomicU64>> = {
+        let network_protocol_bytes_total = Box::new(
+            GenericCounterVec::<AtomicU64>::new(
+                Opts::new(
+                    "network_protocol_bytes_total",
+                    "Total number of bytes exchanged with peers by protocol and direction",
+                ),
+                &[labels::PROTOCOL, labels::DIRECTION],
+            )
+            .expect("Defining the network_protocol_bytes_total metric must succeed"),
+        );
+        prometheus::default_registry()
+            .register(network_protocol_bytes_total.clone())
+            .expect(
+                "Registering the network_protocol_bytes_total metric with the metrics registry must succeed",
+            );
+        network_protocol_bytes_total
+    };
+    pub static ref KADEMLIA_ROUTING_TABLE_SIZE: Box<GenericGauge<AtomicU64>> = {
+        let kademlia_routing_table_size = Box::new(
+            GenericGauge::<AtomicU64>::new(
+                "kademlia_routing_table_size",
+                "Number of peers in the Kademlia routing table",
+            )
+            .expect("Defining the kademlia_routing_table_size metric must succeed"),
+        );
+        prometheus::default_registry()
+            .register(kademlia_routing_table_size.clone())
+            .expect(
+                "Registering the kademlia_routing_table_size metric with the metrics registry must succeed",
+            );
+        kademlia_routing_table_size
+    };
+    pub static ref KADEMLIA_QUERY_TOTAL: Box<GenericCounterVec<AtomicU64>> = {
+        let kademlia_query_total = Box::new(
+            GenericCounterVec::<AtomicU64>::new(
+                Opts::new(
+                    "kademlia_query_total",
+                    "Total number of Kademlia queries completed by kind and result",
+                ),
+                &[labels::KIND, labels::RESULT],
+            )
+            .expect("Defining the kademlia_query_total metric must succeed"),
+        );
+        prometheus::default_registry()
+            .register(kademlia_query_total.clone())
+            .expect(
+                "Registering the kademlia_query_total metric with the metrics registry must succeed",
+            );
+        kademlia_query_total
+    };
+}
+
+pub mod labels {
+    pub const RESULT: &str = "result";
+    pub const DIRECTION: &str = "direction";
+    pub const PROTOCOL: &str = "protocol";
+    pub const KIND: &str = "kind";
+}
+
+pub mod values {
+    // chain_exchange_request_total
+    pub const SUCCESS: &str = "success";
+    pub const PARTIAL: &str = "partial";
+    pub const ERROR: &str = "error";
+    pub const BAD_REQUEST: &str = "bad_request";
+    pub const RATE_LIMITED: &str = "rate_limited";
+    pub const BUSY: &str = "busy";
+
+    // chain_exchange_cache_total
+    pub const HIT: &str = "hit";
+    pub const MISS: &str = "miss";
+
+    // gossip_validation_total
+    pub const ACCEPT: &str = "accept";
+    pub const REJECT: &str = "reject";
+    pub const IGNORE: &str = "ignore";
+
+    // bitswap_want_total
+    pub const NOT_FOUND: &str = "not_found";
+
+    // network_bytes_total, network_protocol_bytes_total
+    pub const INBOUND: &str = "inbound";
+    pub const OUTBOUND: &str = "outbound";
+
+    // kademlia_query_total
+    pub const RANDOM_WALK: &str = "random_walk";
+    pub const FIND_PEER: &str = "find_peer";
+    pub const BOOTSTRAP: &str = "bootstrap";
+    pub const TIMEOUT: &str = "timeout";
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn metrics_defined_and_registered() {
+        let _ = CHAIN_EXCHANGE_REQUEST_TOTAL.with_label_values(&[values::SUCCESS]);
+        let _ = CHAIN_EXCHANGE_TIPSETS_SERVED_TOTAL.get();
+        let _ = CHAIN_EXCHANGE_CACHE_TOTAL.with_label_values(&[values::HIT]);
+        let _ = GOSSIP_VALIDATION_TOTAL.with_label_values(&[values::ACCEPT]);
+        let _ = BITSWAP_WANT_TOTAL.with_label_values(&[values::SUCCESS]);
+        let _ = BITSWAP_BYTES_SERVED_TOTAL.get();
+        let _ = NETWORK_BYTES_TOTAL.with_label_values(&[values::INBOUND]);
+        let _ =
+            NETWORK_PROTOCOL_BYTES_TOTAL.with_label_values(&["/fil/hello/1.0.0", values::OUTBOUND]);
+        let _ = KADEMLIA_ROUTING_TABLE_SIZE.get();
+        let _ = KADEMLIA_QUERY_TOTAL.with_label_values(&[values::RANDOM_WALK, values::SUCCESS]);
+    }
+}
@@ -0,0 +1,280 @@
+// Copyright 2019-2022 ChainSafe Systems
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+use crate::gater::multiaddr_ip;
+use libp2p::core::Multiaddr;
+use libp2p::PeerId;
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::time::{Duration, Instant};
+
+/// Number of outbound connections without any inbound connection from a public address after
+/// which the node is considered private.
+const PRIVATE_OUTBOUND_THRESHOLD: usize = 16;
+
+/// Time after the last inbound connection from a public address for which the node is still
+/// considered public.
+const PUBLIC_TTL: Duration = Duration::from_secs(60 * 60);
+
+/// Number of peers that must have observed an address before it is reported as public.
+const OBSERVED_ADDR_CONFIRMATIONS: usize = 2;
+
+/// Time after which an observation of the node's address by a peer is forgotten.
+const OBSERVED_ADDR_TTL: Duration = Duration::from_secs(60 * 60);
+
+/// Maximum number of observed addresses tracked at once. The least recently observed address is
+/// dropped when a new one doesn't fit.
+const MAX_OBSERVED_ADDRS: usize = 32;
+
+/// Whether the node can be dialed by other peers.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Reachability {
+    Unknown,
+    Public,
+    Private,
+}
+
+/// Reachability of the node along with the address peers can dial it at, if known.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct NatStatus {
+    pub reachability: Reachability,
+    pub public_addr: Option<Multiaddr>,
+}
+
+/// Tracks the reachability of the node from the connections peers open to it. The node is
+/// public while peers at public IP addresses dial it, and private once it made enough outbound
+/// connections without any such peer dialing it.
+pub(crate) struct ReachabilityTracker {
+    /// Addresses announced in the configuration, which take precedence over observed ones.
+    announce_addrs: Vec<Multiaddr>,
+    /// External address mapped on the UPnP gateway, preferred over observed addresses.
+    mapped_addr: Option<Multiaddr>,
+    last_public_inbound: Option<Instant>,
+    /// Outbound connections since the last inbound connection from a public address.
+    outbound_since_inbound: usize,
+    /// Peers that observed the node at each address, with the time of their last observation.
+    /// Only the latest address observed by each peer is kept.
+    observed_addrs: HashMap<Multiaddr, HashMap<PeerId, Instant>>,
+}
+
+impl ReachabilityTracker {
+    pub fn new(announce_addrs: Vec<Multiaddr>) -> Self {
+        Self {
+            announce_addrs,
+            mapped_addr: None,
+            last_public_inbound: None,
+            outbound_since_inbound: 0,
+            observed_addrs: Default::default(),
+        }
+    }
+
+    /// Records a connection opened by a peer at the given address.
+    pub fn inbound_connection(&mut self, remote_addr: &Multiaddr) {
+        if multiaddr_ip(remote_addr).map_or(false, |ip| is_public_ip(&ip)) {
+            self.last_public_inbound = Some(Instant::now());
+            self.outbound_since_inbound = 0;
+        }
+    }
+
+    /// Records a connection opened by the node.
+    pub fn outbound_connection(&mut self) {
+        self.outbound_since_inbound += 1;
+    }
+
+    /// Records the address a peer observed the node at.
+    pub fn observed_addr(&mut self, peer_id: PeerId, addr: Multiaddr) {
+        if !multiaddr_ip(&addr).map_or(false, |ip| is_public_ip(&ip)) {
+            return;
+        }
+        self.observed_addrs.retain(|_, peers| {
+            peers.remove(&peer_id);
+            peers.retain(|_, observed| observed.elapsed() < OBSERVED_ADDR_TTL);
+            !peers.is_empty()
+        });
+        if !self.observed_addrs.contains_key(&addr)
+            && self.observed_addrs.len() >= MAX_OBSERVED_ADDRS
+        {
+            let oldest = self
+                .observed_addrs
+                .iter()
+                .min_by_key(|(_, peers)| peers.values().max().copied())
+                .map(|(addr, _)| addr.clone());
+            if let Some(oldest) = oldest {
+                self.observed_addrs.remove(&oldest);
+            }
+        }
+        self.observed_addrs
+            .entry(addr)
+            .or_default()
+            .insert(peer_id, Instant::now());
+    }
+
+    /// Records the external address a port was mapped to on the UPnP gateway.
+    pub fn mapped_addr(&mut self, addr: Multiaddr) {
+        self.mapped_addr = Some(addr);
+    }
+
+    pub fn status(&self) -> NatStatus {
+        let recently_dialed = self
+            .last_public_inbound
+            .map_or(false, |last| last.elapsed() < PUBLIC_TTL);
+        let reachability = if recently_dialed {
+            Reachability::Public
+        } else if self.outbound_since_inbound >= PRIVATE_OUTBOUND_THRESHOLD {
+            Reachability::Private
+        } else {
+            Reachability::Unknown
+        };
+
+        let public_addr = match reachability {
+            Reachability::Public => self
+                .announce_addrs
+                .first()
+                .or(self.mapped_addr.as_ref())
+                .cloned()
+                .or_else(|| {
+                    self.observed_addrs
+                        .iter()
+                        .map(|(addr, peers)| {
+                            let count = peers
+                                .values()
+                                .filter(|observed| observed.elapsed() < OBSERVED_ADDR_TTL)
+                                .count();
+                            (addr, count)
+                        })
+                        .filter(|(_, count)| *count >= OBSERVED_ADDR_CONFIRMATIONS)
+                        .max_by_key(|(_, count)| *count)
+                        .map(|(addr, _)| addr.clone())
+                }),
+            _ => None,
+        };
+        NatStatus {
+            reachability,
+            public_addr,
+        }
+    }
+}
+
+/// Returns true if the IP address is globally routable.
+fn is_public_ip(ip: &IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => {
+            let octets = ip.octets();
+            // Shared address space of carrier grade NATs, 100.64.0.0/10
+            let shared = octets[0] == 100 && (octets[1] & 0b1100_0000) == 64;
+            !(ip.is_private()
+                || ip.is_loopback()
+                || ip.is_link_local()
+                || ip.is_unspecified()
+                || ip.is_broadcast()
+                || ip.is_documentation()
+                || shared)
+        }
+        IpAddr::V6(ip) => {
+            let first = ip.segments()[0];
+            // Unique local addresses, fc00::/7, and link local addresses, fe80::/10
+            let unique_local = (first & 0xfe00) == 0xfc00;
+            let link_local = (first & 0xffc0) == 0xfe80;
+            !(ip.is_loopback() || ip.is_unspecified() || unique_local || link_local)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn addr(ip: &str) -> Multiaddr {
+        format!("/ip4/{}/tcp/1234", ip).parse().unwrap()
+    }
+
+    #[test]
+    fn detects_reachability() {
+        let mut tracker = ReachabilityTracker::new(vec![]);
+        assert_eq!(tracker.status().reachability, Reachability::Unknown);
+
+        for _ in 0..PRIVATE_OUTBOUND_THRESHOLD {
+            tracker.outbound_connection();
+        }
+        // Connections from the local network don't make the node public
+        tracker.inbound_connection(&addr("192.168.1.2"));
+        assert_eq!(tracker.status().reachability, Reachability::Private);
+
+        let (first, second) = (PeerId::random(), PeerId::random());
+        tracker.observed_addr(first, addr("1.2.3.4"));
+        tracker.inbound_connection(&addr("5.6.7.8"));
+        let status = tracker.status();
+        assert_eq!(status.reachability, Reachability::Public);
+        // The address needs to be observed by more peers to be reported
+        assert_eq!(status.public_addr, None);
+
+        tracker.observed_addr(second, addr("1.2.3.4"));
+        tracker.observed_addr(PeerId::random(), addr("10.0.0.1"));
+        assert_eq!(tracker.status().public_addr, Some(addr("1.2.3.4")));
+    }
+
+    #[test]
+    fn counts_distinct_peers() {
+        let mut tracker = ReachabilityTracker::new(vec![]);
+        tracker.inbound_connection(&addr("5.6.7.8"));
+        let peer = PeerId::random();
+        for _ in 0..OBSERVED_ADDR_CONFIRMATIONS {
+            tracker.observed_addr(peer, addr("1.2.3.4"));
+        }
+        assert_eq!(tracker.status().public_addr, None);
+
+        // A peer observing a new address no longer counts towards the previous one
+        let other = PeerId::random();
+        tracker.observed_addr(other, addr("1.2.3.4"));
+        assert_eq!(tracker.status().public_addr, Some(addr("1.2.3.4")));
+        tracker.observed_addr(other, addr("4.3.2.1"));
+        assert_eq!(tracker.status().public_addr, None);
+    }
+
+    #[test]
+    fn caps_observed_addrs() {
+        let mut tracker = ReachabilityTracker::new(vec![]);
+        for i in 0..MAX_OBSERVED_ADDRS + 8 {
+            tracker.observed_addr(PeerId::random(), addr(&format!("1.2.3.{}", i)));
+        }
+        assert_eq!(tracker.observed_addrs.len(), MAX_OBSERVED_ADDRS);
+    }
+
+    #[test]
+    fn prefers_announced_addresses() {
+        let mut tracker = ReachabilityTracker::new(vec![addr("9.9.9.9")]);
+        tracker.inbound_connection(&addr("5.6.7.8"));
+        for _ in 0..OBSERVED_ADDR_CONFIRMATIONS {
+            tracker.observed_addr(PeerId::random(), addr("1.2.3.4"));
+        }
+        assert_eq!(tracker.status().public_addr, Some(addr("9.9.9.9")));
+    }
+
+    #[test]
+    fn prefers_mapped_addresses() {
+        let mut tracker = ReachabilityTracker::new(vec![]);
+        tracker.inbound_connection(&addr("5.6.7.8"));
+        for _ in 0..OBSERVED_ADDR_CONFIRMATIONS {
+            tracker.observed_addr(PeerId::random(), addr("1.2.3.4"));
+        }
+        tracker.mapped_addr(addr("9.9.9.9"));
+        assert_eq!(tracker.status().public_addr, Some(addr("9.9.9.9")));
+    }
+
+    #[test]
+    fn public_ips() {
+        for ip in ["1.2.3.4", "2001:4860::1"] {
+            assert!(is_public_ip(&ip.parse().unwrap()), "{}", ip);
+        }
+        for ip in [
+            "10.0.0.1",
+            "100.64.0.1",
+            "127.0.0.1",
+            "fd00::1",
+            "fe80::1",
+            "::1",
+        ] {
+            assert!(!is_public_ip(&ip.parse().unwrap()), "{}", ip);
+        }
+    }
+}
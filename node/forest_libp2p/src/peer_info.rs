@@ -0,0 +1,189 @@
+// Copyright 2019-2022 ChainSafe Systems
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+use crate::PeerRecord;
+use futures::channel::oneshot::Sender as OneShotSender;
+use libp2p::core::{Multiaddr, PeerId};
+use libp2p::identify::IdentifyInfo;
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+/// What is known about a connected peer.
+#[derive(Clone, Debug)]
+pub struct PeerInfo {
+    /// Agent version announced over Identify, empty until the peer is identified.
+    pub agent_version: String,
+    /// Protocols announced over Identify.
+    pub protocols: Vec<String>,
+    /// Addresses the peer listens on, announced over Identify.
+    pub listen_addrs: Vec<Multiaddr>,
+    /// Round trip time of the last successful ping.
+    pub ping_rtt: Option<Duration>,
+    /// True if the peer opened the first connection to the node.
+    pub inbound: bool,
+    /// Time since the first connection to the peer was established.
+    pub connected_for: Duration,
+    /// GossipSub score of the peer, if peer scoring is enabled.
+    pub gossipsub_score: Option<f64>,
+    /// Chain exchange statistics of the peer.
+    pub record: Option<PeerRecord>,
+}
+
+struct ConnectedPeer {
+    inbound: bool,
+    connected_at: Instant,
+    agent_version: String,
+    protocols: Vec<String>,
+    listen_addrs: Vec<Multiaddr>,
+    ping_rtt: Option<Duration>,
+    /// Channels waiting for the round trip time of the next ping.
+    ping_waiters: Vec<OneShotSender<Duration>>,
+}
+
+/// Keeps the connection, Identify and ping details of the connected peers.
+#[derive(Default)]
+pub(crate) struct PeerInfoTracker {
+    peers: HashMap<PeerId, ConnectedPeer>,
+}
+
+impl PeerInfoTracker {
+    /// Records a connection to a peer, only the first connection to a peer is kept track of.
+    pub fn connection_established(&mut self, peer_id: PeerId, inbound: bool) {
+        self.peers.entry(peer_id).or_insert_with(|| ConnectedPeer {
+            inbound,
+            connected_at: Instant::now(),
+            agent_version: String::new(),
+            protocols: vec![],
+            listen_addrs: vec![],
+            ping_rtt: None,
+            ping_waiters: vec![],
+        });
+    }
+
+    /// Forgets a peer once its last connection is closed, dropping the channels waiting for a
+    /// ping of the peer.
+    pub fn connection_closed(&mut self, peer_id: &PeerId, num_established: u32) {
+        if num_established == 0 {
+            self.peers.remove(peer_id);
+        }
+    }
+
+    pub fn identified(&mut self, peer_id: &PeerId, info: IdentifyInfo) {
+        if let Some(peer) = self.peers.get_mut(peer_id) {
+            peer.agent_version = info.agent_version;
+            peer.protocols = info.protocols;
+            peer.listen_addrs = info.listen_addrs;
+        }
+    }
+
+    /// Records the round trip time of a ping and sends it to the channels waiting for it.
+    pub fn ping(&mut self, peer_id: &PeerId, rtt: Duration) {
+        if let Some(peer) = self.peers.get_mut(peer_id) {
+            peer.ping_rtt = Some(rtt);
+            for waiter in peer.ping_waiters.drain(..) {
+                let _ = waiter.send(rtt);
+            }
+        }
+    }
+
+    /// Registers a channel to send the round trip time of the next ping of a peer to. The
+    /// channel is given back if the peer is not connected.
+    pub fn wait_for_ping(
+        &mut self,
+        peer_id: &PeerId,
+        waiter: OneShotSender<Duration>,
+    ) -> Result<(), OneShotSender<Duration>> {
+        match self.peers.get_mut(peer_id) {
+            Some(peer) => {
+                peer.ping_waiters.push(waiter);
+                Ok(())
+            }
+            None => Err(waiter),
+        }
+    }
+
+    /// Returns the details of a connected peer, along with its score and statistics kept
+    /// elsewhere.
+    pub fn info(
+        &self,
+        peer_id: &PeerId,
+        gossipsub_score: Option<f64>,
+        record: Option<PeerRecord>,
+    ) -> Option<PeerInfo> {
+        self.peers.get(peer_id).map(|peer| PeerInfo {
+            agent_version: peer.agent_version.clone(),
+            protocols: peer.protocols.clone(),
+            listen_addrs: peer.listen_addrs.clone(),
+            ping_rtt: peer.ping_rtt,
+            inbound: peer.inbound,
+            connected_for: peer.connected_at.elapsed(),
+            gossipsub_score,
+            record,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::channel::oneshot;
+    use libp2p::identity::Keypair;
+
+    fn identify_info(agent_version: &str) -> IdentifyInfo {
+        IdentifyInfo {
+            public_key: Keypair::generate_ed25519().public(),
+            protocol_version: "ipfs/0.1.0".into(),
+            agent_version: agent_version.into(),
+            listen_addrs: vec!["/ip4/1.2.3.4/tcp/1234".parse().unwrap()],
+            protocols: vec!["/ipfs/ping/1.0.0".into()],
+            observed_addr: "/ip4/5.6.7.8/tcp/1234".parse().unwrap(),
+        }
+    }
+
+    #[test]
+    fn tracks_connected_peers() {
+        let mut tracker = PeerInfoTracker::default();
+        let peer = PeerId::random();
+        tracker.identified(&peer, identify_info("lotus"));
+        assert!(tracker.info(&peer, None, None).is_none());
+
+        tracker.connection_established(peer, true);
+        // The direction of the first connection is kept
+        tracker.connection_established(peer, false);
+        tracker.identified(&peer, identify_info("lotus"));
+        tracker.ping(&peer, Duration::from_millis(20));
+
+        let info = tracker.info(&peer, Some(1.5), None).unwrap();
+        assert_eq!(info.agent_version, "lotus");
+        assert_eq!(info.protocols, vec!["/ipfs/ping/1.0.0".to_owned()]);
+        assert_eq!(info.ping_rtt, Some(Duration::from_millis(20)));
+        assert!(info.inbound);
+        assert_eq!(info.gossipsub_score, Some(1.5));
+
+        tracker.connection_closed(&peer, 1);
+        assert!(tracker.info(&peer, None, None).is_some());
+        tracker.connection_closed(&peer, 0);
+        assert!(tracker.info(&peer, None, None).is_none());
+    }
+
+    #[test]
+    fn notifies_ping_waiters() {
+        let mut tracker = PeerInfoTracker::default();
+        let peer = PeerId::random();
+        let (tx, _rx) = oneshot::channel();
+        assert!(tracker.wait_for_ping(&peer, tx).is_err());
+
+        tracker.connection_established(peer, false);
+        let (tx, mut rx) = oneshot::channel();
+        assert!(tracker.wait_for_ping(&peer, tx).is_ok());
+        assert_eq!(rx.try_recv(), Ok(None));
+        tracker.ping(&peer, Duration::from_millis(5));
+        assert_eq!(rx.try_recv(), Ok(Some(Duration::from_millis(5))));
+
+        // Waiters are dropped along with the peer
+        let (tx, mut rx) = oneshot::channel();
+        tracker.wait_for_ping(&peer, tx).unwrap();
+        tracker.connection_closed(&peer, 0);
+        assert!(rx.try_recv().is_err());
+    }
+}
@@ -0,0 +1,367 @@
+// Copyright 2019-2022 ChainSafe Systems
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+use db::Store;
+use forest_encoding::tuple::*;
+use forest_encoding::{from_slice, serde_bytes, to_vec};
+use libp2p::core::{Multiaddr, PeerId};
+use log::debug;
+use std::collections::{HashMap, HashSet};
+use std::convert::TryFrom;
+use std::sync::RwLock;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Key under which the peer records are persisted in the database.
+const PEER_STORE_KEY: &str = "peer_store";
+
+/// Maximum number of peer records kept, in memory and in the database. The peers seen least
+/// recently are dropped first when saving, peers with an active ban are always kept.
+const MAX_PEER_RECORDS: usize = 1000;
+
+/// Ban of a peer, lifted once the expiry time has passed.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize_tuple, Deserialize_tuple)]
+pub struct PeerBan {
+    pub reason: String,
+    /// Unix timestamp in seconds at which the ban expires.
+    pub expiry: u64,
+}
+
+/// What is known about a peer across restarts.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct PeerRecord {
+    /// Addresses the peer was last reachable at.
+    pub addrs: Vec<Multiaddr>,
+    /// Unix timestamp in seconds of the last time the peer was connected.
+    pub last_seen: u64,
+    /// Number of successful requests to the peer.
+    pub successes: u32,
+    /// Number of failed requests to the peer.
+    pub failures: u32,
+    /// Average response time of the peer.
+    pub average_time: Duration,
+    pub ban: Option<PeerBan>,
+}
+
+impl PeerRecord {
+    /// Share of successful requests, with peers that have not served any request yet ranking
+    /// in the middle.
+    pub fn score(&self) -> f64 {
+        f64::from(self.successes + 1) / f64::from(self.successes + self.failures + 2)
+    }
+
+    /// Returns the active ban of the peer, if any.
+    pub fn active_ban(&self) -> Option<&PeerBan> {
+        self.ban.as_ref().filter(|ban| ban.expiry > unix_now())
+    }
+}
+
+/// Encoded form of a peer record.
+#[derive(Serialize_tuple, Deserialize_tuple)]
+struct StoredPeer {
+    #[serde(with = "serde_bytes")]
+    peer_id: Vec<u8>,
+    addrs: Vec<serde_bytes::ByteBuf>,
+    last_seen: u64,
+    successes: u32,
+    failures: u32,
+    average_time_ms: u64,
+    ban: Option<PeerBan>,
+}
+
+/// Records of the peers the node has been connected to, persisted in the database so that
+/// known-good peers can be dialed first and bans outlive a restart.
+#[derive(Default)]
+pub struct PeerStore {
+    records: RwLock<HashMap<PeerId, PeerRecord>>,
+}
+
+impl PeerStore {
+    /// Loads the peer records persisted in the database.
+    pub fn load<DB: Store>(db: &DB) -> Result<Self, anyhow::Error> {
+        let stored: Vec<StoredPeer> = match db.read(PEER_STORE_KEY)? {
+            Some(bytes) => from_slice(&bytes)?,
+            None => Vec::new(),
+        };
+
+        let records = stored
+            .into_iter()
+            .filter_map(|peer| {
+                let peer_id = PeerId::from_bytes(&peer.peer_id).ok()?;
+                let addrs = peer
+                    .addrs
+                    .into_iter()
+                    .filter_map(|addr| Multiaddr::try_from(addr.into_vec()).ok())
+                    .collect();
+                let record = PeerRecord {
+                    addrs,
+                    last_seen: peer.last_seen,
+                    successes: peer.successes,
+                    failures: peer.failures,
+                    average_time: Duration::from_millis(peer.average_time_ms),
+                    ban: peer.ban,
+                };
+                Some((peer_id, record))
+            })
+            .collect::<HashMap<_, _>>();
+        debug!("Loaded {} peer records", records.len());
+
+        Ok(Self {
+            records: RwLock::new(records),
+        })
+    }
+
+    /// Persists the peer records in the database, dropping the records past
+    /// `MAX_PEER_RECORDS` from memory as well.
+    pub fn save<DB: Store>(&self, db: &DB) -> Result<(), anyhow::Error> {
+        let records = {
+            let mut all_records = self.records.write().unwrap();
+            let mut records: Vec<_> = all_records
+                .iter()
+                .map(|(peer_id, record)| (*peer_id, record.clone()))
+                .collect();
+            records.sort_by_key(|(_, record)| {
+                (
+                    std::cmp::Reverse(record.active_ban().is_some()),
+                    std::cmp::Reverse(record.last_seen),
+                )
+            });
+            records.truncate(MAX_PEER_RECORDS);
+            if records.len() < all_records.len() {
+                let kept: HashSet<_> = records.iter().map(|(peer_id, _)| *peer_id).collect();
+                all_records.retain(|peer_id, _| kept.contains(peer_id));
+            }
+            records
+        };
+
+        let stored: Vec<_> = records
+            .into_iter()
+            .map(|(peer_id, record)| StoredPeer {
+                peer_id: peer_id.to_bytes(),
+                addrs: record
+                    .addrs
+                    .iter()
+                    .map(|addr| serde_bytes::ByteBuf::from(addr.to_vec()))
+                    .collect(),
+                last_seen: record.last_seen,
+                successes: record.successes,
+                failures: record.failures,
+                average_time_ms: record.average_time.as_millis() as u64,
+                ban: record.ban,
+            })
+            .collect();
+        Ok(db.write(PEER_STORE_KEY, to_vec(&stored)?)?)
+    }
+
+    /// Returns the record of a peer.
+    pub fn get(&self, peer_id: &PeerId) -> Option<PeerRecord> {
+        self.records.read().unwrap().get(peer_id).cloned()
+    }
+
+    /// Records that the peer is connected, reachable at the given addresses.
+    pub fn peer_seen(&self, peer_id: PeerId, addrs: Vec<Multiaddr>) {
+        let mut records = self.records.write().unwrap();
+        let record = records.entry(peer_id).or_default();
+        if !addrs.is_empty() {
+            record.addrs = addrs;
+        }
+        record.last_seen = unix_now();
+    }
+
+    /// Updates the request statistics of a peer.
+    pub fn update_stats(
+        &self,
+        peer_id: PeerId,
+        successes: u32,
+        failures: u32,
+        average_time: Duration,
+    ) {
+        let mut records = self.records.write().unwrap();
+        let record = records.entry(peer_id).or_default();
+        record.successes = successes;
+        record.failures = failures;
+        record.average_time = average_time;
+    }
+
+    /// Bans a peer for the given duration.
+    pub fn ban(&self, peer_id: PeerId, reason: String, duration: Duration) {
+        let mut records = self.records.write().unwrap();
+        records.entry(peer_id).or_default().ban = Some(PeerBan {
+            reason,
+            expiry: unix_now() + duration.as_secs(),
+        });
+    }
+
+    /// Lifts the ban of a peer, returning whether the peer had an active ban.
+    pub fn unban(&self, peer_id: &PeerId) -> bool {
+        let mut records = self.records.write().unwrap();
+        match records.get_mut(peer_id) {
+            Some(record) => {
+                let banned = record.active_ban().is_some();
+                record.ban = None;
+                banned
+            }
+            None => false,
+        }
+    }
+
+    /// Returns true if the peer has an active ban.
+    pub fn is_banned(&self, peer_id: &PeerId) -> bool {
+        self.records
+            .read()
+            .unwrap()
+            .get(peer_id)
+            .map_or(false, |record| record.active_ban().is_some())
+    }
+
+    /// Returns the peers with an active ban.
+    pub fn banned_peers(&self) -> Vec<PeerId> {
+        self.records
+            .read()
+            .unwrap()
+            .iter()
+            .filter(|(_, record)| record.active_ban().is_some())
+            .map(|(peer_id, _)| *peer_id)
+            .collect()
+    }
+
+    /// Clears the bans that have expired, returning the peers they applied to.
+    pub fn expire_bans(&self) -> Vec<PeerId> {
+        let mut records = self.records.write().unwrap();
+        records
+            .iter_mut()
+            .filter(|(_, record)| record.ban.is_some() && record.active_ban().is_none())
+            .map(|(peer_id, record)| {
+                record.ban = None;
+                *peer_id
+            })
+            .collect()
+    }
+
+    /// Returns up to `limit` peers that are not banned and have known addresses, best scored and
+    /// most recently seen first.
+    pub fn dial_candidates(&self, limit: usize) -> Vec<(PeerId, Vec<Multiaddr>)> {
+        let records = self.records.read().unwrap();
+        let mut candidates: Vec<_> = records
+            .iter()
+            .filter(|(_, record)| !record.addrs.is_empty() && record.active_ban().is_none())
+            .collect();
+        candidates.sort_by(|(_, a), (_, b)| {
+            b.score()
+                .partial_cmp(&a.score())
+                .unwrap_or(std::cmp::Ordering::Equal)
+                .then(b.last_seen.cmp(&a.last_seen))
+        });
+        candidates
+            .into_iter()
+            .take(limit)
+            .map(|(peer_id, record)| (*peer_id, record.addrs.clone()))
+            .collect()
+    }
+}
+
+fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use db::MemoryDB;
+
+    #[test]
+    fn persists_records() {
+        let db = MemoryDB::default();
+        let store = PeerStore::default();
+        let peer = PeerId::random();
+        let addr: Multiaddr = "/ip4/127.0.0.1/tcp/1234".parse().unwrap();
+
+        store.peer_seen(peer, vec![addr.clone()]);
+        store.update_stats(peer, 3, 1, Duration::from_millis(250));
+        store.ban(PeerId::random(), "bad".to_owned(), Duration::from_secs(60));
+        store.save(&db).unwrap();
+
+        let loaded = PeerStore::load(&db).unwrap();
+        let record = loaded.get(&peer).unwrap();
+        assert_eq!(record.addrs, vec![addr]);
+        assert_eq!((record.successes, record.failures), (3, 1));
+        assert_eq!(record.average_time, Duration::from_millis(250));
+        assert_eq!(loaded.banned_peers().len(), 1);
+    }
+
+    #[test]
+    fn prunes_least_recently_seen() {
+        let db = MemoryDB::default();
+        let store = PeerStore::default();
+        let peers: Vec<_> = (0..MAX_PEER_RECORDS + 2)
+            .map(|_| PeerId::random())
+            .collect();
+        {
+            let mut records = store.records.write().unwrap();
+            for (last_seen, peer) in peers.iter().enumerate() {
+                records.insert(
+                    *peer,
+                    PeerRecord {
+                        last_seen: last_seen as u64,
+                        ..Default::default()
+                    },
+                );
+            }
+        }
+        // Banned peers are kept even when they were seen the longest time ago
+        store.ban(peers[0], "bad".to_owned(), Duration::from_secs(60));
+        store.save(&db).unwrap();
+
+        assert_eq!(store.records.read().unwrap().len(), MAX_PEER_RECORDS);
+        assert!(store.is_banned(&peers[0]));
+        assert!(store.get(&peers[1]).is_none());
+        assert!(store.get(&peers[2]).is_none());
+        assert!(store.get(&peers[3]).is_some());
+
+        let loaded = PeerStore::load(&db).unwrap();
+        assert_eq!(loaded.records.read().unwrap().len(), MAX_PEER_RECORDS);
+        assert!(loaded.get(&peers[2]).is_none());
+    }
+
+    #[test]
+    fn bans_expire() {
+        let store = PeerStore::default();
+        let peer = PeerId::random();
+
+        store.ban(peer, "bad".to_owned(), Duration::from_secs(60));
+        assert!(store.is_banned(&peer));
+        assert!(store.expire_bans().is_empty());
+        assert!(store.unban(&peer));
+        assert!(!store.is_banned(&peer));
+
+        store.ban(peer, "bad".to_owned(), Duration::ZERO);
+        assert!(!store.is_banned(&peer));
+        assert_eq!(store.expire_bans(), vec![peer]);
+        assert!(store.get(&peer).unwrap().ban.is_none());
+    }
+
+    #[test]
+    fn dials_best_peers_first() {
+        let store = PeerStore::default();
+        let addr: Multiaddr = "/ip4/127.0.0.1/tcp/1234".parse().unwrap();
+        let (good, bad, banned) = (PeerId::random(), PeerId::random(), PeerId::random());
+        for peer in [good, bad, banned] {
+            store.peer_seen(peer, vec![addr.clone()]);
+        }
+        store.update_stats(good, 10, 0, Duration::default());
+        store.update_stats(bad, 0, 10, Duration::default());
+        store.ban(banned, "bad".to_owned(), Duration::from_secs(60));
+        // Peers without a known address can't be dialed
+        store.update_stats(PeerId::random(), 20, 0, Duration::default());
+
+        let candidates: Vec<_> = store
+            .dial_candidates(10)
+            .into_iter()
+            .map(|(peer, _)| peer)
+            .collect();
+        assert_eq!(candidates, vec![good, bad]);
+        assert_eq!(store.dial_candidates(1).len(), 1);
+    }
+}
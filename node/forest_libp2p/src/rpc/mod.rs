@@ -1,6 +1,7 @@
 // Copyright 2019-2022 ChainSafe Systems
 // SPDX-License-Identifier: Apache-2.0, MIT
 
+use crate::bandwidth::{CountingStream, RecordTraffic, Traffic};
 use async_trait::async_trait;
 use asynchronous_codec::FramedRead;
 use forest_encoding::de::DeserializeOwned;
@@ -12,6 +13,7 @@ use libp2p::request_response::RequestResponseCodec;
 use serde::Serialize;
 use std::io;
 use std::marker::PhantomData;
+use std::sync::Arc;
 
 mod cbor_codec;
 use cbor_codec::Decoder;
@@ -23,18 +25,28 @@ pub struct CborRequestResponse<P, RQ, RS> {
     protocol: PhantomData<P>,
     request: PhantomData<RQ>,
     response: PhantomData<RS>,
+    /// Bytes of the requests and responses read and written.
+    traffic: Arc<Traffic>,
 }
 
-impl<P, RQ, RS> Default for CborRequestResponse<P, RQ, RS> {
-    fn default() -> Self {
+impl<P, RQ, RS> CborRequestResponse<P, RQ, RS> {
+    /// Creates a codec accounting the bytes of the requests and responses to the given traffic.
+    pub fn new(traffic: Arc<Traffic>) -> Self {
         Self {
             protocol: PhantomData::<P>::default(),
             request: PhantomData::<RQ>::default(),
             response: PhantomData::<RS>::default(),
+            traffic,
         }
     }
 }
 
+impl<P, RQ, RS> Default for CborRequestResponse<P, RQ, RS> {
+    fn default() -> Self {
+        Self::new(Default::default())
+    }
+}
+
 /// libp2p request response outbound error type. This indicates a failure sending a request to
 /// a peer. This is different from a failure response from a node, as this is an error that
 /// prevented a response.
@@ -84,6 +96,7 @@ where
     where
         T: AsyncRead + Unpin + Send,
     {
+        let io = CountingStream::new(io, self.traffic.clone());
         let mut reader = FramedRead::new(io, Decoder::<RQ>::new());
         // Expect only one request
         let req = reader
@@ -103,6 +116,7 @@ where
     where
         T: AsyncRead + Unpin + Send,
     {
+        let io = CountingStream::new(io, self.traffic.clone());
         let mut reader = FramedRead::new(io, Decoder::<RS>::new());
         // Expect only one response
         let resp = reader
@@ -125,10 +139,10 @@ where
     {
         // TODO: Use FramedWrite to stream write. Dilemma right now is if we should fork the cbor codec so we can replace serde_cbor to our fork of serde_cbor
 
-        io.write_all(
-            &to_vec(&req).map_err(|e| io::Error::new(io::ErrorKind::Other, e.to_string()))?,
-        )
-        .await?;
+        let bytes =
+            to_vec(&req).map_err(|e| io::Error::new(io::ErrorKind::Other, e.to_string()))?;
+        io.write_all(&bytes).await?;
+        self.traffic.record_out(bytes.len());
         io.close().await?;
         Ok(())
     }
@@ -143,10 +157,10 @@ where
         T: AsyncWrite + Unpin + Send,
     {
         // TODO: Use FramedWrite to stream write. Dilemma right now is if we should fork the cbor codec so we can replace serde_cbor to our fork of serde_cbor
-        io.write_all(
-            &to_vec(&res).map_err(|e| io::Error::new(io::ErrorKind::Other, e.to_string()))?,
-        )
-        .await?;
+        let bytes =
+            to_vec(&res).map_err(|e| io::Error::new(io::ErrorKind::Other, e.to_string()))?;
+        io.write_all(&bytes).await?;
+        self.traffic.record_out(bytes.len());
         io.close().await?;
         Ok(())
     }
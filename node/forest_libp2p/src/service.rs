@@ -1,42 +1,50 @@
 // Copyright 2019-2022 ChainSafe Systems
 // SPDX-License-Identifier: Apache-2.0, MIT
 
-use super::chain_exchange::{
-    make_chain_exchange_response, ChainExchangeRequest, ChainExchangeResponse,
-};
-use super::{ForestBehaviour, ForestBehaviourEvent, Libp2pConfig};
+use super::bandwidth::{BandwidthMeter, BandwidthStats, CountingStream};
+use super::bitswap::{verify_block, BitswapServer};
+use super::chain_exchange::{ChainExchangeRequest, ChainExchangeResponse, ChainExchangeServer};
+use super::gater::{BlockList, ConnectionGater};
+use super::nat::{NatStatus, ReachabilityTracker};
+use super::peer_info::{PeerInfo, PeerInfoTracker};
+use super::upnp;
+use super::{ForestBehaviour, ForestBehaviourEvent, Libp2pConfig, PeerStore, TransportConfig};
 use crate::{
     hello::{HelloRequest, HelloResponse},
+    metrics::{self, values},
     rpc::RequestResponseError,
 };
 use async_std::channel::{unbounded, Receiver, Sender};
 use async_std::{stream, task};
 use chain::ChainStore;
-use cid::{multihash::Code::Blake2b256, Cid};
+use cid::Cid;
 use forest_blocks::GossipBlock;
 use forest_encoding::from_slice;
 use forest_message::SignedMessage;
 use futures::channel::oneshot::Sender as OneShotSender;
-use futures::select;
+use futures::{future, select};
 use futures_util::stream::StreamExt;
-use ipld_blockstore::{BlockStore, BlockStoreExt};
+use fvm_ipld_blockstore::Blockstore;
+use ipld_blockstore::BlockStore;
 pub use libp2p::gossipsub::IdentTopic;
 pub use libp2p::gossipsub::Topic;
+pub use libp2p::gossipsub::{MessageAcceptance, MessageId};
 use libp2p::multiaddr::Protocol;
 use libp2p::multihash::Multihash;
 use libp2p::request_response::ResponseChannel;
-use libp2p::swarm::SwarmEvent;
+use libp2p::swarm::{AddressScore, SwarmEvent};
 use libp2p::{
     core,
-    core::connection::ConnectionLimits,
+    core::connection::{ConnectedPoint, ConnectionLimits},
     core::muxing::StreamMuxerBox,
-    core::transport::Boxed,
+    core::transport::{Boxed, MemoryTransport, OptionalTransport},
     identity::{ed25519, Keypair},
     mplex, noise, yamux, PeerId, Swarm, Transport,
 };
 use libp2p::{core::Multiaddr, swarm::SwarmBuilder};
 use log::{debug, error, info, trace, warn};
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
+use std::io;
 use std::path::Path;
 use std::sync::Arc;
 use std::time::Duration;
@@ -53,8 +61,11 @@ const PUBSUB_TOPICS: [&str; 2] = [PUBSUB_BLOCK_STR, PUBSUB_MSG_STR];
 #[allow(clippy::large_enum_variant)]
 #[derive(Debug)]
 pub enum NetworkEvent {
+    /// Message received over GossipSub. It is only forwarded to other peers once reported
+    /// valid with [`NetworkMessage::ValidationResult`].
     PubsubMessage {
         source: PeerId,
+        message_id: MessageId,
         message: PubsubMessage,
     },
     HelloRequest {
@@ -106,6 +117,17 @@ pub enum NetworkMessage {
     JSONRPCRequest {
         method: NetRPCMethods,
     },
+    /// Reports the validation result of a message received over GossipSub.
+    ValidationResult {
+        message_id: MessageId,
+        source: PeerId,
+        acceptance: MessageAcceptance,
+    },
+    /// Sets the application specific part of the GossipSub score of a peer.
+    ApplicationScore {
+        peer_id: PeerId,
+        score: f64,
+    },
 }
 
 /// Network RPC API methods used to gather data from libp2p node.
@@ -115,6 +137,16 @@ pub enum NetRPCMethods {
     NetPeers(OneShotSender<HashMap<PeerId, Vec<Multiaddr>>>),
     NetConnect(OneShotSender<bool>, PeerId, Vec<Multiaddr>),
     NetDisconnect(OneShotSender<()>, PeerId),
+    NetBlockAdd(OneShotSender<Result<(), String>>, BlockList),
+    NetBlockRemove(OneShotSender<Result<(), String>>, BlockList),
+    NetBlockList(OneShotSender<BlockList>),
+    NetAutoNatStatus(OneShotSender<NatStatus>),
+    NetBandwidthStats(OneShotSender<BandwidthStats>),
+    NetBandwidthStatsByPeer(OneShotSender<HashMap<PeerId, BandwidthStats>>),
+    NetBandwidthStatsByProtocol(OneShotSender<HashMap<String, BandwidthStats>>),
+    NetPeerInfo(OneShotSender<Option<PeerInfo>>, PeerId),
+    NetPing(OneShotSender<Duration>, PeerId),
+    NetFindPeer(OneShotSender<Result<Vec<Multiaddr>, String>>, PeerId),
 }
 
 /// The Libp2pService listens to events from the Libp2p swarm.
@@ -128,6 +160,24 @@ pub struct Libp2pService<DB> {
     network_sender_out: Sender<NetworkEvent>,
     network_name: String,
     bitswap_response_channels: HashMap<Cid, Vec<OneShotSender<()>>>,
+    chain_exchange_server: Arc<ChainExchangeServer>,
+    bitswap_server: BitswapServer,
+    peer_store: Arc<PeerStore>,
+    /// Peers currently banned in the swarm.
+    banned_peers: HashSet<PeerId>,
+    gater: Arc<ConnectionGater>,
+    /// Addresses of the peers kept connected.
+    static_peers: HashMap<PeerId, Vec<Multiaddr>>,
+    reachability: ReachabilityTracker,
+    bandwidth: Arc<BandwidthMeter>,
+    peer_info: PeerInfoTracker,
+    /// Addresses never advertised as external addresses.
+    no_announce_addrs: Vec<Multiaddr>,
+    upnp: bool,
+    /// Listening ports mapped on the UPnP gateway.
+    upnp_ports: HashSet<u16>,
+    upnp_sender: Sender<Multiaddr>,
+    upnp_receiver: Receiver<Multiaddr>,
 }
 
 impl<DB> Libp2pService<DB>
@@ -137,12 +187,41 @@ where
     pub fn new(
         config: Libp2pConfig,
         cs: Arc<ChainStore<DB>>,
+        peer_store: Arc<PeerStore>,
         net_keypair: Keypair,
         network_name: &str,
     ) -> Self {
         let peer_id = PeerId::from(net_keypair.public());
 
-        let transport = build_transport(net_keypair.clone());
+        let mut static_peers: HashMap<PeerId, Vec<Multiaddr>> = HashMap::new();
+        for addr in config.static_peers.iter() {
+            match split_peer_addr(addr) {
+                Some((peer_id, addr)) => static_peers.entry(peer_id).or_default().push(addr),
+                None => warn!("Static peer address {} has no peer ID, ignoring it", addr),
+            }
+        }
+        let gater = Arc::new(build_gater(&config, cs.blockstore(), &static_peers));
+
+        let bandwidth = Arc::new(BandwidthMeter::default());
+
+        // Account the traffic of the connections to their peer and refuse the connections that
+        // are not allowed before they reach the swarm
+        let transport_gater = gater.clone();
+        let transport_bandwidth = bandwidth.clone();
+        let transport = build_transport(net_keypair.clone(), &config.transport, bandwidth.clone())
+            .and_then(move |(peer_id, muxer), endpoint| {
+                transport_bandwidth
+                    .connection_authenticated(endpoint.get_remote_address(), peer_id);
+                let result = transport_gater
+                    .check_connection(&peer_id, endpoint.get_remote_address())
+                    .map(|()| (peer_id, muxer))
+                    .map_err(|e| {
+                        debug!("Refusing connection: {}", e);
+                        io::Error::new(io::ErrorKind::PermissionDenied, e)
+                    });
+                future::ready(result)
+            })
+            .boxed();
 
         let limits = ConnectionLimits::default()
             .with_max_pending_incoming(Some(10))
@@ -153,7 +232,7 @@ where
 
         let mut swarm = SwarmBuilder::new(
             transport,
-            ForestBehaviour::new(&net_keypair, &config, network_name),
+            ForestBehaviour::new(&net_keypair, &config, network_name, &bandwidth),
             peer_id,
         )
         .connection_limits(limits)
@@ -161,7 +240,16 @@ where
         .connection_event_buffer_size(64)
         .build();
 
-        Swarm::listen_on(&mut swarm, config.listening_multiaddr).unwrap();
+        for addr in config.listening_multiaddrs {
+            if let Err(e) = Swarm::listen_on(&mut swarm, addr.clone()) {
+                error!("Failed to listen on {}: {}", addr, e);
+            }
+        }
+
+        // Advertised to peers through Identify along with the listening addresses
+        for addr in config.announce_addresses.iter() {
+            swarm.add_external_address(addr.clone(), AddressScore::Infinite);
+        }
 
         // Subscribe to gossipsub topics with the network name suffix
         for topic in PUBSUB_TOPICS.iter() {
@@ -169,13 +257,33 @@ where
             swarm.behaviour_mut().subscribe(&t).unwrap();
         }
 
+        // Refuse connections from banned peers and dial the known-good peers first
+        let banned_peers: HashSet<_> = peer_store
+            .banned_peers()
+            .into_iter()
+            .filter(|peer_id| !gater.is_static_peer(peer_id))
+            .collect();
+        for peer_id in banned_peers.iter() {
+            swarm.ban_peer_id(*peer_id);
+        }
+        for (peer_id, addrs) in static_peers.iter() {
+            swarm.behaviour_mut().add_explicit_peer(peer_id);
+            dial_peer(&mut swarm, *peer_id, addrs.clone());
+        }
+        for (peer_id, addrs) in peer_store.dial_candidates(config.target_peer_count as usize) {
+            dial_peer(&mut swarm, peer_id, addrs);
+        }
+
         // Bootstrap with Kademlia
         if let Err(e) = swarm.behaviour_mut().bootstrap() {
             warn!("Failed to bootstrap with Kademlia: {}", e);
         }
 
+        let chain_exchange_server = Arc::new(ChainExchangeServer::new(config.chain_exchange));
+
         let (network_sender_in, network_receiver_in) = unbounded();
         let (network_sender_out, network_receiver_out) = unbounded();
+        let (upnp_sender, upnp_receiver) = unbounded();
 
         Libp2pService {
             swarm,
@@ -186,6 +294,20 @@ where
             network_sender_out,
             network_name: network_name.to_owned(),
             bitswap_response_channels: Default::default(),
+            chain_exchange_server,
+            bitswap_server: BitswapServer::new(config.bitswap),
+            peer_store,
+            banned_peers,
+            gater,
+            static_peers,
+            reachability: ReachabilityTracker::new(config.announce_addresses),
+            bandwidth,
+            peer_info: Default::default(),
+            no_announce_addrs: config.no_announce_addresses,
+            upnp: config.upnp,
+            upnp_ports: Default::default(),
+            upnp_sender,
+            upnp_receiver,
         }
     }
 
@@ -193,6 +315,7 @@ where
     pub async fn run(mut self) {
         let mut swarm_stream = self.swarm.fuse();
         let mut network_stream = self.network_receiver_in.fuse();
+        let mut upnp_stream = self.upnp_receiver.fuse();
         let mut interval = stream::interval(Duration::from_secs(15)).fuse();
         let pubsub_block_str = format!("{}/{}", PUBSUB_BLOCK_STR, self.network_name);
         let pubsub_msg_str = format!("{}/{}", PUBSUB_MSG_STR, self.network_name);
@@ -204,14 +327,35 @@ where
                     Some(event) => match event {
                         SwarmEvent::Behaviour(ForestBehaviourEvent::PeerConnected(peer_id)) => {
                             debug!("Peer connected, {:?}", peer_id);
+                            let addrs = swarm_stream.get_mut().behaviour_mut().peer_addresses().get(&peer_id).cloned().unwrap_or_default();
+                            self.peer_store.peer_seen(peer_id, addrs);
                             emit_event(&self.network_sender_out,
                                 NetworkEvent::PeerConnected(peer_id)).await;
                         }
+                        SwarmEvent::ConnectionEstablished { peer_id, endpoint, .. } => {
+                            self.gater.connection_established(peer_id, endpoint.get_remote_address());
+                            self.peer_info.connection_established(peer_id, endpoint.is_listener());
+                            match endpoint {
+                                ConnectedPoint::Listener { send_back_addr, .. } => self.reachability.inbound_connection(&send_back_addr),
+                                ConnectedPoint::Dialer { .. } => self.reachability.outbound_connection(),
+                            }
+                        }
+                        SwarmEvent::ConnectionClosed { peer_id, endpoint, num_established, .. } => {
+                            self.gater.connection_closed(&peer_id, endpoint.get_remote_address());
+                            self.peer_info.connection_closed(&peer_id, num_established);
+                            if num_established == 0 {
+                                if let Some(addrs) = self.static_peers.get(&peer_id) {
+                                    debug!("Reconnecting to static peer {}", peer_id);
+                                    dial_peer(swarm_stream.get_mut(), peer_id, addrs.clone());
+                                }
+                            }
+                        }
                         SwarmEvent::Behaviour(ForestBehaviourEvent::PeerDisconnected(peer_id)) => {
                             emit_event(&self.network_sender_out, NetworkEvent::PeerDisconnected(peer_id)).await;
                         }
                         SwarmEvent::Behaviour(ForestBehaviourEvent::GossipMessage {
                             source,
+                            message_id,
                             topic,
                             message,
                         }) => {
@@ -222,11 +366,13 @@ where
                                     Ok(b) => {
                                         emit_event(&self.network_sender_out, NetworkEvent::PubsubMessage{
                                             source,
+                                            message_id,
                                             message: PubsubMessage::Block(b),
                                         }).await;
                                     }
                                     Err(e) => {
                                         warn!("Gossip Block from peer {:?} could not be deserialized: {}", source, e);
+                                        report_validation_result(swarm_stream.get_mut(), message_id, source, MessageAcceptance::Reject);
                                     }
                                 }
                             } else if topic == pubsub_msg_str {
@@ -234,17 +380,27 @@ where
                                     Ok(m) => {
                                         emit_event(&self.network_sender_out, NetworkEvent::PubsubMessage{
                                             source,
+                                            message_id,
                                             message: PubsubMessage::Message(m),
                                         }).await;
                                     }
                                     Err(e) => {
                                         warn!("Gossip Message from peer {:?} could not be deserialized: {}", source, e);
+                                        report_validation_result(swarm_stream.get_mut(), message_id, source, MessageAcceptance::Reject);
                                     }
                                 }
                             } else {
                                 warn!("Getting gossip messages from unknown topic: {}", topic);
+                                report_validation_result(swarm_stream.get_mut(), message_id, source, MessageAcceptance::Ignore);
                             }
                         }
+                        SwarmEvent::Behaviour(ForestBehaviourEvent::PeerIdentified { peer, info }) => {
+                            self.reachability.observed_addr(peer, info.observed_addr.clone());
+                            self.peer_info.identified(&peer, info);
+                        }
+                        SwarmEvent::Behaviour(ForestBehaviourEvent::PeerPinged { peer, rtt }) => {
+                            self.peer_info.ping(&peer, rtt);
+                        }
                         SwarmEvent::Behaviour(ForestBehaviourEvent::HelloRequest { request,  peer } )=> {
                             debug!("Received hello request (peer_id: {:?})", peer);
                             emit_event(&self.network_sender_out, NetworkEvent::HelloRequest {
@@ -255,24 +411,26 @@ where
                         SwarmEvent::Behaviour(ForestBehaviourEvent::ChainExchangeRequest { channel, peer, request }) => {
                             debug!("Received chain_exchange request (peer_id: {:?})", peer);
                             let db = self.cs.clone();
+                            let server = self.chain_exchange_server.clone();
 
                             task::spawn(async move {
-                                channel.send(make_chain_exchange_response(db.as_ref(), &request).await)
+                                channel.send(server.respond(db.as_ref(), peer, &request).await)
                             });
                         }
                         SwarmEvent::Behaviour(ForestBehaviourEvent::BitswapReceivedBlock(_peer_id, cid, block)) => {
-                            let res: Result<_, String> = self.cs.blockstore().put_raw(block.into(), Blake2b256).map_err(|e| e.to_string());
-                            match res {
-                                Ok(actual_cid) => {
-                                    if actual_cid != cid {
-                                        warn!("Bitswap cid mismatch: cid {:?}, expected cid: {:?}", actual_cid, cid);
-                                    } else if let Some (chans) = self.bitswap_response_channels.remove(&cid) {
-                                            for chan in chans.into_iter(){
-                                                if chan.send(()).is_err() {
-                                                    debug!("Bitswap response channel send failed");
-                                                }
-                                                trace!("Saved Bitswap block with cid {:?}", cid);
+                            if !verify_block(&cid, &block) {
+                                warn!("Bitswap block data does not match cid {}", cid);
+                                continue;
+                            }
+                            match self.cs.blockstore().put_keyed(&cid, &block) {
+                                Ok(()) => {
+                                    if let Some(chans) = self.bitswap_response_channels.remove(&cid) {
+                                        for chan in chans.into_iter() {
+                                            if chan.send(()).is_err() {
+                                                debug!("Bitswap response channel send failed");
+                                            }
                                         }
+                                        trace!("Saved Bitswap block with cid {:?}", cid);
                                     } else {
                                         debug!("Received Bitswap response, but response channel cannot be found");
                                     }
@@ -283,9 +441,9 @@ where
                                 }
                             }
                         },
-                        SwarmEvent::Behaviour(ForestBehaviourEvent::BitswapReceivedWant(peer_id, cid,)) => match self.cs.blockstore().get_obj(&cid) {
-                            Ok(Some(data)) => {
-                                match swarm_stream.get_mut().behaviour_mut().send_block(&peer_id, cid, data) {
+                        SwarmEvent::Behaviour(ForestBehaviourEvent::BitswapReceivedWant(peer_id, cid)) => {
+                            if let Some(data) = self.bitswap_server.serve(self.cs.blockstore(), peer_id, &cid) {
+                                match swarm_stream.get_mut().behaviour_mut().send_block(&peer_id, cid, data.into()) {
                                     Ok(_) => {
                                         trace!("Sent bitswap message successfully");
                                     },
@@ -294,19 +452,26 @@ where
                                     },
                                 }
                             }
-                            Ok(None) => {
-                                trace!("Don't have data for: {}", cid);
-                            }
-                            Err(e) => {
-                                trace!("Failed to get data: {}", e.to_string());
-                            }
                         },
+                        SwarmEvent::NewListenAddr { address, .. } => {
+                            if let Some(port) = upnp::mappable_port(&address).filter(|_| self.upnp) {
+                                if self.upnp_ports.insert(port) {
+                                    task::spawn(upnp::keep_port_mapped(port, self.upnp_sender.clone()));
+                                }
+                            }
+                        }
                         _ => {
                             continue;
                         }
                     }
                     None => { break; }
                 },
+                mapped_addr = upnp_stream.next() => if let Some(addr) = mapped_addr {
+                    if !self.no_announce_addrs.contains(&addr) {
+                        swarm_stream.get_mut().add_external_address(addr.clone(), AddressScore::Finite(1));
+                        self.reachability.mapped_addr(addr);
+                    }
+                },
                 rpc_message = network_stream.next() => match rpc_message {
                     // Inbound messages
                     Some(message) =>  match message {
@@ -330,6 +495,14 @@ where
                                 self.bitswap_response_channels.insert(cid, vec![response_channel]);
                             }
                         }
+                        NetworkMessage::ValidationResult { message_id, source, acceptance } => {
+                            report_validation_result(swarm_stream.get_mut(), message_id, source, acceptance);
+                        }
+                        NetworkMessage::ApplicationScore { peer_id, score } => {
+                            if !swarm_stream.get_mut().behaviour_mut().set_application_score(&peer_id, score) {
+                                trace!("Could not set application score of peer {}", peer_id);
+                            }
+                        }
                         NetworkMessage::JSONRPCRequest { method } => {
                             match method {
                                 NetRPCMethods::NetAddrsListen(response_channel) => {
@@ -371,6 +544,62 @@ where
                                         warn!("Failed to disconnect from a peer");
                                     }
                                 }
+                                NetRPCMethods::NetBlockAdd(response_channel, entries) => {
+                                    for peer_id in self.gater.block(entries) {
+                                        debug!("Disconnecting blocked peer {}", peer_id);
+                                        let _ = Swarm::disconnect_peer_id(swarm_stream.get_mut(), peer_id);
+                                    }
+                                    let result = self.gater.block_list().save(self.cs.blockstore()).map_err(|e| e.to_string());
+                                    if response_channel.send(result).is_err() {
+                                        warn!("Failed to add to the block list");
+                                    }
+                                }
+                                NetRPCMethods::NetBlockRemove(response_channel, entries) => {
+                                    self.gater.unblock(&entries);
+                                    let result = self.gater.block_list().save(self.cs.blockstore()).map_err(|e| e.to_string());
+                                    if response_channel.send(result).is_err() {
+                                        warn!("Failed to remove from the block list");
+                                    }
+                                }
+                                NetRPCMethods::NetBlockList(response_channel) => {
+                                    if response_channel.send(self.gater.block_list()).is_err() {
+                                        warn!("Failed to get the block list");
+                                    }
+                                }
+                                NetRPCMethods::NetAutoNatStatus(response_channel) => {
+                                    if response_channel.send(self.reachability.status()).is_err() {
+                                        warn!("Failed to get the NAT status");
+                                    }
+                                }
+                                NetRPCMethods::NetBandwidthStats(response_channel) => {
+                                    if response_channel.send(self.bandwidth.stats()).is_err() {
+                                        warn!("Failed to get the bandwidth stats");
+                                    }
+                                }
+                                NetRPCMethods::NetBandwidthStatsByPeer(response_channel) => {
+                                    if response_channel.send(self.bandwidth.stats_by_peer()).is_err() {
+                                        warn!("Failed to get the bandwidth stats by peer");
+                                    }
+                                }
+                                NetRPCMethods::NetBandwidthStatsByProtocol(response_channel) => {
+                                    if response_channel.send(self.bandwidth.stats_by_protocol()).is_err() {
+                                        warn!("Failed to get the bandwidth stats by protocol");
+                                    }
+                                }
+                                NetRPCMethods::NetPeerInfo(response_channel, peer_id) => {
+                                    let score = swarm_stream.get_mut().behaviour().peer_score(&peer_id);
+                                    let info = self.peer_info.info(&peer_id, score, self.peer_store.get(&peer_id));
+                                    if response_channel.send(info).is_err() {
+                                        warn!("Failed to get the peer info");
+                                    }
+                                }
+                                NetRPCMethods::NetFindPeer(response_channel, peer_id) => {
+                                    swarm_stream.get_mut().behaviour_mut().find_peer(peer_id, response_channel);
+                                }
+                                NetRPCMethods::NetPing(response_channel, peer_id) => {
+                                    // Dropping the channel tells the caller the peer is not connected
+                                    let _ = self.peer_info.wait_for_ping(&peer_id, response_channel);
+                                }
                             }
                         }
                     }
@@ -379,9 +608,23 @@ where
                 interval_event = interval.next() => if interval_event.is_some() {
                     // Print peer count on an interval.
                     debug!("Peers connected: {}", swarm_stream.get_mut().behaviour_mut().peers().len());
+                    sync_bans(&self.peer_store, &self.gater, &mut self.banned_peers, swarm_stream.get_mut());
+                    remove_no_announce_addrs(swarm_stream.get_mut(), &self.no_announce_addrs);
+                    self.bandwidth.sample();
+                    for (peer_id, addrs) in self.static_peers.iter() {
+                        if !Swarm::is_connected(swarm_stream.get_mut(), peer_id) {
+                            dial_peer(swarm_stream.get_mut(), *peer_id, addrs.clone());
+                        }
+                    }
+                    if let Err(e) = self.peer_store.save(self.cs.blockstore()) {
+                        warn!("Failed to persist peer records: {}", e);
+                    }
                 }
             };
         }
+        if let Err(e) = self.peer_store.save(self.cs.blockstore()) {
+            warn!("Failed to persist peer records: {}", e);
+        }
     }
 
     /// Returns a sender which allows sending messages to the libp2p service.
@@ -395,17 +638,164 @@ where
     }
 }
 
+/// Removes the external addresses that must not be advertised, which the swarm adds from the
+/// addresses peers observed the node at.
+fn remove_no_announce_addrs(swarm: &mut Swarm<ForestBehaviour>, no_announce_addrs: &[Multiaddr]) {
+    let addrs: Vec<_> = swarm
+        .external_addresses()
+        .filter(|record| no_announce_addrs.contains(&record.addr))
+        .map(|record| record.addr.clone())
+        .collect();
+    for addr in addrs {
+        swarm.remove_external_address(&addr);
+    }
+}
+
+/// Dials a peer at the first of its addresses that can be dialed.
+fn dial_peer(swarm: &mut Swarm<ForestBehaviour>, peer_id: PeerId, addrs: Vec<Multiaddr>) {
+    for mut addr in addrs {
+        addr.push(Protocol::P2p(
+            Multihash::from_bytes(&peer_id.to_bytes()).unwrap(),
+        ));
+        if Swarm::dial_addr(swarm, addr).is_ok() {
+            return;
+        }
+    }
+    debug!("Failed to dial known peer {}", peer_id);
+}
+
+/// Splits a peer address ending with `/p2p/` into the peer ID and the address to dial.
+fn split_peer_addr(addr: &Multiaddr) -> Option<(PeerId, Multiaddr)> {
+    let mut addr = addr.clone();
+    match addr.pop() {
+        Some(Protocol::P2p(hash)) => PeerId::from_multihash(hash)
+            .ok()
+            .map(|peer_id| (peer_id, addr)),
+        _ => None,
+    }
+}
+
+/// Builds the connection gater from the denylist of the configuration and the block list
+/// persisted in the database.
+fn build_gater<DB: BlockStore>(
+    config: &Libp2pConfig,
+    db: &DB,
+    static_peers: &HashMap<PeerId, Vec<Multiaddr>>,
+) -> ConnectionGater {
+    let mut denied = BlockList::default();
+    for peer in config.denied_peers.iter() {
+        match peer.parse() {
+            Ok(peer_id) => {
+                denied.peers.insert(peer_id);
+            }
+            Err(e) => warn!("Invalid denied peer ID {}: {}", peer, e),
+        }
+    }
+    denied
+        .ip_subnets
+        .extend(config.denied_ip_ranges.iter().cloned());
+    let blocked = BlockList::load(db).unwrap_or_else(|e| {
+        warn!("Failed to load the block list: {}", e);
+        BlockList::default()
+    });
+    ConnectionGater::new(
+        denied,
+        blocked,
+        static_peers.keys().cloned().collect(),
+        config.max_connections_per_ip,
+    )
+}
+
+/// Applies the bans of the peer store to the swarm, lifting the ones that expired or were lifted
+/// in the peer store. Static peers are never banned.
+fn sync_bans(
+    peer_store: &PeerStore,
+    gater: &ConnectionGater,
+    banned_peers: &mut HashSet<PeerId>,
+    swarm: &mut Swarm<ForestBehaviour>,
+) {
+    peer_store.expire_bans();
+    let active: HashSet<_> = peer_store
+        .banned_peers()
+        .into_iter()
+        .filter(|peer_id| !gater.is_static_peer(peer_id))
+        .collect();
+    for peer_id in active.iter() {
+        if banned_peers.insert(*peer_id) {
+            debug!("Banning peer {}", peer_id);
+            swarm.ban_peer_id(*peer_id);
+        }
+    }
+    banned_peers.retain(|peer_id| {
+        let banned = active.contains(peer_id);
+        if !banned {
+            debug!("Ban of peer {} lifted", peer_id);
+            swarm.unban_peer_id(*peer_id);
+        }
+        banned
+    });
+}
+
+/// Reports the validation result of a GossipSub message, so that it is forwarded if accepted.
+fn report_validation_result(
+    swarm: &mut Swarm<ForestBehaviour>,
+    message_id: MessageId,
+    source: PeerId,
+    acceptance: MessageAcceptance,
+) {
+    metrics::GOSSIP_VALIDATION_TOTAL
+        .with_label_values(&[match acceptance {
+            MessageAcceptance::Accept => values::ACCEPT,
+            MessageAcceptance::Reject => values::REJECT,
+            MessageAcceptance::Ignore => values::IGNORE,
+        }])
+        .inc();
+    match swarm
+        .behaviour_mut()
+        .report_message_validation_result(&message_id, &source, acceptance)
+    {
+        Ok(true) => {}
+        Ok(false) => trace!(
+            "Validated gossip message {} is no longer cached",
+            message_id
+        ),
+        Err(e) => warn!("Failed to forward gossip message {}: {:?}", message_id, e),
+    }
+}
+
 async fn emit_event(sender: &Sender<NetworkEvent>, event: NetworkEvent) {
     if sender.send(event).await.is_err() {
         error!("Failed to emit event: Network channel receiver has been dropped");
     }
 }
 
-/// Builds the transport stack that LibP2P will communicate over.
-pub fn build_transport(local_key: Keypair) -> Boxed<(PeerId, StreamMuxerBox)> {
+/// Builds the transport stack that LibP2P will communicate over, accounting the traffic of the
+/// connections to the bandwidth meter.
+pub fn build_transport(
+    local_key: Keypair,
+    config: &TransportConfig,
+    bandwidth: Arc<BandwidthMeter>,
+) -> Boxed<(PeerId, StreamMuxerBox)> {
     let transport = libp2p::tcp::TcpConfig::new().nodelay(true);
-    let transport = libp2p::websocket::WsConfig::new(transport.clone()).or_transport(transport);
+    let websocket = if config.websocket {
+        OptionalTransport::some(libp2p::websocket::WsConfig::new(transport.clone()))
+    } else {
+        OptionalTransport::none()
+    };
+    let transport = websocket.or_transport(transport);
+    let memory = if config.memory {
+        OptionalTransport::some(MemoryTransport::default())
+    } else {
+        OptionalTransport::none()
+    };
+    let transport = memory.or_transport(transport);
     let transport = async_std::task::block_on(libp2p::dns::DnsConfig::system(transport)).unwrap();
+    let transport = transport.map(move |connection, endpoint| {
+        CountingStream::new(
+            connection,
+            bandwidth.connection(endpoint.get_remote_address()),
+        )
+    });
     let auth_config = {
         let dh_keys = noise::Keypair::<noise::X25519Spec>::new()
             .into_authentic(&local_key)
@@ -414,23 +804,29 @@ pub fn build_transport(local_key: Keypair) -> Boxed<(PeerId, StreamMuxerBox)> {
         noise::NoiseConfig::xx(dh_keys).into_authenticated()
     };
 
-    let mplex_config = {
+    let mut yamux_config = yamux::YamuxConfig::default();
+    yamux_config.set_max_buffer_size(config.yamux_max_buffer_size);
+    yamux_config.set_receive_window_size(config.yamux_receive_window_size);
+    // yamux_config.set_window_update_mode(WindowUpdateMode::OnRead);
+
+    let timeout = Duration::from_secs(config.connection_timeout_secs);
+    let transport = transport
+        .upgrade(core::upgrade::Version::V1)
+        .authenticate(auth_config);
+    if config.mplex {
         let mut mplex_config = mplex::MplexConfig::new();
         mplex_config.set_max_buffer_size(usize::MAX);
 
-        let mut yamux_config = yamux::YamuxConfig::default();
-        yamux_config.set_max_buffer_size(16 * 1024 * 1024);
-        yamux_config.set_receive_window_size(16 * 1024 * 1024);
-        // yamux_config.set_window_update_mode(WindowUpdateMode::OnRead);
-        core::upgrade::SelectUpgrade::new(yamux_config, mplex_config)
-    };
-
-    transport
-        .upgrade(core::upgrade::Version::V1)
-        .authenticate(auth_config)
-        .multiplex(mplex_config)
-        .timeout(Duration::from_secs(20))
-        .boxed()
+        transport
+            .multiplex(core::upgrade::SelectUpgrade::new(
+                yamux_config,
+                mplex_config,
+            ))
+            .timeout(timeout)
+            .boxed()
+    } else {
+        transport.multiplex(yamux_config).timeout(timeout).boxed()
+    }
 }
 
 /// Fetch keypair from disk, returning none if it cannot be decoded.
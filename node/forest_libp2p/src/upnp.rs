@@ -0,0 +1,231 @@
+// Copyright 2019-2022 ChainSafe Systems
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+use async_std::channel::Sender;
+use async_std::task;
+use igd::{Gateway, PortMappingProtocol, SearchOptions};
+use libp2p::core::Multiaddr;
+use libp2p::multiaddr::Protocol;
+use log::{debug, info};
+use std::io;
+use std::net::{Ipv4Addr, SocketAddr, SocketAddrV4, UdpSocket};
+use std::time::Duration;
+
+/// Lease of the port mappings requested from the gateway. Mappings are renewed after half of it.
+const LEASE_DURATION: Duration = Duration::from_secs(60 * 60);
+
+/// Delay before searching the gateway again after the port could not be mapped.
+const RETRY_INTERVAL: Duration = Duration::from_secs(5 * 60);
+
+const MAPPING_DESCRIPTION: &str = "forest";
+
+/// Returns the TCP port of a listening address that can be mapped on a UPnP gateway.
+pub(crate) fn mappable_port(addr: &Multiaddr) -> Option<u16> {
+    let mut protocols = addr.iter();
+    match (protocols.next(), protocols.next(), protocols.next()) {
+        (Some(Protocol::Ip4(ip)), Some(Protocol::Tcp(port)), None) if !ip.is_loopback() => {
+            Some(port)
+        }
+        _ => None,
+    }
+}
+
+/// Keeps the TCP port mapped on the UPnP gateway of the local network, sending the external
+/// address of the node each time the mapping is renewed. Resolves when the receiver is dropped.
+pub(crate) async fn keep_port_mapped(port: u16, sender: Sender<Multiaddr>) {
+    loop {
+        let mapped = task::spawn_blocking(move || -> Result<_, anyhow::Error> {
+            let gateway = igd::search_gateway(SearchOptions::default())?;
+            map_port(&gateway, port)
+        })
+        .await;
+        let delay = match mapped {
+            Ok(addr) => {
+                info!("Mapped port {} on the UPnP gateway to {}", port, addr);
+                if sender.send(addr).await.is_err() {
+                    return;
+                }
+                LEASE_DURATION / 2
+            }
+            Err(e) => {
+                debug!("Failed to map port {} on a UPnP gateway: {}", port, e);
+                RETRY_INTERVAL
+            }
+        };
+        task::sleep(delay).await;
+    }
+}
+
+/// Maps the TCP port on the gateway to the same port of the local address the gateway is
+/// reached from, and returns the external address of the mapping.
+pub(crate) fn map_port(gateway: &Gateway, port: u16) -> Result<Multiaddr, anyhow::Error> {
+    let local_ip = local_ip_towards(gateway.addr)?;
+    gateway.add_port(
+        PortMappingProtocol::TCP,
+        port,
+        SocketAddrV4::new(local_ip, port),
+        LEASE_DURATION.as_secs() as u32,
+        MAPPING_DESCRIPTION,
+    )?;
+    let external_ip = gateway.get_external_ip()?;
+    Ok(Multiaddr::empty()
+        .with(Protocol::Ip4(external_ip))
+        .with(Protocol::Tcp(port)))
+}
+
+/// Returns the local IP address packets to the gateway are sent from.
+fn local_ip_towards(gateway: SocketAddrV4) -> io::Result<Ipv4Addr> {
+    let socket = UdpSocket::bind((Ipv4Addr::UNSPECIFIED, 0))?;
+    socket.connect(gateway)?;
+    match socket.local_addr()? {
+        SocketAddr::V4(addr) => Ok(*addr.ip()),
+        SocketAddr::V6(addr) => Err(io::Error::new(
+            io::ErrorKind::AddrNotAvailable,
+            format!("no IPv4 address to reach the gateway from, got {}", addr),
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+    use std::io::{BufRead, BufReader, Read, Write};
+    use std::net::TcpListener;
+    use std::sync::mpsc;
+    use std::thread;
+
+    /// Serves SOAP requests like a gateway would, answering each action with the given response
+    /// body and sending the received requests back.
+    fn mock_gateway(responses: HashMap<&'static str, String>) -> (Gateway, mpsc::Receiver<String>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = match listener.local_addr().unwrap() {
+            SocketAddr::V4(addr) => addr,
+            SocketAddr::V6(_) => unreachable!(),
+        };
+        let (requests, received) = mpsc::channel();
+        thread::spawn(move || {
+            for stream in listener.incoming() {
+                let mut stream = stream.unwrap();
+                let mut reader = BufReader::new(stream.try_clone().unwrap());
+                let (mut action, mut length) = (String::new(), 0);
+                loop {
+                    let mut line = String::new();
+                    reader.read_line(&mut line).unwrap();
+                    let line = line.trim_end();
+                    if line.is_empty() {
+                        break;
+                    }
+                    if let Some((name, value)) = line.split_once(':') {
+                        match name.to_lowercase().as_str() {
+                            "soapaction" => action = value.trim().to_owned(),
+                            "content-length" => length = value.trim().parse().unwrap(),
+                            _ => {}
+                        }
+                    }
+                }
+                let mut body = vec![0; length];
+                reader.read_exact(&mut body).unwrap();
+
+                let name = action
+                    .trim_matches('"')
+                    .rsplit('#')
+                    .next()
+                    .unwrap()
+                    .to_owned();
+                let response = format!(
+                    r#"<?xml version="1.0"?><s:Envelope xmlns:s="http://schemas.xmlsoap.org/soap/envelope/"><s:Body>{}</s:Body></s:Envelope>"#,
+                    responses[name.as_str()]
+                );
+                write!(
+                    stream,
+                    "HTTP/1.1 200 OK\r\nContent-Type: text/xml\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                    response.len(),
+                    response
+                )
+                .unwrap();
+                let _ = requests.send(String::from_utf8(body).unwrap());
+            }
+        });
+
+        let arguments = [
+            "NewRemoteHost",
+            "NewExternalPort",
+            "NewProtocol",
+            "NewInternalPort",
+            "NewInternalClient",
+            "NewEnabled",
+            "NewPortMappingDescription",
+            "NewLeaseDuration",
+        ];
+        let gateway = Gateway {
+            addr,
+            root_url: "/rootDesc.xml".to_owned(),
+            control_url: "/ctl/IPConn".to_owned(),
+            control_schema_url: "/WANIPCn.xml".to_owned(),
+            control_schema: HashMap::from([(
+                "AddPortMapping".to_owned(),
+                arguments.iter().map(|arg| arg.to_string()).collect(),
+            )]),
+        };
+        (gateway, received)
+    }
+
+    fn external_ip_response() -> String {
+        r#"<u:GetExternalIPAddressResponse xmlns:u="urn:schemas-upnp-org:service:WANIPConnection:1"><NewExternalIPAddress>1.2.3.4</NewExternalIPAddress></u:GetExternalIPAddressResponse>"#.to_owned()
+    }
+
+    #[test]
+    fn maps_port_on_gateway() {
+        let (gateway, requests) = mock_gateway(HashMap::from([
+            (
+                "AddPortMapping",
+                r#"<u:AddPortMappingResponse xmlns:u="urn:schemas-upnp-org:service:WANIPConnection:1"/>"#.to_owned(),
+            ),
+            ("GetExternalIPAddress", external_ip_response()),
+        ]));
+
+        let addr = map_port(&gateway, 1347).unwrap();
+        assert_eq!(addr, "/ip4/1.2.3.4/tcp/1347".parse().unwrap());
+
+        let mapping = requests.recv().unwrap();
+        for argument in [
+            "<NewExternalPort>1347</NewExternalPort>",
+            "<NewInternalPort>1347</NewInternalPort>",
+            "<NewInternalClient>127.0.0.1</NewInternalClient>",
+            "<NewProtocol>TCP</NewProtocol>",
+            "<NewLeaseDuration>3600</NewLeaseDuration>",
+        ] {
+            assert!(
+                mapping.contains(argument),
+                "{} not in {}",
+                argument,
+                mapping
+            );
+        }
+    }
+
+    #[test]
+    fn reports_refused_mappings() {
+        let (gateway, _requests) = mock_gateway(HashMap::from([
+            (
+                "AddPortMapping",
+                r#"<s:Fault><faultcode>s:Client</faultcode><faultstring>UPnPError</faultstring><detail><UPnPError xmlns="urn:schemas-upnp-org:control-1-0"><errorCode>718</errorCode><errorDescription>ConflictInMappingEntry</errorDescription></UPnPError></detail></s:Fault>"#.to_owned(),
+            ),
+            ("GetExternalIPAddress", external_ip_response()),
+        ]));
+
+        assert!(map_port(&gateway, 1347).is_err());
+    }
+
+    #[test]
+    fn mappable_ports() {
+        let port = |addr: &str| mappable_port(&addr.parse().unwrap());
+        assert_eq!(port("/ip4/0.0.0.0/tcp/1347"), Some(1347));
+        assert_eq!(port("/ip4/192.168.1.2/tcp/1347"), Some(1347));
+        assert_eq!(port("/ip4/127.0.0.1/tcp/1347"), None);
+        assert_eq!(port("/ip4/0.0.0.0/tcp/1347/ws"), None);
+        assert_eq!(port("/ip6/::/tcp/1347"), None);
+        assert_eq!(port("/memory/1347"), None);
+    }
+}
@@ -2,15 +2,16 @@
 // SPDX-License-Identifier: Apache-2.0, MIT
 
 use async_std::channel::Sender;
-use async_std::sync::{Arc, RwLock};
+use async_std::sync::{Arc, Mutex, RwLock};
 use beacon::BeaconEntry;
 use fil_types::SectorSize;
 use jsonrpc_v2::{MapRouter as JsonRpcMapRouter, Server as JsonRpcServer};
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 
 use actor::market::{DealProposal, DealState};
 use beacon::{json::BeaconEntryJson, Beacon, BeaconSchedule};
-use chain::{headchange_json::SubscriptionHeadChange, ChainStore};
+use chain::ChainStore;
 use chain_sync::{BadBlockCache, SyncState};
 use cid::Cid;
 use fil_types::{json::SectorInfoJson, sector::post::json::PoStProofJson};
@@ -35,16 +36,16 @@ use fvm_shared::bigint::BigInt;
 use fvm_shared::clock::ChainEpoch;
 use fvm_shared::message::Message;
 use ipld_blockstore::BlockStore;
-use key_management::KeyStore;
+use key_management::{KeyStore, Signer};
 use message_pool::{MessagePool, MpoolRpcProvider};
 use state_manager::{MiningBaseInfo, StateManager};
 
 // RPC State
 #[derive(Serialize)]
-pub struct StreamingData<'a> {
+pub struct StreamingData<'a, T> {
     pub json_rpc: &'a str,
     pub method: &'a str,
-    pub params: SubscriptionHeadChange,
+    pub params: T,
 }
 
 /// This is where you store persistent data, or at least access to stateful data.
@@ -54,9 +55,12 @@ where
     B: Beacon + Send + Sync + 'static,
 {
     pub keystore: Arc<RwLock<KeyStore>>,
+    pub signer: Arc<dyn Signer>,
     pub chain_store: Arc<ChainStore<DB>>,
     pub state_manager: Arc<StateManager<DB>>,
     pub mpool: Arc<MessagePool<MpoolRpcProvider<DB>>>,
+    /// Serializes the nonce assignment of the messages signed by the node.
+    pub mpool_push_lock: Mutex<()>,
     pub bad_blocks: Arc<BadBlockCache>,
     pub sync_state: Arc<RwLock<SyncState>>,
     pub network_send: Sender<NetworkMessage>,
@@ -91,6 +95,70 @@ pub struct MessageSendSpec {
     max_fee: TokenAmount,
 }
 
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct MessagePrototype {
+    #[serde(with = "forest_message::message::json")]
+    pub message: Message,
+    /// Whether the nonce of the message is set and should be checked
+    pub valid_nonce: bool,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct MessageCheckStatus {
+    #[serde(with = "forest_json::cid")]
+    pub cid: Cid,
+    pub code: u64,
+    #[serde(rename = "OK")]
+    pub ok: bool,
+    pub err: String,
+    pub hint: HashMap<String, String>,
+}
+
+impl From<message_pool::MessageCheckStatus> for MessageCheckStatus {
+    fn from(status: message_pool::MessageCheckStatus) -> Self {
+        Self {
+            cid: status.cid,
+            code: status.code as u64,
+            ok: status.ok,
+            err: status.err,
+            hint: status.hint,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct MpoolUpdateJson {
+    /// 0 when the message was added to the pool, 1 when it was removed
+    #[serde(rename = "Type")]
+    pub update_type: u8,
+    pub message: SignedMessageJson,
+}
+
+impl From<message_pool::MpoolUpdate> for MpoolUpdateJson {
+    fn from(update: message_pool::MpoolUpdate) -> Self {
+        Self {
+            update_type: update.update_type as u8,
+            message: SignedMessageJson(update.message),
+        }
+    }
+}
+
+// Gas API
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct FeeHistoryEntry {
+    pub epoch: ChainEpoch,
+    #[serde(with = "json")]
+    pub base_fee: TokenAmount,
+    /// Gas limit of the included messages relative to the gas target of the tipset's blocks
+    pub gas_target_ratio: f64,
+    /// Gas premiums at the requested percentiles, in attoFIL
+    pub premiums: Vec<String>,
+}
+
 // State API
 #[derive(Serialize)]
 #[serde(rename_all = "PascalCase")]
@@ -162,7 +230,7 @@ pub struct MarketDeal {
     pub state: DealState,
 }
 
-#[derive(Serialize)]
+#[derive(Serialize, Deserialize)]
 #[serde(rename_all = "PascalCase")]
 pub struct MessageLookup {
     pub receipt: MessageReceiptJson,
@@ -173,6 +241,21 @@ pub struct MessageLookup {
     pub return_dec: IpldJson,
 }
 
+// Multisig API
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct MsigTransaction {
+    #[serde(rename = "ID")]
+    pub id: i64,
+    pub to: AddressJson,
+    #[serde(with = "json")]
+    pub value: TokenAmount,
+    pub method: u64,
+    /// Base64 encoded method parameters
+    pub params: String,
+    pub approved: Vec<AddressJson>,
+}
+
 #[derive(Deserialize)]
 #[serde(rename_all = "PascalCase")]
 pub struct BlockTemplate {
@@ -236,6 +319,66 @@ pub struct AddrInfo {
     pub addrs: Vec<Multiaddr>,
 }
 
+/// Peers, IP addresses and IP subnets refused connections by the node.
+#[derive(Serialize, Deserialize, Default)]
+#[serde(rename_all = "PascalCase", default)]
+pub struct NetBlockList {
+    pub peers: Vec<String>,
+    #[serde(rename = "IPAddrs")]
+    pub ip_addrs: Vec<String>,
+    #[serde(rename = "IPSubnets")]
+    pub ip_subnets: Vec<String>,
+}
+
+/// Reachability of the node, as detected from the connections peers open to it.
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct NatInfo {
+    /// Unknown (0), public (1) or private (2).
+    pub reachability: i32,
+    /// Address the node can be dialed at, empty if unknown.
+    pub public_addr: String,
+}
+
+/// Bandwidth used by the node, a peer or a protocol, in bytes and bytes per second.
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct BandwidthStats {
+    pub total_in: u64,
+    pub total_out: u64,
+    pub rate_in: f64,
+    pub rate_out: f64,
+}
+
+/// Connection, Identify and scoring details of a connected peer. Durations are in nanoseconds.
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct ExtendedPeerInfo {
+    #[serde(rename = "ID")]
+    pub id: String,
+    /// Agent version announced by the peer, empty if it was not identified yet.
+    pub agent: String,
+    pub addrs: Vec<String>,
+    pub protocols: Vec<String>,
+    /// Round trip time of the last ping, zero if the peer was not pinged yet.
+    pub latency: u64,
+    /// True if the peer opened the connection to the node.
+    pub inbound: bool,
+    pub connected_for: u64,
+    pub gossip_score: Option<f64>,
+    pub chain_exchange: ChainExchangeStats,
+}
+
+/// Chain exchange requests made to a peer.
+#[derive(Serialize, Deserialize, Default)]
+#[serde(rename_all = "PascalCase")]
+pub struct ChainExchangeStats {
+    pub successes: u32,
+    pub failures: u32,
+    /// Average response time in nanoseconds.
+    pub average_time: u64,
+}
+
 #[derive(Serialize, Deserialize)]
 pub struct PeerID {
     pub multihash: Multihash,
@@ -43,12 +43,26 @@ pub static ACCESS_MAP: Lazy<HashMap<&str, Access>> = Lazy::new(|| {
     access.insert(chain_api::CHAIN_GET_RANDOMNESS_FROM_BEACON, Access::Read);
 
     // Message Pool API
+    access.insert(mpool_api::MPOOL_CHECK_MESSAGES, Access::Read);
+    access.insert(mpool_api::MPOOL_CHECK_PENDING_MESSAGES, Access::Read);
     access.insert(mpool_api::MPOOL_ESTIMATE_GAS_PRICE, Access::Read);
     access.insert(mpool_api::MPOOL_GET_NONCE, Access::Read);
     access.insert(mpool_api::MPOOL_PENDING, Access::Read);
     access.insert(mpool_api::MPOOL_PUSH, Access::Write);
     access.insert(mpool_api::MPOOL_PUSH_MESSAGE, Access::Sign);
+    access.insert(mpool_api::MPOOL_REPLACE, Access::Sign);
     access.insert(mpool_api::MPOOL_SELECT, Access::Read);
+    access.insert(mpool_api::MPOOL_SNAPSHOT, Access::Read);
+    access.insert(mpool_api::MPOOL_SUB, Access::Read);
+    access.insert(mpool_api::MPOOL_UPDATES, Access::Read);
+
+    // Multisig API
+    access.insert(msig_api::MSIG_CREATE, Access::Sign);
+    access.insert(msig_api::MSIG_PROPOSE, Access::Sign);
+    access.insert(msig_api::MSIG_APPROVE, Access::Sign);
+    access.insert(msig_api::MSIG_CANCEL, Access::Sign);
+    access.insert(msig_api::MSIG_GET_PENDING, Access::Read);
+    access.insert(msig_api::MSIG_GET_VESTED, Access::Read);
 
     // Sync API
     access.insert(sync_api::SYNC_CHECK_BAD, Access::Read);
@@ -110,6 +124,7 @@ pub static ACCESS_MAP: Lazy<HashMap<&str, Access>> = Lazy::new(|| {
     access.insert(gas_api::GAS_ESTIMATE_GAS_PREMIUM, Access::Read);
     access.insert(gas_api::GAS_ESTIMATE_FEE_CAP, Access::Read);
     access.insert(gas_api::GAS_ESTIMATE_MESSAGE_GAS, Access::Read);
+    access.insert(gas_api::GAS_ESTIMATE_FEE_HISTORY, Access::Read);
 
     // Common API
     access.insert(common_api::VERSION, Access::Read);
@@ -119,6 +134,18 @@ pub static ACCESS_MAP: Lazy<HashMap<&str, Access>> = Lazy::new(|| {
     access.insert(net_api::NET_PEERS, Access::Read);
     access.insert(net_api::NET_CONNECT, Access::Write);
     access.insert(net_api::NET_DISCONNECT, Access::Write);
+    access.insert(net_api::NET_BLOCK_ADD, Access::Admin);
+    access.insert(net_api::NET_BLOCK_REMOVE, Access::Admin);
+    access.insert(net_api::NET_BLOCK_LIST, Access::Read);
+    access.insert(net_api::NET_AUTO_NAT_STATUS, Access::Read);
+    access.insert(net_api::NET_BITSWAP_FETCH, Access::Write);
+    access.insert(net_api::NET_BANDWIDTH_STATS, Access::Read);
+    access.insert(net_api::NET_BANDWIDTH_STATS_BY_PEER, Access::Read);
+    access.insert(net_api::NET_BANDWIDTH_STATS_BY_PROTOCOL, Access::Read);
+    access.insert(net_api::NET_PEER_INFO, Access::Read);
+    access.insert(net_api::NET_AGENT_VERSION, Access::Read);
+    access.insert(net_api::NET_PING, Access::Read);
+    access.insert(net_api::NET_FIND_PEER, Access::Read);
 
     access
 });
@@ -232,11 +259,22 @@ pub mod chain_api {
 
 /// Message Pool API
 pub mod mpool_api {
-    use crate::data_types::MessageSendSpec;
+    use crate::data_types::{
+        MessageCheckStatus, MessagePrototype, MessageSendSpec, MpoolUpdateJson,
+    };
     use forest_blocks::{tipset_keys_json::TipsetKeysJson, TipsetKeys};
+    use forest_json::address::json::AddressJson;
     use forest_json::cid::CidJson;
     use forest_message::{message::json::MessageJson, signed_message::json::SignedMessageJson};
 
+    pub const MPOOL_CHECK_MESSAGES: &str = "Filecoin.MpoolCheckMessages";
+    pub type MpoolCheckMessagesParams = (Vec<MessagePrototype>,);
+    pub type MpoolCheckMessagesResult = Vec<Vec<MessageCheckStatus>>;
+
+    pub const MPOOL_CHECK_PENDING_MESSAGES: &str = "Filecoin.MpoolCheckPendingMessages";
+    pub type MpoolCheckPendingMessagesParams = (AddressJson,);
+    pub type MpoolCheckPendingMessagesResult = Vec<Vec<MessageCheckStatus>>;
+
     pub const MPOOL_ESTIMATE_GAS_PRICE: &str = "Filecoin.MpoolEstimateGasPrice";
     pub type MpoolEstimateGasPriceParams = (u64, String, u64, TipsetKeys);
     pub type MpoolEstimateGasPriceResult = String;
@@ -260,13 +298,67 @@ pub mod mpool_api {
     pub type MpoolPushMessageParams = (MessageJson, Option<MessageSendSpec>);
     pub type MpoolPushMessageResult = SignedMessageJson;
 
+    pub const MPOOL_REPLACE: &str = "Filecoin.MpoolReplace";
+    /// Sender, nonce, whether to re-estimate the fees, and gas premium and gas fee cap in attoFIL.
+    /// The premium is required unless the fees are re-estimated
+    pub type MpoolReplaceParams = (AddressJson, u64, bool, Option<String>, Option<String>);
+    pub type MpoolReplaceResult = SignedMessageJson;
+
     pub const MPOOL_SELECT: &str = "Filecoin.MpoolSelect";
     pub type MpoolSelectParams = (TipsetKeysJson, f64);
     pub type MpoolSelectResult = Vec<SignedMessageJson>;
 
+    pub const MPOOL_SNAPSHOT: &str = "Filecoin.MpoolSnapshot";
+    pub type MpoolSnapshotParams = ();
+    /// CBOR encoded snapshot, in base64
+    pub type MpoolSnapshotResult = String;
+
+    pub const MPOOL_SUB: &str = "Filecoin.MpoolSub";
+    /// Senders to stream updates of, updates of every sender are streamed when empty
+    pub type MpoolSubParams = Vec<AddressJson>;
+    pub type MpoolSubResult = i64;
+
     pub const MPOOL_UPDATES: &str = "Filecoin.MpoolUpdates";
     pub type MpoolUpdatesParams = ();
-    pub type MpoolUpdatesResult = ();
+    pub type MpoolUpdatesResult = (i64, MpoolUpdateJson);
+}
+
+/// Multisig API
+pub mod msig_api {
+    use crate::data_types::MsigTransaction;
+    use forest_blocks::tipset_keys_json::TipsetKeysJson;
+    use forest_json::address::json::AddressJson;
+    use forest_json::cid::CidJson;
+    use fvm_shared::clock::ChainEpoch;
+
+    pub const MSIG_CREATE: &str = "Filecoin.MsigCreate";
+    /// Required approvals, signers, unlock duration, initial balance and sender
+    pub type MsigCreateParams = (u64, Vec<AddressJson>, ChainEpoch, String, AddressJson);
+    pub type MsigCreateResult = CidJson;
+
+    pub const MSIG_PROPOSE: &str = "Filecoin.MsigPropose";
+    /// Multisig, recipient, value, proposer, method number and base64 encoded method params
+    pub type MsigProposeParams = (AddressJson, AddressJson, String, AddressJson, u64, String);
+    pub type MsigProposeResult = CidJson;
+
+    pub const MSIG_APPROVE: &str = "Filecoin.MsigApprove";
+    /// Multisig, transaction ID and approver
+    pub type MsigApproveParams = (AddressJson, i64, AddressJson);
+    pub type MsigApproveResult = CidJson;
+
+    pub const MSIG_CANCEL: &str = "Filecoin.MsigCancel";
+    /// Multisig, transaction ID and canceller
+    pub type MsigCancelParams = (AddressJson, i64, AddressJson);
+    pub type MsigCancelResult = CidJson;
+
+    pub const MSIG_GET_PENDING: &str = "Filecoin.MsigGetPending";
+    pub type MsigGetPendingParams = (AddressJson, TipsetKeysJson);
+    pub type MsigGetPendingResult = Vec<MsigTransaction>;
+
+    pub const MSIG_GET_VESTED: &str = "Filecoin.MsigGetVested";
+    /// Multisig, start and end tipsets
+    pub type MsigGetVestedParams = (AddressJson, TipsetKeysJson, TipsetKeysJson);
+    pub type MsigGetVestedResult = String;
 }
 
 /// Sync API
@@ -485,7 +577,7 @@ pub mod state_api {
 
 /// Gas API
 pub mod gas_api {
-    use crate::data_types::MessageSendSpec;
+    use crate::data_types::{FeeHistoryEntry, MessageSendSpec};
     use forest_blocks::tipset_keys_json::TipsetKeysJson;
     use forest_json::address::json::AddressJson;
     use forest_message::message::json::MessageJson;
@@ -505,6 +597,11 @@ pub mod gas_api {
     pub const GAS_ESTIMATE_MESSAGE_GAS: &str = "Filecoin.GasEstimateMessageGas";
     pub type GasEstimateMessageGasParams = (MessageJson, Option<MessageSendSpec>, TipsetKeysJson);
     pub type GasEstimateMessageGasResult = MessageJson;
+
+    pub const GAS_ESTIMATE_FEE_HISTORY: &str = "Filecoin.GasEstimateFeeHistory";
+    /// Number of epochs, last tipset and premium percentiles
+    pub type GasEstimateFeeHistoryParams = (u64, TipsetKeysJson, Vec<f64>);
+    pub type GasEstimateFeeHistoryResult = Vec<FeeHistoryEntry>;
 }
 
 /// Common API
@@ -518,7 +615,9 @@ pub mod common_api {
 
 /// Net API
 pub mod net_api {
-    use crate::data_types::AddrInfo;
+    use crate::data_types::{AddrInfo, BandwidthStats, ExtendedPeerInfo, NatInfo, NetBlockList};
+    use forest_json::cid::CidJson;
+    use std::collections::HashMap;
 
     pub const NET_ADDRS_LISTEN: &str = "Filecoin.NetAddrsListen";
     pub type NetAddrsListenParams = ();
@@ -535,4 +634,55 @@ pub mod net_api {
     pub const NET_DISCONNECT: &str = "Filecoin.NetDisconnect";
     pub type NetDisconnectParams = (String,);
     pub type NetDisconnectResult = ();
+
+    pub const NET_BLOCK_ADD: &str = "Filecoin.NetBlockAdd";
+    pub type NetBlockAddParams = (NetBlockList,);
+    pub type NetBlockAddResult = ();
+
+    pub const NET_BLOCK_REMOVE: &str = "Filecoin.NetBlockRemove";
+    pub type NetBlockRemoveParams = (NetBlockList,);
+    pub type NetBlockRemoveResult = ();
+
+    pub const NET_BLOCK_LIST: &str = "Filecoin.NetBlockList";
+    pub type NetBlockListParams = ();
+    pub type NetBlockListResult = NetBlockList;
+
+    pub const NET_AUTO_NAT_STATUS: &str = "Filecoin.NetAutoNatStatus";
+    pub type NetAutoNatStatusParams = ();
+    pub type NetAutoNatStatusResult = NatInfo;
+
+    pub const NET_BITSWAP_FETCH: &str = "Filecoin.NetBitswapFetch";
+    pub type NetBitswapFetchParams = (CidJson,);
+    pub type NetBitswapFetchResult = ();
+
+    pub const NET_BANDWIDTH_STATS: &str = "Filecoin.NetBandwidthStats";
+    pub type NetBandwidthStatsParams = ();
+    pub type NetBandwidthStatsResult = BandwidthStats;
+
+    pub const NET_BANDWIDTH_STATS_BY_PEER: &str = "Filecoin.NetBandwidthStatsByPeer";
+    pub type NetBandwidthStatsByPeerParams = ();
+    pub type NetBandwidthStatsByPeerResult = HashMap<String, BandwidthStats>;
+
+    /// Payload bytes exchanged over the hello, chain exchange, GossipSub and bitswap protocols.
+    /// Kademlia, Identify, ping and framing overhead are not counted.
+    pub const NET_BANDWIDTH_STATS_BY_PROTOCOL: &str = "Filecoin.NetBandwidthStatsByProtocol";
+    pub type NetBandwidthStatsByProtocolParams = ();
+    pub type NetBandwidthStatsByProtocolResult = HashMap<String, BandwidthStats>;
+
+    pub const NET_PEER_INFO: &str = "Filecoin.NetPeerInfo";
+    pub type NetPeerInfoParams = (String,);
+    pub type NetPeerInfoResult = ExtendedPeerInfo;
+
+    pub const NET_AGENT_VERSION: &str = "Filecoin.NetAgentVersion";
+    pub type NetAgentVersionParams = (String,);
+    pub type NetAgentVersionResult = String;
+
+    /// Round trip time in nanoseconds.
+    pub const NET_PING: &str = "Filecoin.NetPing";
+    pub type NetPingParams = (String,);
+    pub type NetPingResult = u64;
+
+    pub const NET_FIND_PEER: &str = "Filecoin.NetFindPeer";
+    pub type NetFindPeerParams = (String,);
+    pub type NetFindPeerResult = AddrInfo;
 }
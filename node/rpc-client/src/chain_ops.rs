@@ -28,3 +28,9 @@ pub async fn chain_read_obj(cid: ChainReadObjParams) -> Result<ChainReadObjResul
 pub async fn chain_get_tipset(keys: ChainGetTipSetParams) -> Result<ChainGetTipSetResult, Error> {
     call(CHAIN_GET_TIPSET, keys).await
 }
+
+pub async fn chain_get_tipset_by_height(
+    params: ChainGetTipsetByHeightParams,
+) -> Result<ChainGetTipsetByHeightResult, Error> {
+    call(CHAIN_GET_TIPSET_BY_HEIGHT, params).await
+}
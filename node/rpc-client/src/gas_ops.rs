@@ -0,0 +1,12 @@
+// Copyright 2019-2022 ChainSafe Systems
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+use crate::call;
+use jsonrpc_v2::Error;
+use rpc_api::gas_api::*;
+
+pub async fn gas_estimate_message_gas(
+    params: GasEstimateMessageGasParams,
+) -> Result<GasEstimateMessageGasResult, Error> {
+    call(GAS_ESTIMATE_MESSAGE_GAS, params).await
+}
@@ -8,3 +8,25 @@ use rpc_api::mpool_api::*;
 pub async fn mpool_pending(params: MpoolPendingParams) -> Result<MpoolPendingResult, Error> {
     call(MPOOL_PENDING, params).await
 }
+
+pub async fn mpool_get_nonce(params: MpoolGetNonceParams) -> Result<MpoolGetNonceResult, Error> {
+    call(MPOOL_GET_NONCE, params).await
+}
+
+pub async fn mpool_push(params: MpoolPushParams) -> Result<MpoolPushResult, Error> {
+    call(MPOOL_PUSH, params).await
+}
+
+pub async fn mpool_push_message(
+    params: MpoolPushMessageParams,
+) -> Result<MpoolPushMessageResult, Error> {
+    call(MPOOL_PUSH_MESSAGE, params).await
+}
+
+pub async fn mpool_snapshot(params: MpoolSnapshotParams) -> Result<MpoolSnapshotResult, Error> {
+    call(MPOOL_SNAPSHOT, params).await
+}
+
+pub async fn mpool_replace(params: MpoolReplaceParams) -> Result<MpoolReplaceResult, Error> {
+    call(MPOOL_REPLACE, params).await
+}
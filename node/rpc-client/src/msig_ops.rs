@@ -0,0 +1,30 @@
+// Copyright 2019-2022 ChainSafe Systems
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+use crate::call;
+use jsonrpc_v2::Error;
+use rpc_api::msig_api::*;
+
+pub async fn msig_create(params: MsigCreateParams) -> Result<MsigCreateResult, Error> {
+    call(MSIG_CREATE, params).await
+}
+
+pub async fn msig_propose(params: MsigProposeParams) -> Result<MsigProposeResult, Error> {
+    call(MSIG_PROPOSE, params).await
+}
+
+pub async fn msig_approve(params: MsigApproveParams) -> Result<MsigApproveResult, Error> {
+    call(MSIG_APPROVE, params).await
+}
+
+pub async fn msig_cancel(params: MsigCancelParams) -> Result<MsigCancelResult, Error> {
+    call(MSIG_CANCEL, params).await
+}
+
+pub async fn msig_get_pending(params: MsigGetPendingParams) -> Result<MsigGetPendingResult, Error> {
+    call(MSIG_GET_PENDING, params).await
+}
+
+pub async fn msig_get_vested(params: MsigGetVestedParams) -> Result<MsigGetVestedResult, Error> {
+    call(MSIG_GET_VESTED, params).await
+}
@@ -20,3 +20,63 @@ pub async fn net_connect(params: NetConnectParams) -> Result<NetConnectResult, E
 pub async fn net_disconnect(params: NetDisconnectParams) -> Result<NetDisconnectResult, Error> {
     call(NET_DISCONNECT, params).await
 }
+
+pub async fn net_block_add(params: NetBlockAddParams) -> Result<NetBlockAddResult, Error> {
+    call(NET_BLOCK_ADD, params).await
+}
+
+pub async fn net_block_remove(params: NetBlockRemoveParams) -> Result<NetBlockRemoveResult, Error> {
+    call(NET_BLOCK_REMOVE, params).await
+}
+
+pub async fn net_block_list(params: NetBlockListParams) -> Result<NetBlockListResult, Error> {
+    call(NET_BLOCK_LIST, params).await
+}
+
+pub async fn net_auto_nat_status(
+    params: NetAutoNatStatusParams,
+) -> Result<NetAutoNatStatusResult, Error> {
+    call(NET_AUTO_NAT_STATUS, params).await
+}
+
+pub async fn net_bitswap_fetch(
+    params: NetBitswapFetchParams,
+) -> Result<NetBitswapFetchResult, Error> {
+    call(NET_BITSWAP_FETCH, params).await
+}
+
+pub async fn net_bandwidth_stats(
+    params: NetBandwidthStatsParams,
+) -> Result<NetBandwidthStatsResult, Error> {
+    call(NET_BANDWIDTH_STATS, params).await
+}
+
+pub async fn net_bandwidth_stats_by_peer(
+    params: NetBandwidthStatsByPeerParams,
+) -> Result<NetBandwidthStatsByPeerResult, Error> {
+    call(NET_BANDWIDTH_STATS_BY_PEER, params).await
+}
+
+pub async fn net_bandwidth_stats_by_protocol(
+    params: NetBandwidthStatsByProtocolParams,
+) -> Result<NetBandwidthStatsByProtocolResult, Error> {
+    call(NET_BANDWIDTH_STATS_BY_PROTOCOL, params).await
+}
+
+pub async fn net_peer_info(params: NetPeerInfoParams) -> Result<NetPeerInfoResult, Error> {
+    call(NET_PEER_INFO, params).await
+}
+
+pub async fn net_agent_version(
+    params: NetAgentVersionParams,
+) -> Result<NetAgentVersionResult, Error> {
+    call(NET_AGENT_VERSION, params).await
+}
+
+pub async fn net_find_peer(params: NetFindPeerParams) -> Result<NetFindPeerResult, Error> {
+    call(NET_FIND_PEER, params).await
+}
+
+pub async fn net_ping(params: NetPingParams) -> Result<NetPingResult, Error> {
+    call(NET_PING, params).await
+}
@@ -30,3 +30,7 @@ pub async fn state_account_key(
 ) -> Result<StateAccountKeyResult, Error> {
     call(STATE_ACCOUNT_KEY, params).await
 }
+
+pub async fn state_wait_msg(params: StateWaitMsgParams) -> Result<StateWaitMsgResult, Error> {
+    call(STATE_WAIT_MSG, params).await
+}
@@ -46,3 +46,9 @@ pub async fn wallet_sign(message: WalletSignParams) -> Result<WalletSignResult,
 pub async fn wallet_verify(message: WalletVerifyParams) -> Result<WalletVerifyResult, Error> {
     call(WALLET_VERIFY, message).await
 }
+
+pub async fn wallet_sign_message(
+    params: WalletSignMessageParams,
+) -> Result<WalletSignMessageResult, Error> {
+    call(WALLET_SIGN_MESSAGE, params).await
+}
@@ -8,18 +8,21 @@ use rand_distr::{Distribution, Normal};
 use beacon::Beacon;
 use chain::{BASE_FEE_MAX_CHANGE_DENOM, BLOCK_GAS_TARGET, MINIMUM_BASE_FEE};
 use fil_types::BLOCK_GAS_LIMIT;
-use forest_blocks::{tipset_keys_json::TipsetKeysJson, TipsetKeys};
+use forest_blocks::{tipset_keys_json::TipsetKeysJson, Tipset, TipsetKeys};
 use forest_json::address::json::AddressJson;
 use forest_message::{message::json::MessageJson, ChainMessage};
+use fvm_shared::address::Address;
 use fvm_shared::bigint::BigInt;
 use fvm_shared::message::Message;
 use ipld_blockstore::BlockStore;
 use rpc_api::{
-    data_types::{MessageSendSpec, RPCState},
+    data_types::{FeeHistoryEntry, MessageSendSpec, RPCState},
     gas_api::*,
 };
+use std::sync::Arc;
 
 const MIN_GAS_PREMIUM: f64 = 100000.0;
+const MAX_FEE_HISTORY_EPOCHS: u64 = 1024;
 
 /// Estimate the fee cap
 pub(crate) async fn gas_estimate_fee_cap<DB, B>(
@@ -41,18 +44,13 @@ async fn estimate_fee_cap<DB, B>(
     data: &Data<RPCState<DB, B>>,
     msg: Message,
     max_queue_blks: i64,
-    _tsk: TipsetKeys,
+    tsk: TipsetKeys,
 ) -> Result<BigInt, JsonRpcError>
 where
     DB: BlockStore + Send + Sync + 'static,
     B: Beacon + Send + Sync + 'static,
 {
-    let ts = data
-        .state_manager
-        .chain_store()
-        .heaviest_tipset()
-        .await
-        .ok_or("can't find heaviest tipset")?;
+    let ts = tipset_or_heaviest(data, &tsk).await?;
 
     let parent_base_fee = ts.blocks()[0].parent_base_fee();
     let increase_factor =
@@ -75,15 +73,21 @@ where
     DB: BlockStore + Send + Sync + 'static,
     B: Beacon + Send + Sync + 'static,
 {
-    let (nblocksincl, AddressJson(_sender), _gas_limit, TipsetKeysJson(_tsk)) = params;
-    estimate_gas_premium::<DB, B>(&data, nblocksincl)
+    let (nblocksincl, AddressJson(sender), gas_limit, TipsetKeysJson(tsk)) = params;
+    estimate_gas_premium::<DB, B>(&data, nblocksincl, &sender, gas_limit, tsk)
         .await
         .map(|n| BigInt::to_string(&n))
 }
 
+/// Estimate the premium needed for a message with the given gas limit to be included within
+/// `nblocksincl` blocks. The sender's pending messages are ahead of it in the queue, so their gas
+/// limits are reserved along with the message's own.
 async fn estimate_gas_premium<DB, B>(
     data: &Data<RPCState<DB, B>>,
     mut nblocksincl: u64,
+    sender: &Address,
+    gas_limit: i64,
+    tsk: TipsetKeys,
 ) -> Result<BigInt, JsonRpcError>
 where
     DB: BlockStore + Send + Sync + 'static,
@@ -93,6 +97,20 @@ where
         nblocksincl = 1;
     }
 
+    let mut ts = tipset_or_heaviest(data, &tsk).await?;
+    let pending_gas: i64 = match data.state_manager.resolve_to_key_addr(sender, &ts).await {
+        Ok(key_addr) => data
+            .mpool
+            .pending_for(&key_addr)
+            .await
+            .unwrap_or_default()
+            .iter()
+            .map(|m| m.message().gas_limit)
+            .sum(),
+        // Unknown senders can't have pending messages
+        Err(_) => 0,
+    };
+
     struct GasMeta {
         pub price: BigInt,
         pub limit: i64,
@@ -101,13 +119,6 @@ where
     let mut prices: Vec<GasMeta> = Vec::new();
     let mut blocks = 0;
 
-    let mut ts = data
-        .state_manager
-        .chain_store()
-        .heaviest_tipset()
-        .await
-        .ok_or("cant get heaviest tipset")?;
-
     for _ in 0..(nblocksincl * 2) {
         if ts.epoch() == 0 {
             break;
@@ -134,7 +145,7 @@ where
 
     prices.sort_by(|a, b| b.price.cmp(&a.price));
     // TODO: From lotus, account for how full blocks are
-    let mut at = BLOCK_GAS_TARGET * blocks as i64 / 2;
+    let mut at = BLOCK_GAS_TARGET * blocks as i64 / 2 - pending_gas - gas_limit.max(0);
     let mut prev = BigInt::zero();
     let mut premium = BigInt::zero();
 
@@ -187,10 +198,13 @@ where
     estimate_gas_limit::<DB, B>(&data, msg, tsk).await
 }
 
+/// Estimate the gas limit on top of the given tipset, or the message pool's head when no tipset
+/// is given. On the message pool's head the sender's pending messages are applied first, and a
+/// message without a nonce takes the nonce following them.
 async fn estimate_gas_limit<DB, B>(
     data: &Data<RPCState<DB, B>>,
     msg: Message,
-    _: TipsetKeys,
+    tsk: TipsetKeys,
 ) -> Result<i64, JsonRpcError>
 where
     DB: BlockStore + Send + Sync + 'static,
@@ -201,29 +215,37 @@ where
     msg.gas_fee_cap = MINIMUM_BASE_FEE.clone() + 1;
     msg.gas_premium = 1.into();
 
-    let curr_ts = data
-        .state_manager
-        .chain_store()
-        .heaviest_tipset()
-        .await
-        .ok_or("cant find the current heaviest tipset")?;
+    let mpool_ts = data.mpool.cur_tipset.as_ref().read().await.clone();
+    let ts = if tsk.cids.is_empty() {
+        mpool_ts.clone()
+    } else {
+        data.chain_store.tipset_from_keys(&tsk).await?
+    };
     let from_a = data
         .state_manager
-        .resolve_to_key_addr(&msg.from, &curr_ts)
+        .resolve_to_key_addr(&msg.from, &ts)
         .await?;
 
-    let pending = data.mpool.pending_for(&from_a).await;
-    let prior_messages: Vec<ChainMessage> = pending
-        .map(|s| s.into_iter().map(ChainMessage::Signed).collect::<Vec<_>>())
-        .unwrap_or_default();
+    // Pending messages build on the message pool's head only
+    let mut prior_messages: Vec<ChainMessage> = Vec::new();
+    if ts == mpool_ts {
+        if msg.sequence == 0 {
+            msg.sequence = data.mpool.get_sequence(&from_a).await?;
+        }
+        prior_messages = data
+            .mpool
+            .pending_for(&from_a)
+            .await
+            .unwrap_or_default()
+            .into_iter()
+            .filter(|m| m.message().sequence < msg.sequence)
+            .map(ChainMessage::Signed)
+            .collect();
+    }
 
     let res = data
         .state_manager
-        .call_with_gas(
-            &mut ChainMessage::Unsigned(msg),
-            &prior_messages,
-            Some(data.mpool.cur_tipset.as_ref().read().await.clone()),
-        )
+        .call_with_gas(&mut ChainMessage::Unsigned(msg), &prior_messages, Some(ts))
         .await?;
     match res.msg_rct {
         Some(rct) => {
@@ -269,7 +291,7 @@ where
         msg.gas_limit = gl;
     }
     if msg.gas_premium.is_zero() {
-        let gp = estimate_gas_premium(data, 10).await?;
+        let gp = estimate_gas_premium(data, 10, &msg.from, msg.gas_limit, tsk.clone()).await?;
         msg.gas_premium = gp;
     }
     if msg.gas_fee_cap.is_zero() {
@@ -279,3 +301,131 @@ where
     // TODO: Cap Gas Fee https://github.com/ChainSafe/forest/issues/901
     Ok(msg)
 }
+
+/// Summarise the base fee, gas usage and premium percentiles of the tipsets up to the given one
+pub(crate) async fn gas_estimate_fee_history<DB, B>(
+    data: Data<RPCState<DB, B>>,
+    Params(params): Params<GasEstimateFeeHistoryParams>,
+) -> Result<GasEstimateFeeHistoryResult, JsonRpcError>
+where
+    DB: BlockStore + Send + Sync + 'static,
+    B: Beacon + Send + Sync + 'static,
+{
+    let (epochs, TipsetKeysJson(tsk), percentiles) = params;
+    if epochs == 0 || epochs > MAX_FEE_HISTORY_EPOCHS {
+        return Err(format!(
+            "Number of epochs must be between 1 and {}",
+            MAX_FEE_HISTORY_EPOCHS
+        )
+        .into());
+    }
+    if percentiles.iter().any(|p| !(0.0..=100.0).contains(p)) {
+        return Err("Percentiles must be between 0 and 100".into());
+    }
+
+    let mut ts = tipset_or_heaviest(&data, &tsk).await?;
+    let mut history = Vec::new();
+    for _ in 0..epochs {
+        let msgs = chain::messages_for_tipset(data.state_manager.blockstore(), &ts)?;
+        let gas_limit: i64 = msgs.iter().map(|m| m.message().gas_limit).sum();
+        let premiums = msgs
+            .iter()
+            .map(|m| (m.message().gas_premium.clone(), m.message().gas_limit))
+            .collect();
+
+        history.push(FeeHistoryEntry {
+            epoch: ts.epoch(),
+            base_fee: ts.blocks()[0].parent_base_fee().clone(),
+            gas_target_ratio: gas_limit as f64
+                / (BLOCK_GAS_TARGET * ts.blocks().len() as i64) as f64,
+            premiums: premium_percentiles(premiums, &percentiles)
+                .iter()
+                .map(BigInt::to_string)
+                .collect(),
+        });
+
+        if ts.epoch() == 0 {
+            break;
+        }
+        ts = data.chain_store.tipset_from_keys(ts.parents()).await?;
+    }
+    history.reverse();
+
+    Ok(history)
+}
+
+/// Premiums at the given percentiles of the gas included in a tipset. Each message weighs by its
+/// gas limit, zero is returned for tipsets without messages.
+fn premium_percentiles(mut premiums: Vec<(BigInt, i64)>, percentiles: &[f64]) -> Vec<BigInt> {
+    premiums.sort_by(|a, b| a.0.cmp(&b.0));
+    let total_gas: i64 = premiums.iter().map(|(_, limit)| limit).sum();
+
+    percentiles
+        .iter()
+        .map(|p| {
+            let threshold = (total_gas as f64 * p / 100.0).ceil() as i64;
+            let mut cumulative = 0;
+            premiums
+                .iter()
+                .find(|(_, limit)| {
+                    cumulative += limit;
+                    cumulative >= threshold
+                })
+                .or_else(|| premiums.last())
+                .map(|(premium, _)| premium.clone())
+                .unwrap_or_default()
+        })
+        .collect()
+}
+
+/// Load the tipset with the given keys, or the heaviest tipset when no keys are given
+async fn tipset_or_heaviest<DB, B>(
+    data: &Data<RPCState<DB, B>>,
+    tsk: &TipsetKeys,
+) -> Result<Arc<Tipset>, JsonRpcError>
+where
+    DB: BlockStore + Send + Sync + 'static,
+    B: Beacon + Send + Sync + 'static,
+{
+    if tsk.cids.is_empty() {
+        Ok(data
+            .chain_store
+            .heaviest_tipset()
+            .await
+            .ok_or("can't find heaviest tipset")?)
+    } else {
+        Ok(data.chain_store.tipset_from_keys(tsk).await?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn premium_percentiles_weigh_by_gas() {
+        let premiums = vec![
+            (BigInt::from(300), 1_000),
+            (BigInt::from(100), 6_000),
+            (BigInt::from(200), 3_000),
+        ];
+        assert_eq!(
+            premium_percentiles(premiums, &[0.0, 50.0, 60.0, 75.0, 100.0]),
+            vec![
+                BigInt::from(100),
+                BigInt::from(100),
+                BigInt::from(100),
+                BigInt::from(200),
+                BigInt::from(300)
+            ]
+        );
+    }
+
+    #[test]
+    fn premium_percentiles_without_messages() {
+        assert_eq!(
+            premium_percentiles(Vec::new(), &[25.0, 90.0]),
+            vec![BigInt::default(), BigInt::default()]
+        );
+    }
+}
@@ -7,6 +7,7 @@ mod chain_api;
 mod common_api;
 mod gas_api;
 mod mpool_api;
+mod msig_api;
 mod net_api;
 mod rpc_http_handler;
 mod rpc_util;
@@ -32,8 +33,8 @@ use crate::rpc_ws_handler::rpc_ws_handler;
 use crate::{beacon_api::beacon_get_entry, common_api::version, state_api::*};
 
 use rpc_api::{
-    auth_api::*, beacon_api::*, chain_api::*, common_api::*, gas_api::*, mpool_api::*, net_api::*,
-    state_api::*, sync_api::*, wallet_api::*,
+    auth_api::*, beacon_api::*, chain_api::*, common_api::*, gas_api::*, mpool_api::*, msig_api::*,
+    net_api::*, state_api::*, sync_api::*, wallet_api::*,
 };
 
 pub async fn start_rpc<DB, B, V, S>(
@@ -50,6 +51,7 @@ where
     use chain_api::*;
     use gas_api::*;
     use mpool_api::*;
+    use msig_api::*;
     use sync_api::*;
     use wallet_api::*;
 
@@ -88,12 +90,28 @@ where
             )
             .with_method(CHAIN_GET_BLOCK, chain_api::chain_get_block::<DB, B>)
             // Message Pool API
+            .with_method(MPOOL_CHECK_MESSAGES, mpool_check_messages::<DB, B>)
+            .with_method(
+                MPOOL_CHECK_PENDING_MESSAGES,
+                mpool_check_pending_messages::<DB, B>,
+            )
             .with_method(MPOOL_ESTIMATE_GAS_PRICE, estimate_gas_premium::<DB, B>)
             .with_method(MPOOL_GET_NONCE, mpool_get_sequence::<DB, B>)
             .with_method(MPOOL_PENDING, mpool_pending::<DB, B>)
             .with_method(MPOOL_PUSH, mpool_push::<DB, B>)
             .with_method(MPOOL_PUSH_MESSAGE, mpool_push_message::<DB, B>)
+            .with_method(MPOOL_REPLACE, mpool_replace::<DB, B>)
             .with_method(MPOOL_SELECT, mpool_select::<DB, B>)
+            .with_method(MPOOL_SNAPSHOT, mpool_snapshot::<DB, B>)
+            .with_method(MPOOL_SUB, mpool_sub::<DB, B>)
+            .with_method(MPOOL_UPDATES, mpool_updates::<DB, B>)
+            // Multisig API
+            .with_method(MSIG_CREATE, msig_create::<DB, B>)
+            .with_method(MSIG_PROPOSE, msig_propose::<DB, B>)
+            .with_method(MSIG_APPROVE, msig_approve::<DB, B>)
+            .with_method(MSIG_CANCEL, msig_cancel::<DB, B>)
+            .with_method(MSIG_GET_PENDING, msig_get_pending::<DB, B>)
+            .with_method(MSIG_GET_VESTED, msig_get_vested::<DB, B>)
             // Sync API
             .with_method(SYNC_CHECK_BAD, sync_check_bad::<DB, B>)
             .with_method(SYNC_MARK_BAD, sync_mark_bad::<DB, B>)
@@ -161,6 +179,7 @@ where
             .with_method(GAS_ESTIMATE_GAS_LIMIT, gas_estimate_gas_limit::<DB, B>)
             .with_method(GAS_ESTIMATE_GAS_PREMIUM, gas_estimate_gas_premium::<DB, B>)
             .with_method(GAS_ESTIMATE_MESSAGE_GAS, gas_estimate_message_gas::<DB, B>)
+            .with_method(GAS_ESTIMATE_FEE_HISTORY, gas_estimate_fee_history::<DB, B>)
             // Common API
             .with_method(VERSION, move || version(block_delay))
             // Net API
@@ -168,6 +187,24 @@ where
             .with_method(NET_PEERS, net_api::net_peers::<DB, B>)
             .with_method(NET_CONNECT, net_api::net_connect::<DB, B>)
             .with_method(NET_DISCONNECT, net_api::net_disconnect::<DB, B>)
+            .with_method(NET_BLOCK_ADD, net_api::net_block_add::<DB, B>)
+            .with_method(NET_BLOCK_REMOVE, net_api::net_block_remove::<DB, B>)
+            .with_method(NET_BLOCK_LIST, net_api::net_block_list::<DB, B>)
+            .with_method(NET_AUTO_NAT_STATUS, net_api::net_auto_nat_status::<DB, B>)
+            .with_method(NET_BITSWAP_FETCH, net_api::net_bitswap_fetch::<DB, B>)
+            .with_method(NET_BANDWIDTH_STATS, net_api::net_bandwidth_stats::<DB, B>)
+            .with_method(
+                NET_BANDWIDTH_STATS_BY_PEER,
+                net_api::net_bandwidth_stats_by_peer::<DB, B>,
+            )
+            .with_method(
+                NET_BANDWIDTH_STATS_BY_PROTOCOL,
+                net_api::net_bandwidth_stats_by_protocol::<DB, B>,
+            )
+            .with_method(NET_PEER_INFO, net_api::net_peer_info::<DB, B>)
+            .with_method(NET_AGENT_VERSION, net_api::net_agent_version::<DB, B>)
+            .with_method(NET_PING, net_api::net_ping::<DB, B>)
+            .with_method(NET_FIND_PEER, net_api::net_find_peer::<DB, B>)
             .finish_unwrapped(),
     );
 
@@ -2,20 +2,68 @@
 // SPDX-License-Identifier: Apache-2.0, MIT
 
 use super::gas_api::estimate_message_gas;
+use crate::rpc_util::get_error_obj;
 use beacon::Beacon;
 use encoding::Cbor;
 use forest_blocks::TipsetKeys;
+use forest_json::address::json::AddressJson;
 use forest_json::cid::{vec::CidJsonVec, CidJson};
 use forest_message::message::json::MessageJson;
 use forest_message::{signed_message::json::SignedMessageJson, SignedMessage};
 use fvm_shared::address::{Address, Protocol};
+use fvm_shared::bigint::BigInt;
+use fvm_shared::message::Message;
 use ipld_blockstore::BlockStore;
-use rpc_api::data_types::RPCState;
+use key_management::{MsgMeta, MsgType};
+use rpc_api::data_types::{MessageCheckStatus, MessageSendSpec, RPCState};
 use rpc_api::mpool_api::*;
 
-use jsonrpc_v2::{Data, Error as JsonRpcError, Params};
+use jsonrpc_v2::{Data, Error as JsonRpcError, Id, Params};
+use std::cmp::max;
+use std::collections::HashSet;
 use std::str::FromStr;
-use std::{collections::HashSet, convert::TryFrom};
+
+/// Run messages through the message pool checks against the current head, return the outcome
+/// of every check for each message
+pub(crate) async fn mpool_check_messages<DB, B>(
+    data: Data<RPCState<DB, B>>,
+    Params(params): Params<MpoolCheckMessagesParams>,
+) -> Result<MpoolCheckMessagesResult, JsonRpcError>
+where
+    DB: BlockStore + Send + Sync + 'static,
+    B: Beacon + Send + Sync + 'static,
+{
+    let (protos,) = params;
+    let msgs = protos
+        .into_iter()
+        .map(|proto| (proto.message, proto.valid_nonce))
+        .collect();
+    let statuses = data.mpool.check_messages(msgs).await?;
+    Ok(into_check_statuses(statuses))
+}
+
+/// Run the pending messages of a sender through the message pool checks
+pub(crate) async fn mpool_check_pending_messages<DB, B>(
+    data: Data<RPCState<DB, B>>,
+    Params(params): Params<MpoolCheckPendingMessagesParams>,
+) -> Result<MpoolCheckPendingMessagesResult, JsonRpcError>
+where
+    DB: BlockStore + Send + Sync + 'static,
+    B: Beacon + Send + Sync + 'static,
+{
+    let (AddressJson(from),) = params;
+    let statuses = data.mpool.check_pending_messages(&from).await?;
+    Ok(into_check_statuses(statuses))
+}
+
+fn into_check_statuses(
+    statuses: Vec<Vec<message_pool::MessageCheckStatus>>,
+) -> Vec<Vec<MessageCheckStatus>> {
+    statuses
+        .into_iter()
+        .map(|msg_statuses| msg_statuses.into_iter().map(Into::into).collect())
+        .collect()
+}
 
 /// Estimate the gas price for an Address
 pub(crate) async fn estimate_gas_premium<DB, B>(
@@ -141,9 +189,26 @@ where
 {
     let (MessageJson(umsg), spec) = params;
 
+    let smsg = sign_and_push_message(&data, umsg, spec).await?;
+
+    Ok(SignedMessageJson(smsg))
+}
+
+/// Estimate gas for the message, assign its nonce, sign it with the sender's key and add it to
+/// the mpool
+pub(crate) async fn sign_and_push_message<DB, B>(
+    data: &Data<RPCState<DB, B>>,
+    umsg: Message,
+    spec: Option<MessageSendSpec>,
+) -> Result<SignedMessage, JsonRpcError>
+where
+    DB: BlockStore + Send + Sync + 'static,
+    B: Beacon + Send + Sync + 'static,
+{
     let from = umsg.from;
 
-    let mut keystore = data.keystore.as_ref().write().await;
+    // Held until the message is in the mpool, so that concurrent pushes get distinct nonces
+    let _push_guard = data.mpool_push_lock.lock().await;
     let heaviest_tipset = data
         .state_manager
         .chain_store()
@@ -160,7 +225,7 @@ where
             "Expected nonce for MpoolPushMessage is 0, and will be calculated for you.".into(),
         );
     }
-    let mut umsg = estimate_message_gas::<DB, B>(&data, umsg, spec, Default::default()).await?;
+    let mut umsg = estimate_message_gas::<DB, B>(data, umsg, spec, Default::default()).await?;
     if umsg.gas_premium > umsg.gas_fee_cap {
         return Err("After estimation, gas premium is greater than gas fee cap".into());
     }
@@ -170,18 +235,117 @@ where
     }
     let nonce = data.mpool.get_sequence(&from).await?;
     umsg.sequence = nonce;
-    let key = key_management::Key::try_from(key_management::try_find(&key_addr, &mut *keystore)?)?;
-    let sig = key_management::sign(
-        *key.key_info.key_type(),
-        key.key_info.private_key(),
-        umsg.to_signing_bytes().as_slice(),
-    )?;
+
+    sign_with_key_and_push(data, &key_addr, umsg).await
+}
+
+/// Replace a pending message with the same nonce and higher gas fees, return the new
+/// SignedMessage
+pub(crate) async fn mpool_replace<DB, B>(
+    data: Data<RPCState<DB, B>>,
+    Params(params): Params<MpoolReplaceParams>,
+) -> Result<MpoolReplaceResult, JsonRpcError>
+where
+    DB: BlockStore + Send + Sync + 'static,
+    B: Beacon + Send + Sync + 'static,
+{
+    let (AddressJson(from), nonce, auto, gas_premium, gas_fee_cap) = params;
+
+    if auto && (gas_premium.is_some() || gas_fee_cap.is_some()) {
+        return Err("Gas fees can't be given when they are re-estimated".into());
+    }
+    if !auto && gas_premium.is_none() {
+        return Err("Gas premium is required unless the fees are re-estimated".into());
+    }
+
+    // Held until the replacement is in the mpool, so that a concurrent push from the sender
+    // doesn't race with it
+    let _push_guard = data.mpool_push_lock.lock().await;
+    let heaviest_tipset = data
+        .state_manager
+        .chain_store()
+        .heaviest_tipset()
+        .await
+        .ok_or_else(|| "Could not get heaviest tipset".to_string())?;
+    let key_addr = data
+        .state_manager
+        .resolve_to_key_addr(&from, &heaviest_tipset)
+        .await?;
+
+    // Pushed messages are keyed by the key address, messages received over pubsub may not be
+    let pending = match data.mpool.pending_for(&key_addr).await {
+        Some(pending) => Some(pending),
+        None => data.mpool.pending_for(&from).await,
+    };
+    let old = pending
+        .into_iter()
+        .flatten()
+        .find(|m| m.message().sequence == nonce)
+        .ok_or_else(|| format!("No pending message from {} with nonce {}", from, nonce))?;
+
+    let min_premium = data
+        .mpool
+        .min_replacement_premium(&old.message().gas_premium);
+    let mut umsg = old.message().clone();
+    let gas_fee_cap = gas_fee_cap
+        .map(|fee_cap| BigInt::from_str(&fee_cap))
+        .transpose()?;
+
+    match gas_premium {
+        Some(gas_premium) => {
+            let gas_premium = BigInt::from_str(&gas_premium)?;
+            if gas_premium < min_premium {
+                return Err(format!(
+                    "Gas premium {} is below the minimum replacement premium {}",
+                    gas_premium, min_premium
+                )
+                .into());
+            }
+            umsg.gas_fee_cap =
+                gas_fee_cap.unwrap_or_else(|| max(umsg.gas_fee_cap.clone(), gas_premium.clone()));
+            umsg.gas_premium = gas_premium;
+        }
+        // Re-estimated fees
+        None => {
+            umsg.gas_premium = BigInt::default();
+            umsg.gas_fee_cap = BigInt::default();
+            umsg = estimate_message_gas::<DB, B>(&data, umsg, None, Default::default()).await?;
+            umsg.gas_premium = max(umsg.gas_premium, min_premium);
+            umsg.gas_fee_cap = max(umsg.gas_fee_cap, umsg.gas_premium.clone());
+        }
+    }
+    if umsg.gas_premium > umsg.gas_fee_cap {
+        return Err("Gas premium is greater than gas fee cap".into());
+    }
+
+    let smsg = sign_with_key_and_push(&data, &key_addr, umsg).await?;
+
+    Ok(SignedMessageJson(smsg))
+}
+
+async fn sign_with_key_and_push<DB, B>(
+    data: &Data<RPCState<DB, B>>,
+    key_addr: &Address,
+    umsg: Message,
+) -> Result<SignedMessage, JsonRpcError>
+where
+    DB: BlockStore + Send + Sync + 'static,
+    B: Beacon + Send + Sync + 'static,
+{
+    let sig = data
+        .signer
+        .sign(
+            key_addr,
+            umsg.to_signing_bytes().as_slice(),
+            MsgMeta::new(MsgType::ChainMsg, umsg.marshal_cbor()?),
+        )
+        .await?;
 
     let smsg = SignedMessage::new_from_parts(umsg, sig)?;
 
     data.mpool.as_ref().push(smsg.clone()).await?;
 
-    Ok(SignedMessageJson(smsg))
+    Ok(smsg)
 }
 
 pub(crate) async fn mpool_select<DB, B>(
@@ -204,3 +368,50 @@ where
         .map(|e| e.into())
         .collect())
 }
+
+/// Return a snapshot of the pending messages, replayable through message selection, CBOR encoded
+/// in base64
+pub(crate) async fn mpool_snapshot<DB, B>(
+    data: Data<RPCState<DB, B>>,
+) -> Result<MpoolSnapshotResult, JsonRpcError>
+where
+    DB: BlockStore + Send + Sync + 'static,
+    B: Beacon + Send + Sync + 'static,
+{
+    let snapshot = data.mpool.snapshot().await?;
+    Ok(base64::encode(snapshot.marshal_cbor()?))
+}
+
+/// Subscribe to messages added to and removed from the message pool, return the subscription ID
+pub(crate) async fn mpool_sub<DB, B>(
+    data: Data<RPCState<DB, B>>,
+    Params(params): Params<MpoolSubParams>,
+) -> Result<MpoolSubResult, JsonRpcError>
+where
+    DB: BlockStore + Send + Sync + 'static,
+    B: Beacon + Send + Sync + 'static,
+{
+    let from = params.into_iter().map(|a| a.0).collect();
+    Ok(data.mpool.sub_updates(from).await)
+}
+
+/// Wait for the next update of a message pool subscription
+pub(crate) async fn mpool_updates<DB, B>(
+    data: Data<RPCState<DB, B>>,
+    id: Id,
+) -> Result<MpoolUpdatesResult, JsonRpcError>
+where
+    DB: BlockStore + Send + Sync + 'static,
+    B: Beacon + Send + Sync + 'static,
+{
+    if let Id::Num(id) = id {
+        let update = data
+            .mpool
+            .next_update(&id)
+            .await
+            .ok_or_else(|| format!("Mpool subscription {} is closed", id))?;
+        Ok((id, update.into()))
+    } else {
+        Err(get_error_obj(-32600, "Invalid request".to_owned()))
+    }
+}
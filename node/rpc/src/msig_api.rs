@@ -0,0 +1,345 @@
+// Copyright 2019-2022 ChainSafe Systems
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+use super::mpool_api::sign_and_push_message;
+use actor::{init, multisig, system, ActorVersion};
+use beacon::Beacon;
+use cid::Cid;
+use encoding::Cbor;
+use forest_blocks::tipset_keys_json::TipsetKeysJson;
+use forest_blocks::Tipset;
+use forest_json::address::json::AddressJson;
+use forest_json::cid::CidJson;
+use forest_vm::{Serialized, TokenAmount};
+use fvm_shared::address::Address;
+use fvm_shared::bigint::BigInt;
+use fvm_shared::clock::ChainEpoch;
+use fvm_shared::message::Message;
+use fvm_shared::MethodNum;
+use ipld_blockstore::{BlockStore, BlockStoreExt};
+use rpc_api::data_types::{MsigTransaction, RPCState};
+use rpc_api::msig_api::*;
+
+use jsonrpc_v2::{Data, Error as JsonRpcError, Params};
+use std::str::FromStr;
+
+/// Create a multisig wallet through the init actor, return the CID of the creation message
+pub(crate) async fn msig_create<DB, B>(
+    data: Data<RPCState<DB, B>>,
+    Params(params): Params<MsigCreateParams>,
+) -> Result<MsigCreateResult, JsonRpcError>
+where
+    DB: BlockStore + Send + Sync + 'static,
+    B: Beacon + Send + Sync + 'static,
+{
+    let (required, signers, unlock_duration, value, AddressJson(from)) = params;
+    let signers: Vec<Address> = signers.into_iter().map(|a| a.0).collect();
+
+    let heaviest_tipset = data
+        .chain_store
+        .heaviest_tipset()
+        .await
+        .ok_or("can't find heaviest tipset")?;
+    let code_cid = multisig_code(&data, *heaviest_tipset.parent_state())?;
+
+    let params = create_params(code_cid, required, signers, unlock_duration)?;
+
+    push_message(
+        &data,
+        from,
+        init::ADDRESS,
+        TokenAmount::from_str(&value)?,
+        init::Method::Exec as MethodNum,
+        params,
+    )
+    .await
+}
+
+/// Propose a transaction to the multisig signers, return the CID of the proposal message
+pub(crate) async fn msig_propose<DB, B>(
+    data: Data<RPCState<DB, B>>,
+    Params(params): Params<MsigProposeParams>,
+) -> Result<MsigProposeResult, JsonRpcError>
+where
+    DB: BlockStore + Send + Sync + 'static,
+    B: Beacon + Send + Sync + 'static,
+{
+    let (AddressJson(msig), AddressJson(to), value, AddressJson(from), method, txn_params) = params;
+
+    let params = Serialized::serialize(multisig::ProposeParams {
+        to,
+        value: TokenAmount::from_str(&value)?,
+        method,
+        params: Serialized::new(base64::decode(txn_params)?),
+    })?;
+
+    push_message(
+        &data,
+        from,
+        msig,
+        TokenAmount::default(),
+        multisig::Method::Propose as MethodNum,
+        params,
+    )
+    .await
+}
+
+/// Approve a pending multisig transaction, return the CID of the approval message
+pub(crate) async fn msig_approve<DB, B>(
+    data: Data<RPCState<DB, B>>,
+    Params(params): Params<MsigApproveParams>,
+) -> Result<MsigApproveResult, JsonRpcError>
+where
+    DB: BlockStore + Send + Sync + 'static,
+    B: Beacon + Send + Sync + 'static,
+{
+    let (AddressJson(msig), txn_id, AddressJson(from)) = params;
+    push_txn_id_message(&data, msig, txn_id, from, multisig::Method::Approve).await
+}
+
+/// Cancel a pending multisig transaction, return the CID of the cancellation message
+pub(crate) async fn msig_cancel<DB, B>(
+    data: Data<RPCState<DB, B>>,
+    Params(params): Params<MsigCancelParams>,
+) -> Result<MsigCancelResult, JsonRpcError>
+where
+    DB: BlockStore + Send + Sync + 'static,
+    B: Beacon + Send + Sync + 'static,
+{
+    let (AddressJson(msig), txn_id, AddressJson(from)) = params;
+    push_txn_id_message(&data, msig, txn_id, from, multisig::Method::Cancel).await
+}
+
+/// Return the pending transactions of a multisig at the given tipset
+pub(crate) async fn msig_get_pending<DB, B>(
+    data: Data<RPCState<DB, B>>,
+    Params(params): Params<MsigGetPendingParams>,
+) -> Result<MsigGetPendingResult, JsonRpcError>
+where
+    DB: BlockStore + Send + Sync + 'static,
+    B: Beacon + Send + Sync + 'static,
+{
+    let (AddressJson(msig), TipsetKeysJson(tsk)) = params;
+    let ts = data.chain_store.tipset_from_keys(&tsk).await?;
+    let state = load_msig_state(&data, &msig, &ts)?;
+
+    Ok(state
+        .pending_transactions(data.state_manager.blockstore())?
+        .into_iter()
+        .map(|(id, txn)| MsigTransaction {
+            id,
+            to: AddressJson(txn.to),
+            value: txn.value,
+            method: txn.method,
+            params: base64::encode(txn.params.bytes()),
+            approved: txn.approved.into_iter().map(AddressJson).collect(),
+        })
+        .collect())
+}
+
+/// Return the amount of the multisig's initial balance vested between two tipsets
+pub(crate) async fn msig_get_vested<DB, B>(
+    data: Data<RPCState<DB, B>>,
+    Params(params): Params<MsigGetVestedParams>,
+) -> Result<MsigGetVestedResult, JsonRpcError>
+where
+    DB: BlockStore + Send + Sync + 'static,
+    B: Beacon + Send + Sync + 'static,
+{
+    let (AddressJson(msig), TipsetKeysJson(start_tsk), TipsetKeysJson(end_tsk)) = params;
+    let start = data.chain_store.tipset_from_keys(&start_tsk).await?;
+    let end = data.chain_store.tipset_from_keys(&end_tsk).await?;
+
+    if start.epoch() > end.epoch() {
+        return Err("Start tipset must not be after the end tipset".into());
+    }
+    if start.epoch() == end.epoch() {
+        return Ok(BigInt::default().to_string());
+    }
+
+    let state = load_msig_state(&data, &msig, &end)?;
+
+    let start_locked = state.locked_balance(start.epoch());
+    let end_locked = state.locked_balance(end.epoch());
+
+    Ok((start_locked - end_locked).to_string())
+}
+
+/// Parameters of the init actor's `Exec` method creating a multisig with the given code.
+fn create_params(
+    code_cid: Cid,
+    required: u64,
+    signers: Vec<Address>,
+    unlock_duration: ChainEpoch,
+) -> Result<Serialized, JsonRpcError> {
+    if signers.is_empty() {
+        return Err("Multisig must have at least one signer".into());
+    }
+    if required == 0 || required > signers.len() as u64 {
+        return Err(format!(
+            "Required approvals must be between 1 and the number of signers ({})",
+            signers.len()
+        )
+        .into());
+    }
+
+    let constructor_params = Serialized::serialize(multisig::ConstructorParams {
+        signers,
+        num_approvals_threshold: required,
+        unlock_duration,
+        start_epoch: 0,
+    })?;
+    Ok(Serialized::serialize(init::ExecParams {
+        code_cid,
+        constructor_params,
+    })?)
+}
+
+/// Returns the multisig actor code listed in the manifest of the actors the network runs.
+fn multisig_code<DB, B>(data: &Data<RPCState<DB, B>>, state_root: Cid) -> Result<Cid, JsonRpcError>
+where
+    DB: BlockStore + Send + Sync + 'static,
+    B: Beacon + Send + Sync + 'static,
+{
+    let store = data.state_manager.blockstore();
+    let system_actor = data
+        .state_manager
+        .get_actor(&system::ADDRESS, state_root)?
+        .ok_or("System actor not found")?;
+    let system_state: system::State = store
+        .get_obj(&system_actor.state)?
+        .ok_or("System actor state not found")?;
+    Ok(
+        system::builtin_actor_code(store, &system_state, "multisig")?
+            .ok_or("No multisig actor in the builtin actors manifest")?,
+    )
+}
+
+/// Loads the state of a multisig at the given tipset, decoded for the actors version of the
+/// network at that epoch.
+fn load_msig_state<DB, B>(
+    data: &Data<RPCState<DB, B>>,
+    msig: &Address,
+    ts: &Tipset,
+) -> Result<multisig::State, JsonRpcError>
+where
+    DB: BlockStore + Send + Sync + 'static,
+    B: Beacon + Send + Sync + 'static,
+{
+    let actor = data
+        .state_manager
+        .get_actor(msig, *ts.parent_state())?
+        .ok_or_else(|| format!("Multisig actor {} not found", msig))?;
+    if actor.code != multisig_code(data, *ts.parent_state())? {
+        return Err(format!("Actor {} is not a multisig", msig).into());
+    }
+    let version = ActorVersion::from(data.state_manager.get_network_version(ts.epoch()));
+    Ok(multisig::State::load(
+        data.state_manager.blockstore(),
+        &actor,
+        version,
+    )?)
+}
+
+/// Approve or cancel a pending transaction, identified by its ID and the hash of its proposal
+/// so that a different transaction stored under the same ID is not acted upon.
+async fn push_txn_id_message<DB, B>(
+    data: &Data<RPCState<DB, B>>,
+    msig: Address,
+    txn_id: i64,
+    from: Address,
+    method: multisig::Method,
+) -> Result<CidJson, JsonRpcError>
+where
+    DB: BlockStore + Send + Sync + 'static,
+    B: Beacon + Send + Sync + 'static,
+{
+    let heaviest_tipset = data
+        .chain_store
+        .heaviest_tipset()
+        .await
+        .ok_or("can't find heaviest tipset")?;
+    let state = load_msig_state(data, &msig, &heaviest_tipset)?;
+    let (_, txn) = state
+        .pending_transactions(data.state_manager.blockstore())?
+        .into_iter()
+        .find(|(id, _)| *id == txn_id)
+        .ok_or_else(|| format!("Transaction {} not pending in multisig {}", txn_id, msig))?;
+
+    let params = Serialized::serialize(multisig::TxnIDParams {
+        id: multisig::TxnID(txn_id),
+        proposal_hash: multisig::compute_proposal_hash(&txn)?.to_vec(),
+    })?;
+
+    push_message(
+        data,
+        from,
+        msig,
+        TokenAmount::default(),
+        method as MethodNum,
+        params,
+    )
+    .await
+}
+
+async fn push_message<DB, B>(
+    data: &Data<RPCState<DB, B>>,
+    from: Address,
+    to: Address,
+    value: TokenAmount,
+    method_num: MethodNum,
+    params: Serialized,
+) -> Result<CidJson, JsonRpcError>
+where
+    DB: BlockStore + Send + Sync + 'static,
+    B: Beacon + Send + Sync + 'static,
+{
+    let umsg = Message {
+        version: Default::default(),
+        from,
+        to,
+        sequence: 0,
+        value,
+        method_num,
+        params,
+        gas_limit: 0,
+        gas_fee_cap: Default::default(),
+        gas_premium: Default::default(),
+    };
+
+    let smsg = sign_and_push_message(data, umsg, None).await?;
+
+    Ok(CidJson(smsg.cid()?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn code_cid() -> Cid {
+        Cid::try_from("bafk2bzacec66wmb4kohuzvuxsulhcgiwju7sqkldwfpmmgw7dbbwgm5l2574q").unwrap()
+    }
+
+    #[test]
+    fn create_params_encode_constructor() {
+        let signers = vec![Address::new_id(1001), Address::new_id(1002)];
+        let params = create_params(code_cid(), 2, signers.clone(), 100).unwrap();
+
+        let exec: init::ExecParams = params.deserialize().unwrap();
+        assert_eq!(exec.code_cid, code_cid());
+        let constructor: multisig::ConstructorParams =
+            exec.constructor_params.deserialize().unwrap();
+        assert_eq!(constructor.signers, signers);
+        assert_eq!(constructor.num_approvals_threshold, 2);
+        assert_eq!(constructor.unlock_duration, 100);
+        assert_eq!(constructor.start_epoch, 0);
+    }
+
+    #[test]
+    fn create_params_check_threshold() {
+        let signers = vec![Address::new_id(1001), Address::new_id(1002)];
+        assert!(create_params(code_cid(), 1, vec![], 0).is_err());
+        assert!(create_params(code_cid(), 0, signers.clone(), 0).is_err());
+        assert!(create_params(code_cid(), 3, signers, 0).is_err());
+    }
+}
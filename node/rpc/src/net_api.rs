@@ -1,18 +1,32 @@
 // Copyright 2019-2022 ChainSafe Systems
 // SPDX-License-Identifier: Apache-2.0, MIT
 
+use async_std::future;
 use futures::channel::oneshot;
 use jsonrpc_v2::{Data, Error as JsonRpcError, Params};
 use log::error;
+use std::time::Duration;
 
 use beacon::Beacon;
-use forest_libp2p::{NetRPCMethods, NetworkMessage, PeerId};
-use ipld_blockstore::BlockStore;
+use forest_json::cid::CidJson;
+use forest_libp2p::{
+    BandwidthStats, BlockList, NetRPCMethods, NetworkMessage, PeerId, PeerInfo, Reachability,
+};
+use ipld_blockstore::{BlockStore, BlockStoreExt};
 use rpc_api::{
-    data_types::{AddrInfo, RPCState},
+    data_types::{
+        self, AddrInfo, ChainExchangeStats, ExtendedPeerInfo, NatInfo, NetBlockList, RPCState,
+    },
     net_api::*,
 };
 
+/// Time to wait for a block fetched over bitswap.
+const BITSWAP_FETCH_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Time to wait for the next ping of a peer, longer than the interval between pings and their
+/// timeout.
+const PING_TIMEOUT: Duration = Duration::from_secs(40);
+
 pub(crate) async fn net_addrs_listen<
     DB: BlockStore + Send + Sync + 'static,
     B: Beacon + Send + Sync + 'static,
@@ -105,3 +119,301 @@ pub(crate) async fn net_disconnect<
 
     Ok(())
 }
+
+pub(crate) async fn net_block_add<
+    DB: BlockStore + Send + Sync + 'static,
+    B: Beacon + Send + Sync + 'static,
+>(
+    data: Data<RPCState<DB, B>>,
+    Params(params): Params<NetBlockAddParams>,
+) -> Result<NetBlockAddResult, JsonRpcError> {
+    let (entries,) = params;
+    let entries = BlockList::parse(&entries.peers, &entries.ip_addrs, &entries.ip_subnets)?;
+
+    let (tx, rx) = oneshot::channel();
+    let req = NetworkMessage::JSONRPCRequest {
+        method: NetRPCMethods::NetBlockAdd(tx, entries),
+    };
+
+    data.network_send.send(req).await?;
+    rx.await??;
+
+    Ok(())
+}
+
+pub(crate) async fn net_block_remove<
+    DB: BlockStore + Send + Sync + 'static,
+    B: Beacon + Send + Sync + 'static,
+>(
+    data: Data<RPCState<DB, B>>,
+    Params(params): Params<NetBlockRemoveParams>,
+) -> Result<NetBlockRemoveResult, JsonRpcError> {
+    let (entries,) = params;
+    let entries = BlockList::parse(&entries.peers, &entries.ip_addrs, &entries.ip_subnets)?;
+
+    let (tx, rx) = oneshot::channel();
+    let req = NetworkMessage::JSONRPCRequest {
+        method: NetRPCMethods::NetBlockRemove(tx, entries),
+    };
+
+    data.network_send.send(req).await?;
+    rx.await??;
+
+    Ok(())
+}
+
+pub(crate) async fn net_block_list<
+    DB: BlockStore + Send + Sync + 'static,
+    B: Beacon + Send + Sync + 'static,
+>(
+    data: Data<RPCState<DB, B>>,
+) -> Result<NetBlockListResult, JsonRpcError> {
+    let (tx, rx) = oneshot::channel();
+    let req = NetworkMessage::JSONRPCRequest {
+        method: NetRPCMethods::NetBlockList(tx),
+    };
+
+    data.network_send.send(req).await?;
+    let block_list = rx.await?;
+
+    Ok(NetBlockList {
+        peers: block_list.peers.iter().map(PeerId::to_string).collect(),
+        ip_addrs: block_list
+            .ip_addrs
+            .iter()
+            .map(|ip| ip.to_string())
+            .collect(),
+        ip_subnets: block_list
+            .ip_subnets
+            .iter()
+            .map(|subnet| subnet.to_string())
+            .collect(),
+    })
+}
+
+pub(crate) async fn net_auto_nat_status<
+    DB: BlockStore + Send + Sync + 'static,
+    B: Beacon + Send + Sync + 'static,
+>(
+    data: Data<RPCState<DB, B>>,
+) -> Result<NetAutoNatStatusResult, JsonRpcError> {
+    let (tx, rx) = oneshot::channel();
+    let req = NetworkMessage::JSONRPCRequest {
+        method: NetRPCMethods::NetAutoNatStatus(tx),
+    };
+
+    data.network_send.send(req).await?;
+    let status = rx.await?;
+
+    Ok(NatInfo {
+        reachability: match status.reachability {
+            Reachability::Unknown => 0,
+            Reachability::Public => 1,
+            Reachability::Private => 2,
+        },
+        public_addr: status
+            .public_addr
+            .map(|addr| addr.to_string())
+            .unwrap_or_default(),
+    })
+}
+
+pub(crate) async fn net_bitswap_fetch<
+    DB: BlockStore + Send + Sync + 'static,
+    B: Beacon + Send + Sync + 'static,
+>(
+    data: Data<RPCState<DB, B>>,
+    Params(params): Params<NetBitswapFetchParams>,
+) -> Result<NetBitswapFetchResult, JsonRpcError> {
+    let (CidJson(cid),) = params;
+    if data.chain_store.blockstore().get_bytes(&cid)?.is_some() {
+        return Ok(());
+    }
+
+    let (tx, rx) = oneshot::channel();
+    let req = NetworkMessage::BitswapRequest {
+        cid,
+        response_channel: tx,
+    };
+
+    data.network_send.send(req).await?;
+    future::timeout(BITSWAP_FETCH_TIMEOUT, rx)
+        .await
+        .map_err(|_| format!("Bitswap fetch of {} timed out", cid))??;
+    Ok(())
+}
+
+pub(crate) async fn net_bandwidth_stats<
+    DB: BlockStore + Send + Sync + 'static,
+    B: Beacon + Send + Sync + 'static,
+>(
+    data: Data<RPCState<DB, B>>,
+) -> Result<NetBandwidthStatsResult, JsonRpcError> {
+    let (tx, rx) = oneshot::channel();
+    let req = NetworkMessage::JSONRPCRequest {
+        method: NetRPCMethods::NetBandwidthStats(tx),
+    };
+
+    data.network_send.send(req).await?;
+    Ok(bandwidth_stats(rx.await?))
+}
+
+pub(crate) async fn net_bandwidth_stats_by_peer<
+    DB: BlockStore + Send + Sync + 'static,
+    B: Beacon + Send + Sync + 'static,
+>(
+    data: Data<RPCState<DB, B>>,
+) -> Result<NetBandwidthStatsByPeerResult, JsonRpcError> {
+    let (tx, rx) = oneshot::channel();
+    let req = NetworkMessage::JSONRPCRequest {
+        method: NetRPCMethods::NetBandwidthStatsByPeer(tx),
+    };
+
+    data.network_send.send(req).await?;
+    Ok(rx
+        .await?
+        .into_iter()
+        .map(|(peer_id, stats)| (peer_id.to_string(), bandwidth_stats(stats)))
+        .collect())
+}
+
+pub(crate) async fn net_bandwidth_stats_by_protocol<
+    DB: BlockStore + Send + Sync + 'static,
+    B: Beacon + Send + Sync + 'static,
+>(
+    data: Data<RPCState<DB, B>>,
+) -> Result<NetBandwidthStatsByProtocolResult, JsonRpcError> {
+    let (tx, rx) = oneshot::channel();
+    let req = NetworkMessage::JSONRPCRequest {
+        method: NetRPCMethods::NetBandwidthStatsByProtocol(tx),
+    };
+
+    data.network_send.send(req).await?;
+    Ok(rx
+        .await?
+        .into_iter()
+        .map(|(protocol_id, stats)| (protocol_id, bandwidth_stats(stats)))
+        .collect())
+}
+
+pub(crate) async fn net_peer_info<
+    DB: BlockStore + Send + Sync + 'static,
+    B: Beacon + Send + Sync + 'static,
+>(
+    data: Data<RPCState<DB, B>>,
+    Params(params): Params<NetPeerInfoParams>,
+) -> Result<NetPeerInfoResult, JsonRpcError> {
+    let (id,) = params;
+    let peer_id: PeerId = id.parse()?;
+    let info = peer_info(&data, peer_id).await?;
+
+    let chain_exchange = info
+        .record
+        .map(|record| ChainExchangeStats {
+            successes: record.successes,
+            failures: record.failures,
+            average_time: record.average_time.as_nanos() as u64,
+        })
+        .unwrap_or_default();
+    Ok(ExtendedPeerInfo {
+        id: peer_id.to_string(),
+        agent: info.agent_version,
+        addrs: info
+            .listen_addrs
+            .iter()
+            .map(|addr| addr.to_string())
+            .collect(),
+        protocols: info.protocols,
+        latency: info.ping_rtt.map_or(0, |rtt| rtt.as_nanos() as u64),
+        inbound: info.inbound,
+        connected_for: info.connected_for.as_nanos() as u64,
+        gossip_score: info.gossipsub_score,
+        chain_exchange,
+    })
+}
+
+pub(crate) async fn net_agent_version<
+    DB: BlockStore + Send + Sync + 'static,
+    B: Beacon + Send + Sync + 'static,
+>(
+    data: Data<RPCState<DB, B>>,
+    Params(params): Params<NetAgentVersionParams>,
+) -> Result<NetAgentVersionResult, JsonRpcError> {
+    let (id,) = params;
+    Ok(peer_info(&data, id.parse()?).await?.agent_version)
+}
+
+/// Waits for the next periodic ping of a connected peer and returns its round trip time.
+pub(crate) async fn net_ping<
+    DB: BlockStore + Send + Sync + 'static,
+    B: Beacon + Send + Sync + 'static,
+>(
+    data: Data<RPCState<DB, B>>,
+    Params(params): Params<NetPingParams>,
+) -> Result<NetPingResult, JsonRpcError> {
+    let (id,) = params;
+    let peer_id: PeerId = id.parse()?;
+
+    let (tx, rx) = oneshot::channel();
+    let req = NetworkMessage::JSONRPCRequest {
+        method: NetRPCMethods::NetPing(tx, peer_id),
+    };
+
+    data.network_send.send(req).await?;
+    let rtt = future::timeout(PING_TIMEOUT, rx)
+        .await
+        .map_err(|_| format!("Ping of {} timed out", peer_id))?
+        .map_err(|_| format!("Peer {} is not connected", peer_id))?;
+    Ok(rtt.as_nanos() as u64)
+}
+
+pub(crate) async fn net_find_peer<
+    DB: BlockStore + Send + Sync + 'static,
+    B: Beacon + Send + Sync + 'static,
+>(
+    data: Data<RPCState<DB, B>>,
+    Params(params): Params<NetFindPeerParams>,
+) -> Result<NetFindPeerResult, JsonRpcError> {
+    let (id,) = params;
+    let peer_id: PeerId = id.parse()?;
+
+    let (tx, rx) = oneshot::channel();
+    let req = NetworkMessage::JSONRPCRequest {
+        method: NetRPCMethods::NetFindPeer(tx, peer_id),
+    };
+
+    data.network_send.send(req).await?;
+    let addrs = rx.await??;
+    Ok(AddrInfo {
+        id: peer_id.to_string(),
+        addrs,
+    })
+}
+
+async fn peer_info<DB, B>(
+    data: &Data<RPCState<DB, B>>,
+    peer_id: PeerId,
+) -> Result<PeerInfo, JsonRpcError>
+where
+    DB: BlockStore + Send + Sync + 'static,
+    B: Beacon + Send + Sync + 'static,
+{
+    let (tx, rx) = oneshot::channel();
+    let req = NetworkMessage::JSONRPCRequest {
+        method: NetRPCMethods::NetPeerInfo(tx, peer_id),
+    };
+
+    data.network_send.send(req).await?;
+    Ok(rx
+        .await?
+        .ok_or_else(|| format!("Peer {} is not connected", peer_id))?)
+}
+
+fn bandwidth_stats(stats: BandwidthStats) -> data_types::BandwidthStats {
+    data_types::BandwidthStats {
+        total_in: stats.total_in,
+        total_out: stats.total_out,
+        rate_in: stats.rate_in,
+        rate_out: stats.rate_out,
+    }
+}
@@ -8,7 +8,12 @@ use tide::http::headers::HeaderValues;
 use beacon::Beacon;
 use ipld_blockstore::BlockStore;
 use rpc_api::{
-    auth_api::*, chain_api::*, check_access, data_types::JsonRpcServerState, ACCESS_MAP,
+    auth_api::*,
+    chain_api::*,
+    check_access,
+    data_types::JsonRpcServerState,
+    mpool_api::{MPOOL_SUB, MPOOL_UPDATES},
+    ACCESS_MAP,
 };
 
 pub fn get_error_obj(code: i64, message: String) -> jsonrpc_v2::Error {
@@ -38,7 +43,12 @@ pub fn get_error_str(code: i64, message: String) -> String {
     }
 }
 
-const STREAMING_METHODS: [&str; 2] = [CHAIN_HEAD_SUBSCRIPTION, CHAIN_NOTIFY];
+const STREAMING_METHODS: [&str; 4] = [
+    CHAIN_HEAD_SUBSCRIPTION,
+    CHAIN_NOTIFY,
+    MPOOL_SUB,
+    MPOOL_UPDATES,
+];
 
 pub fn is_streaming_method(method_name: &str) -> bool {
     STREAMING_METHODS.contains(&method_name)
@@ -14,6 +14,7 @@ use ipld_blockstore::BlockStore;
 use rpc_api::{
     chain_api::*,
     data_types::{JsonRpcServerState, StreamingData},
+    mpool_api::{MpoolUpdatesResult, MPOOL_SUB, MPOOL_UPDATES},
 };
 
 use crate::rpc_util::{call_rpc, call_rpc_str, check_permissions, get_auth_header, get_error_str};
@@ -99,6 +100,42 @@ where
                 }
             }
         }
+        MPOOL_SUB => {
+            debug!("Received MpoolSub request with RPC ID: {:?}", call_id);
+
+            // The request is forwarded as is, so that its sender filter reaches the subscription
+            let (subscription_response, subscription_id) =
+                call_rpc::<i64>(rpc_server.clone(), rpc_call).await?;
+
+            ws_sender.send(Message::Text(subscription_response)).await?;
+
+            info!("RPC WS MpoolSub for subscription ID: {}", subscription_id);
+
+            while is_socket_active.load() {
+                let (_, update) = call_rpc::<MpoolUpdatesResult>(
+                    rpc_server.clone(),
+                    jsonrpc_v2::RequestObject::request()
+                        .with_method(MPOOL_UPDATES)
+                        .with_id(subscription_id)
+                        .finish(),
+                )
+                .await?;
+
+                let update_response = StreamingData {
+                    json_rpc: "2.0",
+                    method: "xrpc.ch.val",
+                    params: update,
+                };
+
+                if let Err(msg) = ws_sender
+                    .send(Message::Text(serde_json::to_string(&update_response)?))
+                    .await
+                {
+                    warn!("WS connection closed. {:?}", msg);
+                    is_socket_active.store(false);
+                }
+            }
+        }
         _ => {
             info!("RPC WS called method: {}", call_method);
             let response = call_rpc_str(rpc_server.clone(), rpc_call).await?;
@@ -15,6 +15,7 @@ use actor::{
 use beacon::{Beacon, BeaconEntry};
 use bls_signatures::Serialize as SerializeBls;
 use cid::{multihash::Code::Blake2b256, Cid};
+use encoding::Cbor;
 use fil_types::{
     verifier::{FullVerifier, ProofVerifier},
     PoStProof,
@@ -35,6 +36,7 @@ use fvm::state_tree::StateTree;
 use fvm_shared::crypto::signature::SignatureType;
 use fvm_shared::{address::Address, bigint::BigInt, crypto::signature::Signature};
 use ipld_blockstore::{BlockStore, BlockStoreExt};
+use key_management::{MsgMeta, MsgType};
 use legacy_ipld_amt::Amt;
 use networks::Height;
 use rpc_api::{
@@ -693,12 +695,14 @@ pub(crate) async fn miner_create_block<
         .signature(None)
         .build()?;
 
-    let key = key_management::find_key(&worker, &*data.keystore.as_ref().write().await)?;
-    let sig = key_management::sign(
-        *key.key_info.key_type(),
-        key.key_info.private_key(),
-        &next.to_signing_bytes(),
-    )?;
+    let sig = data
+        .signer
+        .sign(
+            &worker,
+            &next.to_signing_bytes(),
+            MsgMeta::new(MsgType::BlockHeader, next.marshal_cbor()?),
+        )
+        .await?;
     next.signature = Some(sig);
 
     Ok(BlockMsgJson(BlockMsg {
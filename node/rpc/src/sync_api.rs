@@ -120,7 +120,7 @@ mod tests {
     use forest_blocks::{BlockHeader, Tipset};
     use forest_libp2p::NetworkMessage;
     use fvm_shared::address::Address;
-    use key_management::{KeyStore, KeyStoreConfig};
+    use key_management::{KeyStore, KeyStoreConfig, MultiSigner};
     use message_pool::{MessagePool, MpoolRpcProvider};
     use networks::ChainConfig;
     use serde_json::from_str;
@@ -182,10 +182,13 @@ mod tests {
             .unwrap()
         });
         let (new_mined_block_tx, _) = bounded(5);
+        let keystore = Arc::new(RwLock::new(KeyStore::new(KeyStoreConfig::Memory).unwrap()));
         let state = Arc::new(RPCState {
             state_manager,
-            keystore: Arc::new(RwLock::new(KeyStore::new(KeyStoreConfig::Memory).unwrap())),
+            signer: Arc::new(MultiSigner::new(keystore.clone())),
+            keystore,
             mpool: Arc::new(pool),
+            mpool_push_lock: Default::default(),
             bad_blocks: Default::default(),
             sync_state: Arc::new(RwLock::new(Default::default())),
             network_send,
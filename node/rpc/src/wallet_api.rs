@@ -16,7 +16,7 @@ use fvm::state_tree::StateTree;
 use fvm_shared::address::Address;
 use fvm_shared::bigint::BigUint;
 use ipld_blockstore::BlockStore;
-use key_management::{json::KeyInfoJson, Error, Key};
+use key_management::{json::KeyInfoJson, Error, Key, MsgMeta, MsgType};
 use rpc_api::{data_types::RPCState, wallet_api::*};
 
 /// Return the balance from StateManager for a given Address
@@ -97,10 +97,7 @@ where
     let (addr_str,) = params;
     let addr = Address::from_str(&addr_str)?;
 
-    let keystore = data.keystore.read().await;
-
-    let key = key_management::find_key(&addr, &*keystore).is_ok();
-    Ok(key)
+    Ok(data.signer.has_key(&addr).await?)
 }
 
 /// Import Keyinfo to the Wallet, return the Address that corresponds to it
@@ -144,8 +141,10 @@ where
     DB: BlockStore + Send + Sync + 'static,
     B: Beacon + Send + Sync + 'static,
 {
-    let keystore = data.keystore.read().await;
-    Ok(key_management::list_addrs(&*keystore)?
+    Ok(data
+        .signer
+        .list_addrs()
+        .await?
         .into_iter()
         .map(AddressJson::from)
         .collect())
@@ -214,20 +213,10 @@ where
     let key_addr = state_manager
         .resolve_to_key_addr(&address, &heaviest_tipset)
         .await?;
-    let keystore = &mut *data.keystore.write().await;
-    let key = match key_management::find_key(&key_addr, keystore) {
-        Ok(key) => key,
-        Err(_) => {
-            let key_info = key_management::try_find(&key_addr, keystore)?;
-            Key::try_from(key_info)?
-        }
-    };
-
-    let sig = key_management::sign(
-        *key.key_info.key_type(),
-        key.key_info.private_key(),
-        &base64::decode(msg_string)?,
-    )?;
+    let sig = data
+        .signer
+        .sign(&key_addr, &base64::decode(msg_string)?, MsgMeta::default())
+        .await?;
 
     Ok(SignatureJson(sig))
 }
@@ -245,15 +234,14 @@ where
     let address = Address::from_str(&addr_str)?;
     let msg_cid = msg.cid()?;
 
-    let keystore = data.keystore.write().await;
-
-    let key = key_management::find_key(&address, &*keystore)?;
-
-    let sig = key_management::sign(
-        *key.key_info.key_type(),
-        key.key_info.private_key(),
-        msg_cid.to_bytes().as_slice(),
-    )?;
+    let sig = data
+        .signer
+        .sign(
+            &address,
+            msg_cid.to_bytes().as_slice(),
+            MsgMeta::new(MsgType::ChainMsg, msg.marshal_cbor()?),
+        )
+        .await?;
 
     let smsg = SignedMessage::new_from_parts(msg, sig)?;
 
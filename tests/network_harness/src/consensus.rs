@@ -0,0 +1,43 @@
+// Copyright 2019-2022 ChainSafe Systems
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+use async_trait::async_trait;
+use chain::{Scale, Weight};
+use chain_sync::Consensus;
+use forest_blocks::{Block, Tipset};
+use ipld_blockstore::BlockStore;
+use nonempty::NonEmpty;
+use state_manager::StateManager;
+use std::convert::Infallible;
+use std::sync::Arc;
+
+/// Consensus accepting every block, in place of the Filecoin consensus which needs the beacon,
+/// the proofs and the power of the miners. The weight of a tipset is the number of blocks in the
+/// chain up to it, so that longer chains are heavier.
+#[derive(Debug, Default)]
+pub struct MockConsensus;
+
+impl Scale for MockConsensus {
+    fn weight<DB>(_: &DB, ts: &Tipset) -> Result<Weight, anyhow::Error>
+    where
+        DB: BlockStore,
+    {
+        Ok(ts.weight() + Weight::from(ts.blocks().len()))
+    }
+}
+
+#[async_trait]
+impl Consensus for MockConsensus {
+    type Error = Infallible;
+
+    async fn validate_block<DB>(
+        &self,
+        _: Arc<StateManager<DB>>,
+        _: Arc<Block>,
+    ) -> Result<(), NonEmpty<Self::Error>>
+    where
+        DB: BlockStore + Sync + Send + 'static,
+    {
+        Ok(())
+    }
+}
@@ -0,0 +1,170 @@
+// Copyright 2019-2022 ChainSafe Systems
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+use anyhow::Context;
+use chain_sync::TipsetValidator;
+use cid::multihash::Code::Blake2b256;
+use cid::Cid;
+use encoding::BytesDe;
+use forest_blocks::BlockHeader;
+use forest_message::MessageReceipt;
+use forest_vm::{ActorState, TokenAmount};
+use fvm::state_tree::StateTree;
+use fvm_shared::address::Address;
+use fvm_shared::crypto::signature::SignatureType;
+use fvm_shared::sector::RegisteredPoStProof;
+use fvm_shared::state::StateTreeVersion;
+use ipld_blockstore::{BlockStore, BlockStoreExt, FvmRefStore};
+use key_management::{generate_key, Key};
+use legacy_ipld_amt::Amt;
+use networks::ChainConfig;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Name of the test network, used in the GossipSub topics.
+pub const NETWORK_NAME: &str = "testnet";
+
+/// Address of the miner mining all the blocks of the test network.
+pub const MINER_ADDRESS: Address = Address::new_id(1000);
+
+/// Block delay of the test network.
+const BLOCK_DELAY_SECS: u64 = 30;
+
+/// Balance of the worker account, spent by the messages pushed to the nodes.
+const WORKER_BALANCE: u64 = 1_000_000_000_000_000_000;
+
+/// Base fee of the genesis block.
+const GENESIS_BASE_FEE: u64 = 100;
+
+// The v8 calibnet code CIDs of the actors, which are the ones recognized by the state accessors.
+const INIT_ACTOR_CODE: &str = "bafk2bzaceadyfilb22bcvzvnpzbg2lyg6npmperyq6es2brvzjdh5rmywc4ry";
+const ACCOUNT_ACTOR_CODE: &str = "bafk2bzacecruossn66xqbeutqx5r4k2kjzgd43frmwd4qkw6haez44ubvvpxo";
+const MINER_ACTOR_CODE: &str = "bafk2bzacea6rabflc7kpwr6y4lzcqsnuahr4zblyq3rhzrrsfceeiw2lufrb4";
+
+/// Genesis shared by the nodes of a test network. The genesis state only holds the init actor,
+/// the account of the worker key and a miner actor controlled by it, which is enough for blocks
+/// and messages signed by the worker key to pass validation.
+///
+/// The genesis is built again in the store of every node, and is the same for all of them.
+pub struct TestGenesis {
+    worker: Key,
+    timestamp: u64,
+}
+
+impl TestGenesis {
+    /// Creates a genesis with a new worker key. The genesis timestamp is a day in the past, so
+    /// that the nodes accept blocks of epochs well ahead of the genesis.
+    pub fn new() -> anyhow::Result<Self> {
+        let now = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs();
+        Ok(Self {
+            worker: generate_key(SignatureType::Secp256k1)?,
+            timestamp: now - 24 * 60 * 60,
+        })
+    }
+
+    /// Key signing the blocks and the messages of the test network.
+    pub fn worker(&self) -> &Key {
+        &self.worker
+    }
+
+    pub fn timestamp(&self) -> u64 {
+        self.timestamp
+    }
+
+    /// Chain configuration of the test network, with all the network upgrades at genesis.
+    pub fn chain_config(&self) -> ChainConfig {
+        let mut config = ChainConfig {
+            name: NETWORK_NAME.to_owned(),
+            bootstrap_peers: vec![],
+            block_delay_secs: BLOCK_DELAY_SECS,
+            // The Drand servers can't be reached by tests
+            verify_drand_chain_info: false,
+            ..ChainConfig::calibnet()
+        };
+        for info in config.height_infos.iter_mut() {
+            info.epoch = -1;
+        }
+        config
+    }
+
+    /// Writes the genesis state to the store and returns the genesis block header.
+    pub fn build<DB: BlockStore>(&self, db: &DB) -> anyhow::Result<BlockHeader> {
+        let config = self.chain_config();
+        let state_root = self.build_state(db, &config)?;
+        let (messages, message_receipts) = empty_roots(db)?;
+        Ok(BlockHeader::builder()
+            .miner_address(Address::new_id(0))
+            .state_root(state_root)
+            .message_receipts(message_receipts)
+            .messages(messages)
+            .timestamp(self.timestamp)
+            .parent_base_fee(TokenAmount::from(GENESIS_BASE_FEE))
+            .build()?)
+    }
+
+    fn build_state<DB: BlockStore>(&self, db: &DB, config: &ChainConfig) -> anyhow::Result<Cid> {
+        let store = FvmRefStore::new(db);
+        let mut tree = StateTree::new(&store, StateTreeVersion::V4)?;
+
+        let mut init_state = fil_actor_init_v8::State::new(&store, NETWORK_NAME.to_owned())?;
+        let worker_id =
+            Address::new_id(init_state.map_address_to_new_id(&store, &self.worker.address)?);
+        tree.set_actor(
+            &Address::new_id(1),
+            ActorState::new(
+                Cid::try_from(INIT_ACTOR_CODE)?,
+                db.put_obj(&init_state, Blake2b256)?,
+                TokenAmount::from(0),
+                0,
+            ),
+        )?;
+
+        let account_state = fil_actor_account_v8::State {
+            address: self.worker.address,
+        };
+        tree.set_actor(
+            &worker_id,
+            ActorState::new(
+                Cid::try_from(ACCOUNT_ACTOR_CODE)?,
+                db.put_obj(&account_state, Blake2b256)?,
+                TokenAmount::from(WORKER_BALANCE),
+                0,
+            ),
+        )?;
+
+        let info = fil_actor_miner_v8::MinerInfo::new(
+            worker_id,
+            worker_id,
+            vec![],
+            vec![],
+            Vec::<BytesDe>::new(),
+            RegisteredPoStProof::StackedDRGWindow32GiBV1,
+        )
+        .map_err(|e| anyhow::anyhow!("{}", e))?;
+        let miner_state = fil_actor_miner_v8::State::new(
+            &config.policy,
+            &store,
+            db.put_obj(&info, Blake2b256)?,
+            0,
+            0,
+        )?;
+        tree.set_actor(
+            &MINER_ADDRESS,
+            ActorState::new(
+                Cid::try_from(MINER_ACTOR_CODE)?,
+                db.put_obj(&miner_state, Blake2b256)?,
+                TokenAmount::from(0),
+                0,
+            ),
+        )?;
+
+        tree.flush()
+            .context("Failed to flush the genesis state tree")
+    }
+}
+
+/// Returns the roots of the messages and of the receipts of a block without messages.
+fn empty_roots<DB: BlockStore>(db: &DB) -> anyhow::Result<(Cid, Cid)> {
+    let messages = TipsetValidator::compute_msg_root(db, &[], &[])?;
+    let receipts = Amt::<MessageReceipt, _>::new(db).flush()?;
+    Ok((messages, receipts))
+}
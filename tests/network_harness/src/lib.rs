@@ -0,0 +1,16 @@
+// Copyright 2019-2022 ChainSafe Systems
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+//! Harness running several Forest nodes in one process, to test syncing, gossiping and chain
+//! exchange end to end. The nodes use in-memory stores and transports, so tests don't need
+//! network access.
+
+mod consensus;
+mod genesis;
+mod network;
+mod node;
+
+pub use self::consensus::MockConsensus;
+pub use self::genesis::{TestGenesis, MINER_ADDRESS, NETWORK_NAME};
+pub use self::network::TestNetwork;
+pub use self::node::TestNode;
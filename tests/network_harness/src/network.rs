@@ -0,0 +1,274 @@
+// Copyright 2019-2022 ChainSafe Systems
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+use crate::consensus::MockConsensus;
+use crate::genesis::{TestGenesis, MINER_ADDRESS};
+use crate::node::{TestNode, POLL_INTERVAL};
+use async_std::future::timeout;
+use async_std::sync::Mutex;
+use async_std::task;
+use chain::Scale;
+use chain_sync::TipsetValidator;
+use cid::Cid;
+use encoding::Cbor;
+use forest_blocks::{BlockHeader, Ticket, Tipset, TipsetKeys};
+use forest_crypto::VRFProof;
+use forest_libp2p::Libp2pConfig;
+use forest_message::SignedMessage;
+use forest_vm::TokenAmount;
+use fvm_shared::address::Address;
+use fvm_shared::crypto::signature::{Signature, SignatureType};
+use fvm_shared::message::Message;
+use key_management::sign;
+use networks::Height;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Time given to the GossipSub subscriptions to be exchanged once two nodes are connected.
+const SUBSCRIPTION_DELAY: Duration = Duration::from_secs(1);
+
+/// Gas limit of the messages pushed to the nodes.
+const MESSAGE_GAS_LIMIT: i64 = 1_000_000;
+
+/// A network of Forest nodes sharing a test genesis, running in the test process.
+///
+/// Blocks are produced without running the Filecoin consensus: the nodes use [`MockConsensus`],
+/// and blocks carry no messages. The state of every produced tipset is the genesis state, which
+/// is recorded in the state manager of all the nodes instead of being computed, as the test
+/// genesis can't be executed by the virtual machine.
+pub struct TestNetwork {
+    genesis: TestGenesis,
+    /// Network configuration of the nodes.
+    libp2p_config: Libp2pConfig,
+    nodes: Vec<TestNode>,
+    /// States of the produced tipsets, recorded in the nodes started later on.
+    states: Mutex<Vec<(TipsetKeys, (Cid, Cid))>>,
+    /// Sequence of the next message sent by the worker.
+    sequence: AtomicU64,
+    /// Number of invalid blocks published, to tell them apart.
+    invalid_blocks: AtomicU64,
+}
+
+impl TestNetwork {
+    /// Starts `n` nodes, which aren't connected to each other.
+    pub async fn new(n: usize) -> anyhow::Result<Self> {
+        Self::with_libp2p_config(n, Libp2pConfig::default()).await
+    }
+
+    /// Starts `n` nodes with the given network configuration, which aren't connected to each
+    /// other. The nodes always listen on the in-memory transport.
+    pub async fn with_libp2p_config(n: usize, libp2p_config: Libp2pConfig) -> anyhow::Result<Self> {
+        let mut network = Self {
+            genesis: TestGenesis::new()?,
+            libp2p_config,
+            nodes: vec![],
+            states: Mutex::new(vec![]),
+            sequence: AtomicU64::new(0),
+            invalid_blocks: AtomicU64::new(0),
+        };
+        for _ in 0..n {
+            network.start_node().await?;
+        }
+        Ok(network)
+    }
+
+    /// Starts a new node and returns its index.
+    pub async fn start_node(&mut self) -> anyhow::Result<usize> {
+        let node = TestNode::start(&self.genesis, self.libp2p_config.clone()).await?;
+        for (key, state) in self.states.lock().await.iter() {
+            node.state_manager()
+                .set_tipset_state(key.clone(), *state)
+                .await;
+        }
+        self.nodes.push(node);
+        Ok(self.nodes.len() - 1)
+    }
+
+    pub fn genesis(&self) -> &TestGenesis {
+        &self.genesis
+    }
+
+    pub fn node(&self, index: usize) -> &TestNode {
+        &self.nodes[index]
+    }
+
+    pub fn nodes(&self) -> &[TestNode] {
+        &self.nodes
+    }
+
+    /// Connects two nodes, and waits for them to be able to gossip with each other.
+    pub async fn connect(&self, from: usize, to: usize) -> anyhow::Result<()> {
+        let (from, to) = (&self.nodes[from], &self.nodes[to]);
+        from.connect(to).await?;
+        while !from.peers().await?.contains_key(&to.peer_id())
+            || !to.peers().await?.contains_key(&from.peer_id())
+        {
+            task::sleep(POLL_INTERVAL).await;
+        }
+        task::sleep(SUBSCRIPTION_DELAY).await;
+        Ok(())
+    }
+
+    /// Connects every node to every other node.
+    pub async fn connect_all(&self) -> anyhow::Result<()> {
+        for from in 0..self.nodes.len() {
+            for to in from + 1..self.nodes.len() {
+                self.connect(from, to).await?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Produces a block on top of the head of a node, which the node then syncs and publishes
+    /// to its peers. Producing blocks on several nodes for the same epoch isn't supported, as
+    /// the state of the tipsets they form isn't known.
+    pub async fn produce_block(&self, index: usize) -> anyhow::Result<Arc<Tipset>> {
+        let node = &self.nodes[index];
+        let parent = node.head().await;
+        let db = node.state_manager().blockstore();
+        let messages = TipsetValidator::compute_msg_root(db, &[], &[])?;
+        let ticket = format!("{}/{}", index, parent.epoch() + 1);
+        let header = self.block_header(index, &parent, messages, ticket)?;
+
+        let key = Tipset::new(vec![header.clone()])?.key().clone();
+        let state = (
+            *parent.parent_state(),
+            *parent.blocks()[0].message_receipts(),
+        );
+        for node in &self.nodes {
+            node.state_manager()
+                .set_tipset_state(key.clone(), state)
+                .await;
+        }
+        self.states.lock().await.push((key, state));
+        node.submit_block(header).await
+    }
+
+    /// Publishes a block on top of the head of a node to its peers, which passes the checks done
+    /// before relaying it, but whose message root doesn't match its messages. The peers find it
+    /// invalid once they assemble the tipset.
+    pub async fn publish_invalid_block(&self, index: usize) -> anyhow::Result<()> {
+        let node = &self.nodes[index];
+        let parent = node.head().await;
+        let ticket = format!(
+            "{}/{}/invalid/{}",
+            index,
+            parent.epoch() + 1,
+            self.invalid_blocks.fetch_add(1, Ordering::SeqCst)
+        );
+        let header = self.block_header(index, &parent, *parent.parent_state(), ticket)?;
+        node.publish_block(header).await
+    }
+
+    /// Builds and signs a block mined by the worker on top of `parent`, on behalf of a node.
+    fn block_header(
+        &self,
+        index: usize,
+        parent: &Tipset,
+        messages: Cid,
+        ticket: String,
+    ) -> anyhow::Result<BlockHeader> {
+        let node = &self.nodes[index];
+        let db = node.state_manager().blockstore();
+        let config = node.state_manager().chain_config();
+        let epoch = parent.epoch() + 1;
+
+        let mut header = BlockHeader::builder()
+            .parents(parent.key().clone())
+            .weight(MockConsensus::weight(db, parent)?)
+            .epoch(epoch)
+            .miner_address(MINER_ADDRESS)
+            .messages(messages)
+            .message_receipts(*parent.blocks()[0].message_receipts())
+            .state_root(*parent.parent_state())
+            .timestamp(self.genesis.timestamp() + epoch as u64 * config.block_delay_secs)
+            .ticket(Some(Ticket::new(VRFProof::new(ticket.into_bytes()))))
+            .bls_aggregate(Some(empty_bls_aggregate()))
+            .parent_base_fee(chain::compute_base_fee(
+                db,
+                parent,
+                config.epoch(Height::Smoke),
+            )?)
+            .build()?;
+        let worker = self.genesis.worker();
+        header.signature = Some(sign(
+            SignatureType::Secp256k1,
+            worker.key_info.private_key(),
+            &header.to_signing_bytes(),
+        )?);
+        Ok(header)
+    }
+
+    /// Pushes a message sent by the worker to the message pool of a node, which publishes it to
+    /// its peers. Returns the CID of the message.
+    pub async fn push_message(
+        &self,
+        index: usize,
+        to: Address,
+        value: TokenAmount,
+    ) -> anyhow::Result<Cid> {
+        let worker = self.genesis.worker();
+        let message = Message {
+            from: worker.address,
+            to,
+            value,
+            sequence: self.sequence.fetch_add(1, Ordering::SeqCst),
+            gas_limit: MESSAGE_GAS_LIMIT,
+            gas_fee_cap: TokenAmount::from(10_000),
+            gas_premium: TokenAmount::from(1_000),
+            ..Message::default()
+        };
+        let signature = sign(
+            SignatureType::Secp256k1,
+            worker.key_info.private_key(),
+            &message.to_signing_bytes(),
+        )?;
+        let message =
+            SignedMessage::new_from_parts(message, signature).map_err(|e| anyhow::anyhow!(e))?;
+        Ok(self.nodes[index].mpool().push(message).await?)
+    }
+
+    /// Waits for the heads of all the nodes to be the given tipset, each within `limit`.
+    pub async fn wait_for_head(&self, tipset: &Tipset, limit: Duration) -> anyhow::Result<()> {
+        for node in &self.nodes {
+            node.wait_for_head(tipset, limit).await?;
+        }
+        Ok(())
+    }
+
+    /// Waits for a message to be pending in the message pools of all the nodes.
+    pub async fn wait_for_message(&self, cid: &Cid, limit: Duration) -> anyhow::Result<()> {
+        timeout(limit, async {
+            for node in &self.nodes {
+                while !is_pending(node, cid).await {
+                    task::sleep(POLL_INTERVAL).await;
+                }
+            }
+        })
+        .await
+        .map_err(|_| anyhow::anyhow!("Message {} wasn't received by all the nodes", cid))
+    }
+
+    /// Stops all the nodes.
+    pub async fn stop(self) {
+        for node in self.nodes {
+            node.stop().await;
+        }
+    }
+}
+
+async fn is_pending(node: &TestNode, cid: &Cid) -> bool {
+    match node.mpool().pending().await {
+        Ok((messages, _)) => messages.iter().any(|m| m.cid().ok().as_ref() == Some(cid)),
+        Err(_) => false,
+    }
+}
+
+/// BLS aggregate of no signatures, the compressed point at infinity, carried by blocks without
+/// BLS messages.
+fn empty_bls_aggregate() -> Signature {
+    let mut bytes = vec![0; 96];
+    bytes[0] = 0xc0;
+    Signature::new_bls(bytes)
+}
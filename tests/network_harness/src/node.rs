@@ -0,0 +1,265 @@
+// Copyright 2019-2022 ChainSafe Systems
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+use crate::consensus::MockConsensus;
+use crate::genesis::{TestGenesis, NETWORK_NAME};
+use async_std::channel::{bounded, Sender};
+use async_std::future::timeout;
+use async_std::task::{self, JoinHandle};
+use chain::ChainStore;
+use chain_sync::{ChainMuxer, SyncConfig};
+use db::MemoryDB;
+use encoding::Cbor;
+use forest_blocks::{BlockHeader, GossipBlock, Tipset};
+use forest_libp2p::{
+    Libp2pConfig, Libp2pService, Multiaddr, NetRPCMethods, NetworkMessage, PeerId, PeerInfo,
+    PeerStore, Topic, TransportConfig, PUBSUB_BLOCK_STR,
+};
+use futures::channel::oneshot;
+use libp2p::identity::Keypair;
+use log::warn;
+use message_pool::{MessagePool, MpoolConfig, MpoolRpcProvider};
+use state_manager::StateManager;
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Interval at which the nodes are polled while waiting for them.
+pub(crate) const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// A Forest node running in the test process, with an in-memory store and transport.
+pub struct TestNode {
+    peer_id: PeerId,
+    addrs: Vec<Multiaddr>,
+    state_manager: Arc<StateManager<MemoryDB>>,
+    mpool: Arc<MessagePool<MpoolRpcProvider<MemoryDB>>>,
+    network_send: Sender<NetworkMessage>,
+    tipset_sink: Sender<Arc<Tipset>>,
+    tasks: Vec<JoinHandle<()>>,
+}
+
+impl TestNode {
+    /// Starts a node the way the daemon does, and waits for it to listen. The node listens on
+    /// the in-memory transport, whatever the addresses and transports of `config`.
+    pub(crate) async fn start(genesis: &TestGenesis, config: Libp2pConfig) -> anyhow::Result<Self> {
+        let db = Arc::new(MemoryDB::default());
+        let chain_store = Arc::new(ChainStore::new(Arc::clone(&db)));
+        let genesis_header = genesis.build(db.as_ref())?;
+        chain_store.set_genesis(&genesis_header)?;
+        let genesis_tipset = Arc::new(Tipset::new(vec![genesis_header])?);
+        chain_store
+            .set_heaviest_tipset(Arc::clone(&genesis_tipset))
+            .await?;
+
+        let state_manager = Arc::new(
+            StateManager::new(Arc::clone(&chain_store), Arc::new(genesis.chain_config())).await?,
+        );
+
+        let config = Libp2pConfig {
+            listening_multiaddrs: vec!["/memory/0".parse()?],
+            transport: TransportConfig {
+                memory: true,
+                ..config.transport
+            },
+            ..config
+        };
+        let peer_store = Arc::new(PeerStore::default());
+        let p2p_service = Libp2pService::new(
+            config,
+            Arc::clone(&chain_store),
+            Arc::clone(&peer_store),
+            Keypair::generate_ed25519(),
+            NETWORK_NAME,
+        );
+        let network_rx = p2p_service.network_receiver();
+        let network_send = p2p_service.network_sender();
+
+        let provider =
+            MpoolRpcProvider::new(chain_store.publisher().clone(), Arc::clone(&state_manager));
+        let mpool = Arc::new(
+            MessagePool::new(
+                provider,
+                NETWORK_NAME.to_owned(),
+                network_send.clone(),
+                MpoolConfig::default(),
+                Arc::clone(state_manager.chain_config()),
+            )
+            .await?,
+        );
+
+        let (tipset_sink, tipset_stream) = bounded(20);
+        let chain_muxer = ChainMuxer::new(
+            Arc::new(MockConsensus),
+            Arc::clone(&state_manager),
+            Arc::clone(&mpool),
+            network_send.clone(),
+            network_rx,
+            peer_store,
+            genesis_tipset,
+            tipset_sink.clone(),
+            tipset_stream,
+            // A single peer is enough to tell the head of the network
+            SyncConfig {
+                tipset_sample_size: 1,
+                ..Default::default()
+            },
+        )?;
+
+        let tasks = vec![
+            task::spawn(p2p_service.run()),
+            task::spawn(async move {
+                let why = chain_muxer.await;
+                warn!("Chain muxer of the test node stopped: {}", why);
+            }),
+        ];
+
+        // Wait for the listener to be up
+        let (peer_id, addrs) = loop {
+            let (tx, rx) = oneshot::channel();
+            network_send
+                .send(NetworkMessage::JSONRPCRequest {
+                    method: NetRPCMethods::NetAddrsListen(tx),
+                })
+                .await?;
+            let (peer_id, addrs) = rx.await?;
+            if !addrs.is_empty() {
+                break (peer_id, addrs);
+            }
+            task::sleep(Duration::from_millis(50)).await;
+        };
+
+        Ok(Self {
+            peer_id,
+            addrs,
+            state_manager,
+            mpool,
+            network_send,
+            tipset_sink,
+            tasks,
+        })
+    }
+
+    pub fn peer_id(&self) -> PeerId {
+        self.peer_id
+    }
+
+    /// Addresses the node listens on.
+    pub fn addrs(&self) -> &[Multiaddr] {
+        &self.addrs
+    }
+
+    pub fn state_manager(&self) -> &Arc<StateManager<MemoryDB>> {
+        &self.state_manager
+    }
+
+    pub fn chain_store(&self) -> &Arc<ChainStore<MemoryDB>> {
+        self.state_manager.chain_store()
+    }
+
+    pub fn mpool(&self) -> &Arc<MessagePool<MpoolRpcProvider<MemoryDB>>> {
+        &self.mpool
+    }
+
+    /// Returns the heaviest tipset of the node.
+    pub async fn head(&self) -> Arc<Tipset> {
+        self.chain_store()
+            .heaviest_tipset()
+            .await
+            .expect("The heaviest tipset is set at genesis")
+    }
+
+    /// Returns the connected peers of the node.
+    pub async fn peers(&self) -> anyhow::Result<HashMap<PeerId, Vec<Multiaddr>>> {
+        let (tx, rx) = oneshot::channel();
+        self.send(NetRPCMethods::NetPeers(tx)).await?;
+        Ok(rx.await?)
+    }
+
+    /// Returns what the node knows about a connected peer.
+    pub async fn peer_info(&self, peer_id: PeerId) -> anyhow::Result<Option<PeerInfo>> {
+        let (tx, rx) = oneshot::channel();
+        self.send(NetRPCMethods::NetPeerInfo(tx, peer_id)).await?;
+        Ok(rx.await?)
+    }
+
+    /// Looks a peer up in the Kademlia DHT, returning its addresses or why it wasn't found.
+    pub async fn find_peer(
+        &self,
+        peer_id: PeerId,
+    ) -> anyhow::Result<Result<Vec<Multiaddr>, String>> {
+        let (tx, rx) = oneshot::channel();
+        self.send(NetRPCMethods::NetFindPeer(tx, peer_id)).await?;
+        Ok(rx.await?)
+    }
+
+    /// Waits for the head of the node to be the given tipset.
+    pub async fn wait_for_head(&self, tipset: &Tipset, limit: Duration) -> anyhow::Result<()> {
+        timeout(limit, async {
+            while self.head().await.key() != tipset.key() {
+                task::sleep(POLL_INTERVAL).await;
+            }
+        })
+        .await
+        .map_err(|_| {
+            anyhow::anyhow!(
+                "Node {} didn't reach the head at epoch {}",
+                self.peer_id,
+                tipset.epoch()
+            )
+        })
+    }
+
+    /// Dials another node.
+    pub async fn connect(&self, other: &TestNode) -> anyhow::Result<()> {
+        let (tx, rx) = oneshot::channel();
+        self.send(NetRPCMethods::NetConnect(
+            tx,
+            other.peer_id,
+            other.addrs.clone(),
+        ))
+        .await?;
+        if !rx.await? {
+            anyhow::bail!("Failed to connect to {}", other.peer_id);
+        }
+        Ok(())
+    }
+
+    /// Hands a block mined by the node to its syncer and publishes it, as the `SyncSubmitBlock`
+    /// RPC method does.
+    pub(crate) async fn submit_block(&self, header: BlockHeader) -> anyhow::Result<Arc<Tipset>> {
+        let tipset = Arc::new(Tipset::new(vec![header.clone()])?);
+        self.tipset_sink.send(Arc::clone(&tipset)).await?;
+        self.publish_block(header).await?;
+        Ok(tipset)
+    }
+
+    /// Publishes a block to the peers of the node, without handing it to its syncer.
+    pub(crate) async fn publish_block(&self, header: BlockHeader) -> anyhow::Result<()> {
+        let block = GossipBlock {
+            header,
+            bls_messages: vec![],
+            secpk_messages: vec![],
+        };
+        self.network_send
+            .send(NetworkMessage::PubsubMessage {
+                topic: Topic::new(format!("{}/{}", PUBSUB_BLOCK_STR, NETWORK_NAME)),
+                message: block.marshal_cbor()?,
+            })
+            .await?;
+        Ok(())
+    }
+
+    /// Stops the services of the node.
+    pub async fn stop(self) {
+        for task in self.tasks {
+            task.cancel().await;
+        }
+    }
+
+    async fn send(&self, method: NetRPCMethods) -> anyhow::Result<()> {
+        self.network_send
+            .send(NetworkMessage::JSONRPCRequest { method })
+            .await?;
+        Ok(())
+    }
+}
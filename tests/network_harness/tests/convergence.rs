@@ -0,0 +1,54 @@
+// Copyright 2019-2022 ChainSafe Systems
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+use forest_vm::TokenAmount;
+use fvm_shared::address::Address;
+use network_harness::TestNetwork;
+use std::time::Duration;
+
+const TIMEOUT: Duration = Duration::from_secs(60);
+
+#[async_std::test]
+async fn nodes_follow_gossiped_blocks() {
+    let network = TestNetwork::new(3).await.unwrap();
+    network.connect_all().await.unwrap();
+
+    for producer in [0, 1, 2, 0] {
+        let head = network.produce_block(producer).await.unwrap();
+        network.wait_for_head(&head, TIMEOUT).await.unwrap();
+    }
+    assert_eq!(network.node(2).head().await.epoch(), 4);
+    network.stop().await;
+}
+
+#[async_std::test]
+async fn late_node_catches_up_over_chain_exchange() {
+    let mut network = TestNetwork::new(2).await.unwrap();
+    network.connect(0, 1).await.unwrap();
+    let mut head = network.produce_block(0).await.unwrap();
+    for _ in 0..4 {
+        network.wait_for_head(&head, TIMEOUT).await.unwrap();
+        head = network.produce_block(1).await.unwrap();
+    }
+    network.wait_for_head(&head, TIMEOUT).await.unwrap();
+
+    let late = network.start_node().await.unwrap();
+    network.connect(late, 0).await.unwrap();
+    network.wait_for_head(&head, TIMEOUT).await.unwrap();
+    assert_eq!(network.node(late).head().await.epoch(), 5);
+    network.stop().await;
+}
+
+#[async_std::test]
+async fn messages_reach_every_message_pool() {
+    let network = TestNetwork::new(3).await.unwrap();
+    network.connect(0, 1).await.unwrap();
+    network.connect(1, 2).await.unwrap();
+
+    let cid = network
+        .push_message(0, Address::new_id(1234), TokenAmount::from(1))
+        .await
+        .unwrap();
+    network.wait_for_message(&cid, TIMEOUT).await.unwrap();
+    network.stop().await;
+}
@@ -0,0 +1,41 @@
+// Copyright 2019-2022 ChainSafe Systems
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+use async_std::future::timeout;
+use forest_libp2p::PeerId;
+use network_harness::TestNetwork;
+use std::time::Duration;
+
+const TIMEOUT: Duration = Duration::from_secs(30);
+
+#[async_std::test]
+async fn finds_peers_through_the_dht() {
+    let network = TestNetwork::new(3).await.unwrap();
+    let (node, relay, target) = (network.node(0), network.node(1), network.node(2));
+    // The relay learns the address of the target by dialing it
+    relay.connect(target).await.unwrap();
+    node.connect(relay).await.unwrap();
+
+    let addrs = timeout(TIMEOUT, node.find_peer(target.peer_id()))
+        .await
+        .expect("FindPeer query timed out")
+        .unwrap()
+        .expect("Target peer was not found");
+    assert!(addrs.contains(&target.addrs()[0]));
+
+    network.stop().await;
+}
+
+#[async_std::test]
+async fn fails_to_find_unknown_peers() {
+    let network = TestNetwork::new(2).await.unwrap();
+    network.connect(0, 1).await.unwrap();
+
+    let result = timeout(TIMEOUT, network.node(0).find_peer(PeerId::random()))
+        .await
+        .expect("FindPeer query timed out")
+        .unwrap();
+    assert!(result.is_err());
+
+    network.stop().await;
+}
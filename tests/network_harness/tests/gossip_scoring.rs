@@ -0,0 +1,62 @@
+// Copyright 2019-2022 ChainSafe Systems
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+use async_std::future::timeout;
+use async_std::task;
+use forest_libp2p::{Libp2pConfig, PeerId};
+use network_harness::{TestNetwork, TestNode};
+use std::time::Duration;
+
+const TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Graylist threshold of the GossipSub peer scoring parameters, below which the messages of a
+/// peer are ignored.
+const GRAYLIST_THRESHOLD: f64 = -2500.0;
+
+/// Invalid blocks published by the spammer while the honest block is being delivered.
+const SPAM_BLOCKS: usize = 50;
+
+const RECEIVER: usize = 0;
+const HONEST: usize = 1;
+const SPAMMER: usize = 2;
+
+async fn is_graylisted(node: &TestNode, peer_id: PeerId) -> bool {
+    let info = node.peer_info(peer_id).await.unwrap();
+    matches!(info.and_then(|i| i.gossipsub_score), Some(s) if s < GRAYLIST_THRESHOLD)
+}
+
+#[async_std::test]
+async fn graylisted_spammers_do_not_block_honest_blocks() {
+    let config = Libp2pConfig {
+        gossipsub_scoring: true,
+        ..Default::default()
+    };
+    let network = TestNetwork::with_libp2p_config(3, config).await.unwrap();
+    network.connect(HONEST, RECEIVER).await.unwrap();
+    network.connect(SPAMMER, RECEIVER).await.unwrap();
+    let receiver = network.node(RECEIVER);
+    let spammer = network.node(SPAMMER).peer_id();
+
+    // The chain sync of the receiver finds the blocks of the spammer invalid, the score of the
+    // spammer is lowered through the peer manager
+    for _ in 0..3 {
+        network.publish_invalid_block(SPAMMER).await.unwrap();
+    }
+    timeout(TIMEOUT, async {
+        while !is_graylisted(receiver, spammer).await {
+            task::sleep(Duration::from_millis(100)).await;
+        }
+    })
+    .await
+    .expect("The spammer wasn't graylisted");
+
+    // The block of the honest node still gets through while the spammer floods the receiver
+    for _ in 0..SPAM_BLOCKS {
+        network.publish_invalid_block(SPAMMER).await.unwrap();
+    }
+    let head = network.produce_block(HONEST).await.unwrap();
+    receiver.wait_for_head(&head, TIMEOUT).await.unwrap();
+    assert!(is_graylisted(receiver, spammer).await);
+
+    network.stop().await;
+}
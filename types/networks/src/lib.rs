@@ -149,6 +149,9 @@ pub struct ChainConfig {
     #[serde(default = "default_policy")]
     #[serde(with = "serde_policy")]
     pub policy: Policy,
+    /// Whether the chain info of the Drand beacons is checked against the Drand servers in debug
+    /// builds. Nodes running without network access turn it off.
+    pub verify_drand_chain_info: bool,
 }
 
 // FIXME: remove this trait once builtin-actors Policy have it
@@ -160,6 +163,7 @@ impl PartialEq for ChainConfig {
             && self.block_delay_secs == other.block_delay_secs
             && self.version_schedule == other.version_schedule
             && self.height_infos == other.height_infos
+            && self.verify_drand_chain_info == other.verify_drand_chain_info
             && (self.policy.max_aggregated_sectors == other.policy.max_aggregated_sectors
                 && self.policy.min_aggregated_sectors == other.policy.min_aggregated_sectors
                 && self.policy.max_aggregated_proof_size == other.policy.max_aggregated_proof_size
@@ -238,6 +242,7 @@ impl ChainConfig {
                 minimum_consensus_power: StoragePower::from(MINIMUM_CONSENSUS_POWER),
                 ..Policy::default()
             },
+            verify_drand_chain_info: true,
         }
     }
 
@@ -268,11 +273,14 @@ impl ChainConfig {
         };
         let mut points = BeaconSchedule::with_capacity(ds_iter.len());
         for dc in ds_iter {
+            let beacon = if self.verify_drand_chain_info {
+                DrandBeacon::new(genesis_ts, self.block_delay_secs, dc.config).await?
+            } else {
+                DrandBeacon::new_unverified(genesis_ts, self.block_delay_secs, dc.config)?
+            };
             points.0.push(BeaconPoint {
                 height: dc.height,
-                beacon: Arc::new(
-                    DrandBeacon::new(genesis_ts, self.block_delay_secs, dc.config).await?,
-                ),
+                beacon: Arc::new(beacon),
             });
         }
         Ok(points)
@@ -321,6 +329,7 @@ impl Default for ChainConfig {
                 ]),
                 ..Policy::default()
             },
+            verify_drand_chain_info: true,
         }
     }
 }
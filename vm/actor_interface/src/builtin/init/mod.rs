@@ -15,6 +15,9 @@ pub const ADDRESS: Address = Address::new_id(1);
 /// Init actor method.
 pub type Method = fil_actor_init_v8::Method;
 
+/// Parameters of the `Exec` method, used to create new actors.
+pub type ExecParams = fil_actor_init_v8::ExecParams;
+
 pub fn is_v8_init_cid(cid: &Cid) -> bool {
     let known_cids = vec![
         // calibnet
@@ -1,26 +1,241 @@
 // Copyright 2019-2022 ChainSafe Systems
 // SPDX-License-Identifier: Apache-2.0, MIT
 
-use forest_vm::ActorState;
-use ipld_blockstore::BlockStore;
+use crate::{ActorVersion, Map};
+use encoding::tuple::*;
+use encoding::{blake2b_256, to_vec};
+use forest_vm::{ActorState, Serialized, TokenAmount};
+use fvm_shared::address::Address;
+use fvm_shared::clock::ChainEpoch;
+use fvm_shared::MethodNum;
+use ipld_blockstore::{BlockStore, BlockStoreExt};
 use serde::Serialize;
 
+use anyhow::Context;
+
 /// Multisig actor method.
 pub type Method = fil_actor_multisig_v8::Method;
 
+/// Multisig constructor parameters, passed to the init actor's `Exec` method.
+pub type ConstructorParams = fil_actor_multisig_v8::ConstructorParams;
+
+/// Parameters of the `Propose` method.
+pub type ProposeParams = fil_actor_multisig_v8::ProposeParams;
+
+/// Parameters of the `Approve` and `Cancel` methods.
+pub type TxnIDParams = fil_actor_multisig_v8::TxnIDParams;
+
+/// Identifier of a pending multisig transaction.
+pub type TxnID = fil_actor_multisig_v8::TxnID;
+
+/// Pending multisig transaction.
+pub type Transaction = fil_actor_multisig_v8::Transaction;
+
+/// Fields of a pending transaction hashed to identify the proposal, requested by its first
+/// approver.
+#[derive(Serialize_tuple)]
+struct ProposalHashData {
+    requester: Option<Address>,
+    to: Address,
+    #[serde(with = "fvm_shared::bigint::bigint_ser")]
+    value: TokenAmount,
+    method: MethodNum,
+    params: Serialized,
+}
+
+/// Computes the hash the multisig actor checks a transaction against when it is approved or
+/// cancelled, so that a transaction replaced under the same ID is not acted upon.
+pub fn compute_proposal_hash(txn: &Transaction) -> anyhow::Result<[u8; 32]> {
+    let data = ProposalHashData {
+        requester: txn.approved.first().copied(),
+        to: txn.to,
+        value: txn.value.clone(),
+        method: txn.method,
+        params: txn.params.clone(),
+    };
+    Ok(blake2b_256(&to_vec(&data)?))
+}
+
 /// Multisig actor state.
 #[derive(Serialize)]
 #[serde(untagged)]
-pub enum State {}
+pub enum State {
+    V8(fil_actor_multisig_v8::State),
+}
 
 impl State {
-    pub fn load<BS>(_store: &BS, actor: &ActorState) -> anyhow::Result<State>
+    /// Loads the state of a multisig actor, as written by the given version of the actors.
+    pub fn load<BS>(store: &BS, actor: &ActorState, version: ActorVersion) -> anyhow::Result<State>
     where
         BS: BlockStore,
     {
-        Err(anyhow::anyhow!(
-            "Unknown multisig actor code {}",
-            actor.code
-        ))
+        match version {
+            ActorVersion::V8 => store
+                .get_obj(&actor.state)?
+                .map(State::V8)
+                .context("Actor state doesn't exist in store"),
+            _ => Err(anyhow::anyhow!(
+                "Unsupported multisig actor version {}",
+                version
+            )),
+        }
+    }
+
+    /// Addresses allowed to propose and approve transactions.
+    pub fn signers(&self) -> &[Address] {
+        match self {
+            State::V8(st) => &st.signers,
+        }
+    }
+
+    /// Number of approvals required to execute a transaction.
+    pub fn threshold(&self) -> u64 {
+        match self {
+            State::V8(st) => st.num_approvals_threshold,
+        }
+    }
+
+    pub fn initial_balance(&self) -> TokenAmount {
+        match self {
+            State::V8(st) => st.initial_balance.clone(),
+        }
+    }
+
+    pub fn start_epoch(&self) -> ChainEpoch {
+        match self {
+            State::V8(st) => st.start_epoch,
+        }
+    }
+
+    pub fn unlock_duration(&self) -> ChainEpoch {
+        match self {
+            State::V8(st) => st.unlock_duration,
+        }
+    }
+
+    /// Amount of the initial balance still locked at the given epoch.
+    pub fn locked_balance(&self, epoch: ChainEpoch) -> TokenAmount {
+        match self {
+            State::V8(st) => st.amount_locked(epoch - st.start_epoch),
+        }
+    }
+
+    /// Returns the pending transactions, ordered by transaction ID.
+    pub fn pending_transactions<BS: BlockStore>(
+        &self,
+        store: &BS,
+    ) -> anyhow::Result<Vec<(i64, Transaction)>> {
+        match self {
+            State::V8(st) => {
+                let map = Map::<_, Transaction>::load(&st.pending_txs, store, ActorVersion::V8)?;
+                let mut txns = Vec::new();
+                map.for_each(|key, txn| {
+                    txns.push((parse_txn_id_key(&key.0)?, txn.clone()));
+                    Ok(())
+                })?;
+                txns.sort_by_key(|(id, _)| *id);
+                Ok(txns)
+            }
+        }
+    }
+}
+
+/// Decodes a pending transactions HAMT key, a zig-zag encoded signed varint.
+fn parse_txn_id_key(key: &[u8]) -> anyhow::Result<i64> {
+    let mut ux: u64 = 0;
+    for (i, b) in key.iter().enumerate() {
+        if i >= 10 {
+            break;
+        }
+        ux |= u64::from(b & 0x7f) << (7 * i);
+        if b & 0x80 == 0 {
+            let x = (ux >> 1) as i64;
+            return Ok(if ux & 1 != 0 { !x } else { x });
+        }
+    }
+    Err(anyhow::anyhow!("Invalid transaction ID key {:?}", key))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use db::MemoryDB;
+
+    #[test]
+    fn txn_id_keys() {
+        for (key, id) in [
+            (vec![0x00], 0),
+            (vec![0x01], -1),
+            (vec![0x02], 1),
+            (vec![0x7f], -64),
+            (vec![0x80, 0x01], 64),
+            (vec![0xac, 0x02], 150),
+            (
+                vec![0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0x01],
+                i64::MIN,
+            ),
+        ] {
+            assert_eq!(parse_txn_id_key(&key).unwrap(), id);
+            // Keys must match the ones the multisig actor uses
+            assert_eq!(TxnID(id).key().0, key);
+        }
+
+        assert!(parse_txn_id_key(&[]).is_err());
+        assert!(parse_txn_id_key(&[0x80]).is_err());
+    }
+
+    #[test]
+    fn proposal_hash() {
+        let mut txn = Transaction {
+            to: Address::new_id(100),
+            value: TokenAmount::from(1000),
+            method: 2,
+            params: Serialized::new(vec![1, 2]),
+            approved: vec![Address::new_id(101), Address::new_id(102)],
+        };
+        // Hash of the CBOR tuple (f0101, f0100, 1000, 2, 0x0102), as Lotus computes it
+        let expected = [
+            0x41, 0x59, 0xd4, 0x4b, 0xc3, 0xd9, 0x0d, 0xd9, 0x79, 0xd4, 0x54, 0xd3, 0x88, 0x5b,
+            0x19, 0xbe, 0xca, 0x78, 0x11, 0x0f, 0xba, 0x6a, 0xe7, 0xaa, 0x89, 0x0d, 0x94, 0xbe,
+            0xee, 0x5d, 0x2f, 0x7a,
+        ];
+        assert_eq!(compute_proposal_hash(&txn).unwrap(), expected);
+
+        txn.params = Serialized::new(vec![1, 3]);
+        assert_ne!(compute_proposal_hash(&txn).unwrap(), expected);
+    }
+
+    #[test]
+    fn pending_transactions_ordered_by_id() {
+        let store = MemoryDB::default();
+        let mut pending = Map::new(&store, ActorVersion::V8);
+        for id in [64, -1, 3] {
+            let txn = Transaction {
+                to: Address::new_id(100),
+                value: TokenAmount::from(id),
+                method: 0,
+                params: Default::default(),
+                approved: vec![Address::new_id(101)],
+            };
+            pending.set(TxnID(id).key(), txn).unwrap();
+        }
+        let state = State::V8(fil_actor_multisig_v8::State {
+            signers: vec![Address::new_id(101), Address::new_id(102)],
+            num_approvals_threshold: 2,
+            next_tx_id: TxnID(65),
+            initial_balance: TokenAmount::from(1000),
+            start_epoch: 10,
+            unlock_duration: 100,
+            pending_txs: pending.flush().unwrap(),
+        });
+
+        let txns = state.pending_transactions(&store).unwrap();
+        let ids: Vec<_> = txns.iter().map(|(id, _)| *id).collect();
+        assert_eq!(ids, vec![-1, 3, 64]);
+        assert_eq!(txns[0].1.value, TokenAmount::from(-1));
+
+        assert_eq!(state.locked_balance(10), TokenAmount::from(1000));
+        assert_eq!(state.locked_balance(60), TokenAmount::from(500));
+        assert_eq!(state.locked_balance(110), TokenAmount::from(0));
     }
 }
@@ -1,10 +1,65 @@
 // Copyright 2019-2022 ChainSafe Systems
 // SPDX-License-Identifier: Apache-2.0, MIT
 
+use anyhow::Context;
+use cid::Cid;
 use fvm_shared::address::Address;
+use ipld_blockstore::{BlockStore, BlockStoreExt};
 
 /// System actor address.
 pub const ADDRESS: Address = Address::new_id(0);
 
 /// System actor method.
 pub type Method = fil_actor_system_v8::Method;
+
+/// System actor state, which points to the manifest of the builtin actors.
+pub type State = fil_actor_system_v8::State;
+
+/// Returns the code CID of a builtin actor, e.g. `multisig`, as listed in the manifest of the
+/// actors bundle the network runs.
+pub fn builtin_actor_code<BS: BlockStore>(
+    store: &BS,
+    state: &State,
+    name: &str,
+) -> anyhow::Result<Option<Cid>> {
+    let manifest: Vec<(String, Cid)> = store
+        .get_obj(&state.builtin_actors)?
+        .context("Builtin actors manifest doesn't exist in store")?;
+    Ok(manifest
+        .into_iter()
+        .find_map(|(actor, code)| (actor == name).then(|| code)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cid::multihash::Code::Blake2b256;
+    use db::MemoryDB;
+
+    #[test]
+    fn builtin_actor_code_from_manifest() {
+        let store = MemoryDB::default();
+        let account =
+            Cid::try_from("bafk2bzacecruossn66xqbeutqx5r4k2kjzgd43frmwd4qkw6haez44ubvvpxo")
+                .unwrap();
+        let multisig =
+            Cid::try_from("bafk2bzacec66wmb4kohuzvuxsulhcgiwju7sqkldwfpmmgw7dbbwgm5l2574q")
+                .unwrap();
+        let manifest = vec![
+            ("account".to_owned(), account),
+            ("multisig".to_owned(), multisig),
+        ];
+        let state = State {
+            builtin_actors: store.put_obj(&manifest, Blake2b256).unwrap(),
+        };
+
+        assert_eq!(
+            builtin_actor_code(&store, &state, "multisig").unwrap(),
+            Some(multisig)
+        );
+        assert_eq!(
+            builtin_actor_code(&store, &state, "paymentchannel").unwrap(),
+            None
+        );
+    }
+}